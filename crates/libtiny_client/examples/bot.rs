@@ -0,0 +1,92 @@
+//! A bot with a few commands, using `libtiny_client::bot`.
+//!
+//! Commands: `!echo <msg>`, `!roll <sides>`, and `!topic <topic>` (ops only). The bot joins
+//! channels it's invited to.
+
+use libtiny_client::bot::{Bot, Permission};
+use libtiny_client::{Endpoint, ServerInfo};
+use libtiny_common::ChanNameRef;
+
+use std::process::exit;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    // bot <nick> <server> <port> [<chan_1> ... <chan_N>]
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 4 {
+        println!("bot <nick> <server> <port> [<chan_1> .. <chan_N>]");
+        exit(1);
+    }
+
+    let port = match args[3].parse::<u16>() {
+        Ok(port) => port,
+        Err(err) => {
+            println!("Can't parse port: {:?}", args[3]);
+            println!("{err}");
+            exit(1);
+        }
+    };
+
+    let server_info = ServerInfo {
+        name: args[2].clone(),
+        endpoints: vec![Endpoint {
+            addr: args[2].clone(),
+            port,
+            tls: false,
+        }],
+        connect_timeout: libtiny_client::DEFAULT_CONNECT_TIMEOUT_SECS,
+        prefer_addr_family: None,
+        bind_addr: None,
+        sts_file: None,
+        record_dir: None,
+        pass: None,
+        user: None,
+        realname: "tiny bot".to_owned(),
+        nicks: vec![args[1].clone()],
+        auto_join: args[4..]
+            .iter()
+            .map(|c| ChanNameRef::new(c).to_owned())
+            .collect(),
+        auto_join_on_invite: vec![],
+        rejoin_on_kick: Default::default(),
+        nickserv_ident: None,
+        sasl_auth: None,
+    };
+
+    let bot = Bot::new(server_info)
+        .command("echo", Permission::Anyone, |ctx, args| {
+            ctx.reply(args.rest(0));
+            Ok(())
+        })
+        .command("roll", Permission::Anyone, |ctx, args| {
+            let sides: u64 = args.parse(0)?;
+            if sides == 0 {
+                return Err("Can't roll a die with 0 sides".to_owned());
+            }
+            // Not a good source of randomness, but good enough for an example
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .subsec_nanos();
+            let nick = ctx.nick().to_owned();
+            ctx.reply(&format!("{nick} rolled {}", u64::from(nanos) % sides + 1));
+            Ok(())
+        })
+        .command("topic", Permission::Op, |ctx, args| {
+            let chan = ctx.chan().ok_or("Not in a channel")?.display().to_owned();
+            let topic = args.rest(0).to_owned();
+            ctx.client().raw_msg(&format!("TOPIC {chan} :{topic}"));
+            Ok(())
+        })
+        .on_invite(|ctx, invite| {
+            let chan = invite.chan.to_owned();
+            ctx.client().join(std::iter::once(chan.as_ref()));
+        });
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let local = tokio::task::LocalSet::new();
+    local.block_on(&runtime, bot.run());
+}
@@ -1,6 +1,6 @@
 //! An echo bot that just repeats stuff sent to it (either in a channel or as PRIVMSG).
 
-use libtiny_client::{Client, Event, ServerInfo};
+use libtiny_client::{Client, Endpoint, Event, ServerInfo};
 use libtiny_common::ChanNameRef;
 use libtiny_wire::{Cmd, Msg, MsgTarget, Pfx};
 
@@ -35,14 +35,24 @@ fn main() {
         .collect::<Vec<_>>();
 
     let server_info = ServerInfo {
-        addr: server,
-        port,
-        tls: false,
+        name: server.clone(),
+        endpoints: vec![Endpoint {
+            addr: server,
+            port,
+            tls: false,
+        }],
+        connect_timeout: libtiny_client::DEFAULT_CONNECT_TIMEOUT_SECS,
+        prefer_addr_family: None,
+        bind_addr: None,
+        sts_file: None,
+        record_dir: None,
         pass: None,
         user: None,
         realname: "tiny echo bot".to_owned(),
         nicks: vec![nick],
         auto_join: chans,
+        auto_join_on_invite: vec![],
+        rejoin_on_kick: Default::default(),
         nickserv_ident: None,
         sasl_auth: None,
     };
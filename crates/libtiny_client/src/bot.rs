@@ -0,0 +1,847 @@
+//! A framework for writing bots on top of `Client`.
+//!
+//! A `Bot` is configured with a builder-style API and then run with `Bot::run`:
+//!
+//! ```no_run
+//! # use libtiny_client::ServerInfo;
+//! use libtiny_client::bot::{Bot, Permission};
+//!
+//! # async fn run(server_info: ServerInfo) {
+//! Bot::new(server_info)
+//!     .prefix("!")
+//!     .command("echo", Permission::Anyone, |ctx, args| {
+//!         ctx.reply(args.rest(0));
+//!         Ok(())
+//!     })
+//!     .command("kick", Permission::Op, |ctx, args| {
+//!         let chan = ctx.chan().ok_or("Not in a channel")?.display().to_owned();
+//!         let nick: String = args.parse(0)?;
+//!         ctx.client().raw_msg(&format!("KICK {chan} {nick}"));
+//!         Ok(())
+//!     })
+//!     .on_join(|ctx, join| ctx.reply(&format!("Welcome, {}!", join.nick)))
+//!     .run()
+//!     .await;
+//! # }
+//! ```
+//!
+//! Commands are messages starting with the prefix in channels. In private messages the prefix is
+//! optional. Handlers return an error message to reply with on invalid arguments etc.
+//!
+//! Permissions of commands are checked against the modes (op, voice etc.) of the sender in the
+//! channel the command is sent to, which the bot tracks from NAMES replies and MODE messages.
+//! Commands sent in private messages can't have any permissions, so only commands with
+//! `Permission::Anyone` can be used in private messages.
+//!
+//! Replies are rate limited to avoid getting disconnected for flooding, see `Bot::rate_limit`.
+//! Messages sent directly with `Client` (e.g. `ctx.client().privmsg(...)`) are not rate limited.
+//!
+//! `Bot::run` should be called in a `tokio::task::LocalSet`, as `Client` spawns local tasks.
+
+use crate::{Client, Event, ServerInfo};
+use libtiny_common::{ChanName, ChanNameRef};
+use libtiny_wire::{Cmd, Msg, MsgTarget, Pfx};
+
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
+use std::time::Duration;
+
+use tokio::select;
+use tokio::time::{Instant, sleep_until};
+
+/// Default for `Bot::rate_limit`.
+pub const DEFAULT_RATE_LIMIT_BURST: u32 = 5;
+
+/// Default for `Bot::rate_limit`.
+pub const DEFAULT_RATE_LIMIT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Max. number of replies waiting to be sent. The oldest replies are dropped when more are queued.
+const MAX_QUEUED_REPLIES: usize = 100;
+
+/// Permission needed to use a command, based on channel modes of the sender. Each permission
+/// includes the ones before it, e.g. ops can use commands that need voice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Permission {
+    Anyone,
+    /// `+v`
+    Voice,
+    /// `+h`
+    HalfOp,
+    /// `+o`, `+a` (admin), or `+q` (owner)
+    Op,
+}
+
+impl Permission {
+    fn from_modes(modes: &[char]) -> Permission {
+        if modes.iter().any(|mode| matches!(mode, 'q' | 'a' | 'o')) {
+            Permission::Op
+        } else if modes.contains(&'h') {
+            Permission::HalfOp
+        } else if modes.contains(&'v') {
+            Permission::Voice
+        } else {
+            Permission::Anyone
+        }
+    }
+}
+
+/// A user joined a channel. Also used when the bot joins a channel.
+#[derive(Debug)]
+pub struct Join<'a> {
+    pub nick: &'a str,
+    pub chan: &'a ChanNameRef,
+}
+
+/// A user left a channel. Also used when the bot leaves a channel.
+#[derive(Debug)]
+pub struct Part<'a> {
+    pub nick: &'a str,
+    pub chan: &'a ChanNameRef,
+    pub msg: Option<&'a str>,
+}
+
+/// `by` kicked `nick` from a channel. `nick` may be the bot's nick.
+#[derive(Debug)]
+pub struct Kick<'a> {
+    pub by: &'a str,
+    pub nick: &'a str,
+    pub chan: &'a ChanNameRef,
+    pub msg: Option<&'a str>,
+}
+
+/// `by` invited the bot to a channel.
+#[derive(Debug)]
+pub struct Invite<'a> {
+    pub by: &'a str,
+    pub chan: &'a ChanNameRef,
+}
+
+/// Arguments of a command: the words after the command name. Arguments with spaces can be
+/// quoted with double quotes, e.g. `!cmd "an argument" another`.
+#[derive(Debug)]
+pub struct Args {
+    /// Text after the command name.
+    raw: String,
+
+    /// Arguments and their start indices in `raw`.
+    args: Vec<(usize, String)>,
+}
+
+impl Args {
+    fn new(raw: &str) -> Args {
+        let raw = raw.trim().to_owned();
+        let mut args = vec![];
+        let mut chars = raw.char_indices().peekable();
+        while let Some(&(start, c)) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+                continue;
+            }
+            let mut arg = String::new();
+            if c == '"' {
+                chars.next();
+                for (_, c) in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                    arg.push(c);
+                }
+            } else {
+                while let Some(&(_, c)) = chars.peek() {
+                    if c.is_whitespace() {
+                        break;
+                    }
+                    arg.push(c);
+                    chars.next();
+                }
+            }
+            args.push((start, arg));
+        }
+        Args { raw, args }
+    }
+
+    pub fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty()
+    }
+
+    /// Argument at index `idx`, starting from 0.
+    pub fn get(&self, idx: usize) -> Option<&str> {
+        self.args.get(idx).map(|(_, arg)| arg.as_str())
+    }
+
+    /// Parse argument at index `idx`. The error message can be returned from handlers as is.
+    pub fn parse<T: FromStr>(&self, idx: usize) -> Result<T, String> {
+        let arg = self
+            .get(idx)
+            .ok_or_else(|| format!("Missing argument {}", idx + 1))?;
+        arg.parse()
+            .map_err(|_| format!("Invalid argument {}: {arg}", idx + 1))
+    }
+
+    /// Text starting from argument at index `idx`, as sent by the user (quotes are not removed).
+    /// Useful for the last argument of commands like `!kick <nick> <reason>`.
+    pub fn rest(&self, idx: usize) -> &str {
+        match self.args.get(idx) {
+            Some((start, _)) => &self.raw[*start..],
+            None => "",
+        }
+    }
+}
+
+/// Context of a handler: the client, and the user and the channel that caused the handler to run.
+pub struct Context<'a> {
+    client: &'a mut Client,
+    replies: &'a mut RateLimiter,
+    nick: &'a str,
+    chan: Option<&'a ChanNameRef>,
+}
+
+impl Context<'_> {
+    pub fn client(&mut self) -> &mut Client {
+        self.client
+    }
+
+    /// Nick of the user that sent the command or caused the event.
+    pub fn nick(&self) -> &str {
+        self.nick
+    }
+
+    /// Channel of the command or event. `None` for commands sent in private messages.
+    pub fn chan(&self) -> Option<&ChanNameRef> {
+        self.chan
+    }
+
+    /// Reply to the channel of the command or event, or to the user for private messages.
+    pub fn reply(&mut self, msg: &str) {
+        let target = match self.chan {
+            Some(chan) => chan.display(),
+            None => self.nick,
+        };
+        self.replies.push(target, msg);
+    }
+
+    /// Send a message to a channel or user, rate limited like replies.
+    pub fn send(&mut self, target: &str, msg: &str) {
+        self.replies.push(target, msg);
+    }
+}
+
+type CommandHandler = Box<dyn FnMut(&mut Context, &Args) -> Result<(), String>>;
+type JoinHandler = Box<dyn FnMut(&mut Context, &Join)>;
+type PartHandler = Box<dyn FnMut(&mut Context, &Part)>;
+type KickHandler = Box<dyn FnMut(&mut Context, &Kick)>;
+type InviteHandler = Box<dyn FnMut(&mut Context, &Invite)>;
+
+struct Command {
+    permission: Permission,
+    handler: CommandHandler,
+}
+
+/// A bot. See the module documentation.
+pub struct Bot {
+    server_info: ServerInfo,
+    prefix: String,
+    commands: HashMap<String, Command>,
+
+    /// Per-channel permissions of commands, overriding the permission in `commands`.
+    chan_permissions: HashMap<(ChanName, String), Permission>,
+
+    rate_limit_burst: u32,
+    rate_limit_interval: Duration,
+
+    join_handlers: Vec<JoinHandler>,
+    part_handlers: Vec<PartHandler>,
+    kick_handlers: Vec<KickHandler>,
+    invite_handlers: Vec<InviteHandler>,
+
+    modes: ChanModes,
+}
+
+impl Bot {
+    pub fn new(server_info: ServerInfo) -> Bot {
+        Bot {
+            server_info,
+            prefix: "!".to_owned(),
+            commands: HashMap::new(),
+            chan_permissions: HashMap::new(),
+            rate_limit_burst: DEFAULT_RATE_LIMIT_BURST,
+            rate_limit_interval: DEFAULT_RATE_LIMIT_INTERVAL,
+            join_handlers: vec![],
+            part_handlers: vec![],
+            kick_handlers: vec![],
+            invite_handlers: vec![],
+            modes: ChanModes::default(),
+        }
+    }
+
+    /// Prefix of commands in channels. Default is `!`.
+    pub fn prefix(mut self, prefix: &str) -> Bot {
+        self.prefix = prefix.to_owned();
+        self
+    }
+
+    /// Add a command. `name` is the command without the prefix.
+    pub fn command<F>(mut self, name: &str, permission: Permission, handler: F) -> Bot
+    where
+        F: FnMut(&mut Context, &Args) -> Result<(), String> + 'static,
+    {
+        self.commands.insert(
+            name.to_owned(),
+            Command {
+                permission,
+                handler: Box::new(handler),
+            },
+        );
+        self
+    }
+
+    /// Override the permission of a command in a channel.
+    pub fn chan_permission(
+        mut self,
+        chan: &ChanNameRef,
+        name: &str,
+        permission: Permission,
+    ) -> Bot {
+        self.chan_permissions
+            .insert((chan.to_owned(), name.to_owned()), permission);
+        self
+    }
+
+    /// Send at most `burst` replies at once, and then one reply every `interval`. Replies that
+    /// can't be sent yet are queued. Default is `DEFAULT_RATE_LIMIT_BURST` replies and
+    /// `DEFAULT_RATE_LIMIT_INTERVAL`.
+    pub fn rate_limit(mut self, burst: u32, interval: Duration) -> Bot {
+        self.rate_limit_burst = burst;
+        self.rate_limit_interval = interval;
+        self
+    }
+
+    pub fn on_join<F: FnMut(&mut Context, &Join) + 'static>(mut self, handler: F) -> Bot {
+        self.join_handlers.push(Box::new(handler));
+        self
+    }
+
+    pub fn on_part<F: FnMut(&mut Context, &Part) + 'static>(mut self, handler: F) -> Bot {
+        self.part_handlers.push(Box::new(handler));
+        self
+    }
+
+    pub fn on_kick<F: FnMut(&mut Context, &Kick) + 'static>(mut self, handler: F) -> Bot {
+        self.kick_handlers.push(Box::new(handler));
+        self
+    }
+
+    pub fn on_invite<F: FnMut(&mut Context, &Invite) + 'static>(mut self, handler: F) -> Bot {
+        self.invite_handlers.push(Box::new(handler));
+        self
+    }
+
+    /// Connect to the server and run the bot. Returns when the client stops, e.g. after
+    /// `ctx.client().quit(...)`.
+    pub async fn run(mut self) {
+        let (mut client, mut rcv_ev) = Client::new(self.server_info.clone());
+        let mut replies = RateLimiter::new(self.rate_limit_burst, self.rate_limit_interval);
+
+        loop {
+            let next_reply = replies.next_send(Instant::now());
+            select! {
+                ev = rcv_ev.recv() => {
+                    match ev {
+                        None => return,
+                        Some(ev) => self.handle_event(&mut client, &mut replies, ev),
+                    }
+                }
+                () = sleep_until(next_reply.unwrap_or_else(Instant::now)), if next_reply.is_some() => {
+                    while let Some((target, msg)) = replies.pop(Instant::now()) {
+                        client.privmsg(&target, &msg, false);
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_event(&mut self, client: &mut Client, replies: &mut RateLimiter, ev: Event) {
+        let msg = match ev {
+            Event::Connected => {
+                self.modes = ChanModes::default();
+                return;
+            }
+            Event::Netsplit { chans, .. } => {
+                for (chan, nicks) in chans {
+                    for nick in nicks {
+                        self.modes.remove_nick(&chan, &nick);
+                    }
+                }
+                return;
+            }
+            Event::Netjoin { chans } => {
+                for (chan, nicks) in chans {
+                    for nick in nicks {
+                        self.modes.add_nick(&chan, &nick);
+                    }
+                }
+                return;
+            }
+            Event::Msg(msg) => msg,
+            _ => return,
+        };
+
+        let Msg { pfx, cmd } = msg;
+        let nick = match &pfx {
+            Some(Pfx::User { nick, .. }) | Some(Pfx::Ambiguous(nick)) => nick.as_str(),
+            Some(Pfx::Server(_)) | None => "",
+        };
+        let our_nick = client.get_nick();
+
+        match &cmd {
+            Cmd::PRIVMSG {
+                target,
+                msg,
+                is_notice: false,
+                ctcp: None,
+            } => {
+                let chan = match target {
+                    MsgTarget::Chan(chan) => Some(chan.as_ref()),
+                    MsgTarget::User(_) => None,
+                };
+                self.handle_privmsg(client, replies, nick, chan, msg);
+            }
+
+            Cmd::JOIN { chan } => {
+                if nick_eq(nick, &our_nick) {
+                    self.modes.remove_chan(chan);
+                }
+                self.modes.add_nick(chan, nick);
+                let mut ctx = Context {
+                    client,
+                    replies,
+                    nick,
+                    chan: Some(chan),
+                };
+                for handler in &mut self.join_handlers {
+                    handler(&mut ctx, &Join { nick, chan });
+                }
+            }
+
+            Cmd::PART { chan, msg } => {
+                if nick_eq(nick, &our_nick) {
+                    self.modes.remove_chan(chan);
+                } else {
+                    self.modes.remove_nick(chan, nick);
+                }
+                let mut ctx = Context {
+                    client,
+                    replies,
+                    nick,
+                    chan: Some(chan),
+                };
+                let part = Part {
+                    nick,
+                    chan,
+                    msg: msg.as_deref(),
+                };
+                for handler in &mut self.part_handlers {
+                    handler(&mut ctx, &part);
+                }
+            }
+
+            Cmd::KICK {
+                chan,
+                nick: kicked,
+                msg,
+            } => {
+                if nick_eq(kicked, &our_nick) {
+                    self.modes.remove_chan(chan);
+                } else {
+                    self.modes.remove_nick(chan, kicked);
+                }
+                let mut ctx = Context {
+                    client,
+                    replies,
+                    nick,
+                    chan: Some(chan),
+                };
+                let kick = Kick {
+                    by: nick,
+                    nick: kicked,
+                    chan,
+                    msg: msg.as_deref(),
+                };
+                for handler in &mut self.kick_handlers {
+                    handler(&mut ctx, &kick);
+                }
+            }
+
+            Cmd::QUIT { chans, .. } => {
+                for chan in chans {
+                    self.modes.remove_nick(chan, nick);
+                }
+            }
+
+            Cmd::NICK {
+                nick: new_nick,
+                chans,
+            } => {
+                for chan in chans {
+                    self.modes.rename_nick(chan, nick, new_nick);
+                }
+            }
+
+            Cmd::Other { cmd, params } if cmd == "MODE" && params.len() >= 2 => {
+                let chan = ChanNameRef::new(&params[0]);
+                self.modes.update(chan, &params[1], &params[2..]);
+            }
+
+            Cmd::INVITE {
+                nick: invited,
+                chan,
+            } if nick_eq(invited, &our_nick) => {
+                let mut ctx = Context {
+                    client,
+                    replies,
+                    nick,
+                    chan: Some(chan),
+                };
+                let invite = Invite { by: nick, chan };
+                for handler in &mut self.invite_handlers {
+                    handler(&mut ctx, &invite);
+                }
+            }
+
+            // RPL_NAMREPLY
+            Cmd::Reply { num: 353, params } if params.len() >= 4 => {
+                let chan = ChanNameRef::new(&params[2]);
+                for nick in params[3].split_whitespace() {
+                    self.modes.add_names_nick(chan, nick);
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    fn handle_privmsg(
+        &mut self,
+        client: &mut Client,
+        replies: &mut RateLimiter,
+        nick: &str,
+        chan: Option<&ChanNameRef>,
+        msg: &str,
+    ) {
+        let text = match (chan, msg.strip_prefix(&self.prefix)) {
+            (_, Some(text)) => text,
+            (None, None) => msg,
+            (Some(_), None) => return,
+        };
+        let (name, args) = text.split_once(' ').unwrap_or((text, ""));
+        let command = match self.commands.get_mut(name) {
+            Some(command) => command,
+            None => return,
+        };
+
+        let required = chan
+            .and_then(|chan| {
+                self.chan_permissions
+                    .get(&(chan.to_owned(), name.to_owned()))
+                    .copied()
+            })
+            .unwrap_or(command.permission);
+        let permission = match chan {
+            Some(chan) => self.modes.permission(chan, nick),
+            None => Permission::Anyone,
+        };
+
+        let mut ctx = Context {
+            client,
+            replies,
+            nick,
+            chan,
+        };
+
+        let result = if permission < required {
+            Err(format!(
+                "You don't have permission to use {}{name}",
+                self.prefix
+            ))
+        } else {
+            (command.handler)(&mut ctx, &Args::new(args))
+        };
+
+        if let Err(err) = result {
+            match chan {
+                Some(_) => ctx.reply(&format!("{nick}: {err}")),
+                None => ctx.reply(&err),
+            }
+        }
+    }
+}
+
+/// Nicks are case insensitive, with the same rules as channel names.
+fn nick_eq(nick1: &str, nick2: &str) -> bool {
+    ChanNameRef::new(nick1) == ChanNameRef::new(nick2)
+}
+
+/// Normalized nick, used as the key of nicks in `ChanModes`. See `nick_eq`.
+fn nick_key(nick: &str) -> String {
+    ChanNameRef::new(nick).normalized()
+}
+
+/// Modes of users in channels, e.g. `o` for ops and `v` for voiced users.
+#[derive(Debug, Default)]
+struct ChanModes {
+    /// Nicks are normalized with `nick_key`.
+    chans: HashMap<ChanName, HashMap<String, Vec<char>>>,
+}
+
+/// User mode prefixes in NAMES replies and the corresponding modes.
+const MODE_PREFIXES: [(char, char); 5] =
+    [('~', 'q'), ('&', 'a'), ('@', 'o'), ('%', 'h'), ('+', 'v')];
+
+impl ChanModes {
+    fn remove_chan(&mut self, chan: &ChanNameRef) {
+        self.chans.remove(chan);
+    }
+
+    fn add_nick(&mut self, chan: &ChanNameRef, nick: &str) {
+        self.chans
+            .entry(chan.to_owned())
+            .or_default()
+            .insert(nick_key(nick), vec![]);
+    }
+
+    /// Add a nick from a NAMES reply, with mode prefixes, e.g. `@+nick`.
+    fn add_names_nick(&mut self, chan: &ChanNameRef, mut nick: &str) {
+        let mut modes = vec![];
+        while let Some(c) = nick.chars().next()
+            && let Some((_, mode)) = MODE_PREFIXES.iter().find(|(prefix, _)| *prefix == c)
+        {
+            modes.push(*mode);
+            nick = &nick[c.len_utf8()..];
+        }
+        self.chans
+            .entry(chan.to_owned())
+            .or_default()
+            .insert(nick_key(nick), modes);
+    }
+
+    fn remove_nick(&mut self, chan: &ChanNameRef, nick: &str) {
+        if let Some(nicks) = self.chans.get_mut(chan) {
+            nicks.remove(&nick_key(nick));
+        }
+    }
+
+    fn rename_nick(&mut self, chan: &ChanNameRef, old_nick: &str, new_nick: &str) {
+        if let Some(nicks) = self.chans.get_mut(chan)
+            && let Some(modes) = nicks.remove(&nick_key(old_nick))
+        {
+            nicks.insert(nick_key(new_nick), modes);
+        }
+    }
+
+    /// Update modes with a MODE message, e.g. `MODE #chan +o-v nick1 nick2`.
+    fn update(&mut self, chan: &ChanNameRef, modes: &str, mut params: &[String]) {
+        let nicks = match self.chans.get_mut(chan) {
+            Some(nicks) => nicks,
+            None => return,
+        };
+        let mut add = true;
+        for mode in modes.chars() {
+            match mode {
+                '+' => add = true,
+                '-' => add = false,
+                'q' | 'a' | 'o' | 'h' | 'v' => {
+                    let Some((nick, rest)) = params.split_first() else {
+                        return;
+                    };
+                    params = rest;
+                    if let Some(nick_modes) = nicks.get_mut(&nick_key(nick)) {
+                        nick_modes.retain(|m| *m != mode);
+                        if add {
+                            nick_modes.push(mode);
+                        }
+                    }
+                }
+                // Other modes with parameters: bans, ban exceptions, invite exceptions, key, and
+                // limit (only has a parameter when set)
+                'b' | 'e' | 'I' | 'k' => params = params.get(1..).unwrap_or(&[]),
+                'l' if add => params = params.get(1..).unwrap_or(&[]),
+                _ => {}
+            }
+        }
+    }
+
+    fn permission(&self, chan: &ChanNameRef, nick: &str) -> Permission {
+        self.chans
+            .get(chan)
+            .and_then(|nicks| nicks.get(&nick_key(nick)))
+            .map(|modes| Permission::from_modes(modes))
+            .unwrap_or(Permission::Anyone)
+    }
+}
+
+/// Rate limits messages to at most `burst` messages at once, and then one message every
+/// `interval`.
+#[derive(Debug)]
+struct RateLimiter {
+    /// Messages waiting to be sent, with their targets.
+    queue: VecDeque<(String, String)>,
+    burst: u32,
+    interval: Duration,
+
+    /// Theoretical arrival time: when the next message could be sent if there was no burst.
+    /// Messages can be sent at `tat - (burst - 1) * interval`.
+    tat: Instant,
+}
+
+impl RateLimiter {
+    fn new(burst: u32, interval: Duration) -> RateLimiter {
+        RateLimiter {
+            queue: VecDeque::new(),
+            burst: burst.max(1),
+            interval,
+            tat: Instant::now(),
+        }
+    }
+
+    /// Queue a message. The oldest message is dropped when the queue is full, e.g. when the bot
+    /// is flooded with commands.
+    fn push(&mut self, target: &str, msg: &str) {
+        if self.queue.len() >= MAX_QUEUED_REPLIES {
+            debug!("Reply queue full, dropping the oldest reply");
+            self.queue.pop_front();
+        }
+        self.queue.push_back((target.to_owned(), msg.to_owned()));
+    }
+
+    /// When the next message in the queue can be sent. `None` if the queue is empty.
+    fn next_send(&self, now: Instant) -> Option<Instant> {
+        if self.queue.is_empty() {
+            return None;
+        }
+        let slack = self.interval * (self.burst - 1);
+        Some(now + self.tat.saturating_duration_since(now + slack))
+    }
+
+    /// Take the next message in the queue if it can be sent at `now`.
+    fn pop(&mut self, now: Instant) -> Option<(String, String)> {
+        if self.next_send(now)? > now {
+            return None;
+        }
+        self.tat = self.tat.max(now) + self.interval;
+        self.queue.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_args() {
+        let args = Args::new(" nick  \"a long reason\" more ");
+        assert_eq!(args.len(), 3);
+        assert_eq!(args.get(0), Some("nick"));
+        assert_eq!(args.get(1), Some("a long reason"));
+        assert_eq!(args.get(2), Some("more"));
+        assert_eq!(args.get(3), None);
+        assert_eq!(args.rest(1), "\"a long reason\" more");
+        assert_eq!(args.rest(3), "");
+
+        let args = Args::new("10 x");
+        assert_eq!(args.parse::<u32>(0), Ok(10));
+        assert_eq!(
+            args.parse::<u32>(1),
+            Err("Invalid argument 2: x".to_owned())
+        );
+        assert_eq!(args.parse::<u32>(2), Err("Missing argument 3".to_owned()));
+
+        assert!(Args::new("").is_empty());
+    }
+
+    #[test]
+    fn test_chan_modes() {
+        let chan = ChanNameRef::new("#chan");
+        let mut modes = ChanModes::default();
+        for nick in ["@+op", "%halfop", "+voice", "user", "~owner"] {
+            modes.add_names_nick(chan, nick);
+        }
+        assert_eq!(modes.permission(chan, "op"), Permission::Op);
+        assert_eq!(modes.permission(chan, "halfop"), Permission::HalfOp);
+        assert_eq!(modes.permission(chan, "voice"), Permission::Voice);
+        assert_eq!(modes.permission(chan, "user"), Permission::Anyone);
+        assert_eq!(modes.permission(chan, "owner"), Permission::Op);
+        assert_eq!(modes.permission(chan, "unknown"), Permission::Anyone);
+        assert_eq!(
+            modes.permission(ChanNameRef::new("#other"), "op"),
+            Permission::Anyone
+        );
+
+        // Ban mask parameter is skipped
+        let params = ["*!*@host", "user", "op"].map(str::to_owned);
+        modes.update(chan, "+bv-o", &params);
+        assert_eq!(modes.permission(chan, "user"), Permission::Voice);
+        // Still voiced
+        assert_eq!(modes.permission(chan, "op"), Permission::Voice);
+
+        modes.rename_nick(chan, "user", "user_");
+        assert_eq!(modes.permission(chan, "user"), Permission::Anyone);
+        assert_eq!(modes.permission(chan, "user_"), Permission::Voice);
+
+        modes.remove_nick(chan, "user_");
+        assert_eq!(modes.permission(chan, "user_"), Permission::Anyone);
+
+        // Channel names and nicks are case-insensitive
+        assert_eq!(
+            modes.permission(ChanNameRef::new("#CHAN"), "halfop"),
+            Permission::HalfOp
+        );
+        assert_eq!(modes.permission(chan, "HalfOp"), Permission::HalfOp);
+        modes.update(chan, "-v", &["VOICE".to_owned()]);
+        assert_eq!(modes.permission(chan, "voice"), Permission::Anyone);
+        assert!(nick_eq("nick[a]", "NICK{A}"));
+        assert!(!nick_eq("nick", "nick_"));
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut limiter = RateLimiter::new(2, Duration::from_secs(2));
+        let t0 = limiter.tat;
+        assert_eq!(limiter.next_send(t0), None);
+
+        for i in 0..4 {
+            limiter.push("#chan", &i.to_string());
+        }
+
+        // Burst of 2
+        assert_eq!(limiter.pop(t0), Some(("#chan".to_owned(), "0".to_owned())));
+        assert_eq!(limiter.pop(t0), Some(("#chan".to_owned(), "1".to_owned())));
+        assert_eq!(limiter.pop(t0), None);
+
+        // Then one every 2 seconds
+        assert_eq!(limiter.next_send(t0), Some(t0 + Duration::from_secs(2)));
+        let t1 = t0 + Duration::from_secs(2);
+        assert_eq!(limiter.pop(t1), Some(("#chan".to_owned(), "2".to_owned())));
+        assert_eq!(limiter.pop(t1), None);
+        let t2 = t1 + Duration::from_secs(2);
+        assert_eq!(limiter.pop(t2), Some(("#chan".to_owned(), "3".to_owned())));
+        assert_eq!(limiter.next_send(t2), None);
+
+        // Burst is available again after waiting
+        let t3 = t2 + Duration::from_secs(10);
+        limiter.push("nick", "4");
+        limiter.push("nick", "5");
+        assert!(limiter.pop(t3).is_some());
+        assert!(limiter.pop(t3).is_some());
+
+        // Oldest replies are dropped when the queue is full
+        for i in 0..MAX_QUEUED_REPLIES + 10 {
+            limiter.push("#chan", &i.to_string());
+        }
+        assert_eq!(limiter.queue.len(), MAX_QUEUED_REPLIES);
+        assert_eq!(limiter.queue[0], ("#chan".to_owned(), "10".to_owned()));
+    }
+}
@@ -0,0 +1,333 @@
+//! Resolving server addresses and connecting to the server.
+//!
+//! Addresses are tried "Happy Eyeballs" style (RFC 8305): addresses of the two families are
+//! interleaved, starting with the preferred family, and a new connection attempt is started every
+//! `ATTEMPT_DELAY` or when the previous attempt fails, without waiting for the previous attempts
+//! to time out. The first attempt that succeeds is used and the others are dropped. This way an
+//! unreachable address (e.g. a blackholed IPv6 address) doesn't stall the connection.
+
+use crate::Event;
+
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+
+use tokio::net::{TcpSocket, TcpStream, UnixStream};
+use tokio::select;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+/// Delay between connection attempts. See RFC 8305 section 5.
+const ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Address family of a server address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddrFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl AddrFamily {
+    pub fn of(addr: &SocketAddr) -> AddrFamily {
+        match addr {
+            SocketAddr::V4(_) => AddrFamily::Ipv4,
+            SocketAddr::V6(_) => AddrFamily::Ipv6,
+        }
+    }
+}
+
+impl fmt::Display for AddrFamily {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddrFamily::Ipv4 => f.write_str("IPv4"),
+            AddrFamily::Ipv6 => f.write_str("IPv6"),
+        }
+    }
+}
+
+/// Resolve `serv_name` without blocking the executor. Fails with `io::ErrorKind::TimedOut` if the
+/// name is not resolved in `timeout`.
+pub(crate) async fn resolve_addr(
+    serv_name: &str,
+    port: u16,
+    timeout: Duration,
+) -> io::Result<Vec<SocketAddr>> {
+    match tokio::time::timeout(timeout, tokio::net::lookup_host((serv_name, port))).await {
+        Err(_) => Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("Timed out resolving {serv_name}"),
+        )),
+        Ok(addrs) => Ok(addrs?.collect()),
+    }
+}
+
+/// Order addresses to try: families are interleaved, starting with `prefer`. When `prefer` is not
+/// given the family of the first address is used. Order of the addresses within a family is not
+/// changed.
+pub(crate) fn sort_addrs(addrs: Vec<SocketAddr>, prefer: Option<AddrFamily>) -> Vec<SocketAddr> {
+    let first_family = match prefer.or_else(|| addrs.first().map(AddrFamily::of)) {
+        None => return addrs,
+        Some(family) => family,
+    };
+
+    let (first, second): (Vec<SocketAddr>, Vec<SocketAddr>) = addrs
+        .into_iter()
+        .partition(|addr| AddrFamily::of(addr) == first_family);
+
+    let mut sorted = Vec::with_capacity(first.len() + second.len());
+    let mut first = first.into_iter();
+    let mut second = second.into_iter();
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => return sorted,
+            (addr1, addr2) => sorted.extend(addr1.into_iter().chain(addr2)),
+        }
+    }
+}
+
+/// Connect to one of the addresses, racing connection attempts as described in the module
+/// documentation. When `bind_addr` is given, connections are made from that address, and only the
+/// addresses of its family are tried. `Event::Connecting` is sent for each attempt, and
+/// `Event::IoErr` for each failed attempt. Returns the error of the last attempt when all attempts
+/// fail.
+pub(crate) async fn connect_tcp(
+    addrs: Vec<SocketAddr>,
+    bind_addr: Option<SocketAddr>,
+    snd_ev: &mut mpsc::Sender<Event>,
+) -> io::Result<TcpStream> {
+    let addrs: Vec<SocketAddr> = match bind_addr {
+        None => addrs,
+        Some(bind_addr) => {
+            let family = AddrFamily::of(&bind_addr);
+            let addrs: Vec<SocketAddr> = addrs
+                .into_iter()
+                .filter(|addr| AddrFamily::of(addr) == family)
+                .collect();
+            if addrs.is_empty() {
+                let err = io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Server doesn't have {family} addresses to connect from {bind_addr}"),
+                );
+                snd_ev
+                    .send(Event::IoErr(io::Error::new(err.kind(), err.to_string())))
+                    .await
+                    .unwrap();
+                return Err(err);
+            }
+            addrs
+        }
+    };
+
+    // Dropping the set aborts the attempts in progress
+    let mut attempts: JoinSet<io::Result<TcpStream>> = JoinSet::new();
+    let mut addrs = addrs.into_iter().peekable();
+    let mut last_err: Option<io::Error> = None;
+
+    // Each iteration starts a new attempt, after the previous attempt fails or `ATTEMPT_DELAY`
+    // passes
+    loop {
+        if let Some(addr) = addrs.next() {
+            snd_ev
+                .send(Event::Connecting {
+                    addr,
+                    family: AddrFamily::of(&addr),
+                })
+                .await
+                .unwrap();
+            attempts.spawn(connect_from(addr, bind_addr));
+        }
+
+        if attempts.is_empty() {
+            return Err(last_err.unwrap_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "No addresses to connect to")
+            }));
+        }
+
+        let more_addrs = addrs.peek().is_some();
+
+        select! {
+            result = attempts.join_next() => {
+                match result.unwrap() {
+                    Err(join_err) => {
+                        panic!("Connection task failed: {join_err:?}");
+                    }
+                    Ok(Ok(stream)) => {
+                        return Ok(stream);
+                    }
+                    Ok(Err(err)) => {
+                        debug!("Connection attempt failed: {err:?}");
+                        last_err = Some(io::Error::new(err.kind(), err.to_string()));
+                        snd_ev.send(Event::IoErr(err)).await.unwrap();
+                    }
+                }
+            }
+            () = tokio::time::sleep(ATTEMPT_DELAY), if more_addrs => {}
+        }
+    }
+}
+
+/// Connect to a Unix domain socket.
+pub(crate) async fn connect_unix(path: &Path) -> io::Result<UnixStream> {
+    UnixStream::connect(path).await.map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!("Can't connect to {}: {err}", path.display()),
+        )
+    })
+}
+
+async fn connect_from(addr: SocketAddr, bind_addr: Option<SocketAddr>) -> io::Result<TcpStream> {
+    let bind_addr = match bind_addr {
+        None => return TcpStream::connect(addr).await,
+        Some(bind_addr) => bind_addr,
+    };
+    let bind = || -> io::Result<TcpSocket> {
+        let socket = match bind_addr {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        if bind_addr.port() != 0 {
+            // Allow reconnecting from the same port while the previous connection is in
+            // TIME_WAIT
+            socket.set_reuseaddr(true)?;
+        }
+        socket.bind(bind_addr)?;
+        Ok(socket)
+    };
+    let socket = bind()
+        .map_err(|err| io::Error::new(err.kind(), format!("Can't bind to {bind_addr}: {err}")))?;
+    socket.connect(addr).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addrs(addrs: &[&str]) -> Vec<SocketAddr> {
+        addrs.iter().map(|addr| addr.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_sort_addrs() {
+        let resolved = addrs(&["[::1]:1", "[::2]:1", "[::3]:1", "1.0.0.1:1", "1.0.0.2:1"]);
+
+        assert_eq!(
+            sort_addrs(resolved.clone(), None),
+            addrs(&["[::1]:1", "1.0.0.1:1", "[::2]:1", "1.0.0.2:1", "[::3]:1"])
+        );
+        assert_eq!(
+            sort_addrs(resolved.clone(), Some(AddrFamily::Ipv6)),
+            addrs(&["[::1]:1", "1.0.0.1:1", "[::2]:1", "1.0.0.2:1", "[::3]:1"])
+        );
+        assert_eq!(
+            sort_addrs(resolved, Some(AddrFamily::Ipv4)),
+            addrs(&["1.0.0.1:1", "[::1]:1", "1.0.0.2:1", "[::2]:1", "[::3]:1"])
+        );
+        assert_eq!(
+            sort_addrs(addrs(&["1.0.0.1:1"]), Some(AddrFamily::Ipv6)),
+            addrs(&["1.0.0.1:1"])
+        );
+        assert_eq!(sort_addrs(vec![], None), vec![]);
+    }
+
+    #[test]
+    fn test_connect_fallback() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+
+            // Find a port that's not listened on by binding and dropping a listener
+            let closed_addr = {
+                let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+                listener.local_addr().unwrap()
+            };
+
+            let (mut snd_ev, mut rcv_ev) = mpsc::channel(100);
+            let stream = connect_tcp(vec![closed_addr, addr], None, &mut snd_ev)
+                .await
+                .unwrap();
+            assert_eq!(stream.peer_addr().unwrap(), addr);
+
+            assert!(matches!(
+                rcv_ev.try_recv(),
+                Ok(Event::Connecting { addr: addr_, family: AddrFamily::Ipv4 }) if addr_ == closed_addr
+            ));
+            assert!(matches!(rcv_ev.try_recv(), Ok(Event::IoErr(_))));
+            assert!(matches!(
+                rcv_ev.try_recv(),
+                Ok(Event::Connecting { addr: addr_, .. }) if addr_ == addr
+            ));
+
+            let err = connect_tcp(vec![closed_addr], None, &mut snd_ev).await;
+            assert!(err.is_err());
+        });
+    }
+
+    #[test]
+    fn test_connect_bind_addr() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let (mut snd_ev, mut rcv_ev) = mpsc::channel(100);
+
+            // IPv6 addresses are skipped when binding to an IPv4 address
+            let bind_addr: SocketAddr = "127.0.0.2:0".parse().unwrap();
+            let ipv6_addr: SocketAddr = "[::1]:1".parse().unwrap();
+            let stream = connect_tcp(vec![ipv6_addr, addr], Some(bind_addr), &mut snd_ev)
+                .await
+                .unwrap();
+            assert_eq!(stream.local_addr().unwrap().ip(), bind_addr.ip());
+            let (_, peer_addr) = listener.accept().await.unwrap();
+            assert_eq!(peer_addr.ip(), bind_addr.ip());
+            assert!(matches!(
+                rcv_ev.try_recv(),
+                Ok(Event::Connecting { addr: addr_, .. }) if addr_ == addr
+            ));
+
+            // No addresses of the family
+            let bind_addr: SocketAddr = "[::1]:0".parse().unwrap();
+            assert!(
+                connect_tcp(vec![addr], Some(bind_addr), &mut snd_ev)
+                    .await
+                    .is_err()
+            );
+            assert!(matches!(rcv_ev.try_recv(), Ok(Event::IoErr(_))));
+
+            // Binding to an address that's not ours fails
+            let bind_addr: SocketAddr = "192.0.2.1:0".parse().unwrap();
+            let err = connect_tcp(vec![addr], Some(bind_addr), &mut snd_ev)
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("Can't bind to 192.0.2.1:0"));
+            assert!(matches!(rcv_ev.try_recv(), Ok(Event::Connecting { .. })));
+            assert!(matches!(rcv_ev.try_recv(), Ok(Event::IoErr(_))));
+        });
+    }
+
+    #[test]
+    fn test_resolve_addr() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let addrs = resolve_addr("127.0.0.1", 6667, Duration::from_secs(10))
+                .await
+                .unwrap();
+            assert_eq!(addrs, vec!["127.0.0.1:6667".parse().unwrap()]);
+        });
+    }
+}
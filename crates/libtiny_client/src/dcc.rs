@@ -0,0 +1,1580 @@
+//! DCC file transfers and chats.
+//!
+//! DCC requests are CTCP messages sent over IRC, files and chat messages are transferred over a
+//! direct TCP connection between the clients. Supported requests:
+//!
+//! - `DCC SEND <file> <ip> <port> <size>`: The sender listens on `<port>`, the receiver connects.
+//!
+//! - `DCC SEND <file> <ip> 0 <size> <token>`: Passive (reverse) DCC, for senders that can't
+//!   accept connections. The receiver listens and replies with the same request with its own
+//!   address and port, and the sender connects.
+//!
+//! - `DCC RESUME <file> <port> <pos> [<token>]`: The receiver asks the sender to start from
+//!   `<pos>`. The sender replies with `DCC ACCEPT <file> <port> <pos> [<token>]` and the transfer
+//!   continues as above.
+//!
+//! - `DCC CHAT chat <ip> <port>`: The sender listens on `<port>`, the receiver connects. Chat
+//!   messages are newline-terminated lines. Actions are sent as `\x01ACTION <msg>\x01`.
+//!
+//! In file transfers the receiver acknowledges the data by sending the number of bytes received so
+//! far as a 32-bit integer in network byte order.
+
+use crate::Event;
+use libtiny_wire as wire;
+use libtiny_wire::{Msg, Pfx};
+
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::io::SeekFrom;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{self, Sender};
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, timeout};
+
+/// Id of a DCC transfer. Ids are unique across clients.
+pub type DccId = usize;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// How long to wait for the remote side to connect, or for a connection to the remote side.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// How long to wait for the receiver to acknowledge the data we sent.
+const ACK_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// File and chat offers that are not accepted within this time are dropped.
+const OFFER_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Max. number of file offers from a nick waiting to be accepted. When a nick sends more, its
+/// oldest offer is dropped.
+const MAX_OFFERS_PER_NICK: usize = 5;
+
+/// Progress of a transfer is reported at most this often.
+const REPORT_INTERVAL: Duration = Duration::from_millis(500);
+
+const BUF_SIZE: usize = 16 * 1024;
+
+/// Max. length of a DCC CHAT line, including the newline. The chat is closed when the remote side
+/// sends a longer line.
+const MAX_CHAT_LINE_LEN: usize = 16 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DccDir {
+    /// We're sending the file.
+    Send,
+    /// We're receiving the file.
+    Recv,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DccStatus {
+    /// Offered to us, waiting for `Client::dcc_get`.
+    Offered,
+    /// Waiting for the remote side to accept the offer or connect.
+    Waiting,
+    /// Connected, transferring the file.
+    Active,
+    /// File transferred.
+    Done,
+    /// Transfer failed with the given error.
+    Failed(String),
+    /// Transfer closed with `Client::dcc_close`.
+    Closed,
+}
+
+/// A DCC file transfer. Reported to the users with `Event::Dcc` when it's created, and when its
+/// status or progress changes.
+#[derive(Debug, Clone)]
+pub struct DccTransfer {
+    pub id: DccId,
+    pub dir: DccDir,
+    /// Nick of the remote side.
+    pub nick: String,
+    /// Prefix of the offer, for files offered to us. Can be used to ignore offers.
+    pub pfx: Option<Pfx>,
+    /// File name, without directories.
+    pub file_name: String,
+    /// Local path of the file. `None` until an offer is accepted.
+    pub path: Option<PathBuf>,
+    /// Size of the file, if known.
+    pub size: Option<u64>,
+    /// Number of bytes transferred, including the resumed part.
+    pub pos: u64,
+    pub status: DccStatus,
+}
+
+impl DccTransfer {
+    /// Whether the transfer is done, failed, or closed.
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.status,
+            DccStatus::Done | DccStatus::Failed(_) | DccStatus::Closed
+        )
+    }
+}
+
+/// A DCC CHAT event. Reported to the users with `Event::DccChat`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DccChatEvent {
+    /// A chat is offered to us, waiting for `Client::dcc_chat`. `pfx` is the prefix of the offer,
+    /// can be used to ignore offers.
+    Offered { pfx: Pfx },
+    /// Connected to the remote side, messages can be sent with `Client::dcc_chat_msg`.
+    Connected,
+    /// A message from the remote side.
+    Msg { msg: String, is_action: bool },
+    /// The connection is closed by the remote side, or failed with the given error. Not reported
+    /// for chats closed with `Client::dcc_chat_close`.
+    Closed(Option<String>),
+}
+
+/// DCC transfers and chats of a client.
+#[derive(Clone, Default)]
+pub(crate) struct Dccs {
+    inner: Rc<RefCell<DccsInner>>,
+}
+
+#[derive(Default)]
+struct DccsInner {
+    transfers: Vec<Transfer>,
+
+    chats: Vec<Chat>,
+
+    /// Channel to report transfers. `None` when the client is stopped.
+    snd_ev: Option<Sender<Event>>,
+
+    /// Our address in the connection to the IRC server. Advertised in DCC requests.
+    local_ip: Option<IpAddr>,
+}
+
+struct Transfer {
+    info: DccTransfer,
+
+    /// Address of the remote side, from its request.
+    peer_ip: Option<IpAddr>,
+
+    /// Port in the request. 0 in passive requests.
+    port: u16,
+
+    /// Token of a passive request.
+    token: Option<u64>,
+
+    /// The task connecting and transferring the file.
+    task: Option<JoinHandle<()>>,
+
+    /// When the transfer was offered.
+    created: Instant,
+
+    last_report: Instant,
+}
+
+impl Transfer {
+    fn new(info: DccTransfer, peer_ip: Option<IpAddr>, port: u16, token: Option<u64>) -> Self {
+        Transfer {
+            info,
+            peer_ip,
+            port,
+            token,
+            task: None,
+            created: Instant::now(),
+            last_report: Instant::now(),
+        }
+    }
+
+    /// Whether the transfer is the one in a RESUME, ACCEPT, or passive SEND request.
+    fn matches(&self, dir: DccDir, nick: &str, port: u16, token: Option<u64>) -> bool {
+        self.info.dir == dir
+            && self.info.status == DccStatus::Waiting
+            && self.info.nick.eq_ignore_ascii_case(nick)
+            && match token {
+                Some(token) => self.token == Some(token),
+                None => port != 0 && self.port == port,
+            }
+    }
+}
+
+struct Chat {
+    /// Nick of the remote side.
+    nick: String,
+
+    /// Address of the remote side in a chat offered to us. `None` after the offer is accepted.
+    offer: Option<SocketAddr>,
+
+    /// Channel to send lines to the remote side. `None` until connected.
+    snd_line: Option<Sender<String>>,
+
+    /// The task connecting and relaying the messages.
+    task: Option<JoinHandle<()>>,
+
+    /// When the chat was offered.
+    created: Instant,
+}
+
+/// Passed to the transfer and chat tasks: how to get the connection to the remote side.
+enum Conn {
+    Connect(SocketAddr),
+    Listen(TcpListener),
+}
+
+impl Dccs {
+    pub(crate) fn start(&self, snd_ev: Sender<Event>) {
+        self.inner.borrow_mut().snd_ev = Some(snd_ev);
+    }
+
+    /// Close all transfers and chats. Called when the client stops.
+    pub(crate) fn stop(&self) {
+        let mut inner = self.inner.borrow_mut();
+        inner.snd_ev = None;
+        for chat in inner.chats.drain(..) {
+            if let Some(task) = chat.task {
+                task.abort();
+            }
+        }
+        for transfer in &mut inner.transfers {
+            if let Some(task) = transfer.task.take() {
+                task.abort();
+            }
+            if !transfer.info.is_finished() {
+                transfer.info.status = DccStatus::Closed;
+            }
+        }
+    }
+
+    pub(crate) fn set_local_ip(&self, ip: IpAddr) {
+        self.inner.borrow_mut().local_ip = Some(ip);
+    }
+
+    pub(crate) fn transfers(&self) -> Vec<DccTransfer> {
+        let inner = self.inner.borrow();
+        inner.transfers.iter().map(|t| t.info.clone()).collect()
+    }
+
+    /// Handle a DCC request. Returns whether the message is a DCC request that we handled.
+    pub(crate) fn handle_msg(&self, msg: &Msg, snd_irc_msg: &mut Sender<String>) -> bool {
+        let (pfx, nick, req) = match msg {
+            Msg {
+                pfx: Some(pfx @ (Pfx::User { nick, .. } | Pfx::Ambiguous(nick))),
+                cmd:
+                    wire::Cmd::PRIVMSG {
+                        msg,
+                        is_notice: false,
+                        ctcp: Some(wire::CTCP::Dcc),
+                        ..
+                    },
+            } => match parse_dcc_msg(msg) {
+                Some(req) => (pfx, nick, req),
+                None => return false,
+            },
+            _ => return false,
+        };
+
+        match req {
+            DccMsg::Send {
+                file,
+                ip,
+                port,
+                size,
+                token,
+            } => {
+                // Reply to our passive offer
+                if port != 0 && token.is_some() {
+                    let id = self
+                        .inner
+                        .borrow()
+                        .find(|t| t.matches(DccDir::Send, nick, port, token));
+                    if let Some(id) = id {
+                        self.spawn_transfer(id, Conn::Connect(SocketAddr::new(ip, port)));
+                        return true;
+                    }
+                }
+
+                let info = DccTransfer {
+                    id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+                    dir: DccDir::Recv,
+                    nick: nick.to_owned(),
+                    pfx: Some(pfx.clone()),
+                    file_name: sanitize_file_name(&file),
+                    path: None,
+                    size,
+                    pos: 0,
+                    status: DccStatus::Offered,
+                };
+                let mut inner = self.inner.borrow_mut();
+                inner.expire_offers();
+                let mut offers = inner.transfers.iter().enumerate().filter(|(_, t)| {
+                    t.info.status == DccStatus::Offered && t.info.nick.eq_ignore_ascii_case(nick)
+                });
+                if let Some((oldest, _)) = offers.next()
+                    && offers.count() + 1 >= MAX_OFFERS_PER_NICK
+                {
+                    debug!("Too many DCC offers from {nick}, dropping the oldest one");
+                    inner.close_offer(oldest);
+                }
+                inner.report(&info);
+                inner
+                    .transfers
+                    .push(Transfer::new(info, Some(ip), port, token));
+            }
+
+            DccMsg::Resume {
+                file,
+                port,
+                pos,
+                token,
+            } => {
+                let mut inner = self.inner.borrow_mut();
+                match inner.find(|t| t.matches(DccDir::Send, nick, port, token)) {
+                    None => debug!("Unexpected DCC RESUME from {nick}: {file:?}"),
+                    Some(id) => {
+                        let transfer = inner.get_mut(id).unwrap();
+                        let pos = pos.min(transfer.info.size.unwrap_or(0));
+                        transfer.info.pos = pos;
+                        let info = transfer.info.clone();
+                        let req = resume_req("ACCEPT", nick, &file, port, pos, token);
+                        if let Err(err) = snd_irc_msg.try_send(req) {
+                            warn!("Can't send DCC ACCEPT to {nick}: {err}");
+                        }
+                        inner.report(&info);
+                    }
+                }
+            }
+
+            DccMsg::Accept {
+                file, port, token, ..
+            } => {
+                let id = self
+                    .inner
+                    .borrow()
+                    .find(|t| t.matches(DccDir::Recv, nick, port, token));
+                match id {
+                    None => debug!("Unexpected DCC ACCEPT from {nick}: {file:?}"),
+                    Some(id) => match self.start_recv(id) {
+                        Ok(None) => {}
+                        Ok(Some(req)) => {
+                            if let Err(err) = snd_irc_msg.try_send(req) {
+                                warn!("Can't send DCC SEND to {nick}: {err}");
+                            }
+                        }
+                        Err(err) => self.finish(id, Err(err)),
+                    },
+                }
+            }
+
+            DccMsg::Chat { ip, port } => {
+                let mut inner = self.inner.borrow_mut();
+                inner.expire_offers();
+                match inner.find_chat(nick) {
+                    // Replace the previous offer
+                    Some(idx) if inner.chats[idx].offer.is_some() => {
+                        inner.chats.remove(idx);
+                    }
+                    Some(_) => {
+                        debug!("Ignoring DCC CHAT offer from {nick}: already chatting");
+                        return true;
+                    }
+                    None => {}
+                }
+                inner.chats.push(Chat {
+                    nick: nick.to_owned(),
+                    offer: Some(SocketAddr::new(ip, port)),
+                    snd_line: None,
+                    task: None,
+                    created: Instant::now(),
+                });
+                inner.report_chat(nick, DccChatEvent::Offered { pfx: pfx.clone() });
+            }
+        }
+
+        true
+    }
+
+    /// Accept a DCC CHAT offer from `nick`, or offer a chat to `nick` if there isn't an offer.
+    /// Returns the DCC CHAT request to send when offering.
+    pub(crate) fn chat(&self, nick: &str) -> Result<Option<String>, String> {
+        let offer = {
+            let inner = self.inner.borrow();
+            match inner.find_chat(nick) {
+                None => None,
+                Some(idx) => match inner.chats[idx].offer {
+                    None => return Err(format!("DCC CHAT with {nick} is already open")),
+                    Some(addr) => Some(addr),
+                },
+            }
+        };
+
+        let (conn, req) = match offer {
+            Some(addr) => (Conn::Connect(addr), None),
+            None => {
+                let ip = self.local_ip()?;
+                let (listener, port) = self.listen()?;
+                let req = wire::ctcp(nick, &format!("DCC CHAT chat {} {}", ip_arg(ip), port));
+                (Conn::Listen(listener), Some(req))
+            }
+        };
+
+        let task = tokio::task::spawn_local(chat_task(self.clone(), nick.to_owned(), conn));
+        let mut inner = self.inner.borrow_mut();
+        if let Some(idx) = inner.find_chat(nick) {
+            inner.chats.remove(idx);
+        }
+        inner.chats.push(Chat {
+            nick: nick.to_owned(),
+            offer: None,
+            snd_line: None,
+            task: Some(task),
+            created: Instant::now(),
+        });
+        Ok(req)
+    }
+
+    /// Send a message in the DCC CHAT with `nick`.
+    pub(crate) fn chat_msg(&self, nick: &str, msg: &str, is_action: bool) -> Result<(), String> {
+        let inner = self.inner.borrow();
+        let snd_line = inner
+            .find_chat(nick)
+            .and_then(|idx| inner.chats[idx].snd_line.as_ref())
+            .ok_or_else(|| format!("DCC CHAT with {nick} is not connected"))?;
+        let line = if is_action {
+            format!("\x01ACTION {msg}\x01\n")
+        } else {
+            format!("{msg}\n")
+        };
+        snd_line
+            .try_send(line)
+            .map_err(|err| format!("Can't send message: {err}"))
+    }
+
+    /// Close the DCC CHAT with `nick`, or reject an offer. Returns `false` if the chat doesn't
+    /// exist.
+    pub(crate) fn chat_close(&self, nick: &str) -> bool {
+        let mut inner = self.inner.borrow_mut();
+        match inner.find_chat(nick) {
+            None => false,
+            Some(idx) => {
+                let chat = inner.chats.remove(idx);
+                if let Some(task) = chat.task {
+                    task.abort();
+                }
+                true
+            }
+        }
+    }
+
+    /// Offer a file. Returns the id of the transfer and the DCC SEND request to send.
+    pub(crate) fn send(
+        &self,
+        nick: &str,
+        path: &Path,
+        passive: bool,
+    ) -> Result<(DccId, String), String> {
+        let metadata = std::fs::metadata(path)
+            .map_err(|err| format!("Can't read {}: {err}", path.display()))?;
+        if !metadata.is_file() {
+            return Err(format!("{} is not a file", path.display()));
+        }
+        let file_name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(file_name) => file_name.to_owned(),
+            None => return Err(format!("Invalid file name: {}", path.display())),
+        };
+        let ip = self.local_ip()?;
+
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let (listener, port, token) = if passive {
+            (None, 0, Some(id as u64))
+        } else {
+            let (listener, port) = self.listen()?;
+            (Some(listener), port, None)
+        };
+
+        let req = send_req(nick, &file_name, ip, port, metadata.len(), token);
+
+        let info = DccTransfer {
+            id,
+            dir: DccDir::Send,
+            nick: nick.to_owned(),
+            pfx: None,
+            file_name,
+            path: Some(path.to_owned()),
+            size: Some(metadata.len()),
+            pos: 0,
+            status: DccStatus::Waiting,
+        };
+        {
+            let mut inner = self.inner.borrow_mut();
+            inner.report(&info);
+            inner.transfers.push(Transfer::new(info, None, port, token));
+        }
+
+        if let Some(listener) = listener {
+            self.spawn_transfer(id, Conn::Listen(listener));
+        }
+
+        Ok((id, req))
+    }
+
+    /// Accept an offer, saving the file to `dir`. With `resume`, a file in `dir` with the same name
+    /// that is smaller than the offer is taken as a partial download and the download is resumed.
+    /// Otherwise a new file is created. Returns the DCC request to send, if any.
+    pub(crate) fn get(
+        &self,
+        id: DccId,
+        dir: &Path,
+        resume: bool,
+    ) -> Result<Option<String>, String> {
+        {
+            let mut inner = self.inner.borrow_mut();
+            let transfer = match inner.get_mut(id) {
+                Some(transfer)
+                    if transfer.info.dir == DccDir::Recv
+                        && transfer.info.status == DccStatus::Offered =>
+                {
+                    transfer
+                }
+                _ => return Err(format!("No DCC offer with id {id}")),
+            };
+
+            std::fs::create_dir_all(dir)
+                .map_err(|err| format!("Can't create {}: {err}", dir.display()))?;
+
+            let path = dir.join(&transfer.info.file_name);
+            let partial_size = match (std::fs::metadata(&path), transfer.info.size) {
+                (Ok(metadata), Some(size))
+                    if resume
+                        && metadata.is_file()
+                        && metadata.len() > 0
+                        && metadata.len() < size =>
+                {
+                    Some(metadata.len())
+                }
+                _ => None,
+            };
+
+            if let Some(pos) = partial_size {
+                transfer.info.path = Some(path);
+                transfer.info.pos = pos;
+                transfer.info.status = DccStatus::Waiting;
+                let req = resume_req(
+                    "RESUME",
+                    &transfer.info.nick,
+                    &transfer.info.file_name,
+                    transfer.port,
+                    pos,
+                    transfer.token,
+                );
+                let info = transfer.info.clone();
+                inner.report(&info);
+                return Ok(Some(req));
+            }
+
+            transfer.info.path = Some(unique_path(dir, &transfer.info.file_name));
+        }
+
+        self.start_recv(id)
+    }
+
+    /// Close a transfer. Returns `false` if the transfer doesn't exist.
+    pub(crate) fn close(&self, id: DccId) -> bool {
+        let mut inner = self.inner.borrow_mut();
+        match inner.transfers.iter().position(|t| t.info.id == id) {
+            None => false,
+            Some(idx) => {
+                let mut transfer = inner.transfers.remove(idx);
+                if let Some(task) = transfer.task.take() {
+                    task.abort();
+                }
+                transfer.info.status = DccStatus::Closed;
+                inner.report(&transfer.info);
+                true
+            }
+        }
+    }
+
+    /// Connect to the sender, or listen for the sender in passive offers. Returns the DCC SEND
+    /// request to send in passive offers.
+    fn start_recv(&self, id: DccId) -> Result<Option<String>, String> {
+        let (nick, file_name, peer_ip, port, size, token) = {
+            let inner = self.inner.borrow();
+            let transfer = inner.get(id).unwrap();
+            (
+                transfer.info.nick.clone(),
+                transfer.info.file_name.clone(),
+                transfer.peer_ip,
+                transfer.port,
+                transfer.info.size,
+                transfer.token,
+            )
+        };
+
+        if port != 0 {
+            let peer_ip = peer_ip.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+            self.spawn_transfer(id, Conn::Connect(SocketAddr::new(peer_ip, port)));
+            Ok(None)
+        } else {
+            let ip = self.local_ip()?;
+            let (listener, port) = self.listen()?;
+            self.spawn_transfer(id, Conn::Listen(listener));
+            Ok(Some(send_req(
+                &nick,
+                &file_name,
+                ip,
+                port,
+                size.unwrap_or(0),
+                token,
+            )))
+        }
+    }
+
+    fn local_ip(&self) -> Result<IpAddr, String> {
+        self.inner
+            .borrow()
+            .local_ip
+            .ok_or_else(|| "Not connected to the server".to_owned())
+    }
+
+    fn listen(&self) -> Result<(TcpListener, u16), String> {
+        let ip = self.local_ip()?;
+        let listen = || -> std::io::Result<(TcpListener, u16)> {
+            let listener = std::net::TcpListener::bind((ip, 0))?;
+            listener.set_nonblocking(true)?;
+            let port = listener.local_addr()?.port();
+            Ok((TcpListener::from_std(listener)?, port))
+        };
+        listen().map_err(|err| format!("Can't listen on {ip}: {err}"))
+    }
+
+    fn spawn_transfer(&self, id: DccId, conn: Conn) {
+        let task = tokio::task::spawn_local(transfer_task(self.clone(), id, conn));
+        let mut inner = self.inner.borrow_mut();
+        if let Some(transfer) = inner.get_mut(id) {
+            transfer.task = Some(task);
+            transfer.info.status = DccStatus::Waiting;
+            let info = transfer.info.clone();
+            inner.report(&info);
+        }
+    }
+
+    /// Called by the transfer task when connected. Returns the direction, path, position, and
+    /// size of the transfer.
+    fn activate(&self, id: DccId) -> Option<(DccDir, PathBuf, u64, Option<u64>)> {
+        let mut inner = self.inner.borrow_mut();
+        let transfer = inner.get_mut(id)?;
+        transfer.info.status = DccStatus::Active;
+        let info = transfer.info.clone();
+        inner.report(&info);
+        Some((info.dir, info.path?, info.pos, info.size))
+    }
+
+    fn progress(&self, id: DccId, pos: u64) {
+        let mut inner = self.inner.borrow_mut();
+        let DccsInner {
+            transfers, snd_ev, ..
+        } = &mut *inner;
+        if let Some(transfer) = transfers.iter_mut().find(|t| t.info.id == id) {
+            transfer.info.pos = pos;
+            let now = Instant::now();
+            if now.duration_since(transfer.last_report) >= REPORT_INTERVAL {
+                transfer.last_report = now;
+                if let Some(snd_ev) = snd_ev {
+                    // Progress reports can be dropped when the channel is full
+                    let _ = snd_ev.try_send(Event::Dcc(transfer.info.clone()));
+                }
+            }
+        }
+    }
+
+    fn finish(&self, id: DccId, result: Result<(), String>) {
+        let mut inner = self.inner.borrow_mut();
+        if let Some(transfer) = inner.get_mut(id) {
+            transfer.task = None;
+            transfer.info.status = match result {
+                Ok(()) => DccStatus::Done,
+                Err(err) => DccStatus::Failed(err),
+            };
+            let info = transfer.info.clone();
+            inner.report(&info);
+        }
+    }
+}
+
+impl DccsInner {
+    fn get(&self, id: DccId) -> Option<&Transfer> {
+        self.transfers.iter().find(|t| t.info.id == id)
+    }
+
+    fn get_mut(&mut self, id: DccId) -> Option<&mut Transfer> {
+        self.transfers.iter_mut().find(|t| t.info.id == id)
+    }
+
+    fn find<F: Fn(&Transfer) -> bool>(&self, f: F) -> Option<DccId> {
+        self.transfers.iter().find(|t| f(t)).map(|t| t.info.id)
+    }
+
+    /// Drop file and chat offers that were not accepted within `OFFER_TIMEOUT`.
+    fn expire_offers(&mut self) {
+        let now = Instant::now();
+        while let Some(idx) = self.transfers.iter().position(|t| {
+            t.info.status == DccStatus::Offered && now.duration_since(t.created) >= OFFER_TIMEOUT
+        }) {
+            self.close_offer(idx);
+        }
+        while let Some(idx) = self.chats.iter().position(|chat| {
+            chat.offer.is_some() && now.duration_since(chat.created) >= OFFER_TIMEOUT
+        }) {
+            let chat = self.chats.remove(idx);
+            self.report_chat(
+                &chat.nick,
+                DccChatEvent::Closed(Some("Offer expired".to_owned())),
+            );
+        }
+    }
+
+    /// Remove the file offer at `idx` in `transfers`.
+    fn close_offer(&mut self, idx: usize) {
+        let mut transfer = self.transfers.remove(idx);
+        transfer.info.status = DccStatus::Closed;
+        self.report(&transfer.info);
+    }
+
+    /// Events are dropped when the channel is full, to avoid blocking the connection or crashing
+    /// when the remote side floods us with requests.
+    fn report(&self, info: &DccTransfer) {
+        if let Some(snd_ev) = &self.snd_ev
+            && let Err(err) = snd_ev.try_send(Event::Dcc(info.clone()))
+        {
+            warn!("Dropping DCC event: {err}");
+        }
+    }
+
+    fn find_chat(&self, nick: &str) -> Option<usize> {
+        self.chats
+            .iter()
+            .position(|chat| chat.nick.eq_ignore_ascii_case(nick))
+    }
+
+    fn report_chat(&self, nick: &str, ev: DccChatEvent) {
+        if let Some(snd_ev) = &self.snd_ev
+            && let Err(err) = snd_ev.try_send(Event::DccChat {
+                nick: nick.to_owned(),
+                ev,
+            })
+        {
+            warn!("Dropping DCC CHAT event: {err}");
+        }
+    }
+}
+
+async fn transfer_task(dccs: Dccs, id: DccId, conn: Conn) {
+    let result = transfer(&dccs, id, conn).await;
+    dccs.finish(id, result);
+}
+
+async fn transfer(dccs: &Dccs, id: DccId, conn: Conn) -> Result<(), String> {
+    let stream = connect(conn).await?;
+
+    let (dir, path, pos, size) = match dccs.activate(id) {
+        Some(transfer) => transfer,
+        None => return Err("Transfer closed".to_owned()),
+    };
+
+    match dir {
+        DccDir::Recv => recv_file(dccs, id, stream, &path, pos, size).await,
+        DccDir::Send => send_file(dccs, id, stream, &path, pos, size.unwrap_or(0)).await,
+    }
+}
+
+async fn recv_file(
+    dccs: &Dccs,
+    id: DccId,
+    mut stream: TcpStream,
+    path: &Path,
+    mut pos: u64,
+    size: Option<u64>,
+) -> Result<(), String> {
+    // Never overwrite existing files: downloads resumed with `/dcc get <id> -resume` append, new
+    // downloads need a new file.
+    let file = if pos == 0 {
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .await
+    } else {
+        OpenOptions::new().append(true).open(path).await
+    };
+    let mut file = file.map_err(|err| format!("Can't open {}: {err}", path.display()))?;
+
+    let mut buf = vec![0; BUF_SIZE];
+    while size.is_none_or(|size| pos < size) {
+        // Stop at the advertised size, the sender can't make us write more
+        let max_read = size.map_or(BUF_SIZE, |size| (size - pos).min(BUF_SIZE as u64) as usize);
+        let n_read = stream
+            .read(&mut buf[..max_read])
+            .await
+            .map_err(|err| err.to_string())?;
+        if n_read == 0 {
+            break;
+        }
+        file.write_all(&buf[..n_read])
+            .await
+            .map_err(|err| format!("Can't write {}: {err}", path.display()))?;
+        pos += n_read as u64;
+        // Positions in acknowledgements are 32-bit
+        stream
+            .write_all(&(pos as u32).to_be_bytes())
+            .await
+            .map_err(|err| err.to_string())?;
+        dccs.progress(id, pos);
+    }
+    file.flush().await.map_err(|err| err.to_string())?;
+    dccs.progress(id, pos);
+
+    match size {
+        Some(size) if pos < size => Err(format!("Connection closed after {pos} of {size} bytes")),
+        _ => Ok(()),
+    }
+}
+
+async fn send_file(
+    dccs: &Dccs,
+    id: DccId,
+    stream: TcpStream,
+    path: &Path,
+    mut pos: u64,
+    size: u64,
+) -> Result<(), String> {
+    let mut file = File::open(path)
+        .await
+        .map_err(|err| format!("Can't open {}: {err}", path.display()))?;
+    file.seek(SeekFrom::Start(pos))
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let (mut read_half, mut write_half) = stream.into_split();
+
+    let send = async {
+        let mut buf = vec![0; BUF_SIZE];
+        loop {
+            let n_read = file
+                .read(&mut buf)
+                .await
+                .map_err(|err| format!("Can't read {}: {err}", path.display()))?;
+            if n_read == 0 {
+                return Ok(());
+            }
+            write_half
+                .write_all(&buf[..n_read])
+                .await
+                .map_err(|err| err.to_string())?;
+            pos += n_read as u64;
+            dccs.progress(id, pos);
+        }
+    };
+
+    // Read acknowledgements until the whole file is acknowledged or the receiver closes the
+    // connection. We need to read these while sending, otherwise the receiver may block.
+    let acks = async {
+        let mut ack = [0u8; 4];
+        loop {
+            match timeout(ACK_TIMEOUT, read_half.read_exact(&mut ack)).await {
+                Err(_) => return Err("Timed out waiting for acknowledgement".to_owned()),
+                Ok(Ok(_)) if u32::from_be_bytes(ack) == size as u32 => return Ok(()),
+                Ok(Ok(_)) => {}
+                Ok(Err(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+                Ok(Err(err)) => return Err(err.to_string()),
+            }
+        }
+    };
+
+    tokio::try_join!(send, acks)?;
+    dccs.progress(id, pos);
+    Ok(())
+}
+
+async fn chat_task(dccs: Dccs, nick: String, conn: Conn) {
+    let result = chat(&dccs, &nick, conn).await;
+    let mut inner = dccs.inner.borrow_mut();
+    if let Some(idx) = inner.find_chat(&nick) {
+        inner.chats.remove(idx);
+    }
+    inner.report_chat(&nick, DccChatEvent::Closed(result.err()));
+}
+
+async fn chat(dccs: &Dccs, nick: &str, conn: Conn) -> Result<(), String> {
+    let stream = connect(conn).await?;
+
+    let (snd_line, mut rcv_line) = mpsc::channel::<String>(100);
+    {
+        let mut inner = dccs.inner.borrow_mut();
+        match inner.find_chat(nick) {
+            None => return Ok(()),
+            Some(idx) => inner.chats[idx].snd_line = Some(snd_line),
+        }
+        inner.report_chat(nick, DccChatEvent::Connected);
+    }
+
+    let (read_half, mut write_half) = stream.into_split();
+    let mut read_half = BufReader::new(read_half);
+    let mut buf = vec![];
+    loop {
+        // `buf` may have a part of the line when the other branch was taken
+        let mut line_reader = (&mut read_half).take((MAX_CHAT_LINE_LEN - buf.len()) as u64);
+        tokio::select! {
+            n_read = line_reader.read_until(b'\n', &mut buf) => {
+                let n_read = n_read.map_err(|err| err.to_string())?;
+                if n_read == 0 {
+                    return Ok(());
+                }
+                if buf.len() == MAX_CHAT_LINE_LEN && !buf.ends_with(b"\n") {
+                    return Err(format!("Line longer than {MAX_CHAT_LINE_LEN} bytes"));
+                }
+                let line = String::from_utf8_lossy(&buf);
+                let line = line.trim_end_matches(['\r', '\n']);
+                let ev = match line
+                    .strip_prefix("\x01ACTION ")
+                    .and_then(|msg| msg.strip_suffix('\x01'))
+                {
+                    Some(msg) => DccChatEvent::Msg {
+                        msg: msg.to_owned(),
+                        is_action: true,
+                    },
+                    None => DccChatEvent::Msg {
+                        msg: line.to_owned(),
+                        is_action: false,
+                    },
+                };
+                dccs.inner.borrow().report_chat(nick, ev);
+                buf.clear();
+            }
+            line = rcv_line.recv() => {
+                match line {
+                    None => return Ok(()),
+                    Some(line) => write_half
+                        .write_all(line.as_bytes())
+                        .await
+                        .map_err(|err| err.to_string())?,
+                }
+            }
+        }
+    }
+}
+
+/// Connect to the remote side, or wait for the remote side to connect.
+async fn connect(conn: Conn) -> Result<TcpStream, String> {
+    let connect = async {
+        match conn {
+            Conn::Connect(addr) => TcpStream::connect(addr).await,
+            Conn::Listen(listener) => listener.accept().await.map(|(stream, _)| stream),
+        }
+    };
+    match timeout(CONNECT_TIMEOUT, connect).await {
+        Err(_) => Err("Timed out waiting for connection".to_owned()),
+        Ok(Err(err)) => Err(format!("Connection error: {err}")),
+        Ok(Ok(stream)) => Ok(stream),
+    }
+}
+
+//
+// Parsing and generating DCC requests
+//
+
+#[derive(Debug, PartialEq, Eq)]
+enum DccMsg {
+    Send {
+        file: String,
+        ip: IpAddr,
+        port: u16,
+        size: Option<u64>,
+        token: Option<u64>,
+    },
+    Resume {
+        file: String,
+        port: u16,
+        pos: u64,
+        token: Option<u64>,
+    },
+    Accept {
+        file: String,
+        port: u16,
+        pos: u64,
+        token: Option<u64>,
+    },
+    Chat {
+        ip: IpAddr,
+        port: u16,
+    },
+}
+
+/// Parse a DCC request, without the "DCC " prefix.
+fn parse_dcc_msg(msg: &str) -> Option<DccMsg> {
+    let (ty, rest) = msg.split_once(' ')?;
+    let (file, rest) = split_file_name(rest.trim_start())?;
+    let file = file.to_owned();
+    let mut args = rest.split_whitespace();
+    match ty {
+        "SEND" => {
+            let ip = parse_ip(args.next()?)?;
+            let port = args.next()?.parse().ok()?;
+            let size = args.next().and_then(|size| size.parse().ok());
+            let token = args.next().and_then(|token| token.parse().ok());
+            Some(DccMsg::Send {
+                file,
+                ip,
+                port,
+                size,
+                token,
+            })
+        }
+        "RESUME" | "ACCEPT" => {
+            let port = args.next()?.parse().ok()?;
+            let pos = args.next()?.parse().ok()?;
+            let token = args.next().and_then(|token| token.parse().ok());
+            Some(if ty == "RESUME" {
+                DccMsg::Resume {
+                    file,
+                    port,
+                    pos,
+                    token,
+                }
+            } else {
+                DccMsg::Accept {
+                    file,
+                    port,
+                    pos,
+                    token,
+                }
+            })
+        }
+        "CHAT" if file == "chat" => {
+            let ip = parse_ip(args.next()?)?;
+            let port = args.next()?.parse().ok()?;
+            Some(DccMsg::Chat { ip, port })
+        }
+        _ => None,
+    }
+}
+
+/// Split a (possibly quoted) file name from the rest of the arguments.
+fn split_file_name(args: &str) -> Option<(&str, &str)> {
+    match args.strip_prefix('"') {
+        Some(args) => args.split_once('"'),
+        None => args.split_once(' '),
+    }
+}
+
+/// Parse an IPv4 address as a 32-bit integer, or an IPv6 address.
+fn parse_ip(s: &str) -> Option<IpAddr> {
+    match s.parse::<u32>() {
+        Ok(ip) => Some(IpAddr::V4(Ipv4Addr::from(ip))),
+        Err(_) => s.parse::<IpAddr>().ok(),
+    }
+}
+
+fn ip_arg(ip: IpAddr) -> String {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => u32::from(ip).to_string(),
+        IpAddr::V6(ip) => ip.to_string(),
+    }
+}
+
+fn file_name_arg(file: &str) -> String {
+    if file.contains(' ') {
+        format!("\"{file}\"")
+    } else {
+        file.to_owned()
+    }
+}
+
+fn send_req(
+    nick: &str,
+    file: &str,
+    ip: IpAddr,
+    port: u16,
+    size: u64,
+    token: Option<u64>,
+) -> String {
+    let mut req = format!(
+        "DCC SEND {} {} {} {}",
+        file_name_arg(file),
+        ip_arg(ip),
+        port,
+        size
+    );
+    if let Some(token) = token {
+        write!(req, " {token}").unwrap();
+    }
+    wire::ctcp(nick, &req)
+}
+
+fn resume_req(ty: &str, nick: &str, file: &str, port: u16, pos: u64, token: Option<u64>) -> String {
+    let mut req = format!("DCC {} {} {} {}", ty, file_name_arg(file), port, pos);
+    if let Some(token) = token {
+        write!(req, " {token}").unwrap();
+    }
+    wire::ctcp(nick, &req)
+}
+
+/// Remove directories from a file name in an offer, to make sure we only write to the download
+/// directory.
+fn sanitize_file_name(file: &str) -> String {
+    let name = file.rsplit(['/', '\\']).next().unwrap_or("").trim();
+    if name.is_empty() || name == "." || name == ".." {
+        "file".to_owned()
+    } else {
+        name.to_owned()
+    }
+}
+
+/// Find a path for `file_name` in `dir` that doesn't exist, by adding a number to the file name
+/// when necessary: "file.txt", "file.1.txt", "file.2.txt", ...
+fn unique_path(dir: &Path, file_name: &str) -> PathBuf {
+    let path = dir.join(file_name);
+    if !path.exists() {
+        return path;
+    }
+    let (stem, ext) = match file_name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, Some(ext)),
+        _ => (file_name, None),
+    };
+    (1..)
+        .map(|i| match ext {
+            Some(ext) => dir.join(format!("{stem}.{i}.{ext}")),
+            None => dir.join(format!("{stem}.{i}")),
+        })
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn test_parse_dcc_msg() {
+        assert_eq!(
+            parse_dcc_msg("SEND file.txt 2130706433 1234 100"),
+            Some(DccMsg::Send {
+                file: "file.txt".to_owned(),
+                ip: "127.0.0.1".parse().unwrap(),
+                port: 1234,
+                size: Some(100),
+                token: None,
+            })
+        );
+        assert_eq!(
+            parse_dcc_msg("SEND \"a file.txt\" ::1 0 100 7"),
+            Some(DccMsg::Send {
+                file: "a file.txt".to_owned(),
+                ip: "::1".parse().unwrap(),
+                port: 0,
+                size: Some(100),
+                token: Some(7),
+            })
+        );
+        assert_eq!(
+            parse_dcc_msg("RESUME file.txt 1234 50"),
+            Some(DccMsg::Resume {
+                file: "file.txt".to_owned(),
+                port: 1234,
+                pos: 50,
+                token: None,
+            })
+        );
+        assert_eq!(
+            parse_dcc_msg("ACCEPT \"a file.txt\" 0 50 7"),
+            Some(DccMsg::Accept {
+                file: "a file.txt".to_owned(),
+                port: 0,
+                pos: 50,
+                token: Some(7),
+            })
+        );
+        assert_eq!(parse_dcc_msg("SEND file.txt"), None);
+        assert_eq!(parse_dcc_msg("SEND file.txt x.y 1234"), None);
+        assert_eq!(
+            parse_dcc_msg("CHAT chat 2130706433 1234"),
+            Some(DccMsg::Chat {
+                ip: "127.0.0.1".parse().unwrap(),
+                port: 1234,
+            })
+        );
+        assert_eq!(parse_dcc_msg("CHAT whiteboard 2130706433 1234"), None);
+    }
+
+    #[test]
+    fn test_sanitize_file_name() {
+        assert_eq!(sanitize_file_name("file.txt"), "file.txt");
+        assert_eq!(sanitize_file_name("../../.bashrc"), ".bashrc");
+        assert_eq!(sanitize_file_name("C:\\x\\file.txt"), "file.txt");
+        assert_eq!(sanitize_file_name(".."), "file");
+        assert_eq!(sanitize_file_name("dir/"), "file");
+    }
+
+    #[test]
+    fn test_unique_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        assert_eq!(unique_path(dir, "a.txt"), dir.join("a.txt"));
+        std::fs::write(dir.join("a.txt"), "").unwrap();
+        std::fs::write(dir.join("a.1.txt"), "").unwrap();
+        assert_eq!(unique_path(dir, "a.txt"), dir.join("a.2.txt"));
+        std::fs::write(dir.join("a"), "").unwrap();
+        assert_eq!(unique_path(dir, "a"), dir.join("a.1"));
+    }
+
+    /// Make a message from `nick` with the given IRC message that we send.
+    fn incoming(nick: &str, msg: &str) -> Msg {
+        let mut buf = format!(":{nick}!u@h {msg}").into_bytes();
+        wire::parse_irc_msg(&mut buf).unwrap().unwrap()
+    }
+
+    fn new_dccs() -> (Dccs, mpsc::Receiver<Event>) {
+        let (snd_ev, rcv_ev) = mpsc::channel(100);
+        let dccs = Dccs::default();
+        dccs.start(snd_ev);
+        dccs.set_local_ip("127.0.0.1".parse().unwrap());
+        (dccs, rcv_ev)
+    }
+
+    /// Wait until transfer `id` is finished, return its final state.
+    async fn wait_finished(rcv_ev: &mut mpsc::Receiver<Event>, id: DccId) -> DccTransfer {
+        loop {
+            match rcv_ev.recv().await {
+                Some(Event::Dcc(transfer)) if transfer.id == id && transfer.is_finished() => {
+                    return transfer;
+                }
+                Some(_) => {}
+                None => panic!("Channel closed"),
+            }
+        }
+    }
+
+    fn offer_id(rcv_ev: &mut mpsc::Receiver<Event>) -> DccId {
+        match rcv_ev.try_recv() {
+            Ok(Event::Dcc(transfer)) => {
+                assert_eq!(transfer.status, DccStatus::Offered);
+                transfer.id
+            }
+            other => panic!("Unexpected event: {other:?}"),
+        }
+    }
+
+    fn test_transfer(passive: bool, partial: usize) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let local = tokio::task::LocalSet::new();
+
+        local.block_on(&runtime, async move {
+            let send_dir = tempfile::tempdir().unwrap();
+            let recv_dir = tempfile::tempdir().unwrap();
+            let contents: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+            let send_path = send_dir.path().join("a file.bin");
+            std::fs::write(&send_path, &contents).unwrap();
+            if partial != 0 {
+                std::fs::write(recv_dir.path().join("a file.bin"), &contents[..partial]).unwrap();
+            }
+
+            let (sender, mut sender_evs) = new_dccs();
+            let (receiver, mut receiver_evs) = new_dccs();
+            let (mut snd_sender_msg, mut rcv_sender_msg) = mpsc::channel(10);
+            let (mut snd_receiver_msg, mut rcv_receiver_msg) = mpsc::channel(10);
+
+            let (send_id, req) = sender.send("b", &send_path, passive).unwrap();
+            assert!(receiver.handle_msg(&incoming("a", &req), &mut snd_receiver_msg));
+            let recv_id = offer_id(&mut receiver_evs);
+
+            let mut req = receiver
+                .get(recv_id, recv_dir.path(), partial != 0)
+                .unwrap();
+            if partial != 0 {
+                // RESUME, ACCEPT
+                assert!(sender.handle_msg(&incoming("b", &req.unwrap()), &mut snd_sender_msg));
+                let accept = rcv_sender_msg.try_recv().unwrap();
+                assert!(receiver.handle_msg(&incoming("a", &accept), &mut snd_receiver_msg));
+                req = rcv_receiver_msg.try_recv().ok();
+            }
+            if passive {
+                // Receiver's reply to the passive offer
+                assert!(sender.handle_msg(&incoming("b", &req.unwrap()), &mut snd_sender_msg));
+            } else {
+                assert_eq!(req, None);
+            }
+
+            let sent = wait_finished(&mut sender_evs, send_id).await;
+            assert_eq!(sent.status, DccStatus::Done);
+            let received = wait_finished(&mut receiver_evs, recv_id).await;
+            assert_eq!(received.status, DccStatus::Done);
+            assert_eq!(received.pos, contents.len() as u64);
+
+            let recv_path = received.path.unwrap();
+            assert_eq!(recv_path, recv_dir.path().join("a file.bin"));
+            assert_eq!(std::fs::read(recv_path).unwrap(), contents);
+        });
+    }
+
+    #[test]
+    fn test_send_recv() {
+        test_transfer(false, 0);
+    }
+
+    #[test]
+    fn test_send_recv_passive() {
+        test_transfer(true, 0);
+    }
+
+    #[test]
+    fn test_send_recv_resume() {
+        test_transfer(false, 12345);
+        test_transfer(true, 12345);
+    }
+
+    #[test]
+    fn test_no_overwrite() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let local = tokio::task::LocalSet::new();
+
+        local.block_on(&runtime, async move {
+            // (offer size, resume). An existing file larger than the offer can't be a partial
+            // download. A smaller one is only resumed when asked.
+            for (size, resume) in [(5, true), (50, false)] {
+                let recv_dir = tempfile::tempdir().unwrap();
+                std::fs::write(recv_dir.path().join("file.txt"), "existing file").unwrap();
+
+                let (receiver, mut receiver_evs) = new_dccs();
+                let (mut snd_msg, _rcv_msg) = mpsc::channel(10);
+                let req = wire::ctcp("b", &format!("DCC SEND file.txt 2130706433 1 {size}"));
+                assert!(receiver.handle_msg(&incoming("a", &req), &mut snd_msg));
+                let id = offer_id(&mut receiver_evs);
+                receiver.get(id, recv_dir.path(), resume).unwrap();
+
+                let transfer = receiver
+                    .transfers()
+                    .into_iter()
+                    .find(|t| t.id == id)
+                    .unwrap();
+                assert_eq!(transfer.path, Some(recv_dir.path().join("file.1.txt")));
+                assert!(receiver.close(id));
+                assert_eq!(
+                    std::fs::read_to_string(recv_dir.path().join("file.txt")).unwrap(),
+                    "existing file"
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn test_recv_stops_at_size() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let local = tokio::task::LocalSet::new();
+
+        local.block_on(&runtime, async move {
+            let recv_dir = tempfile::tempdir().unwrap();
+            let (receiver, mut receiver_evs) = new_dccs();
+            let (mut snd_msg, _rcv_msg) = mpsc::channel(10);
+
+            // Sender advertises 10 bytes but sends more
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+            tokio::task::spawn_local(async move {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let _ = stream.write_all(&[b'x'; 1000]).await;
+            });
+
+            let req = wire::ctcp("b", &format!("DCC SEND file.txt 2130706433 {port} 10"));
+            assert!(receiver.handle_msg(&incoming("a", &req), &mut snd_msg));
+            let id = offer_id(&mut receiver_evs);
+            receiver.get(id, recv_dir.path(), false).unwrap();
+
+            let received = wait_finished(&mut receiver_evs, id).await;
+            assert_eq!(received.status, DccStatus::Done);
+            assert_eq!(received.pos, 10);
+            assert_eq!(std::fs::read(received.path.unwrap()).unwrap(), [b'x'; 10]);
+        });
+    }
+
+    #[test]
+    fn test_offer_limits() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .start_paused(true)
+            .build()
+            .unwrap();
+        let local = tokio::task::LocalSet::new();
+
+        local.block_on(&runtime, async move {
+            let (dccs, mut rcv_ev) = new_dccs();
+            let (mut snd_msg, _rcv_msg) = mpsc::channel(10);
+            let offers = |dccs: &Dccs| {
+                dccs.transfers()
+                    .into_iter()
+                    .filter(|t| t.status == DccStatus::Offered)
+                    .map(|t| t.file_name)
+                    .collect::<Vec<_>>()
+            };
+
+            // Oldest offer of a nick is dropped when it sends too many
+            for i in 0..=MAX_OFFERS_PER_NICK {
+                let req = wire::ctcp("b", &format!("DCC SEND {i}.txt 2130706433 1 10"));
+                assert!(dccs.handle_msg(&incoming("a", &req), &mut snd_msg));
+            }
+            let expected: Vec<String> = (1..=MAX_OFFERS_PER_NICK)
+                .map(|i| format!("{i}.txt"))
+                .collect();
+            assert_eq!(offers(&dccs), expected);
+
+            // Flooding the event channel doesn't crash
+            for i in 0..200 {
+                let req = wire::ctcp("b", "DCC CHAT chat 2130706433 1");
+                assert!(dccs.handle_msg(&incoming(&format!("n{i}"), &req), &mut snd_msg));
+            }
+            while rcv_ev.try_recv().is_ok() {}
+
+            // Offers expire
+            tokio::time::advance(OFFER_TIMEOUT).await;
+            let req = wire::ctcp("b", "DCC CHAT chat 2130706433 1");
+            assert!(dccs.handle_msg(&incoming("c", &req), &mut snd_msg));
+            assert!(offers(&dccs).is_empty());
+            let chats: Vec<String> = dccs
+                .inner
+                .borrow()
+                .chats
+                .iter()
+                .map(|chat| chat.nick.clone())
+                .collect();
+            assert_eq!(chats, vec!["c".to_owned()]);
+        });
+    }
+
+    /// Wait for the next DCC CHAT event.
+    async fn next_chat_ev(rcv_ev: &mut mpsc::Receiver<Event>) -> (String, DccChatEvent) {
+        loop {
+            match rcv_ev.recv().await {
+                Some(Event::DccChat { nick, ev }) => return (nick, ev),
+                Some(_) => {}
+                None => panic!("Channel closed"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_chat() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let local = tokio::task::LocalSet::new();
+
+        local.block_on(&runtime, async move {
+            let (a, mut a_evs) = new_dccs();
+            let (b, mut b_evs) = new_dccs();
+            let (mut snd_msg, _rcv_msg) = mpsc::channel(10);
+
+            let req = a.chat("b").unwrap().unwrap();
+            assert!(b.handle_msg(&incoming("a", &req), &mut snd_msg));
+            assert_eq!(
+                next_chat_ev(&mut b_evs).await,
+                (
+                    "a".to_owned(),
+                    DccChatEvent::Offered {
+                        pfx: Pfx::User {
+                            nick: "a".to_owned(),
+                            user: "u@h".to_owned()
+                        }
+                    }
+                )
+            );
+            assert_eq!(b.chat("a").unwrap(), None);
+            assert_eq!(
+                next_chat_ev(&mut a_evs).await,
+                ("b".to_owned(), DccChatEvent::Connected)
+            );
+            assert_eq!(
+                next_chat_ev(&mut b_evs).await,
+                ("a".to_owned(), DccChatEvent::Connected)
+            );
+            assert!(a.chat("b").is_err());
+
+            a.chat_msg("b", "hi", false).unwrap();
+            a.chat_msg("b", "waves", true).unwrap();
+            assert_eq!(
+                next_chat_ev(&mut b_evs).await,
+                (
+                    "a".to_owned(),
+                    DccChatEvent::Msg {
+                        msg: "hi".to_owned(),
+                        is_action: false
+                    }
+                )
+            );
+            assert_eq!(
+                next_chat_ev(&mut b_evs).await,
+                (
+                    "a".to_owned(),
+                    DccChatEvent::Msg {
+                        msg: "waves".to_owned(),
+                        is_action: true
+                    }
+                )
+            );
+
+            // Closing the chat closes the connection
+            assert!(b.chat_close("a"));
+            assert!(!b.chat_close("a"));
+            assert_eq!(
+                next_chat_ev(&mut a_evs).await,
+                ("b".to_owned(), DccChatEvent::Closed(None))
+            );
+            assert!(a.chat_msg("b", "hi", false).is_err());
+        });
+    }
+
+    #[test]
+    fn test_chat_long_line() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let local = tokio::task::LocalSet::new();
+
+        local.block_on(&runtime, async move {
+            let (a, mut a_evs) = new_dccs();
+            let (b, mut b_evs) = new_dccs();
+            let (mut snd_msg, _rcv_msg) = mpsc::channel(10);
+
+            let req = a.chat("b").unwrap().unwrap();
+            assert!(b.handle_msg(&incoming("a", &req), &mut snd_msg));
+            next_chat_ev(&mut b_evs).await; // Offered
+            b.chat("a").unwrap();
+            next_chat_ev(&mut a_evs).await; // Connected
+            next_chat_ev(&mut b_evs).await; // Connected
+
+            a.chat_msg("b", &"x".repeat(MAX_CHAT_LINE_LEN), false)
+                .unwrap();
+            assert_eq!(
+                next_chat_ev(&mut b_evs).await,
+                (
+                    "a".to_owned(),
+                    DccChatEvent::Closed(Some(format!(
+                        "Line longer than {MAX_CHAT_LINE_LEN} bytes"
+                    )))
+                )
+            );
+        });
+    }
+}
@@ -1,19 +1,37 @@
 #![allow(clippy::unneeded_field_pattern)]
 #![allow(clippy::cognitive_complexity)]
 
+pub mod bot;
+mod connect;
+mod dcc;
+mod netsplit;
 mod pinger;
+mod record;
 mod state;
 mod stream;
+mod sts;
 mod utils;
 
-use libtiny_common::{ChanName, ChanNameRef};
+#[cfg(test)]
+mod tests;
+
+use libtiny_common::{ChanName, ChanNameRef, wildcard_match};
 pub use libtiny_wire as wire;
 
+pub use connect::AddrFamily;
+pub use dcc::{DccChatEvent, DccDir, DccId, DccStatus, DccTransfer};
+
+use dcc::Dccs;
 use pinger::Pinger;
+use record::Recorder;
 use state::State;
 use stream::{Stream, StreamError};
 
-use std::net::{SocketAddr, ToSocketAddrs};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::time::Duration;
 
 use futures_util::future::FutureExt;
@@ -33,16 +51,39 @@ extern crate log;
 /// `Client` tries to reconnect on error after this many seconds.
 pub const RECONNECT_SECS: u64 = 30;
 
+/// Default for `ServerInfo::connect_timeout`.
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 30;
+
 #[derive(Debug, Clone)]
 pub struct ServerInfo {
-    /// Server address
-    pub addr: String,
+    /// Name of the network. Returned by `Client::get_serv_name`.
+    pub name: String,
 
-    /// Server port
-    pub port: u16,
+    /// Servers of the network. The client connects to the first one, and tries the next one when
+    /// connecting fails, going back to the first one after the last one. The client waits
+    /// `RECONNECT_SECS` seconds after trying all of the servers.
+    pub endpoints: Vec<Endpoint>,
 
-    /// Use TLS?
-    pub tls: bool,
+    /// Timeout in seconds for resolving the server address, and for connecting to the server
+    /// (including the TLS handshake).
+    pub connect_timeout: u64,
+
+    /// Addresses of this family are tried first when connecting. When not set the order from the
+    /// resolver is used.
+    pub prefer_addr_family: Option<AddrFamily>,
+
+    /// Local address to connect from. Port 0 means any port. When set, only the server addresses
+    /// of the same family are tried.
+    pub bind_addr: Option<SocketAddr>,
+
+    /// File to persist STS policies of servers. Plaintext connections to servers with a policy
+    /// in this file are upgraded to TLS. Policies advertised on plaintext connections are
+    /// followed when this is not set, but they're not persisted.
+    pub sts_file: Option<PathBuf>,
+
+    /// Directory to record raw traffic of connections, for debugging. A new file is created for
+    /// each connection. See `Client::replay` for replaying recordings.
+    pub record_dir: Option<PathBuf>,
 
     /// Server password.
     pub pass: Option<String>,
@@ -59,6 +100,16 @@ pub struct ServerInfo {
     /// Channels to automatically join
     pub auto_join: Vec<ChanName>,
 
+    /// Invites to automatically accept. Patterns starting with a channel prefix (`#`, `&`, `+`,
+    /// `!`) match the channel name, other patterns match the nick of the inviting user. `*` and
+    /// `?` wildcards can be used, e.g. `#rust-*`.
+    pub auto_join_on_invite: Vec<String>,
+
+    /// Channels to rejoin when kicked, with the number of seconds to wait before rejoining. When
+    /// rejoining fails because we're banned the delay, at least one second, is doubled on each
+    /// attempt, up to an hour.
+    pub rejoin_on_kick: HashMap<ChanName, u64>,
+
     /// Nickserv password. Sent to NickServ on connecting to the server and nick change, before
     /// join commands.
     pub nickserv_ident: Option<String>,
@@ -67,6 +118,40 @@ pub struct ServerInfo {
     pub sasl_auth: Option<SASLAuth>,
 }
 
+impl ServerInfo {
+    /// Whether to join `chan` when `nick` invites us to it, according to `auto_join_on_invite`.
+    pub fn auto_join_invite(&self, nick: &str, chan: &ChanNameRef) -> bool {
+        self.auto_join_on_invite.iter().any(|pattern| {
+            if pattern.starts_with(['#', '&', '+', '!']) {
+                wildcard_match(pattern, chan.display())
+            } else {
+                wildcard_match(pattern, nick)
+            }
+        })
+    }
+}
+
+/// A server of a network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    /// Server address. Addresses starting with `unix:` are paths of Unix domain sockets, e.g.
+    /// `unix:/run/user/1000/soju.sock`.
+    pub addr: String,
+
+    /// Server port. Not used with Unix domain sockets.
+    pub port: u16,
+
+    /// Use TLS? Not used with Unix domain sockets.
+    pub tls: bool,
+}
+
+impl Endpoint {
+    /// Path of the Unix domain socket, when the address is a `unix:` address.
+    pub fn unix_path(&self) -> Option<&Path> {
+        self.addr.strip_prefix("unix:").map(Path::new)
+    }
+}
+
 /// SASL authentication mechanisms
 /// - <https://ircv3.net/docs/sasl-mechs>
 /// - <https://www.alphachat.net/sasl.xhtml>
@@ -82,6 +167,22 @@ pub enum SASLAuth {
     },
 }
 
+/// State of the connection to the server. See `Client::state` and `Event::StateChanged`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Resolving the server address, or connecting to the server.
+    Connecting,
+    /// Connected, registering the connection (nick, authentication etc.).
+    Registering,
+    /// Connection registered, the server accepted our nick.
+    Registered,
+    /// Connection failed or lost, waiting `RECONNECT_SECS` seconds before reconnecting.
+    WaitingToReconnect,
+    /// Disconnected with `Client::disconnect`. The client doesn't reconnect until
+    /// `Client::connect` or `Client::reconnect` is called.
+    Disconnected,
+}
+
 /// IRC client events. Returned by `Client` to the users via a channel.
 ///
 /// Note that Client only returns when it can't resolve the domain name. In all other cases (no
@@ -92,8 +193,17 @@ pub enum SASLAuth {
 pub enum Event {
     /// Client resolving domain name
     ResolvingHost,
-    /// Domain name resolved, client trying to connect to the given IP address
-    Connecting(SocketAddr),
+    /// Domain name resolved, client trying to connect to the given IP address. Connection attempts
+    /// to the addresses of a server can overlap, see `ServerInfo::prefer_addr_family`.
+    Connecting {
+        addr: SocketAddr,
+        family: AddrFamily,
+    },
+    /// Server has an STS policy, connecting with TLS to the given port instead of connecting over
+    /// plaintext.
+    StsUpgrade { port: u16 },
+    /// Connection state changed. See `Client::state`.
+    StateChanged(ConnectionState),
     /// TCP connection established *and* the introduction sequence with the IRC server started.
     Connected,
     /// Disconnected from the server. Usually sent right after an `Event::IoErr`. Client tries to
@@ -115,6 +225,20 @@ pub enum Event {
     WireError(String),
     /// Channel join error message
     ChannelJoinError { chan: ChanName, msg: String },
+    /// Users quit because of a netsplit between `servers`. QUIT messages of a netsplit are not
+    /// reported as `Event::Msg`, they're grouped and reported with this event instead. `chans`
+    /// are the channels with the nicks that quit in each channel.
+    Netsplit {
+        servers: (String, String),
+        chans: Vec<(ChanName, Vec<String>)>,
+    },
+    /// Users lost in a netsplit joined back. Like `Netsplit`, JOIN messages of a netjoin are
+    /// grouped and reported with this event instead of `Event::Msg`.
+    Netjoin { chans: Vec<(ChanName, Vec<String>)> },
+    /// A DCC file transfer is offered to us, or status or progress of a transfer changed.
+    Dcc(DccTransfer),
+    /// A DCC CHAT with `nick` is offered to us, connected, closed, or has a new message.
+    DccChat { nick: String, ev: DccChatEvent },
 }
 
 impl From<StreamError> for Event {
@@ -140,6 +264,12 @@ pub struct Client {
     /// Reference to the state, to be able to provide methods like `get_nick` and
     /// `is_nick_accepted`.
     state: State,
+
+    /// DCC file transfers.
+    dccs: Dccs,
+
+    /// Connection state. Updated by the main loop.
+    conn_state: Rc<Cell<ConnectionState>>,
 }
 
 impl Client {
@@ -149,12 +279,71 @@ impl Client {
         connect(server_info)
     }
 
+    /// Create a client that replays a recording made with `ServerInfo::record_dir`, instead of
+    /// connecting to the server. Received messages are handled as if they were received from the
+    /// server, without delays between the messages. Messages sent by the client are ignored.
+    /// Useful for reproducing bugs and in tests.
+    pub fn replay(server_info: ServerInfo, recording: String) -> (Client, mpsc::Receiver<Event>) {
+        let serv_name = server_info.name.clone();
+        let (snd_ev, rcv_ev) = mpsc::channel::<Event>(100);
+        let (snd_cmd, rcv_cmd) = mpsc::channel::<Cmd>(100);
+
+        let irc_state = State::new(server_info);
+        let dccs = Dccs::default();
+        dccs.start(snd_ev.clone());
+        let conn_state = Rc::new(Cell::new(ConnectionState::Connecting));
+
+        let task = replay_loop(
+            recording,
+            irc_state.clone(),
+            dccs.clone(),
+            conn_state.clone(),
+            snd_ev,
+            rcv_cmd,
+        );
+        let dccs_clone = dccs.clone();
+        tokio::task::spawn_local(async move {
+            task.await;
+            dccs_clone.stop();
+        });
+
+        (
+            Client {
+                msg_chan: snd_cmd,
+                serv_name,
+                state: irc_state,
+                dccs,
+                conn_state,
+            },
+            rcv_ev,
+        )
+    }
+
     /// Reconnect to the server, possibly using a new port.
     pub fn reconnect(&mut self, port: Option<u16>) {
         debug!("reconnect cmd received, port: {port:?}");
         self.msg_chan.try_send(Cmd::Reconnect(port)).unwrap()
     }
 
+    /// Close the connection and stay disconnected until `connect` or `reconnect` is called.
+    /// Sends a QUIT message to the server (with optional "reason") if connected.
+    pub fn disconnect(&mut self, reason: Option<String>) {
+        debug!("disconnect cmd received");
+        self.state.kill_join_tasks();
+        self.msg_chan.try_send(Cmd::Disconnect(reason)).unwrap();
+    }
+
+    /// Connect to the server after `disconnect`. Does nothing if not disconnected.
+    pub fn connect(&mut self) {
+        debug!("connect cmd received");
+        self.msg_chan.try_send(Cmd::Connect).unwrap();
+    }
+
+    /// Get the connection state.
+    pub fn state(&self) -> ConnectionState {
+        self.conn_state.get()
+    }
+
     /// Get host name of this connection.
     pub fn get_serv_name(&self) -> &str {
         &self.serv_name
@@ -233,6 +422,39 @@ impl Client {
         self.msg_chan.try_send(Cmd::Msg(wire::join(chans))).unwrap()
     }
 
+    /// Join a channel we're invited to. When `chan` is `None` the channel of the most recent
+    /// invite is joined. Returns the joined channel, or `None` if we're not invited to `chan` or
+    /// there are no pending invites. Invites are pending until accepted, until we join the
+    /// channel, or for an hour. Only the 20 most recent invites are kept.
+    pub fn accept_invite(&mut self, chan: Option<&ChanNameRef>) -> Option<ChanName> {
+        let chan = self.state.take_invite(chan)?;
+        self.join(std::iter::once(chan.as_ref()));
+        Some(chan)
+    }
+
+    /// Whether we're in `chan`.
+    pub fn is_joined(&self, chan: &ChanNameRef) -> bool {
+        self.state.is_joined(chan)
+    }
+
+    /// Whether an invite from `nick` to `chan` is automatically accepted, see
+    /// `ServerInfo::auto_join_on_invite`.
+    pub fn auto_join_invite(&self, nick: &str, chan: &ChanNameRef) -> bool {
+        self.state.auto_join_invite(nick, chan)
+    }
+
+    /// Request the channel list. `pattern` is a channel name mask with `*` and `?` wildcards.
+    /// `min_users` is the minimum number of users in the listed channels.
+    ///
+    /// Filters are only sent when the server supports them (advertised in ELIST), so the replies
+    /// (322 RPL_LIST) should be filtered again by the caller.
+    pub fn list(&mut self, pattern: Option<&str>, min_users: Option<usize>) {
+        let params = self.state.list_params(pattern, min_users);
+        self.msg_chan
+            .try_send(Cmd::Msg(wire::list(&params)))
+            .unwrap()
+    }
+
     /// Leave a channel.
     pub fn part(&mut self, chan: &ChanNameRef, reason: Option<String>) {
         self.state.leave_channel(&mut self.msg_chan, chan, reason)
@@ -266,6 +488,56 @@ impl Client {
     pub fn get_chan_nicks(&self, chan: &ChanNameRef) -> Vec<String> {
         self.state.get_chan_nicks(chan)
     }
+
+    /// Offer a file to `nick` with DCC SEND. With `passive` the receiver listens for a connection
+    /// from us, useful when we can't accept connections. The transfer is reported with
+    /// `Event::Dcc`.
+    pub fn dcc_send(&mut self, nick: &str, path: &Path, passive: bool) -> Result<DccId, String> {
+        let (id, req) = self.dccs.send(nick, path, passive)?;
+        self.msg_chan.try_send(Cmd::Msg(req)).unwrap();
+        Ok(id)
+    }
+
+    /// Accept a DCC SEND offer and save the file to `dir`. With `resume`, a partial download of the
+    /// file in `dir` is resumed. Otherwise a new file is created, existing files are never
+    /// overwritten.
+    pub fn dcc_get(&mut self, id: DccId, dir: &Path, resume: bool) -> Result<(), String> {
+        if let Some(req) = self.dccs.get(id, dir, resume)? {
+            self.msg_chan.try_send(Cmd::Msg(req)).unwrap();
+        }
+        Ok(())
+    }
+
+    /// Close a DCC transfer, or reject an offer. Returns `false` if the transfer doesn't exist.
+    pub fn dcc_close(&self, id: DccId) -> bool {
+        self.dccs.close(id)
+    }
+
+    /// Get DCC transfers, including finished ones.
+    pub fn dcc_transfers(&self) -> Vec<DccTransfer> {
+        self.dccs.transfers()
+    }
+
+    /// Accept a DCC CHAT offer from `nick`, or offer a chat to `nick` if there isn't an offer.
+    /// The chat is reported with `Event::DccChat`.
+    pub fn dcc_chat(&mut self, nick: &str) -> Result<(), String> {
+        if let Some(req) = self.dccs.chat(nick)? {
+            self.msg_chan.try_send(Cmd::Msg(req)).unwrap();
+        }
+        Ok(())
+    }
+
+    /// Send a message in the DCC CHAT with `nick`. The message is sent directly to the remote
+    /// side, bypassing the server.
+    pub fn dcc_chat_msg(&mut self, nick: &str, msg: &str, is_action: bool) -> Result<(), String> {
+        self.dccs.chat_msg(nick, msg, is_action)
+    }
+
+    /// Close the DCC CHAT with `nick`, or reject an offer. Returns `false` if the chat doesn't
+    /// exist.
+    pub fn dcc_chat_close(&self, nick: &str) -> bool {
+        self.dccs.chat_close(nick)
+    }
 }
 
 //
@@ -279,13 +551,18 @@ enum Cmd {
     Msg(String),
     /// Reconnect to the server, possibly using a new port.
     Reconnect(Option<u16>),
+    /// Close the connection and wait for a `Connect` or `Reconnect` command. Sends a QUIT message
+    /// to the server (with optional "reason") if connected.
+    Disconnect(Option<String>),
+    /// Connect to the server after `Disconnect`.
+    Connect,
     /// Close the connection. This sends a QUIT message to the server (with optional "reason") and
     /// then all tasks return.
     Quit(Option<String>),
 }
 
 fn connect(server_info: ServerInfo) -> (Client, mpsc::Receiver<Event>) {
-    let serv_name = server_info.addr.clone();
+    let serv_name = server_info.name.clone();
 
     //
     // Create communication channels
@@ -304,14 +581,33 @@ fn connect(server_info: ServerInfo) -> (Client, mpsc::Receiver<Event>) {
     let irc_state = State::new(server_info.clone());
     let irc_state_clone = irc_state.clone();
 
-    let task = main_loop(server_info, irc_state_clone, snd_ev, rcv_cmd);
-    tokio::task::spawn_local(task);
+    let dccs = Dccs::default();
+    dccs.start(snd_ev.clone());
+    let dccs_clone = dccs.clone();
+
+    let conn_state = Rc::new(Cell::new(ConnectionState::Connecting));
+
+    let task = main_loop(
+        server_info,
+        irc_state_clone,
+        dccs.clone(),
+        conn_state.clone(),
+        snd_ev,
+        rcv_cmd,
+    );
+    tokio::task::spawn_local(async move {
+        task.await;
+        // Drops the event sender in `Dccs`
+        dccs_clone.stop();
+    });
 
     (
         Client {
             msg_chan: snd_cmd,
             serv_name,
             state: irc_state,
+            dccs,
+            conn_state,
         },
         rcv_ev,
     )
@@ -320,117 +616,248 @@ fn connect(server_info: ServerInfo) -> (Client, mpsc::Receiver<Event>) {
 async fn main_loop(
     server_info: ServerInfo,
     irc_state: State,
+    dccs: Dccs,
+    conn_state: Rc<Cell<ConnectionState>>,
     mut snd_ev: mpsc::Sender<Event>,
     rcv_cmd: mpsc::Receiver<Cmd>,
 ) {
     let mut rcv_cmd = ReceiverStream::new(rcv_cmd).fuse();
 
-    // We allow changing ports when reconnecting, so `mut`
-    let mut port = server_info.port;
+    if server_info.endpoints.is_empty() {
+        snd_ev.send(Event::CantResolveAddr).await.unwrap();
+        return;
+    }
+
+    let mut endpoints = Endpoints::new(server_info.endpoints.clone());
 
     // Whether to wait before trying to (re)connect
     let mut wait = false;
 
+    // Whether to wait for a `Connect` or `Reconnect` command before connecting
+    let mut disconnected = false;
+
     // Main loop just tries to (re)connect
     'connect: loop {
+        if disconnected {
+            set_conn_state(&conn_state, ConnectionState::Disconnected, &mut snd_ev).await;
+            match wait_connect(&mut rcv_cmd).await {
+                TaskResult::Done(()) | TaskResult::Disconnect => {}
+                TaskResult::Reconnect(mb_port) => {
+                    endpoints.set_port(mb_port);
+                }
+                TaskResult::Return => {
+                    return;
+                }
+            }
+            disconnected = false;
+            wait = false;
+        }
+
         if wait {
+            set_conn_state(
+                &conn_state,
+                ConnectionState::WaitingToReconnect,
+                &mut snd_ev,
+            )
+            .await;
             match wait_(&mut rcv_cmd).await {
                 TaskResult::Done(()) => {}
                 TaskResult::Reconnect(mb_port) => {
-                    port = mb_port.unwrap_or(port);
+                    endpoints.set_port(mb_port);
                     wait = false;
                     continue;
                 }
+                TaskResult::Disconnect => {
+                    disconnected = true;
+                    continue;
+                }
                 TaskResult::Return => {
                     return;
                 }
             }
         }
 
+        set_conn_state(&conn_state, ConnectionState::Connecting, &mut snd_ev).await;
+
         // Channel for the sender task. Messages are complete IRC messages (including the
         // trailing "\r\n") and the task directly sends them to the server.
         let (mut snd_msg, rcv_msg) = mpsc::channel::<String>(100);
 
-        //
-        // Resolve IP address
-        //
-
-        snd_ev.send(Event::ResolvingHost).await.unwrap();
-
-        let serv_name = server_info.addr.clone();
-
-        debug!("Resolving address");
+        let mut endpoint = endpoints.current().clone();
 
-        let serv_name_clone = serv_name.clone();
-
-        let addr_iter = match resolve_addr(serv_name_clone, port, &mut rcv_cmd).await {
-            TaskResult::Done(Ok(addr_iter)) => {
-                debug!("resolve_addr: done");
-                addr_iter
-            }
-            TaskResult::Done(Err(err)) => {
-                debug!("resolve_addr: {err:?}");
-                snd_ev.send(Event::IoErr(err)).await.unwrap();
-                wait = true;
-                continue;
-            }
-            TaskResult::Reconnect(mb_port) => {
-                debug!("resolve_addr: try again");
-                port = mb_port.unwrap_or(port);
-                wait = false;
-                continue;
+        if !endpoint.tls
+            && endpoint.unix_path().is_none()
+            && let Some(sts_file) = &server_info.sts_file
+        {
+            match sts::policy_port(sts_file, &endpoint.addr) {
+                Ok(None) => {}
+                Ok(Some(port)) => {
+                    endpoints.upgrade_tls(port);
+                    endpoint = endpoints.current().clone();
+                    snd_ev.send(Event::StsUpgrade { port }).await.unwrap();
+                }
+                Err(err) => {
+                    let err = std::io::Error::new(
+                        err.kind(),
+                        format!("Can't read STS policies from {}: {err}", sts_file.display()),
+                    );
+                    snd_ev.send(Event::IoErr(err)).await.unwrap();
+                }
             }
-            TaskResult::Return => {
-                debug!("resolve_addr: return");
-                return;
+        }
+
+        let connect_timeout = Duration::from_secs(server_info.connect_timeout);
+
+        let connect_result = match endpoint.unix_path() {
+            Some(path) => {
+                debug!("Connecting to Unix domain socket {path:?}");
+                let snd_ev_ = snd_ev.clone();
+                let connect = async move {
+                    match connect::connect_unix(path).await {
+                        Ok(unix_stream) => Ok(Stream::new_unix(unix_stream)),
+                        Err(err) => {
+                            let err_ = std::io::Error::new(err.kind(), err.to_string());
+                            snd_ev_.send(Event::IoErr(err_)).await.unwrap();
+                            Err(err.into())
+                        }
+                    }
+                };
+                try_connect(
+                    connect,
+                    &endpoint.addr,
+                    connect_timeout,
+                    &mut rcv_cmd,
+                    &mut snd_ev,
+                )
+                .await
             }
-        };
+            None => {
+                //
+                // Resolve IP address
+                //
 
-        let addrs = addr_iter.collect::<Vec<_>>();
+                snd_ev.send(Event::ResolvingHost).await.unwrap();
 
-        if addrs.is_empty() {
-            snd_ev.send(Event::CantResolveAddr).await.unwrap();
-            return;
-        }
+                let Endpoint {
+                    addr: serv_name,
+                    port,
+                    tls,
+                } = &endpoint;
 
-        debug!("Address resolved: {addrs:?}");
+                debug!("Resolving address");
 
-        //
-        // Establish TCP connection to the server
-        //
+                let addrs =
+                    match resolve_addr(serv_name, *port, connect_timeout, &mut rcv_cmd).await {
+                        TaskResult::Done(Ok(addrs)) => {
+                            debug!("resolve_addr: done");
+                            addrs
+                        }
+                        TaskResult::Done(Err(err)) => {
+                            debug!("resolve_addr: {err:?}");
+                            snd_ev.send(Event::IoErr(err)).await.unwrap();
+                            wait = endpoints.failed();
+                            continue;
+                        }
+                        TaskResult::Reconnect(mb_port) => {
+                            debug!("resolve_addr: try again");
+                            endpoints.set_port(mb_port);
+                            wait = false;
+                            continue;
+                        }
+                        TaskResult::Disconnect => {
+                            debug!("resolve_addr: disconnect");
+                            disconnected = true;
+                            continue;
+                        }
+                        TaskResult::Return => {
+                            debug!("resolve_addr: return");
+                            return;
+                        }
+                    };
 
-        let sasl_pem = if let Some(SASLAuth::External { pem }) = &server_info.sasl_auth {
-            Some(pem)
-        } else {
-            None
+                if addrs.is_empty() {
+                    if endpoints.len() == 1 {
+                        snd_ev.send(Event::CantResolveAddr).await.unwrap();
+                        return;
+                    }
+                    // Try the other endpoints
+                    snd_ev
+                        .send(Event::IoErr(std::io::Error::new(
+                            std::io::ErrorKind::NotFound,
+                            format!("Can't resolve {serv_name}"),
+                        )))
+                        .await
+                        .unwrap();
+                    wait = endpoints.failed();
+                    continue;
+                }
+
+                debug!("Address resolved: {addrs:?}");
+
+                let addrs = connect::sort_addrs(addrs, server_info.prefer_addr_family);
+
+                //
+                // Establish TCP connection to the server
+                //
+
+                let sasl_pem = if let Some(SASLAuth::External { pem }) = &server_info.sasl_auth {
+                    Some(pem)
+                } else {
+                    None
+                };
+
+                // Errors of the connection attempts are reported by `connect_tcp`, using its own
+                // sender
+                let mut snd_ev_ = snd_ev.clone();
+                let connect = async move {
+                    let tcp_stream =
+                        connect::connect_tcp(addrs, server_info.bind_addr, &mut snd_ev_).await?;
+                    if *tls {
+                        Stream::new_tls(tcp_stream, serv_name, sasl_pem).await
+                    } else {
+                        Ok(Stream::new_tcp(tcp_stream))
+                    }
+                };
+                try_connect(
+                    connect,
+                    serv_name,
+                    connect_timeout,
+                    &mut rcv_cmd,
+                    &mut snd_ev,
+                )
+                .await
+            }
         };
 
-        let stream = match try_connect(
-            addrs,
-            &serv_name,
-            server_info.tls,
-            sasl_pem,
-            &mut rcv_cmd,
-            &mut snd_ev,
-        )
-        .await
-        {
+        let stream = match connect_result {
             TaskResult::Done(Some(stream)) => stream,
             TaskResult::Done(None) => {
                 snd_ev.send(Event::Disconnected).await.unwrap();
-                wait = true;
+                wait = endpoints.failed();
                 continue;
             }
             TaskResult::Return => {
                 return;
             }
             TaskResult::Reconnect(mb_port) => {
-                port = mb_port.unwrap_or(port);
+                endpoints.set_port(mb_port);
                 wait = false;
                 continue;
             }
+            TaskResult::Disconnect => {
+                disconnected = true;
+                continue;
+            }
         };
 
+        endpoints.connected();
+        set_conn_state(&conn_state, ConnectionState::Registering, &mut snd_ev).await;
+
+        match stream.local_addr() {
+            Ok(local_addr) => dccs.set_local_ip(local_addr.ip()),
+            Err(err) => debug!("Can't get local address: {err:?}"),
+        }
+
         let (mut read_half, mut write_half) = tokio::io::split(stream);
 
         debug!("Done");
@@ -439,23 +866,38 @@ async fn main_loop(
         // Do the business
         //
 
-        // Reset the connection state
-        irc_state.reset();
-        // Introduce self
-        if server_info.sasl_auth.is_some() {
-            // Will introduce self after getting a response to this LS command.
-            // This is to avoid getting stuck during nick registration. See the
-            // discussion in #91.
-            snd_msg.try_send(wire::cap_ls()).unwrap();
-        } else {
-            irc_state.introduce(&mut snd_msg);
-        }
+        let recorder: Option<Rc<RefCell<Recorder>>> = match &server_info.record_dir {
+            None => None,
+            Some(dir) => match Recorder::create(dir, &server_info.name) {
+                Ok(recorder) => Some(Rc::new(RefCell::new(recorder))),
+                Err(err) => {
+                    let err = std::io::Error::new(
+                        err.kind(),
+                        format!("Can't create recording file: {err}"),
+                    );
+                    snd_ev.send(Event::IoErr(err)).await.unwrap();
+                    None
+                }
+            },
+        };
+
+        // Reset the connection state. Local connections are considered secure, STS policies are
+        // only followed on network connections.
+        irc_state.reset(endpoint.tls || endpoint.unix_path().is_some());
+        // Introduce self. When authenticating, self is introduced after getting a response to the
+        // LS command. This is to avoid getting stuck during nick registration. See the discussion
+        // in #91.
+        irc_state.register(&mut snd_msg);
 
         // Spawn a task for outgoing messages.
         let snd_ev_clone = snd_ev.clone();
+        let recorder_clone = recorder.clone();
         tokio::task::spawn_local(async move {
             let mut rcv_msg = ReceiverStream::new(rcv_msg);
             while let Some(msg) = rcv_msg.next().await {
+                if let Some(recorder) = &recorder_clone {
+                    recorder.borrow_mut().sent(&msg);
+                }
                 if let Err(io_err) = write_half.write_all(msg.as_bytes()).await {
                     debug!("IO error when writing: {io_err:?}");
                     snd_ev_clone.send(Event::IoErr(io_err)).await.unwrap();
@@ -484,12 +926,17 @@ async fn main_loop(
                             snd_msg.try_send(irc_msg).unwrap();
                         }
                         Some(Cmd::Reconnect(mb_port)) => {
-                            if let Some(new_port) = mb_port {
-                                port = new_port;
-                            }
+                            endpoints.set_port(mb_port);
                             wait = false;
                             continue 'connect;
                         }
+                        Some(Cmd::Disconnect(reason)) => {
+                            // Sender task sends the message before returning
+                            snd_msg.try_send(wire::quit(reason)).unwrap();
+                            disconnected = true;
+                            continue 'connect;
+                        }
+                        Some(Cmd::Connect) => {}
                         Some(Cmd::Quit(reason)) => {
                             snd_msg.try_send(wire::quit(reason)).unwrap();
                             // This drops the sender end of the channel that the sender task
@@ -519,6 +966,9 @@ async fn main_loop(
                             continue 'connect;
                         }
                         Ok(bytes) => {
+                            if let Some(recorder) = &recorder {
+                                recorder.borrow_mut().received(&read_buf[0..bytes]);
+                            }
                             parse_buf.extend_from_slice(&read_buf[0..bytes]);
                             while let Some(msg) = wire::parse_irc_msg(&mut parse_buf) {
                                 match msg {
@@ -528,8 +978,53 @@ async fn main_loop(
                                     Ok(mut msg) => {
                                         debug!("parsed msg: {msg:?}");
                                         pinger.reset();
-                                        irc_state.update(&mut msg, &mut snd_ev, &mut snd_msg);
-                                        snd_ev.send(Event::Msg(msg)).await.unwrap();
+                                        if dccs.handle_msg(&msg, &mut snd_msg) {
+                                            continue;
+                                        }
+                                        if irc_state.update(&mut msg, &mut snd_ev, &mut snd_msg) {
+                                            snd_ev.send(Event::Msg(msg)).await.unwrap();
+                                        }
+                                        if irc_state.is_nick_accepted() {
+                                            set_conn_state(
+                                                &conn_state,
+                                                ConnectionState::Registered,
+                                                &mut snd_ev,
+                                            )
+                                            .await;
+                                        }
+                                        if let Some(sts) = irc_state.take_sts() {
+                                            if endpoint.tls {
+                                                if let (Some(duration), Some(sts_file)) =
+                                                    (sts.duration, &server_info.sts_file)
+                                                    && let Err(err) = sts::update_policy(
+                                                        sts_file,
+                                                        &endpoint.addr,
+                                                        endpoint.port,
+                                                        duration,
+                                                    )
+                                                {
+                                                    let err = std::io::Error::new(
+                                                        err.kind(),
+                                                        format!(
+                                                            "Can't save STS policy to {}: {err}",
+                                                            sts_file.display()
+                                                        ),
+                                                    );
+                                                    snd_ev.send(Event::IoErr(err)).await.unwrap();
+                                                }
+                                            } else if endpoint.unix_path().is_none()
+                                                && let Some(port) = sts.port
+                                            {
+                                                // Reconnect with TLS without waiting
+                                                endpoints.upgrade_tls(port);
+                                                snd_ev
+                                                    .send(Event::StsUpgrade { port })
+                                                    .await
+                                                    .unwrap();
+                                                wait = false;
+                                                continue 'connect;
+                                            }
+                                        }
                                     }
                                 }
                             }
@@ -558,10 +1053,146 @@ async fn main_loop(
     }
 }
 
+/// Endpoints of a network, and the one to connect to.
+struct Endpoints {
+    endpoints: Vec<Endpoint>,
+
+    /// Index of the endpoint to connect to.
+    idx: usize,
+
+    /// Number of endpoints failed in a row. Reset when connected.
+    n_failed: usize,
+}
+
+impl Endpoints {
+    fn new(endpoints: Vec<Endpoint>) -> Endpoints {
+        Endpoints {
+            endpoints,
+            idx: 0,
+            n_failed: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    fn current(&self) -> &Endpoint {
+        &self.endpoints[self.idx]
+    }
+
+    /// Update port of the current endpoint, when reconnecting with a new port.
+    fn set_port(&mut self, port: Option<u16>) {
+        if let Some(port) = port {
+            self.endpoints[self.idx].port = port;
+        }
+    }
+
+    /// Switch to the next endpoint after a connection failure. Returns whether to wait before
+    /// connecting, which is after trying all of the endpoints.
+    fn failed(&mut self) -> bool {
+        self.n_failed += 1;
+        self.idx = (self.idx + 1) % self.endpoints.len();
+        self.n_failed.is_multiple_of(self.endpoints.len())
+    }
+
+    fn connected(&mut self) {
+        self.n_failed = 0;
+    }
+
+    /// Switch the current endpoint to TLS on the given port, because of an STS policy.
+    fn upgrade_tls(&mut self, port: u16) {
+        let endpoint = &mut self.endpoints[self.idx];
+        endpoint.tls = true;
+        endpoint.port = port;
+    }
+}
+
 enum TaskResult<A> {
     Done(A),
     Return,
     Reconnect(Option<u16>),
+    Disconnect,
+}
+
+async fn replay_loop(
+    recording: String,
+    irc_state: State,
+    dccs: Dccs,
+    conn_state: Rc<Cell<ConnectionState>>,
+    mut snd_ev: mpsc::Sender<Event>,
+    mut rcv_cmd: mpsc::Receiver<Cmd>,
+) {
+    // Messages sent by the client are dropped
+    let (mut snd_msg, mut rcv_msg) = mpsc::channel::<String>(100);
+
+    irc_state.reset(true);
+    set_conn_state(&conn_state, ConnectionState::Registering, &mut snd_ev).await;
+
+    for (dir, line) in record::parse_recording(&recording) {
+        if dir != record::Direction::Received {
+            continue;
+        }
+        let mut buf: Vec<u8> = format!("{line}\r\n").into_bytes();
+        match wire::parse_irc_msg(&mut buf) {
+            None => {}
+            Some(Err(err)) => {
+                snd_ev.send(Event::WireError(err)).await.unwrap();
+            }
+            Some(Ok(mut msg)) => {
+                if !dccs.handle_msg(&msg, &mut snd_msg)
+                    && irc_state.update(&mut msg, &mut snd_ev, &mut snd_msg)
+                {
+                    snd_ev.send(Event::Msg(msg)).await.unwrap();
+                }
+                if irc_state.is_nick_accepted() {
+                    set_conn_state(&conn_state, ConnectionState::Registered, &mut snd_ev).await;
+                }
+            }
+        }
+        while rcv_msg.try_recv().is_ok() {}
+    }
+
+    // Keep the command channel open until the client is dropped, commands are ignored
+    loop {
+        select! {
+            cmd = rcv_cmd.recv() => {
+                match cmd {
+                    None | Some(Cmd::Quit(_)) => return,
+                    Some(_) => {}
+                }
+            }
+            _ = rcv_msg.recv() => {}
+        }
+    }
+}
+
+async fn set_conn_state(
+    conn_state: &Cell<ConnectionState>,
+    new_state: ConnectionState,
+    snd_ev: &mut mpsc::Sender<Event>,
+) {
+    if conn_state.replace(new_state) != new_state {
+        snd_ev.send(Event::StateChanged(new_state)).await.unwrap();
+    }
+}
+
+/// Wait for a `Connect` or `Reconnect` command after a `Disconnect` command.
+async fn wait_connect<S: StreamExt<Item = Cmd> + Unpin>(rcv_cmd: &mut S) -> TaskResult<()> {
+    loop {
+        match rcv_cmd.next().await {
+            None | Some(Cmd::Quit(_)) => {
+                return TaskResult::Return;
+            }
+            Some(Cmd::Msg(_)) | Some(Cmd::Disconnect(_)) => {}
+            Some(Cmd::Connect) => {
+                return TaskResult::Done(());
+            }
+            Some(Cmd::Reconnect(mb_port)) => {
+                return TaskResult::Reconnect(mb_port);
+            }
+        }
+    }
 }
 
 async fn wait_<S: StreamExt<Item = Cmd> + Unpin>(rcv_cmd: &mut S) -> TaskResult<()> {
@@ -587,6 +1218,12 @@ async fn wait_<S: StreamExt<Item = Cmd> + Unpin>(rcv_cmd: &mut S) -> TaskResult<
                     Some(Cmd::Reconnect(mb_port)) => {
                         return TaskResult::Reconnect(mb_port);
                     }
+                    Some(Cmd::Disconnect(_)) => {
+                        return TaskResult::Disconnect;
+                    }
+                    Some(Cmd::Connect) => {
+                        continue;
+                    }
                     Some(Cmd::Quit(_)) => {
                         return TaskResult::Return;
                     }
@@ -597,25 +1234,18 @@ async fn wait_<S: StreamExt<Item = Cmd> + Unpin>(rcv_cmd: &mut S) -> TaskResult<
 }
 
 async fn resolve_addr<S: StreamExt<Item = Cmd> + Unpin>(
-    serv_name: String,
+    serv_name: &str,
     port: u16,
+    timeout: Duration,
     rcv_cmd: &mut S,
-) -> TaskResult<Result<::std::vec::IntoIter<SocketAddr>, ::std::io::Error>> {
-    let mut addr_iter_task =
-        tokio::task::spawn_blocking(move || (serv_name.as_str(), port).to_socket_addrs()).fuse();
+) -> TaskResult<Result<Vec<SocketAddr>, std::io::Error>> {
+    let resolve_task = connect::resolve_addr(serv_name, port, timeout).fuse();
+    pin!(resolve_task);
 
     loop {
         select! {
-            addr_iter = &mut addr_iter_task => {
-                match addr_iter {
-                    Err(join_err) => {
-                        // TODO (osa): Not sure about this
-                        panic!("DNS thread failed: {join_err:?}");
-                    }
-                    Ok(ret) => {
-                        return TaskResult::Done(ret);
-                    }
-                }
+            addrs = &mut resolve_task => {
+                return TaskResult::Done(addrs);
             }
             cmd = rcv_cmd.next() => {
                 match cmd {
@@ -629,6 +1259,12 @@ async fn resolve_addr<S: StreamExt<Item = Cmd> + Unpin>(
                     Some(Cmd::Reconnect(mb_port)) => {
                         return TaskResult::Reconnect(mb_port);
                     }
+                    Some(Cmd::Disconnect(_)) => {
+                        return TaskResult::Disconnect;
+                    }
+                    Some(Cmd::Connect) => {
+                        continue;
+                    }
                     Some(Cmd::Quit(_)) => {
                         return TaskResult::Return;
                     }
@@ -638,32 +1274,34 @@ async fn resolve_addr<S: StreamExt<Item = Cmd> + Unpin>(
     }
 }
 
-async fn try_connect<S: StreamExt<Item = Cmd> + Unpin>(
-    addrs: Vec<SocketAddr>,
+/// Run the connection future `connect` with a timeout, while handling commands. Errors other than
+/// IO errors are reported here, IO errors should be reported by `connect`.
+async fn try_connect<S, F>(
+    connect: F,
     serv_name: &str,
-    use_tls: bool,
-    sasl_pem: Option<&Vec<u8>>,
+    timeout: Duration,
     rcv_cmd: &mut S,
     snd_ev: &mut mpsc::Sender<Event>,
-) -> TaskResult<Option<Stream>> {
-    let connect_task = async move {
-        for addr in addrs {
-            snd_ev.send(Event::Connecting(addr)).await.unwrap();
-            let mb_stream = if use_tls {
-                Stream::new_tls(addr, serv_name, sasl_pem).await
-            } else {
-                Stream::new_tcp(addr).await
-            };
-            match mb_stream {
-                Err(err) => {
-                    snd_ev.send(Event::from(err)).await.unwrap();
-                }
-                Ok(stream) => {
-                    return Some(stream);
-                }
+) -> TaskResult<Option<Stream>>
+where
+    S: StreamExt<Item = Cmd> + Unpin,
+    F: Future<Output = Result<Stream, StreamError>>,
+{
+    let connect_task = async {
+        match tokio::time::timeout(timeout, connect).await {
+            Ok(Ok(stream)) => return Some(stream),
+            Ok(Err(StreamError::IoError(_))) => {}
+            Ok(Err(err @ StreamError::TlsError(_))) => {
+                snd_ev.send(Event::from(err)).await.unwrap();
+            }
+            Err(_) => {
+                let err = std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!("Timed out connecting to {serv_name}"),
+                );
+                snd_ev.send(Event::IoErr(err)).await.unwrap();
             }
         }
-
         None
     };
 
@@ -687,6 +1325,12 @@ async fn try_connect<S: StreamExt<Item = Cmd> + Unpin>(
                     Some(Cmd::Reconnect(mb_port)) => {
                         return TaskResult::Reconnect(mb_port);
                     }
+                    Some(Cmd::Disconnect(_)) => {
+                        return TaskResult::Disconnect;
+                    }
+                    Some(Cmd::Connect) => {
+                        continue;
+                    }
                     Some(Cmd::Quit(_)) => {
                         return TaskResult::Return;
                     }
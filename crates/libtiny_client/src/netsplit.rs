@@ -0,0 +1,273 @@
+//! Netsplit and netjoin detection.
+//!
+//! When two servers of a network lose connection to each other, every user on the other side of
+//! the split quits with the message "<server1> <server2>", and joins back when the servers are
+//! connected again. In big channels this generates hundreds of QUIT and JOIN messages. We group
+//! these messages for `GROUP_SECS` seconds and report them as `Event::Netsplit` and
+//! `Event::Netjoin`, with the nicks affected in each channel.
+
+use crate::Event;
+use libtiny_common::{ChanName, ChanNameRef};
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// QUITs and JOINs of a netsplit/netjoin are grouped for this many seconds before reporting.
+pub(crate) const GROUP_SECS: u64 = 5;
+
+/// Nicks lost in a netsplit are remembered for this many seconds, to be able to report their
+/// JOINs as a netjoin.
+const REMEMBER_SECS: u64 = 30 * 60;
+
+/// Nicks affected in each channel, in the order we see the channels.
+type ChanNicks = Vec<(ChanName, Vec<String>)>;
+
+#[derive(Debug, Default)]
+pub(crate) struct Netsplits {
+    /// The netsplit being grouped.
+    split: Option<Split>,
+
+    /// The netjoin being grouped.
+    join: ChanNicks,
+
+    /// Nicks lost in netsplits, with the time of the QUIT.
+    split_nicks: HashMap<String, Instant>,
+
+    /// Whether a task to call `flush` is already spawned.
+    flush_scheduled: bool,
+}
+
+#[derive(Debug)]
+struct Split {
+    servers: (String, String),
+    chans: ChanNicks,
+}
+
+impl Netsplits {
+    pub(crate) fn reset(&mut self) {
+        self.split = None;
+        self.join.clear();
+        self.split_nicks.clear();
+    }
+
+    /// Record QUIT of `nick` in a netsplit between `servers`. `chans` are the channels of the
+    /// nick. Returns the previous netsplit if it was between other servers.
+    pub(crate) fn add_quit(
+        &mut self,
+        nick: &str,
+        servers: (&str, &str),
+        chans: &[ChanName],
+        now: Instant,
+    ) -> Option<Event> {
+        self.split_nicks
+            .retain(|_, quit| now.duration_since(*quit) < Duration::from_secs(REMEMBER_SECS));
+        self.split_nicks.insert(nick.to_owned(), now);
+
+        let mut prev_split = None;
+        if let Some(split) = &self.split
+            && (split.servers.0 != servers.0 || split.servers.1 != servers.1)
+        {
+            prev_split = self.split.take().map(Split::into_event);
+        }
+
+        let split = self.split.get_or_insert_with(|| Split {
+            servers: (servers.0.to_owned(), servers.1.to_owned()),
+            chans: vec![],
+        });
+        for chan in chans {
+            add_chan_nick(&mut split.chans, chan, nick);
+        }
+
+        prev_split
+    }
+
+    /// Record JOIN of `nick` to `chan` if the nick was lost in a recent netsplit. Returns whether
+    /// the JOIN is a part of a netjoin.
+    pub(crate) fn add_join(&mut self, nick: &str, chan: &ChanNameRef, now: Instant) -> bool {
+        match self.split_nicks.get(nick) {
+            Some(quit) if now.duration_since(*quit) < Duration::from_secs(REMEMBER_SECS) => {
+                add_chan_nick(&mut self.join, chan, nick);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns whether the caller should spawn a task to `flush` the current groups after
+    /// `GROUP_SECS` seconds.
+    pub(crate) fn schedule_flush(&mut self) -> bool {
+        !std::mem::replace(&mut self.flush_scheduled, true)
+    }
+
+    /// Get the grouped netsplit and netjoin events.
+    pub(crate) fn flush(&mut self) -> Vec<Event> {
+        self.flush_scheduled = false;
+        let mut evs = Vec::with_capacity(2);
+        if let Some(split) = self.split.take() {
+            evs.push(split.into_event());
+        }
+        if !self.join.is_empty() {
+            evs.push(Event::Netjoin {
+                chans: std::mem::take(&mut self.join),
+            });
+        }
+        evs
+    }
+}
+
+impl Split {
+    fn into_event(self) -> Event {
+        Event::Netsplit {
+            servers: self.servers,
+            chans: self.chans,
+        }
+    }
+}
+
+fn add_chan_nick(chans: &mut ChanNicks, chan: &ChanNameRef, nick: &str) {
+    match chans.iter_mut().find(|(chan_, _)| chan_ == chan) {
+        Some((_, nicks)) => nicks.push(nick.to_owned()),
+        None => chans.push((chan.to_owned(), vec![nick.to_owned()])),
+    }
+}
+
+/// Parse a netsplit QUIT message: "<server1> <server2>". Follows irssi's rules to avoid treating
+/// a normal quit message as a netsplit: exactly two words, no ':' or '/', and both words look
+/// like host names with a top-level domain of at least two letters.
+pub(crate) fn parse_split_msg(msg: &str) -> Option<(&str, &str)> {
+    if msg.contains([':', '/']) {
+        return None;
+    }
+    let (server1, server2) = msg.split_once(' ')?;
+    if is_host_name(server1) && is_host_name(server2) && server1 != server2 {
+        Some((server1, server2))
+    } else {
+        None
+    }
+}
+
+fn is_host_name(s: &str) -> bool {
+    match s.rsplit_once('.') {
+        None => false,
+        Some((host, tld)) => {
+            !host.is_empty()
+                && !host.starts_with('.')
+                && !host.contains(' ')
+                && tld.len() >= 2
+                && tld.chars().all(|c| c.is_ascii_alphabetic())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_split_msg() {
+        assert_eq!(
+            parse_split_msg("irc.server1.net irc.server2.net"),
+            Some(("irc.server1.net", "irc.server2.net"))
+        );
+        assert_eq!(parse_split_msg("Quit: leaving"), None);
+        assert_eq!(parse_split_msg("see you.later"), None);
+        assert_eq!(parse_split_msg("a.net b.net c.net"), None);
+        assert_eq!(parse_split_msg("irc.server1.net"), None);
+        assert_eq!(parse_split_msg("http://a.net b.net"), None);
+        assert_eq!(parse_split_msg("a.net a.net"), None);
+        assert_eq!(parse_split_msg("a.n b.net"), None);
+        assert_eq!(parse_split_msg(".net b.net"), None);
+    }
+
+    #[test]
+    fn test_grouping() {
+        let now = Instant::now();
+        let chan1 = ChanName::new("#chan1".to_owned());
+        let chan2 = ChanName::new("#chan2".to_owned());
+        let mut netsplits = Netsplits::default();
+
+        assert!(netsplits.schedule_flush());
+        assert!(
+            netsplits
+                .add_quit(
+                    "a",
+                    ("x.net", "y.net"),
+                    &[chan1.clone(), chan2.clone()],
+                    now
+                )
+                .is_none()
+        );
+        assert!(!netsplits.schedule_flush());
+        assert!(
+            netsplits
+                .add_quit("b", ("x.net", "y.net"), std::slice::from_ref(&chan2), now)
+                .is_none()
+        );
+
+        // Not lost in the split
+        assert!(!netsplits.add_join("c", &chan1, now));
+
+        let evs = netsplits.flush();
+        assert_eq!(evs.len(), 1);
+        match &evs[0] {
+            Event::Netsplit { servers, chans } => {
+                assert_eq!(servers, &("x.net".to_owned(), "y.net".to_owned()));
+                assert_eq!(
+                    chans,
+                    &vec![
+                        (chan1.clone(), vec!["a".to_owned()]),
+                        (chan2.clone(), vec!["a".to_owned(), "b".to_owned()]),
+                    ]
+                );
+            }
+            other => panic!("Unexpected event: {other:?}"),
+        }
+        assert!(netsplits.schedule_flush());
+
+        assert!(netsplits.add_join("a", &chan1, now));
+        assert!(netsplits.add_join("b", &chan2, now));
+        assert!(netsplits.add_join("a", &chan2, now));
+        // Too late, not a netjoin
+        assert!(!netsplits.add_join("b", &chan1, now + Duration::from_secs(REMEMBER_SECS)));
+
+        let evs = netsplits.flush();
+        assert_eq!(evs.len(), 1);
+        match &evs[0] {
+            Event::Netjoin { chans } => {
+                assert_eq!(
+                    chans,
+                    &vec![
+                        (chan1, vec!["a".to_owned()]),
+                        (chan2, vec!["b".to_owned(), "a".to_owned()]),
+                    ]
+                );
+            }
+            other => panic!("Unexpected event: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_different_servers() {
+        let now = Instant::now();
+        let chan = ChanName::new("#chan".to_owned());
+        let mut netsplits = Netsplits::default();
+
+        assert!(
+            netsplits
+                .add_quit("a", ("x.net", "y.net"), std::slice::from_ref(&chan), now)
+                .is_none()
+        );
+        match netsplits.add_quit("b", ("x.net", "z.net"), std::slice::from_ref(&chan), now) {
+            Some(Event::Netsplit { servers, .. }) => {
+                assert_eq!(servers, ("x.net".to_owned(), "y.net".to_owned()))
+            }
+            other => panic!("Unexpected event: {other:?}"),
+        }
+        match netsplits.flush().as_slice() {
+            [Event::Netsplit { servers, .. }] => {
+                assert_eq!(servers, &("x.net".to_owned(), "z.net".to_owned()))
+            }
+            other => panic!("Unexpected events: {other:?}"),
+        }
+    }
+}
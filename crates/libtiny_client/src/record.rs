@@ -0,0 +1,200 @@
+//! Recording raw IRC traffic of connections, for debugging. Recordings can be replayed with
+//! `Client::replay`.
+//!
+//! A recording has one line per IRC message, in `<timestamp> <direction> <message>` format.
+//! `<timestamp>` is milliseconds since Unix epoch, `<direction>` is `<` for messages received from
+//! the server and `>` for messages sent to the server. Messages don't have the "\r\n" suffix.
+//!
+//! Passwords in sent messages are replaced with `<redacted>`, see `redact`. Recording files are
+//! only readable by the user.
+
+use std::borrow::Cow;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Received,
+    Sent,
+}
+
+impl Direction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Direction::Received => "<",
+            Direction::Sent => ">",
+        }
+    }
+}
+
+/// Records traffic of a connection to a file.
+pub(crate) struct Recorder {
+    file: File,
+
+    /// Received bytes of an incomplete message.
+    partial: Vec<u8>,
+}
+
+fn now_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0)
+}
+
+/// Server name as a file name. Server names can be paths (e.g. `unix:/path/to/socket`), which we
+/// can't use in file names, so path separators are replaced with '-'.
+fn file_name(serv_name: &str) -> String {
+    serv_name.replace(['/', '\\'], "-")
+}
+
+impl Recorder {
+    /// Create a recording file for a new connection to `serv_name` in `dir`.
+    pub(crate) fn create(dir: &Path, serv_name: &str) -> io::Result<Recorder> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{}_{}.txt", file_name(serv_name), now_millis()));
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let file = options
+            .open(&path)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", path.display())))?;
+        Ok(Recorder {
+            file,
+            partial: vec![],
+        })
+    }
+
+    /// Record a message sent to the server. `msg` should have the "\r\n" suffix.
+    pub(crate) fn sent(&mut self, msg: &str) {
+        for line in msg.split("\r\n").filter(|line| !line.is_empty()) {
+            self.write_line(Direction::Sent, &redact(line));
+        }
+    }
+
+    /// Record bytes received from the server. Messages are recorded when complete.
+    pub(crate) fn received(&mut self, bytes: &[u8]) {
+        self.partial.extend_from_slice(bytes);
+        while let Some(crlf_idx) = self.partial.windows(2).position(|sub| sub == b"\r\n") {
+            let line: Vec<u8> = self.partial.drain(0..crlf_idx + 2).collect();
+            let line = String::from_utf8_lossy(&line[0..crlf_idx]).into_owned();
+            self.write_line(Direction::Received, &line);
+        }
+    }
+
+    fn write_line(&mut self, dir: Direction, line: &str) {
+        if let Err(err) = writeln!(self.file, "{} {} {line}", now_millis(), dir.as_str()) {
+            debug!("Can't write to recording file: {err:?}");
+        }
+    }
+}
+
+const REDACTED: &str = "<redacted>";
+
+/// Replace the passwords in a message sent to the server: arguments of `PASS` and `AUTHENTICATE`,
+/// and of `IDENTIFY` messages to NickServ.
+fn redact(line: &str) -> Cow<'_, str> {
+    let (cmd, args) = line.split_once(' ').unwrap_or((line, ""));
+    if cmd.eq_ignore_ascii_case("PASS") || cmd.eq_ignore_ascii_case("AUTHENTICATE") {
+        return Cow::Owned(format!("{cmd} {REDACTED}"));
+    }
+    if cmd.eq_ignore_ascii_case("PRIVMSG")
+        && let Some((target, msg)) = args.split_once(' ')
+        && target.eq_ignore_ascii_case("NickServ")
+        && let Some((word, _)) = msg.strip_prefix(':').unwrap_or(msg).split_once(' ')
+        && word.eq_ignore_ascii_case("IDENTIFY")
+    {
+        return Cow::Owned(format!("{cmd} {target} :{word} {REDACTED}"));
+    }
+    Cow::Borrowed(line)
+}
+
+/// Parse a recording. Lines that are not in the recording format are skipped.
+pub(crate) fn parse_recording(recording: &str) -> impl Iterator<Item = (Direction, &str)> {
+    recording.lines().filter_map(|line| {
+        let (_timestamp, rest) = line.split_once(' ')?;
+        let (dir, msg) = rest.split_once(' ')?;
+        let dir = match dir {
+            "<" => Direction::Received,
+            ">" => Direction::Sent,
+            _ => return None,
+        };
+        Some((dir, msg))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut recorder = Recorder::create(dir.path(), "irc.example.org").unwrap();
+        recorder.sent("NICK tiny\r\n");
+        recorder.received(b":irc 001 tiny :Wel");
+        recorder.received(b"come\r\nPING :irc\r\n");
+        recorder.sent("PONG irc\r\n");
+        drop(recorder);
+
+        let entry = fs::read_dir(dir.path()).unwrap().next().unwrap().unwrap();
+        let file_name = entry.file_name().into_string().unwrap();
+        assert!(file_name.starts_with("irc.example.org_"));
+
+        let recording = fs::read_to_string(entry.path()).unwrap();
+        assert_eq!(
+            parse_recording(&recording).collect::<Vec<_>>(),
+            vec![
+                (Direction::Sent, "NICK tiny"),
+                (Direction::Received, ":irc 001 tiny :Welcome"),
+                (Direction::Received, "PING :irc"),
+                (Direction::Sent, "PONG irc"),
+            ]
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = entry.metadata().unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_record_unix_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        drop(Recorder::create(dir.path(), "unix:/run/irc/socket").unwrap());
+
+        let entry = fs::read_dir(dir.path()).unwrap().next().unwrap().unwrap();
+        let file_name = entry.file_name().into_string().unwrap();
+        assert!(file_name.starts_with("unix:-run-irc-socket_"));
+    }
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(redact("PASS hunter2"), "PASS <redacted>");
+        assert_eq!(
+            redact("AUTHENTICATE dGlueQB0aW55"),
+            "AUTHENTICATE <redacted>"
+        );
+        assert_eq!(
+            redact("PRIVMSG NickServ :identify hunter2"),
+            "PRIVMSG NickServ :identify <redacted>"
+        );
+        assert_eq!(
+            redact("PRIVMSG nickserv :IDENTIFY tiny hunter2"),
+            "PRIVMSG nickserv :IDENTIFY <redacted>"
+        );
+        assert_eq!(
+            redact("PRIVMSG #chan :identify yourself"),
+            "PRIVMSG #chan :identify yourself"
+        );
+        assert_eq!(redact("NICK tiny"), "NICK tiny");
+    }
+}
@@ -1,5 +1,7 @@
 #![allow(clippy::get_first, clippy::zero_prefixed_literal)]
 
+use crate::netsplit::{self, Netsplits};
+use crate::sts::{self, StsValue};
 use crate::{Cmd, Event, ServerInfo};
 use crate::{SASLAuth, utils};
 use libtiny_common::{ChanName, ChanNameRef};
@@ -9,6 +11,7 @@ use libtiny_wire::{Msg, Pfx};
 use std::cell::RefCell;
 use std::collections::HashSet;
 use std::rc::Rc;
+use std::time::Instant;
 
 use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::time::{Duration, timeout};
@@ -27,8 +30,10 @@ impl State {
         }
     }
 
-    pub(crate) fn reset(&self) {
-        self.inner.borrow_mut().reset()
+    /// Reset the connection state for a new connection. `secure` is whether the connection is
+    /// encrypted or local, see `sts`.
+    pub(crate) fn reset(&self, secure: bool) {
+        self.inner.borrow_mut().reset(secure)
     }
 
     pub(crate) fn send_ping(&self, snd_irc_msg: &mut Sender<String>) {
@@ -40,12 +45,20 @@ impl State {
         msg: &mut Msg,
         snd_ev: &mut Sender<Event>,
         snd_irc_msg: &mut Sender<String>,
-    ) {
-        self.inner.borrow_mut().update(msg, snd_ev, snd_irc_msg);
+    ) -> bool {
+        self.inner.borrow_mut().update(msg, snd_ev, snd_irc_msg)
+    }
+
+    /// Start connection registration. Registration is completed after the server's CAP LS
+    /// response if we need to send credentials during registration, so that we don't send them
+    /// over a connection that the server's STS policy upgrades.
+    pub(crate) fn register(&self, snd_irc_msg: &mut Sender<String>) {
+        self.inner.borrow_mut().register(snd_irc_msg)
     }
 
-    pub(crate) fn introduce(&self, snd_irc_msg: &mut Sender<String>) {
-        self.inner.borrow_mut().introduce(snd_irc_msg)
+    /// Get the STS policy advertised by the server in the current connection.
+    pub(crate) fn take_sts(&self) -> Option<StsValue> {
+        self.inner.borrow_mut().sts.take()
     }
 
     // FIXME: This allocates a new String
@@ -84,6 +97,43 @@ impl State {
     pub(crate) fn kill_join_tasks(&self) {
         self.inner.borrow_mut().kill_join_tasks();
     }
+
+    pub(crate) fn auto_join_invite(&self, nick: &str, chan: &ChanNameRef) -> bool {
+        self.inner.borrow().server_info.auto_join_invite(nick, chan)
+    }
+
+    /// Remove an invite from pending invites. When `chan` is `None` the most recent invite is
+    /// removed.
+    pub(crate) fn take_invite(&self, chan: Option<&ChanNameRef>) -> Option<ChanName> {
+        self.inner.borrow_mut().take_invite(chan, Instant::now())
+    }
+
+    pub(crate) fn is_joined(&self, chan: &ChanNameRef) -> bool {
+        self.inner.borrow().is_joined(chan)
+    }
+
+    /// Parameters of a LIST message with the given filters. Filters that the server doesn't
+    /// support (see `elist`) are not sent.
+    pub(crate) fn list_params(
+        &self,
+        pattern: Option<&str>,
+        min_users: Option<usize>,
+    ) -> Vec<String> {
+        let elist = &self.inner.borrow().elist;
+        let mut params = vec![];
+        if let Some(pattern) = pattern
+            && elist.contains('M')
+        {
+            params.push(pattern.to_owned());
+        }
+        if let Some(min_users) = min_users
+            && elist.contains('U')
+        {
+            // ">N" is "more than N users"
+            params.push(format!(">{}", min_users.saturating_sub(1)));
+        }
+        params
+    }
 }
 
 struct StateInner {
@@ -128,6 +178,27 @@ struct StateInner {
 
     /// Server information
     server_info: ServerInfo,
+
+    /// Netsplits and netjoins being grouped. Shared with the task that reports the groups.
+    netsplits: Rc<RefCell<Netsplits>>,
+
+    /// Is the current connection encrypted or local? STS policies advertised on connections that
+    /// are not secure stop the registration.
+    secure: bool,
+
+    /// STS policy advertised in CAP LS and not yet handled by the main loop.
+    sts: Option<StsValue>,
+
+    /// Capabilities in the lines of a multi-line CAP LS reply received so far.
+    cap_ls: Vec<String>,
+
+    /// Channels we're invited to and haven't joined yet, with the time of the invite. The most
+    /// recent invite last.
+    invites: Vec<(ChanName, Instant)>,
+
+    /// Search extensions supported in LIST, from the ELIST token of RPL_ISUPPORT (005). Empty
+    /// when the server doesn't advertise ELIST.
+    elist: String,
 }
 
 #[derive(Debug)]
@@ -146,9 +217,9 @@ struct Chan {
 ///    NotJoined -> Joining: When we get 477 for the channel
 ///    NotJoined -> Joined: When we get a JOIN message for the channel on first attempt
 ///    Joining -> Joined: When we get a JOIN message for the channel
-///    Joining -> NotJoined: Connection reset
+///    Joining -> NotJoined: Connection reset, or out of retries after 474
 ///    Joined -> NotJoined: Connection reset
-///    Joined -> Joining: Unexpected/Invalid state
+///    Joined -> Joining: When we're kicked from a channel that we rejoin on kicks
 #[derive(Debug)]
 enum JoinState {
     /// Initial state for Chan
@@ -157,6 +228,8 @@ enum JoinState {
     Joining {
         /// Sender to kill the retry task if tab is closed
         stop_task: Sender<()>,
+        /// Whether we're rejoining after a kick. Bans are only retried in this state.
+        after_kick: bool,
     },
     /// Successfully joined the channel
     Joined,
@@ -164,6 +237,26 @@ enum JoinState {
 
 const MAX_JOIN_RETRIES: u8 = 3;
 
+/// Max. delay in seconds between attempts to rejoin a channel we're banned from.
+const MAX_REJOIN_DELAY_SECS: u64 = 60 * 60;
+
+/// Delay in seconds before rejoin attempt `attempt` (starting with 1) to a channel we're banned
+/// from. The delay doubles on each attempt, starting with at least one second.
+fn rejoin_delay(delay: u64, attempt: u8) -> u64 {
+    let factor = 1u64.checked_shl(u32::from(attempt)).unwrap_or(u64::MAX);
+    delay
+        .max(1)
+        .saturating_mul(factor)
+        .min(MAX_REJOIN_DELAY_SECS)
+}
+
+/// Max. number of pending invites. The oldest invite is dropped when we're invited to more
+/// channels.
+const MAX_INVITES: usize = 20;
+
+/// Invites that are not accepted within this time are dropped.
+const INVITE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
 impl Chan {
     fn new(name: ChanName) -> Chan {
         Chan {
@@ -189,8 +282,38 @@ impl Chan {
         self.join_attempts = MAX_JOIN_RETRIES;
     }
 
-    fn set_joining(&mut self, stop_task: Sender<()>) {
-        self.join_state = JoinState::Joining { stop_task }
+    fn set_joining(&mut self, stop_task: Sender<()>, after_kick: bool) {
+        self.join_state = JoinState::Joining {
+            stop_task,
+            after_kick,
+        }
+    }
+
+    /// Whether we're rejoining the channel after a kick.
+    fn is_rejoining_after_kick(&self) -> bool {
+        matches!(
+            self.join_state,
+            JoinState::Joining {
+                after_kick: true,
+                ..
+            }
+        )
+    }
+
+    /// Spawn a task to rejoin the channel after a kick, after `delay`. The task is aborted when we
+    /// leave the channel before the delay.
+    fn schedule_rejoin(&mut self, delay: Duration, snd_irc_msg: &Sender<String>) {
+        if let JoinState::Joining { stop_task, .. } = &self.join_state {
+            let _ = stop_task.try_send(());
+        }
+        let (snd_abort, rcv_abort) = tokio::sync::mpsc::channel(1);
+        self.set_joining(snd_abort, true);
+        tokio::task::spawn_local(retry_channel_join(
+            self.name.clone(),
+            delay,
+            snd_irc_msg.clone(),
+            rcv_abort,
+        ));
     }
 
     /// Uses a retry.
@@ -225,10 +348,16 @@ impl StateInner {
             usermask: None,
             nick_accepted: false,
             server_info,
+            netsplits: Rc::new(RefCell::new(Netsplits::default())),
+            secure: false,
+            sts: None,
+            cap_ls: vec![],
+            invites: vec![],
+            elist: String::new(),
         }
     }
 
-    fn reset(&mut self) {
+    fn reset(&mut self, secure: bool) {
         self.nick_accepted = false;
         self.nicks.clone_from(&self.server_info.nicks);
         self.current_nick_idx = 0;
@@ -239,6 +368,11 @@ impl StateInner {
         }
         self.servername = None;
         self.usermask = None;
+        self.netsplits.borrow_mut().reset();
+        self.secure = secure;
+        self.sts = None;
+        self.cap_ls.clear();
+        self.elist.clear();
     }
 
     fn send_ping(&mut self, snd_irc_msg: &mut Sender<String>) {
@@ -247,6 +381,18 @@ impl StateInner {
         }
     }
 
+    /// Whether to wait for the CAP LS response before introducing self. See `register`.
+    fn introduce_after_cap_ls(&self) -> bool {
+        self.server_info.pass.is_some() || self.server_info.sasl_auth.is_some()
+    }
+
+    fn register(&mut self, snd_irc_msg: &mut Sender<String>) {
+        snd_irc_msg.try_send(wire::cap_ls()).unwrap();
+        if !self.introduce_after_cap_ls() {
+            self.introduce(snd_irc_msg);
+        }
+    }
+
     fn introduce(&mut self, snd_irc_msg: &mut Sender<String>) {
         if let Some(ref pass) = self.server_info.pass {
             snd_irc_msg.try_send(wire::pass(pass)).unwrap();
@@ -277,12 +423,14 @@ impl StateInner {
         &self.current_nick
     }
 
+    /// Returns whether the message should be reported to the user as `Event::Msg`. Messages of
+    /// netsplits and netjoins are reported later as `Event::Netsplit` and `Event::Netjoin`.
     fn update(
         &mut self,
         msg: &mut Msg,
         snd_ev: &mut Sender<Event>,
         snd_irc_msg: &mut Sender<String>,
-    ) {
+    ) -> bool {
         let Msg { pfx, cmd } = msg;
 
         use wire::Cmd::*;
@@ -307,6 +455,7 @@ impl StateInner {
                 match pfx {
                     Some(Pfx::User { nick, .. }) | Some(Pfx::Ambiguous(nick)) => {
                         if nick == &self.current_nick {
+                            self.invites.retain(|(invite, _)| invite != chan);
                             // We joined a channel, initialize channel state
                             match utils::find_idx(&self.chans, |c| &c.name == chan) {
                                 None => {
@@ -331,8 +480,16 @@ impl StateInner {
                                         .insert(wire::drop_nick_prefix(nick).to_owned());
                                 }
                                 None => {
-                                    debug!("Can't find channel state for JOIN: {cmd:?}");
+                                    debug!("Can't find channel state for JOIN: {}", chan.display());
+                                }
+                            }
+                            let mut netsplits = self.netsplits.borrow_mut();
+                            let nick = wire::drop_nick_prefix(nick);
+                            if netsplits.add_join(nick, chan, Instant::now()) {
+                                if netsplits.schedule_flush() {
+                                    spawn_flush_netsplits(&self.netsplits, snd_ev);
                                 }
+                                return false;
                             }
                         }
                     }
@@ -369,12 +526,63 @@ impl StateInner {
                 Some(Pfx::Server(_)) | None => {}
             },
 
-            // QUIT: Update the `chans` field for the channels that the user was in
-            QUIT { chans, .. } => {
+            // INVITE: Remember the channel for `Client::accept_invite`, join it if the inviting nick
+            // or the channel is in `auto_join_on_invite`.
+            INVITE { nick, chan } if nick == &self.current_nick => {
+                let inviter = match pfx {
+                    Some(Pfx::User { nick, .. }) | Some(Pfx::Ambiguous(nick)) => nick,
+                    Some(Pfx::Server(_)) | None => {
+                        return true;
+                    }
+                };
+                if self.is_joined(chan) {
+                    return true;
+                }
+                if self.server_info.auto_join_invite(inviter, chan) {
+                    snd_irc_msg
+                        .try_send(wire::join(std::iter::once(chan.as_ref())))
+                        .unwrap();
+                } else {
+                    self.add_invite(chan, Instant::now());
+                }
+            }
+
+            // KICK: If we're kicked rejoin the channel when configured, otherwise remove the
+            // channel state. If someone else is kicked remove the nick from the channel.
+            KICK { chan, nick, .. } => {
+                let chan_idx = match utils::find_idx(&self.chans, |c| &c.name == chan) {
+                    Some(chan_idx) => chan_idx,
+                    None => {
+                        debug!("Can't find channel state for KICK: {}", chan.display());
+                        return true;
+                    }
+                };
+                if nick == &self.current_nick {
+                    match self.server_info.rejoin_on_kick.get(chan) {
+                        None => {
+                            self.chans.remove(chan_idx);
+                        }
+                        Some(delay) => {
+                            let chan = &mut self.chans[chan_idx];
+                            chan.nicks.clear();
+                            chan.join_attempts = MAX_JOIN_RETRIES;
+                            chan.schedule_rejoin(Duration::from_secs(*delay), snd_irc_msg);
+                        }
+                    }
+                } else {
+                    self.chans[chan_idx]
+                        .nicks
+                        .remove(wire::drop_nick_prefix(nick));
+                }
+            }
+
+            // QUIT: Update the `chans` field for the channels that the user was in. If this is a
+            // netsplit add the nick to the current netsplit group.
+            QUIT { chans, msg } => {
                 let nick = match pfx {
                     Some(Pfx::User { nick, .. }) | Some(Pfx::Ambiguous(nick)) => nick,
                     Some(Pfx::Server(_)) | None => {
-                        return;
+                        return true;
                     }
                 };
                 for chan in self.chans.iter_mut() {
@@ -383,17 +591,26 @@ impl StateInner {
                         chan.nicks.remove(nick);
                     }
                 }
+                if let Some(servers) = msg.as_deref().and_then(netsplit::parse_split_msg)
+                    && !chans.is_empty()
+                {
+                    let mut netsplits = self.netsplits.borrow_mut();
+                    if let Some(ev) = netsplits.add_quit(nick, servers, chans, Instant::now()) {
+                        snd_ev.try_send(ev).unwrap();
+                    }
+                    if netsplits.schedule_flush() {
+                        spawn_flush_netsplits(&self.netsplits, snd_ev);
+                    }
+                    return false;
+                }
             }
 
             // 396: Try to set usermask.
-            Reply { num: 396, params } => {
+            Reply { num: 396, params } if params.len() == 3 => {
                 // :hobana.freenode.net 396 osa1 haskell/developer/osa1
                 // :is now your hidden host (set by services.)
-                if params.len() == 3 {
-                    let usermask =
-                        format!("{}!~{}@{}", self.current_nick, self.nicks[0], params[1]);
-                    self.usermask = Some(usermask);
-                }
+                let usermask = format!("{}!~{}@{}", self.current_nick, self.nicks[0], params[1]);
+                self.usermask = Some(usermask);
             }
 
             // Reply 477 when user needs to be identified with NickServ to join a channel
@@ -436,15 +653,16 @@ impl StateInner {
                                 // Spawn task and delay rejoin to give NickServ time to identify nick
                                 let (snd_abort, rcv_abort) = tokio::sync::mpsc::channel(1);
                                 match &mut chan.join_state {
-                                    JoinState::NotJoined => chan.set_joining(snd_abort),
+                                    JoinState::NotJoined => chan.set_joining(snd_abort, false),
                                     JoinState::Joining { stop_task, .. } => *stop_task = snd_abort,
                                     JoinState::Joined => {
                                         error!("Unexpected JoinState for channel.");
-                                        return;
+                                        return true;
                                     }
                                 }
                                 tokio::task::spawn_local(retry_channel_join(
                                     channel.to_owned(),
+                                    Duration::from_secs(10),
                                     snd_irc_msg,
                                     rcv_abort,
                                 ));
@@ -465,6 +683,50 @@ impl StateInner {
                 }
             }
 
+            // ERR_BANNEDFROMCHAN: If we're rejoining the channel after a kick, try again with
+            // backoff until the ban expires or we run out of retries.
+            Reply { num: 474, params } => {
+                let Some(channel) = params.get(1) else {
+                    warn!("Could not parse 474 reply: {cmd:?}");
+                    return true;
+                };
+                let channel = ChanNameRef::new(channel);
+                let Some(delay) = self.server_info.rejoin_on_kick.get(channel).copied() else {
+                    return true;
+                };
+                let Some(idx) = utils::find_idx(&self.chans, |c| c.name == *channel) else {
+                    return true;
+                };
+                let chan = &mut self.chans[idx];
+                if !chan.is_rejoining_after_kick() {
+                    return true;
+                }
+                let msg = match chan.retry_join() {
+                    Some(retries) => {
+                        let attempt = MAX_JOIN_RETRIES - retries;
+                        let delay = rejoin_delay(delay, attempt);
+                        chan.schedule_rejoin(Duration::from_secs(delay), snd_irc_msg);
+                        format!(
+                            "Banned from {}, trying again in {} seconds... ({}/{})",
+                            channel.display(),
+                            delay,
+                            attempt,
+                            MAX_JOIN_RETRIES
+                        )
+                    }
+                    None => {
+                        chan.join_state = JoinState::NotJoined;
+                        format!("Unable to join {}.", channel.display())
+                    }
+                };
+                snd_ev
+                    .try_send(Event::ChannelJoinError {
+                        chan: channel.to_owned(),
+                        msg,
+                    })
+                    .unwrap();
+            }
+
             // 302: Try to set usermask.
             Reply { num: 302, params } => {
                 // 302 RPL_USERHOST
@@ -525,20 +787,30 @@ impl StateInner {
                 }
             }
 
-            // ERR_NICKNAMEINUSE: Try another nick if we don't have a nick yet.
-            Reply { num: 433, .. } => {
-                if !self.nick_accepted {
-                    let new_nick = self.get_next_nick();
-                    // debug!("new nick: {}", new_nick);
-                    snd_ev
-                        .try_send(Event::NickChange {
-                            new_nick: new_nick.to_owned(),
-                        })
-                        .unwrap();
-                    snd_irc_msg.try_send(wire::nick(new_nick)).unwrap();
+            // RPL_ISUPPORT: Set search extensions of LIST
+            Reply { num: 005, params } if params.len() > 2 => {
+                // First parameter is our nick, last one is "are supported by this server"
+                for token in &params[1..params.len() - 1] {
+                    if let Some(elist) = token.strip_prefix("ELIST=") {
+                        self.elist = elist.to_ascii_uppercase();
+                    } else if token == "-ELIST" {
+                        self.elist.clear();
+                    }
                 }
             }
 
+            // ERR_NICKNAMEINUSE: Try another nick if we don't have a nick yet.
+            Reply { num: 433, .. } if !self.nick_accepted => {
+                let new_nick = self.get_next_nick();
+                // debug!("new nick: {}", new_nick);
+                snd_ev
+                    .try_send(Event::NickChange {
+                        new_nick: new_nick.to_owned(),
+                    })
+                    .unwrap();
+                snd_irc_msg.try_send(wire::nick(new_nick)).unwrap();
+            }
+
             // NICK message sent from the server when our nick change request was successful
             NICK {
                 nick: new_nick,
@@ -617,34 +889,52 @@ impl StateInner {
                 }
             }
 
-            // SASL authentication
+            // Capability negotiation: SASL authentication and STS
             CAP {
                 client: _,
                 subcommand,
                 params,
+                more,
             } => {
                 match subcommand.as_ref() {
-                    "ACK" => {
-                        if params.iter().any(|cap| cap.as_str() == "sasl") {
-                            if let Some(sasl) = &self.server_info.sasl_auth {
-                                let msg = match sasl {
-                                    SASLAuth::Plain { .. } => "PLAIN",
-                                    SASLAuth::External { .. } => "EXTERNAL",
-                                };
-                                snd_irc_msg.try_send(wire::authenticate(msg)).unwrap();
-                            } else {
-                                warn!("SASL AUTH not set but got SASL ACK");
-                            }
+                    "ACK" if params.iter().any(|cap| cap.as_str() == "sasl") => {
+                        if let Some(sasl) = &self.server_info.sasl_auth {
+                            let msg = match sasl {
+                                SASLAuth::Plain { .. } => "PLAIN",
+                                SASLAuth::External { .. } => "EXTERNAL",
+                            };
+                            snd_irc_msg.try_send(wire::authenticate(msg)).unwrap();
+                        } else {
+                            warn!("SASL AUTH not set but got SASL ACK");
                         }
                     }
                     "NAK" => {
                         snd_irc_msg.try_send(wire::cap_end()).unwrap();
                     }
+                    "LS" if *more => {
+                        self.cap_ls.extend(params.iter().cloned());
+                    }
                     "LS" => {
-                        self.introduce(snd_irc_msg);
-                        if params.iter().any(|cap| cap == "sasl") {
+                        let mut caps = std::mem::take(&mut self.cap_ls);
+                        caps.extend(params.iter().cloned());
+                        let params = &caps;
+                        self.sts = sts::find_sts_value(params);
+                        if !self.secure
+                            && let Some(StsValue { port: Some(_), .. }) = self.sts
+                        {
+                            // Main loop reconnects with TLS, don't send anything more
+                            return true;
+                        }
+                        if self.introduce_after_cap_ls() {
+                            self.introduce(snd_irc_msg);
+                        }
+                        if self.server_info.sasl_auth.is_some()
+                            && params.iter().any(|cap| cap == "sasl")
+                        {
                             snd_irc_msg.try_send(wire::cap_req(&["sasl"])).unwrap();
                             // Will wait for CAP ... ACK from server before authentication.
+                        } else {
+                            snd_irc_msg.try_send(wire::cap_end()).unwrap();
                         }
                     }
                     _ => {}
@@ -652,24 +942,30 @@ impl StateInner {
             }
 
             // https://ircv3.net/specs/extensions/sasl-3.1.html
-            AUTHENTICATE { param } => {
-                if param.as_str() == "+" {
-                    // Empty AUTHENTICATE response; server accepted the specified SASL mechanism
-                    if let Some(ref auth) = self.server_info.sasl_auth {
-                        let msg = match auth {
-                            SASLAuth::Plain { username, password } => {
-                                let msg = format!("{username}\x00{username}\x00{password}");
-                                use base64::engine::Engine;
-                                base64::engine::general_purpose::STANDARD.encode(msg)
-                            }
-                            // Reply with an empty response (Empty responses are sent as "AUTHENTICATE +")
-                            SASLAuth::External { .. } => "+".to_string(),
-                        };
-                        snd_irc_msg.try_send(wire::authenticate(&msg)).unwrap();
-                    }
+            AUTHENTICATE { param } if param.as_str() == "+" => {
+                // Empty AUTHENTICATE response; server accepted the specified SASL mechanism
+                if let Some(ref auth) = self.server_info.sasl_auth {
+                    let msg = match auth {
+                        SASLAuth::Plain { username, password } => {
+                            let msg = format!("{username}\x00{username}\x00{password}");
+                            use base64::engine::Engine;
+                            base64::engine::general_purpose::STANDARD.encode(msg)
+                        }
+                        // Reply with an empty response (Empty responses are sent as "AUTHENTICATE +")
+                        SASLAuth::External { .. } => "+".to_string(),
+                    };
+                    snd_irc_msg.try_send(wire::authenticate(&msg)).unwrap();
                 }
             }
 
+            // ERR_UNKNOWNCOMMAND for CAP LS: server doesn't support capability negotiation
+            Reply { num: 421, params }
+                if params.get(1).map(String::as_str) == Some("CAP")
+                    && self.introduce_after_cap_ls() =>
+            {
+                self.introduce(snd_irc_msg);
+            }
+
             Reply { num: 903, .. } | Reply { num: 904, .. } => {
                 // 903: RPL_SASLSUCCESS, 904: ERR_SASLFAIL
                 snd_irc_msg.try_send(wire::cap_end()).unwrap();
@@ -678,6 +974,34 @@ impl StateInner {
             // Ignore the rest
             _ => {}
         }
+
+        true
+    }
+
+    fn is_joined(&self, chan: &ChanNameRef) -> bool {
+        self.chans
+            .iter()
+            .any(|c| c.name == *chan && matches!(c.join_state, JoinState::Joined))
+    }
+
+    /// Remember an invite to `chan`, replacing an earlier invite to the same channel.
+    fn add_invite(&mut self, chan: &ChanNameRef, now: Instant) {
+        self.invites
+            .retain(|(invite, time)| invite != chan && now.duration_since(*time) < INVITE_TIMEOUT);
+        if self.invites.len() >= MAX_INVITES {
+            self.invites.remove(0);
+        }
+        self.invites.push((chan.to_owned(), now));
+    }
+
+    fn take_invite(&mut self, chan: Option<&ChanNameRef>, now: Instant) -> Option<ChanName> {
+        self.invites
+            .retain(|(_, time)| now.duration_since(*time) < INVITE_TIMEOUT);
+        let idx = match chan {
+            None => self.invites.len().checked_sub(1)?,
+            Some(chan) => utils::find_idx(&self.invites, |(invite, _)| invite == chan)?,
+        };
+        Some(self.invites.remove(idx).0)
     }
 
     fn get_chan_nicks(&self, chan: &ChanNameRef) -> Vec<String> {
@@ -724,7 +1048,7 @@ impl StateInner {
     /// Kills all tasks that are trying to join channels
     fn kill_join_tasks(&mut self) {
         for chan in &mut self.chans {
-            if let JoinState::Joining { stop_task } = &mut chan.join_state {
+            if let JoinState::Joining { stop_task, .. } = &mut chan.join_state {
                 let _ = stop_task.try_send(());
             }
         }
@@ -733,6 +1057,7 @@ impl StateInner {
 
 async fn retry_channel_join(
     channel: ChanName,
+    delay: Duration,
     snd_irc_msg: Sender<String>,
     rcv_abort: Receiver<()>,
 ) {
@@ -741,10 +1066,7 @@ async fn retry_channel_join(
     let mut rcv_abort = ReceiverStream::new(rcv_abort).fuse();
 
     // Send join message after timeout. Ok means channel tab was closed.
-    if timeout(Duration::from_secs(10), rcv_abort.next())
-        .await
-        .is_err()
-    {
+    if timeout(delay, rcv_abort.next()).await.is_err() {
         // Send join message
         snd_irc_msg
             .try_send(wire::join(std::iter::once(channel.as_ref())))
@@ -752,6 +1074,19 @@ async fn retry_channel_join(
     }
 }
 
+fn spawn_flush_netsplits(netsplits: &Rc<RefCell<Netsplits>>, snd_ev: &Sender<Event>) {
+    tokio::task::spawn_local(flush_netsplits(netsplits.clone(), snd_ev.clone()));
+}
+
+/// Report the netsplit and netjoin groups after `netsplit::GROUP_SECS` seconds.
+async fn flush_netsplits(netsplits: Rc<RefCell<Netsplits>>, snd_ev: Sender<Event>) {
+    tokio::time::sleep(Duration::from_secs(netsplit::GROUP_SECS)).await;
+    let evs = netsplits.borrow_mut().flush();
+    for ev in evs {
+        snd_ev.send(ev).await.unwrap();
+    }
+}
+
 const SERVERNAME_PREFIX: &str = "Your host is ";
 const SERVERNAME_PREFIX_LEN: usize = SERVERNAME_PREFIX.len();
 
@@ -835,4 +1170,157 @@ mod tests {
             Some("irc.gitter.im".to_owned())
         );
     }
+
+    fn server_info() -> ServerInfo {
+        ServerInfo {
+            name: "x.y.z".to_owned(),
+            endpoints: vec![crate::Endpoint {
+                addr: "x.y.z".to_owned(),
+                port: 6667,
+                tls: false,
+            }],
+            connect_timeout: crate::DEFAULT_CONNECT_TIMEOUT_SECS,
+            prefer_addr_family: None,
+            bind_addr: None,
+            sts_file: None,
+            record_dir: None,
+            pass: None,
+            user: None,
+            realname: "tiny".to_owned(),
+            nicks: vec!["tiny".to_owned()],
+            auto_join: vec![],
+            auto_join_on_invite: vec![],
+            rejoin_on_kick: Default::default(),
+            nickserv_ident: None,
+            sasl_auth: None,
+        }
+    }
+
+    #[test]
+    fn test_multi_line_cap_ls() {
+        let mut state = StateInner::new(ServerInfo {
+            sasl_auth: Some(SASLAuth::Plain {
+                username: "tiny".to_owned(),
+                password: "hunter2".to_owned(),
+            }),
+            ..server_info()
+        });
+        let (mut snd_ev, _rcv_ev) = tokio::sync::mpsc::channel(100);
+        let (mut snd_irc_msg, mut rcv_irc_msg) = tokio::sync::mpsc::channel(100);
+
+        // Registration waits for the last line of the reply
+        state.register(&mut snd_irc_msg);
+        assert_eq!(rcv_irc_msg.try_recv().unwrap(), "CAP LS 302\r\n");
+
+        let mut parse = |msg: &str| {
+            let mut buf = msg.as_bytes().to_vec();
+            let mut msg = wire::parse_irc_msg(&mut buf).unwrap().unwrap();
+            state.update(&mut msg, &mut snd_ev, &mut snd_irc_msg);
+        };
+        let mut sent = || {
+            let mut msgs = vec![];
+            while let Ok(msg) = rcv_irc_msg.try_recv() {
+                msgs.push(msg);
+            }
+            msgs
+        };
+
+        parse(":x.y.z CAP * LS * :multi-prefix sasl\r\n");
+        assert!(sent().is_empty());
+        parse(":x.y.z CAP * LS :account-notify\r\n");
+        assert_eq!(
+            sent(),
+            vec![
+                "NICK tiny\r\n",
+                "USER tiny 8 * :tiny\r\n",
+                "CAP REQ :sasl\r\n"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejoin_delay() {
+        assert_eq!(rejoin_delay(0, 1), 2);
+        assert_eq!(rejoin_delay(10, 3), 80);
+        assert_eq!(rejoin_delay(u64::MAX / 2, 3), MAX_REJOIN_DELAY_SECS);
+        assert_eq!(rejoin_delay(1, 200), MAX_REJOIN_DELAY_SECS);
+    }
+
+    #[test]
+    fn test_invites() {
+        let mut state = StateInner::new(server_info());
+        let now = Instant::now();
+        let chan = |i: usize| ChanName::new(format!("#chan{i}"));
+
+        // Oldest invites are dropped
+        for i in 0..MAX_INVITES + 1 {
+            state.add_invite(&chan(i), now);
+        }
+        assert_eq!(state.invites.len(), MAX_INVITES);
+        assert_eq!(state.take_invite(Some(&chan(0)), now), None);
+        assert_eq!(state.take_invite(Some(&chan(1)), now), Some(chan(1)));
+        assert_eq!(state.take_invite(None, now), Some(chan(MAX_INVITES)));
+
+        // Invites expire
+        let later = now + INVITE_TIMEOUT;
+        assert_eq!(state.take_invite(None, later), None);
+        assert!(state.invites.is_empty());
+    }
+
+    #[test]
+    fn test_rejoin_on_kick() {
+        // Time is advanced automatically when the retry tasks wait
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .start_paused(true)
+            .build()
+            .unwrap();
+        let local = tokio::task::LocalSet::new();
+
+        local.block_on(&runtime, async move {
+            let chan = ChanName::new("#chan".to_owned());
+            let mut state = StateInner::new(ServerInfo {
+                auto_join: vec![chan.clone()],
+                rejoin_on_kick: [(chan.clone(), 0)].into_iter().collect(),
+                ..server_info()
+            });
+            let (mut snd_ev, _rcv_ev) = tokio::sync::mpsc::channel(100);
+            let (mut snd_irc_msg, mut rcv_irc_msg) = tokio::sync::mpsc::channel(100);
+
+            let mut parse = |msg: &str| {
+                let mut buf = msg.as_bytes().to_vec();
+                let mut msg = wire::parse_irc_msg(&mut buf).unwrap().unwrap();
+                state.update(&mut msg, &mut snd_ev, &mut snd_irc_msg);
+                matches!(
+                    state.chans[..],
+                    [Chan {
+                        join_state: JoinState::Joining { .. },
+                        ..
+                    }]
+                )
+            };
+
+            // Bans are not retried on the first join
+            assert!(!parse(
+                ":x.y.z 474 tiny #chan :Cannot join channel (+b)\r\n"
+            ));
+            assert!(rcv_irc_msg.try_recv().is_err());
+
+            assert!(!parse(":tiny!~tiny@a.b JOIN #chan\r\n"));
+            assert!(parse(":op!~op@a.b KICK #chan tiny :bye\r\n"));
+            assert_eq!(rcv_irc_msg.recv().await.unwrap(), "JOIN #chan\r\n");
+
+            // Banned, try again after a delay, even with `rejoin_on_kick: 0`
+            assert!(parse(":x.y.z 474 tiny #chan :Cannot join channel (+b)\r\n"));
+            let start = tokio::time::Instant::now();
+            assert_eq!(rcv_irc_msg.recv().await.unwrap(), "JOIN #chan\r\n");
+            assert_eq!(start.elapsed().as_secs(), 2);
+            assert!(parse(":x.y.z 474 tiny #chan :Cannot join channel (+b)\r\n"));
+            assert!(parse(":x.y.z 474 tiny #chan :Cannot join channel (+b)\r\n"));
+            // Out of retries
+            assert!(!parse(
+                ":x.y.z 474 tiny #chan :Cannot join channel (+b)\r\n"
+            ));
+        });
+    }
 }
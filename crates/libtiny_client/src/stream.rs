@@ -6,7 +6,7 @@ use std::{
 };
 use tokio::{
     io::{AsyncRead, AsyncWrite, ReadBuf},
-    net::TcpStream,
+    net::{TcpStream, UnixStream},
 };
 
 #[cfg(feature = "tls-native")]
@@ -77,9 +77,11 @@ fn tls_connector(sasl: Option<&Vec<u8>>) -> tokio_rustls::TlsConnector {
 // We box the fields to reduce type size. Without boxing the type size is 64 with native-tls and
 // 1288 with native-tls. With boxing it's 16 in both. More importantly, there's a large size
 // difference between the variants when using rustls, see #189.
+#[allow(clippy::enum_variant_names)]
 pub(crate) enum Stream {
     TcpStream(Box<TcpStream>),
     TlsStream(Box<TlsStream<TcpStream>>),
+    UnixStream(Box<UnixStream>),
 }
 
 #[cfg(feature = "tls-native")]
@@ -105,17 +107,36 @@ impl From<std::io::Error> for StreamError {
 }
 
 impl Stream {
-    pub(crate) async fn new_tcp(addr: SocketAddr) -> Result<Stream, StreamError> {
-        Ok(Stream::TcpStream(TcpStream::connect(addr).await?.into()))
+    /// Local address of the connection. Fails with Unix domain sockets.
+    pub(crate) fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        match self {
+            Stream::UnixStream(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "Unix domain sockets don't have an IP address",
+            )),
+            Stream::TcpStream(tcp_stream) => tcp_stream.local_addr(),
+            #[cfg(feature = "tls-native")]
+            Stream::TlsStream(tls_stream) => tls_stream.get_ref().get_ref().get_ref().local_addr(),
+            #[cfg(feature = "tls-rustls")]
+            Stream::TlsStream(tls_stream) => tls_stream.get_ref().0.local_addr(),
+        }
+    }
+
+    pub(crate) fn new_tcp(tcp_stream: TcpStream) -> Stream {
+        Stream::TcpStream(tcp_stream.into())
+    }
+
+    pub(crate) fn new_unix(unix_stream: UnixStream) -> Stream {
+        Stream::UnixStream(unix_stream.into())
     }
 
     #[cfg(feature = "tls-native")]
+    /// Do the TLS handshake on a connected TCP stream.
     pub(crate) async fn new_tls(
-        addr: SocketAddr,
+        tcp_stream: TcpStream,
         host_name: &str,
         sasl: Option<&Vec<u8>>,
     ) -> Result<Stream, StreamError> {
-        let tcp_stream = TcpStream::connect(addr).await?;
         // If SASL EXTERNAL is enabled create a new TLS connector with client auth cert
         let tls_stream = if sasl.is_some() {
             tls_connector(sasl).connect(host_name, tcp_stream).await?
@@ -126,14 +147,14 @@ impl Stream {
     }
 
     #[cfg(feature = "tls-rustls")]
+    /// Do the TLS handshake on a connected TCP stream.
     pub(crate) async fn new_tls(
-        addr: SocketAddr,
+        tcp_stream: TcpStream,
         host_name: &str,
         sasl: Option<&Vec<u8>>,
     ) -> Result<Stream, StreamError> {
         use tokio_rustls::rustls::ServerName;
 
-        let tcp_stream = TcpStream::connect(addr).await?;
         let name = ServerName::try_from(host_name).unwrap();
         // If SASL EXTERNAL is enabled create a new TLS connector with client auth cert
         let tls_stream = if sasl.is_some() {
@@ -158,6 +179,7 @@ impl AsyncRead for Stream {
         match *self {
             Stream::TcpStream(ref mut tcp_stream) => Pin::new(tcp_stream).poll_read(cx, buf),
             Stream::TlsStream(ref mut tls_stream) => Pin::new(tls_stream).poll_read(cx, buf),
+            Stream::UnixStream(ref mut unix_stream) => Pin::new(unix_stream).poll_read(cx, buf),
         }
     }
 }
@@ -171,6 +193,7 @@ impl AsyncWrite for Stream {
         match *self {
             Stream::TcpStream(ref mut tcp_stream) => Pin::new(tcp_stream).poll_write(cx, buf),
             Stream::TlsStream(ref mut tls_stream) => Pin::new(tls_stream).poll_write(cx, buf),
+            Stream::UnixStream(ref mut unix_stream) => Pin::new(unix_stream).poll_write(cx, buf),
         }
     }
 
@@ -178,6 +201,7 @@ impl AsyncWrite for Stream {
         match *self {
             Stream::TcpStream(ref mut tcp_stream) => Pin::new(tcp_stream).poll_flush(cx),
             Stream::TlsStream(ref mut tls_stream) => Pin::new(tls_stream).poll_flush(cx),
+            Stream::UnixStream(ref mut unix_stream) => Pin::new(unix_stream).poll_flush(cx),
         }
     }
 
@@ -188,6 +212,7 @@ impl AsyncWrite for Stream {
         match *self {
             Stream::TcpStream(ref mut tcp_stream) => Pin::new(tcp_stream).poll_shutdown(cx),
             Stream::TlsStream(ref mut tls_stream) => Pin::new(tls_stream).poll_shutdown(cx),
+            Stream::UnixStream(ref mut unix_stream) => Pin::new(unix_stream).poll_shutdown(cx),
         }
     }
 }
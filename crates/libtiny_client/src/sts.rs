@@ -0,0 +1,174 @@
+//! IRCv3 Strict Transport Security (STS), see <https://ircv3.net/specs/extensions/sts>.
+//!
+//! Servers advertise an STS policy with the `sts` capability. On a plaintext connection the
+//! policy has a `port` key, and we reconnect with TLS on that port. On a TLS connection the
+//! policy has a `duration` key, and we persist the policy so that plaintext connections to the
+//! host are upgraded to TLS until the policy expires.
+//!
+//! Policies are persisted in a text file with one policy per line, in `<host> <port> <expiry>`
+//! format, where `<expiry>` is in seconds since Unix epoch.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Value of an `sts` capability, e.g. `duration=31536000,port=6697`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct StsValue {
+    /// TLS port. Only used on plaintext connections.
+    pub(crate) port: Option<u16>,
+
+    /// Policy duration in seconds. Only used on TLS connections.
+    pub(crate) duration: Option<u64>,
+}
+
+/// Parse a capability value. Unknown and invalid keys are ignored.
+pub(crate) fn parse_sts_value(value: &str) -> StsValue {
+    let mut sts = StsValue::default();
+    for kv in value.split(',') {
+        match kv.split_once('=') {
+            Some(("port", port)) => sts.port = port.parse().ok(),
+            Some(("duration", duration)) => sts.duration = duration.parse().ok(),
+            _ => {}
+        }
+    }
+    sts
+}
+
+/// Find the `sts` capability value in a `CAP LS` capability list.
+pub(crate) fn find_sts_value(caps: &[String]) -> Option<StsValue> {
+    caps.iter().find_map(|cap| {
+        if cap == "sts" {
+            Some(StsValue::default())
+        } else {
+            cap.strip_prefix("sts=").map(parse_sts_value)
+        }
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Policy {
+    port: u16,
+    /// Seconds since Unix epoch
+    expiry: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn read_policies(path: &Path) -> io::Result<HashMap<String, Policy>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(err) => return Err(err),
+    };
+
+    let mut policies = HashMap::new();
+    for line in contents.lines() {
+        let mut words = line.split_whitespace();
+        if let (Some(host), Some(port), Some(expiry)) = (words.next(), words.next(), words.next())
+            && let (Ok(port), Ok(expiry)) = (port.parse(), expiry.parse())
+        {
+            policies.insert(host.to_owned(), Policy { port, expiry });
+        }
+    }
+    Ok(policies)
+}
+
+fn write_policies(path: &Path, policies: &HashMap<String, Policy>) -> io::Result<()> {
+    let mut hosts: Vec<&String> = policies.keys().collect();
+    hosts.sort();
+    let mut contents = String::new();
+    for host in hosts {
+        let Policy { port, expiry } = policies[host];
+        contents.push_str(&format!("{host} {port} {expiry}\n"));
+    }
+    fs::write(path, contents)
+}
+
+/// TLS port of the host's policy, if the host has a policy that's not expired.
+pub(crate) fn policy_port(path: &Path, host: &str) -> io::Result<Option<u16>> {
+    Ok(read_policies(path)?
+        .get(host)
+        .filter(|policy| policy.expiry > now())
+        .map(|policy| policy.port))
+}
+
+/// Persist a policy advertised on a TLS connection to `host:port`. A duration of 0 removes the
+/// host's policy. Expired policies of other hosts are removed as well.
+pub(crate) fn update_policy(path: &Path, host: &str, port: u16, duration: u64) -> io::Result<()> {
+    let now = now();
+    let mut policies = read_policies(path)?;
+    policies.retain(|_, policy| policy.expiry > now);
+    if duration == 0 {
+        policies.remove(host);
+    } else {
+        let expiry = now.saturating_add(duration);
+        policies.insert(host.to_owned(), Policy { port, expiry });
+    }
+    write_policies(path, &policies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sts_value() {
+        assert_eq!(
+            parse_sts_value("duration=31536000,port=6697"),
+            StsValue {
+                port: Some(6697),
+                duration: Some(31536000),
+            }
+        );
+        assert_eq!(
+            parse_sts_value("port=6697,foo=bar,duration=x"),
+            StsValue {
+                port: Some(6697),
+                duration: None,
+            }
+        );
+
+        let caps: Vec<String> = ["multi-prefix", "sts=port=6697", "sasl"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            find_sts_value(&caps),
+            Some(StsValue {
+                port: Some(6697),
+                duration: None,
+            })
+        );
+        assert_eq!(find_sts_value(&caps[..1]), None);
+    }
+
+    #[test]
+    fn test_persist_policies() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sts_policies.txt");
+
+        assert_eq!(policy_port(&path, "irc.example.org").unwrap(), None);
+
+        update_policy(&path, "irc.example.org", 6697, 3600).unwrap();
+        update_policy(&path, "irc.example.com", 7000, 3600).unwrap();
+        assert_eq!(policy_port(&path, "irc.example.org").unwrap(), Some(6697));
+        assert_eq!(policy_port(&path, "irc.example.com").unwrap(), Some(7000));
+
+        // Duration 0 removes the policy
+        update_policy(&path, "irc.example.org", 6697, 0).unwrap();
+        assert_eq!(policy_port(&path, "irc.example.org").unwrap(), None);
+        assert_eq!(policy_port(&path, "irc.example.com").unwrap(), Some(7000));
+
+        // Expired policies are not used
+        fs::write(&path, "irc.example.org 6697 1\n").unwrap();
+        assert_eq!(policy_port(&path, "irc.example.org").unwrap(), None);
+    }
+}
@@ -0,0 +1,64 @@
+//! Tests of `Bot` against `MockServer`.
+
+use super::mock_server::{MockServer, run_test};
+use crate::bot::{Bot, Permission};
+
+use libtiny_common::ChanNameRef;
+
+use std::time::Duration;
+
+#[test]
+fn test_bot_commands() {
+    run_test(async {
+        let server = MockServer::new().await;
+        let mut server_info = server.server_info();
+        server_info.auto_join = vec![ChanNameRef::new("#chan").to_owned()];
+
+        let bot = Bot::new(server_info)
+            .rate_limit(10, Duration::from_secs(1))
+            .command("echo", Permission::Anyone, |ctx, args| {
+                ctx.reply(args.rest(0));
+                Ok(())
+            })
+            .command("kick", Permission::Op, |ctx, args| {
+                let chan = ctx.chan().ok_or("Not in a channel")?.display().to_owned();
+                let nick: String = args.parse(0)?;
+                let reason = args.rest(1).to_owned();
+                ctx.client()
+                    .raw_msg(&format!("KICK {chan} {nick} :{reason}"));
+                Ok(())
+            })
+            .on_join(|ctx, join| ctx.reply(&format!("Welcome, {}!", join.nick)));
+        tokio::task::spawn_local(bot.run());
+
+        let mut conn = server.accept().await;
+        conn.register().await;
+        conn.run(
+            "> JOIN #chan
+             < :tiny!~tiny@127.0.0.1 JOIN #chan
+             > PRIVMSG #chan :Welcome, tiny!
+             < :irc 353 tiny = #chan :tiny @op user
+             < :irc 366 tiny #chan :End of /NAMES list.
+
+             < :user!~user@127.0.0.1 PRIVMSG #chan :!echo hello   world
+             > PRIVMSG #chan :hello   world
+
+             < :user!~user@127.0.0.1 PRIVMSG tiny :echo no prefix in private messages
+             > PRIVMSG user :no prefix in private messages
+
+             < :user!~user@127.0.0.1 PRIVMSG #chan :!kick op
+             > PRIVMSG #chan :user: You don't have permission to use !kick
+
+             < :op!~op@127.0.0.1 PRIVMSG #chan :!kick
+             > PRIVMSG #chan :op: Missing argument 1
+
+             < :op!~op@127.0.0.1 MODE #chan +o user
+             < :user!~user@127.0.0.1 PRIVMSG #chan :!kick op bye now
+             > KICK #chan op :bye now
+
+             < :new!~new@127.0.0.1 JOIN #chan
+             > PRIVMSG #chan :Welcome, new!",
+        )
+        .await;
+    });
+}
@@ -0,0 +1,309 @@
+//! Tests of `Client` and the main loop against `MockServer`.
+
+use super::mock_server::{MockServer, expect_event, expect_reply, expect_state, run_test};
+use crate::*;
+
+use libtiny_common::ChanNameRef;
+
+#[test]
+fn test_registration() {
+    run_test(async {
+        let server = MockServer::new().await;
+        let (client, mut rcv_ev) = Client::new(server.server_info());
+
+        let mut conn = server.accept().await;
+        conn.register().await;
+
+        expect_event(&mut rcv_ev, |ev| match ev {
+            Event::Connected => Some(()),
+            _ => None,
+        })
+        .await;
+        expect_state(&mut rcv_ev, ConnectionState::Registered).await;
+        assert_eq!(client.get_nick(), "tiny");
+        assert!(client.is_nick_accepted());
+    });
+}
+
+#[test]
+fn test_nick_collision() {
+    run_test(async {
+        let server = MockServer::new().await;
+        let mut server_info = server.server_info();
+        server_info.nicks = vec!["tiny".to_owned(), "tiny2".to_owned()];
+        let (client, mut rcv_ev) = Client::new(server_info);
+
+        let mut conn = server.accept().await;
+        conn.run(
+            "> CAP LS 302
+             > NICK tiny
+             > USER tiny 8 * :tiny
+             < :irc CAP * LS :multi-prefix
+             > CAP END
+             < :irc 433 * tiny :Nickname is already in use
+             > NICK tiny2
+             < :irc 433 * tiny2 :Nickname is already in use
+             > NICK tiny2_
+             < :irc 001 tiny2_ :Welcome",
+        )
+        .await;
+
+        expect_state(&mut rcv_ev, ConnectionState::Registered).await;
+        assert_eq!(client.get_nick(), "tiny2_");
+    });
+}
+
+#[test]
+fn test_sasl_plain() {
+    use base64::engine::Engine;
+
+    run_test(async {
+        let server = MockServer::new().await;
+        let mut server_info = server.server_info();
+        server_info.sasl_auth = Some(SASLAuth::Plain {
+            username: "tiny".to_owned(),
+            password: "hunter2".to_owned(),
+        });
+        let (_client, mut rcv_ev) = Client::new(server_info);
+
+        let auth = base64::engine::general_purpose::STANDARD.encode("tiny\0tiny\0hunter2");
+
+        let mut conn = server.accept().await;
+        conn.run(&format!(
+            "> CAP LS 302
+             < :irc CAP * LS :multi-prefix sasl
+             > NICK tiny
+             > USER tiny 8 * :tiny
+             > CAP REQ :sasl
+             < :irc CAP tiny ACK :sasl
+             > AUTHENTICATE PLAIN
+             < AUTHENTICATE +
+             > AUTHENTICATE {auth}
+             < :irc 903 tiny :SASL authentication successful
+             > CAP END
+             < :irc 001 tiny :Welcome"
+        ))
+        .await;
+
+        expect_state(&mut rcv_ev, ConnectionState::Registered).await;
+    });
+}
+
+#[test]
+fn test_cap_not_supported() {
+    run_test(async {
+        let server = MockServer::new().await;
+        let mut server_info = server.server_info();
+        server_info.pass = Some("secret".to_owned());
+        let (_client, mut rcv_ev) = Client::new(server_info);
+
+        // Client waits for the CAP LS response before sending the password, and introduces
+        // itself when the server doesn't know the CAP command
+        let mut conn = server.accept().await;
+        conn.run(
+            "> CAP LS 302
+             < :irc 421 * CAP :Unknown command
+             > PASS secret
+             > NICK tiny
+             > USER tiny 8 * :tiny
+             < :irc 001 tiny :Welcome",
+        )
+        .await;
+
+        expect_state(&mut rcv_ev, ConnectionState::Registered).await;
+    });
+}
+
+#[test]
+fn test_sasl_not_supported() {
+    run_test(async {
+        let server = MockServer::new().await;
+        let mut server_info = server.server_info();
+        server_info.sasl_auth = Some(SASLAuth::Plain {
+            username: "tiny".to_owned(),
+            password: "hunter2".to_owned(),
+        });
+        let (_client, mut rcv_ev) = Client::new(server_info);
+
+        let mut conn = server.accept().await;
+        conn.run(
+            "> CAP LS 302
+             < :irc CAP * LS :multi-prefix
+             > NICK tiny
+             > USER tiny 8 * :tiny
+             > CAP END
+             < :irc 001 tiny :Welcome",
+        )
+        .await;
+
+        expect_state(&mut rcv_ev, ConnectionState::Registered).await;
+    });
+}
+
+#[test]
+fn test_join_retry_477() {
+    run_test(async {
+        let server = MockServer::new().await;
+        let mut server_info = server.server_info();
+        server_info.nickserv_ident = Some("hunter2".to_owned());
+        server_info.auto_join = vec![ChanNameRef::new("#chan").to_owned()];
+        let (_client, mut rcv_ev) = Client::new(server_info);
+
+        let mut conn = server.accept().await;
+        conn.run(
+            "> CAP LS 302
+             > NICK tiny
+             > USER tiny 8 * :tiny
+             < :irc CAP * LS :multi-prefix
+             > CAP END
+             < :irc 001 tiny :Welcome
+             > PRIVMSG NickServ :identify hunter2
+             < :irc 376 tiny :End of /MOTD command.
+             > JOIN #chan
+             < :irc 477 tiny #chan :You need to be identified to join",
+        )
+        .await;
+
+        let msg = expect_event(&mut rcv_ev, |ev| match ev {
+            Event::ChannelJoinError { msg, .. } => Some(msg.clone()),
+            _ => None,
+        })
+        .await;
+        assert_eq!(msg, "Attempting to rejoin #chan in 10 seconds... (1/3)");
+
+        // Retried after 10 seconds
+        tokio::time::pause();
+        conn.run(
+            "> JOIN #chan
+             < :tiny!~tiny@127.0.0.1 JOIN #chan",
+        )
+        .await;
+    });
+}
+
+#[test]
+fn test_ping_timeout() {
+    run_test(async {
+        let server = MockServer::new().await;
+        let (mut client, mut rcv_ev) = Client::new(server.server_info());
+
+        let mut conn = server.accept().await;
+        conn.register().await;
+        // Wait until the client has the server name for the PING message, from 002
+        expect_reply(&mut rcv_ev, 376).await;
+
+        // Client pings the server after 60 seconds of silence, and disconnects when it doesn't
+        // get a response in 60 seconds
+        tokio::time::pause();
+        conn.expect("PING irc.mock").await;
+        expect_event(&mut rcv_ev, |ev| match ev {
+            Event::Disconnected => Some(()),
+            _ => None,
+        })
+        .await;
+        expect_state(&mut rcv_ev, ConnectionState::WaitingToReconnect).await;
+        conn.expect_closed().await;
+
+        // Don't wait `RECONNECT_SECS`, see the module documentation of `mock_server`
+        tokio::time::resume();
+        client.reconnect(None);
+        let mut conn = server.accept().await;
+        conn.register().await;
+        expect_state(&mut rcv_ev, ConnectionState::Registered).await;
+    });
+}
+
+#[test]
+fn test_reconnect_rejoin() {
+    run_test(async {
+        let server = MockServer::new().await;
+        let mut server_info = server.server_info();
+        server_info.auto_join = vec![ChanNameRef::new("#chan").to_owned()];
+        let (mut client, mut rcv_ev) = Client::new(server_info);
+
+        let mut conn = server.accept().await;
+        conn.register().await;
+        conn.run(
+            "> JOIN #chan
+             < :tiny!~tiny@127.0.0.1 JOIN #chan",
+        )
+        .await;
+        expect_state(&mut rcv_ev, ConnectionState::Registered).await;
+
+        // Server closes the connection
+        drop(conn);
+        expect_event(&mut rcv_ev, |ev| match ev {
+            Event::ConnectionClosed => Some(()),
+            _ => None,
+        })
+        .await;
+        expect_state(&mut rcv_ev, ConnectionState::WaitingToReconnect).await;
+
+        // Client reconnects and joins the channel again
+        client.reconnect(None);
+        let mut conn = server.accept().await;
+        conn.register().await;
+        conn.expect("JOIN #chan").await;
+    });
+}
+
+#[test]
+fn test_invite() {
+    run_test(async {
+        let server = MockServer::new().await;
+        let mut server_info = server.server_info();
+        server_info.auto_join_on_invite = vec!["#rust-*".to_owned(), "friend".to_owned()];
+        let (mut client, mut rcv_ev) = Client::new(server_info);
+
+        let mut conn = server.accept().await;
+        conn.register().await;
+        conn.run(
+            "< :stranger!~s@127.0.0.1 INVITE tiny :#secret
+             < :friend!~f@127.0.0.1 INVITE tiny :#other
+             > JOIN #other
+             < :stranger!~s@127.0.0.1 INVITE tiny :#Rust-beginners
+             > JOIN #Rust-beginners",
+        )
+        .await;
+        expect_event(&mut rcv_ev, |ev| match ev {
+            Event::Msg(wire::Msg {
+                cmd: wire::Cmd::INVITE { chan, .. },
+                ..
+            }) if chan.display() == "#Rust-beginners" => Some(()),
+            _ => None,
+        })
+        .await;
+
+        assert_eq!(client.accept_invite(Some(ChanNameRef::new("#other"))), None);
+        assert_eq!(
+            client.accept_invite(None),
+            Some(ChanNameRef::new("#secret").to_owned())
+        );
+        assert_eq!(client.accept_invite(None), None);
+        conn.expect("JOIN #secret").await;
+    });
+}
+
+#[test]
+fn test_list_elist() {
+    run_test(async {
+        let server = MockServer::new().await;
+        let (mut client, mut rcv_ev) = Client::new(server.server_info());
+
+        let mut conn = server.accept().await;
+        conn.register().await;
+        expect_reply(&mut rcv_ev, 376).await;
+
+        // Filters are not sent without ELIST
+        client.list(Some("#rust*"), Some(10));
+        conn.expect("LIST").await;
+
+        conn.send(":irc 005 tiny CHANTYPES=# ELIST=cmntu :are supported by this server")
+            .await;
+        expect_reply(&mut rcv_ev, 5).await;
+        client.list(Some("#rust*"), Some(10));
+        conn.expect("LIST #rust*,>9").await;
+        client.list(None, None);
+        conn.expect("LIST").await;
+    });
+}
@@ -0,0 +1,185 @@
+//! A scriptable IRC server on a loopback port, for testing `Client` and its main loop.
+//!
+//! Scripts have one line per message. Lines starting with `> ` are messages expected from the
+//! client, lines starting with `< ` are messages sent to the client, same as the directions in
+//! recordings (see `record`). Other lines are ignored. Example:
+//!
+//! ```text
+//! > NICK tiny
+//! < :irc 433 * tiny :Nickname is already in use
+//! > NICK tiny_
+//! ```
+//!
+//! Tests that wait for timeouts (pings, join retries) should pause time with
+//! `tokio::time::pause` once the client is connected. When time is paused the runtime advances
+//! time to the next timer whenever it has nothing else to do, including while waiting for socket
+//! IO, so pausing before the connection is established can make connection attempts time out.
+
+use crate::{ConnectionState, DEFAULT_CONNECT_TIMEOUT_SECS, Endpoint, Event, ServerInfo, wire};
+
+use std::collections::HashMap;
+use std::future::Future;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::TcpListener;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::sync::mpsc;
+
+/// Run a test on a single-threaded runtime, in a `LocalSet` as `Client` spawns local tasks.
+pub(crate) fn run_test<F: Future<Output = ()>>(test: F) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let local = tokio::task::LocalSet::new();
+    local.block_on(&runtime, test);
+}
+
+pub(crate) struct MockServer {
+    listener: TcpListener,
+}
+
+impl MockServer {
+    pub(crate) async fn new() -> MockServer {
+        MockServer {
+            listener: TcpListener::bind("127.0.0.1:0").await.unwrap(),
+        }
+    }
+
+    pub(crate) fn port(&self) -> u16 {
+        self.listener.local_addr().unwrap().port()
+    }
+
+    /// `ServerInfo` for connecting to this server, with nick `tiny`.
+    pub(crate) fn server_info(&self) -> ServerInfo {
+        server_info(self.port())
+    }
+
+    /// Accept a connection from the client.
+    pub(crate) async fn accept(&self) -> MockConn {
+        let (stream, _) = self.listener.accept().await.unwrap();
+        let (read_half, write_half) = stream.into_split();
+        MockConn {
+            lines: BufReader::new(read_half).lines(),
+            write_half,
+        }
+    }
+}
+
+/// `ServerInfo` for connecting to a server on a loopback port, with nick `tiny`.
+pub(crate) fn server_info(port: u16) -> ServerInfo {
+    ServerInfo {
+        name: "mock".to_owned(),
+        endpoints: vec![Endpoint {
+            addr: "127.0.0.1".to_owned(),
+            port,
+            tls: false,
+        }],
+        connect_timeout: DEFAULT_CONNECT_TIMEOUT_SECS,
+        prefer_addr_family: None,
+        bind_addr: None,
+        sts_file: None,
+        record_dir: None,
+        pass: None,
+        user: None,
+        realname: "tiny".to_owned(),
+        nicks: vec!["tiny".to_owned()],
+        auto_join: vec![],
+        auto_join_on_invite: vec![],
+        rejoin_on_kick: HashMap::new(),
+        nickserv_ident: None,
+        sasl_auth: None,
+    }
+}
+
+/// A connection from the client.
+pub(crate) struct MockConn {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    write_half: OwnedWriteHalf,
+}
+
+impl MockConn {
+    /// Read the next message from the client. Returns `None` when the client closes the
+    /// connection.
+    pub(crate) async fn next_line(&mut self) -> Option<String> {
+        self.lines.next_line().await.unwrap()
+    }
+
+    /// Expect the client to send `line`.
+    pub(crate) async fn expect(&mut self, line: &str) {
+        assert_eq!(self.next_line().await.as_deref(), Some(line));
+    }
+
+    /// Expect the client to close the connection.
+    pub(crate) async fn expect_closed(&mut self) {
+        assert_eq!(self.next_line().await, None);
+    }
+
+    /// Send a message to the client. "\r\n" is added by this method.
+    pub(crate) async fn send(&mut self, line: &str) {
+        self.write_half
+            .write_all(format!("{line}\r\n").as_bytes())
+            .await
+            .unwrap();
+    }
+
+    /// Run a script. See the module documentation for the format.
+    pub(crate) async fn run(&mut self, script: &str) {
+        for line in script.lines().map(str::trim) {
+            if let Some(line) = line.strip_prefix("> ") {
+                self.expect(line).await;
+            } else if let Some(line) = line.strip_prefix("< ") {
+                self.send(line).await;
+            }
+        }
+    }
+
+    /// Run the registration of a client without a password or SASL, with nick `tiny`.
+    pub(crate) async fn register(&mut self) {
+        self.run(
+            "> CAP LS 302
+             > NICK tiny
+             > USER tiny 8 * :tiny
+             < :irc CAP * LS :multi-prefix
+             > CAP END
+             < :irc 001 tiny :Welcome
+             < :irc 002 tiny :Your host is irc.mock[127.0.0.1/6667], running version 1
+             < :irc 376 tiny :End of /MOTD command.",
+        )
+        .await;
+    }
+}
+
+/// Wait for an event that `f` returns `Some` for.
+pub(crate) async fn expect_event<A>(
+    rcv_ev: &mut mpsc::Receiver<Event>,
+    f: impl Fn(&Event) -> Option<A>,
+) -> A {
+    loop {
+        let ev = rcv_ev.recv().await.expect("Event channel closed");
+        if let Some(a) = f(&ev) {
+            return a;
+        }
+    }
+}
+
+/// Wait for the client to change to `state`.
+pub(crate) async fn expect_state(rcv_ev: &mut mpsc::Receiver<Event>, state: ConnectionState) {
+    expect_event(rcv_ev, |ev| match ev {
+        Event::StateChanged(state_) if *state_ == state => Some(()),
+        _ => None,
+    })
+    .await
+}
+
+/// Wait for the client to receive a numeric reply.
+pub(crate) async fn expect_reply(rcv_ev: &mut mpsc::Receiver<Event>, num: u16) {
+    expect_event(rcv_ev, |ev| match ev {
+        Event::Msg(wire::Msg {
+            cmd: wire::Cmd::Reply { num: num_, .. },
+            ..
+        }) if *num_ == num => Some(()),
+        _ => None,
+    })
+    .await
+}
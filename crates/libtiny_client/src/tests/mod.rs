@@ -0,0 +1,330 @@
+mod bot;
+mod main_loop;
+mod mock_server;
+
+use crate::*;
+
+use tokio::io::AsyncBufReadExt;
+
+#[test]
+fn test_auto_join_invite() {
+    let mut server_info = mock_server::server_info(6667);
+    server_info.auto_join_on_invite = vec!["#rust-*".to_owned(), "fr?end".to_owned()];
+    let chan = ChanNameRef::new;
+    assert!(server_info.auto_join_invite("x", chan("#rust-beginners")));
+    assert!(server_info.auto_join_invite("x", chan("#RUST-")));
+    assert!(!server_info.auto_join_invite("x", chan("#rust")));
+    server_info.auto_join_on_invite = vec!["#r*t-b*s".to_owned(), "fr?end".to_owned()];
+    assert!(server_info.auto_join_invite("x", chan("#rust-beginners")));
+    assert!(!server_info.auto_join_invite("x", chan("#rust-beginner")));
+    assert!(!server_info.auto_join_invite("#rust-x", chan("#other")));
+    assert!(server_info.auto_join_invite("friend", chan("#other")));
+    assert!(server_info.auto_join_invite("FRiend", chan("#other")));
+    assert!(!server_info.auto_join_invite("friends", chan("#other")));
+}
+
+fn endpoint(addr: &str, port: u16) -> Endpoint {
+    Endpoint {
+        addr: addr.to_owned(),
+        port,
+        tls: false,
+    }
+}
+
+#[test]
+fn test_endpoint_rotation() {
+    let mut endpoints = Endpoints::new(vec![endpoint("a", 1), endpoint("b", 2)]);
+    assert_eq!(endpoints.current().addr, "a");
+    assert!(!endpoints.failed());
+    assert_eq!(endpoints.current().addr, "b");
+    assert!(endpoints.failed());
+    assert_eq!(endpoints.current().addr, "a");
+    assert!(!endpoints.failed());
+    endpoints.connected();
+    endpoints.set_port(Some(3));
+    assert_eq!(endpoints.current(), &endpoint("b", 3));
+    assert!(!endpoints.failed());
+    assert!(endpoints.failed());
+
+    let mut endpoints = Endpoints::new(vec![endpoint("a", 1)]);
+    assert!(endpoints.failed());
+    assert!(endpoints.failed());
+}
+
+#[test]
+fn test_connect_next_endpoint() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let local = tokio::task::LocalSet::new();
+
+    local.block_on(&runtime, async move {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        // Find a port that's not listened on by binding and dropping a listener
+        let closed_port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let (client, _rcv_ev) = Client::new(ServerInfo {
+            name: "network".to_owned(),
+            endpoints: vec![
+                endpoint("127.0.0.1", closed_port),
+                endpoint("127.0.0.1", port),
+            ],
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT_SECS,
+            prefer_addr_family: None,
+            bind_addr: None,
+            sts_file: None,
+            record_dir: None,
+            pass: None,
+            user: None,
+            realname: "tiny".to_owned(),
+            nicks: vec!["tiny".to_owned()],
+            auto_join: vec![],
+            auto_join_on_invite: vec![],
+            rejoin_on_kick: HashMap::new(),
+            nickserv_ident: None,
+            sasl_auth: None,
+        });
+        assert_eq!(client.get_serv_name(), "network");
+
+        // Without waiting `RECONNECT_SECS` after the first endpoint fails
+        let (mut stream, _) = tokio::time::timeout(Duration::from_secs(5), listener.accept())
+            .await
+            .unwrap()
+            .unwrap();
+        let mut buf = [0; 12];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"CAP LS 302\r\n");
+    });
+}
+
+#[test]
+fn test_connect_unix_socket() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let local = tokio::task::LocalSet::new();
+
+    local.block_on(&runtime, async move {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bouncer.sock");
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+
+        let (_client, mut rcv_ev) = Client::new(ServerInfo {
+            name: "bouncer".to_owned(),
+            endpoints: vec![endpoint(&format!("unix:{}", path.display()), 0)],
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT_SECS,
+            prefer_addr_family: None,
+            bind_addr: None,
+            sts_file: None,
+            record_dir: None,
+            pass: None,
+            user: None,
+            realname: "tiny".to_owned(),
+            nicks: vec!["tiny".to_owned()],
+            auto_join: vec![],
+            auto_join_on_invite: vec![],
+            rejoin_on_kick: HashMap::new(),
+            nickserv_ident: None,
+            sasl_auth: None,
+        });
+
+        let (stream, _) = tokio::time::timeout(Duration::from_secs(5), listener.accept())
+            .await
+            .unwrap()
+            .unwrap();
+        let (read_half, mut write_half) = tokio::io::split(stream);
+        let mut lines = tokio::io::BufReader::new(read_half).lines();
+
+        // Registration
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "CAP LS 302");
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "NICK tiny");
+        assert!(
+            lines
+                .next_line()
+                .await
+                .unwrap()
+                .unwrap()
+                .starts_with("USER ")
+        );
+        write_half
+            .write_all(b":bouncer CAP * LS :sts=port=6697\r\n")
+            .await
+            .unwrap();
+        // STS is not followed on local connections
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "CAP END");
+        write_half
+            .write_all(b":bouncer 001 tiny :Welcome\r\nPING :bouncer\r\n")
+            .await
+            .unwrap();
+
+        loop {
+            match rcv_ev.recv().await.unwrap() {
+                Event::Connected => break,
+                Event::IoErr(err) => panic!("{err:?}"),
+                _ => {}
+            }
+        }
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "PONG bouncer");
+    });
+}
+
+#[test]
+fn test_sts_upgrade() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let local = tokio::task::LocalSet::new();
+
+    local.block_on(&runtime, async move {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let tls_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let tls_port = tls_listener.local_addr().unwrap().port();
+
+        let dir = tempfile::tempdir().unwrap();
+        let sts_file = dir.path().join("sts_policies.txt");
+
+        let (_client, mut rcv_ev) = Client::new(ServerInfo {
+            name: "network".to_owned(),
+            endpoints: vec![endpoint("127.0.0.1", port)],
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT_SECS,
+            prefer_addr_family: None,
+            bind_addr: None,
+            sts_file: Some(sts_file.clone()),
+            record_dir: None,
+            pass: Some("secret".to_owned()),
+            user: None,
+            realname: "tiny".to_owned(),
+            nicks: vec!["tiny".to_owned()],
+            auto_join: vec![],
+            auto_join_on_invite: vec![],
+            rejoin_on_kick: HashMap::new(),
+            nickserv_ident: None,
+            sasl_auth: None,
+        });
+
+        let (stream, _) = tokio::time::timeout(Duration::from_secs(5), listener.accept())
+            .await
+            .unwrap()
+            .unwrap();
+        let (read_half, mut write_half) = tokio::io::split(stream);
+        let mut lines = tokio::io::BufReader::new(read_half).lines();
+
+        // Password is not sent before the CAP LS response
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "CAP LS 302");
+        write_half
+            .write_all(format!(":irc CAP * LS :sasl sts=port={tls_port}\r\n").as_bytes())
+            .await
+            .unwrap();
+
+        loop {
+            match rcv_ev.recv().await.unwrap() {
+                Event::StsUpgrade { port } => {
+                    assert_eq!(port, tls_port);
+                    break;
+                }
+                Event::Connected => panic!(),
+                _ => {}
+            }
+        }
+
+        // Connection is closed without sending the password, client connects to the TLS port
+        assert_eq!(lines.next_line().await.unwrap(), None);
+        tokio::time::timeout(Duration::from_secs(5), tls_listener.accept())
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Policies from plaintext connections are not persisted
+        assert!(!sts_file.exists());
+    });
+}
+
+#[test]
+fn test_disconnect_connect() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let local = tokio::task::LocalSet::new();
+
+    local.block_on(&runtime, async move {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let (mut client, mut rcv_ev) = Client::new(ServerInfo {
+            name: "network".to_owned(),
+            endpoints: vec![endpoint("127.0.0.1", port)],
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT_SECS,
+            prefer_addr_family: None,
+            bind_addr: None,
+            sts_file: None,
+            record_dir: None,
+            pass: None,
+            user: None,
+            realname: "tiny".to_owned(),
+            nicks: vec!["tiny".to_owned()],
+            auto_join: vec![],
+            auto_join_on_invite: vec![],
+            rejoin_on_kick: HashMap::new(),
+            nickserv_ident: None,
+            sasl_auth: None,
+        });
+        assert_eq!(client.state(), ConnectionState::Connecting);
+
+        async fn wait_state(rcv_ev: &mut mpsc::Receiver<Event>, state: ConnectionState) {
+            loop {
+                if let Event::StateChanged(state_) = rcv_ev.recv().await.unwrap() {
+                    assert_eq!(state_, state);
+                    return;
+                }
+            }
+        }
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let (read_half, mut write_half) = tokio::io::split(stream);
+        let mut lines = tokio::io::BufReader::new(read_half).lines();
+        wait_state(&mut rcv_ev, ConnectionState::Registering).await;
+
+        write_half
+            .write_all(b":irc 001 tiny :Welcome\r\n")
+            .await
+            .unwrap();
+        wait_state(&mut rcv_ev, ConnectionState::Registered).await;
+        assert_eq!(client.state(), ConnectionState::Registered);
+
+        client.disconnect(Some("bye".to_owned()));
+        wait_state(&mut rcv_ev, ConnectionState::Disconnected).await;
+        loop {
+            match lines.next_line().await.unwrap() {
+                None => break,
+                Some(line) if line.starts_with("QUIT") => assert_eq!(line, "QUIT :bye"),
+                Some(_) => {}
+            }
+        }
+
+        // Client doesn't reconnect until asked to
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), listener.accept())
+                .await
+                .is_err()
+        );
+
+        client.connect();
+        wait_state(&mut rcv_ev, ConnectionState::Connecting).await;
+        tokio::time::timeout(Duration::from_secs(5), listener.accept())
+            .await
+            .unwrap()
+            .unwrap();
+        wait_state(&mut rcv_ev, ConnectionState::Registering).await;
+    });
+}
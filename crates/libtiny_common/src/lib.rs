@@ -50,6 +50,39 @@ fn to_lower(c: char) -> char {
     }
 }
 
+/// Match `s` against `pattern`, where `*` in the pattern matches any number of characters and `?`
+/// matches one character. Matching is case insensitive, with the same rules as `ChanName`.
+pub fn wildcard_match(pattern: &str, s: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().map(to_lower).collect();
+    let s: Vec<char> = s.chars().map(to_lower).collect();
+
+    let (mut p_idx, mut s_idx) = (0, 0);
+    // Position of the last `*` in the pattern, and the position in `s` that it matches up to
+    let mut star: Option<(usize, usize)> = None;
+    while s_idx < s.len() {
+        match pattern.get(p_idx) {
+            Some('*') => {
+                star = Some((p_idx, s_idx));
+                p_idx += 1;
+            }
+            Some(c) if *c == '?' || *c == s[s_idx] => {
+                p_idx += 1;
+                s_idx += 1;
+            }
+            _ => match star {
+                Some((star_p_idx, star_s_idx)) => {
+                    // Backtrack: match one more character with the `*`
+                    p_idx = star_p_idx + 1;
+                    s_idx = star_s_idx + 1;
+                    star = Some((star_p_idx, star_s_idx + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p_idx..].iter().all(|c| *c == '*')
+}
+
 impl ChanName {
     pub fn new(name: String) -> Self {
         ChanName(name)
@@ -157,6 +190,9 @@ pub enum MsgTarget<'a> {
     /// Show the message in the privmsg tab.
     User { serv: &'a str, nick: &'a str },
 
+    /// Show the message in the DCC CHAT tab.
+    DccChat { serv: &'a str, nick: &'a str },
+
     /// Show the message in all tabs of a server.
     AllServTabs { serv: &'a str },
 
@@ -170,6 +206,7 @@ impl MsgTarget<'_> {
             MsgTarget::Server { serv }
             | MsgTarget::Chan { serv, .. }
             | MsgTarget::User { serv, .. }
+            | MsgTarget::DccChat { serv, .. }
             | MsgTarget::AllServTabs { serv } => Some(serv),
             _ => None,
         }
@@ -195,6 +232,10 @@ pub enum MsgSource {
 
     /// Message sent in a privmsg tab.
     User { serv: String, nick: String },
+
+    /// Message sent in a DCC CHAT tab. These messages are sent to the peer directly, bypassing the
+    /// server.
+    DccChat { serv: String, nick: String },
 }
 
 impl MsgSource {
@@ -202,7 +243,8 @@ impl MsgSource {
         match self {
             MsgSource::Serv { serv }
             | MsgSource::Chan { serv, .. }
-            | MsgSource::User { serv, .. } => serv,
+            | MsgSource::User { serv, .. }
+            | MsgSource::DccChat { serv, .. } => serv,
         }
     }
 
@@ -221,6 +263,7 @@ impl MsgSource {
                 chan: chan.borrow(),
             },
             MsgSource::User { serv, nick } => MsgTarget::User { serv, nick },
+            MsgSource::DccChat { serv, nick } => MsgTarget::DccChat { serv, nick },
         }
     }
 }
@@ -49,6 +49,7 @@ impl Logger {
     delegate!(new_chan_tab(serv: &str, chan: &ChanNameRef,));
     delegate!(close_chan_tab(serv: &str, chan: &ChanNameRef,));
     delegate!(close_user_tab(serv: &str, nick: &str,));
+    delegate!(close_dcc_chat_tab(serv: &str, nick: &str,));
     delegate!(add_client_msg(msg: &str, target: &MsgTarget,));
     delegate!(add_msg(msg: &str, ts: Tm, target: &MsgTarget,));
     delegate!(add_privmsg(
@@ -61,6 +62,13 @@ impl Logger {
     ));
     delegate!(add_nick(nick: &str, ts: Option<Tm>, target: &MsgTarget,));
     delegate!(remove_nick(nick: &str, ts: Option<Tm>, target: &MsgTarget,));
+    delegate!(add_netsplit(
+        servers: (&str, &str),
+        nicks: &[String],
+        ts: Tm,
+        target: &MsgTarget,
+    ));
+    delegate!(add_netjoin(nicks: &[String], ts: Tm, target: &MsgTarget,));
     delegate!(rename_nick(
         old_nick: &str,
         new_nick: &str,
@@ -98,6 +106,7 @@ struct ServerLogs {
     fd: File,
     chans: HashMap<ChanName, File>,
     users: HashMap<String, File>,
+    dcc_chats: HashMap<String, File>,
 }
 
 fn print_header(fd: &mut File) -> io::Result<()> {
@@ -163,6 +172,7 @@ fn close_server_tabs(server: ServerLogs, report_err: &dyn Fn(String)) {
         mut fd,
         chans,
         users,
+        dcc_chats,
     } = server;
     report_io_err!(report_err, print_footer(&mut fd));
     for (_, mut fd) in chans.into_iter() {
@@ -171,6 +181,9 @@ fn close_server_tabs(server: ServerLogs, report_err: &dyn Fn(String)) {
     for (_, mut fd) in users.into_iter() {
         report_io_err!(report_err, print_footer(&mut fd));
     }
+    for (_, mut fd) in dcc_chats.into_iter() {
+        report_io_err!(report_err, print_footer(&mut fd));
+    }
 }
 
 impl LoggerInner {
@@ -209,6 +222,7 @@ impl LoggerInner {
                     fd,
                     chans: HashMap::new(),
                     users: HashMap::new(),
+                    dcc_chats: HashMap::new(),
                 },
             );
         }
@@ -289,6 +303,22 @@ impl LoggerInner {
         }
     }
 
+    fn close_dcc_chat_tab(&mut self, serv: &str, nick: &str) {
+        match self.servers.get_mut(serv) {
+            None => {
+                info!("close_dcc_chat_tab: can't find server: {serv:?}");
+            }
+            Some(server) => match server.dcc_chats.remove(nick) {
+                None => {
+                    info!("close_dcc_chat_tab: can't find DCC CHAT {nick:?} in server {serv:?}");
+                }
+                Some(mut fd) => {
+                    report_io_err!(self.report_err, print_footer(&mut fd));
+                }
+            },
+        }
+    }
+
     fn add_client_msg(&mut self, msg: &str, target: &MsgTarget) {
         let now = now();
         self.apply_to_target(target, |fd: &mut File, report_err: &dyn Fn(String)| {
@@ -343,6 +373,37 @@ impl LoggerInner {
         }
     }
 
+    fn add_netsplit(
+        &mut self,
+        servers: (&str, &str),
+        nicks: &[String],
+        ts: Tm,
+        target: &MsgTarget,
+    ) {
+        self.apply_to_target(target, |fd: &mut File, report_err: &dyn Fn(String)| {
+            report_io_err!(
+                report_err,
+                writeln!(
+                    fd,
+                    "[{}] Netsplit {} <-> {}, quit: {}",
+                    strf(&ts),
+                    servers.0,
+                    servers.1,
+                    nicks.join(", ")
+                )
+            );
+        });
+    }
+
+    fn add_netjoin(&mut self, nicks: &[String], ts: Tm, target: &MsgTarget) {
+        self.apply_to_target(target, |fd: &mut File, report_err: &dyn Fn(String)| {
+            report_io_err!(
+                report_err,
+                writeln!(fd, "[{}] Netjoin, joined: {}", strf(&ts), nicks.join(", "))
+            );
+        });
+    }
+
     fn rename_nick(&mut self, old_nick: &str, new_nick: &str, ts: Tm, target: &MsgTarget) {
         self.apply_to_target(target, |fd: &mut File, report_err: &dyn Fn(String)| {
             report_io_err!(
@@ -429,12 +490,36 @@ impl LoggerInner {
                     }
                 }
             }
+            MsgTarget::DccChat { serv, nick } => match self.servers.get_mut(serv) {
+                None => {
+                    info!("Can't find server: {serv:?}");
+                }
+                Some(ServerLogs { dcc_chats, .. }) => match dcc_chats.get_mut(nick) {
+                    Some(fd) => {
+                        f(fd, &*self.report_err);
+                    }
+                    None => {
+                        // DCC CHAT logs are kept separate from the privmsg logs with the same
+                        // user, as the messages don't go through the server
+                        let mut path = self.log_dir.clone();
+                        path.push(format!("{}_={}.txt", serv, replace_forward_slash(nick)));
+                        if let Some(mut fd) = try_open_log_file(&path, &*self.report_err) {
+                            report_io_err!(self.report_err, print_header(&mut fd));
+                            f(&mut fd, &*self.report_err);
+                            dcc_chats.insert(nick.to_owned(), fd);
+                        }
+                    }
+                },
+            },
             MsgTarget::AllServTabs { serv } => match self.servers.get_mut(serv) {
                 None => {
                     info!("Can't find server: {serv:?}");
                 }
                 Some(ServerLogs {
-                    fd, chans, users, ..
+                    fd,
+                    chans,
+                    users,
+                    dcc_chats,
                 }) => {
                     f(fd, &*self.report_err);
                     for (_, fd) in chans.iter_mut() {
@@ -443,6 +528,9 @@ impl LoggerInner {
                     for (_, fd) in users.iter_mut() {
                         f(fd, &*self.report_err);
                     }
+                    for (_, fd) in dcc_chats.iter_mut() {
+                        f(fd, &*self.report_err);
+                    }
                 }
             },
             MsgTarget::CurrentTab => {
@@ -76,6 +76,9 @@ fn handle_input_ev(ui: &TUI, ev: Event) {
                     MsgSource::User { serv, nick } => {
                         ui.close_user_tab(&serv, &nick);
                     }
+                    MsgSource::DccChat { serv, nick } => {
+                        ui.close_dcc_chat_tab(&serv, &nick);
+                    }
                 }
             }
         }
@@ -0,0 +1,302 @@
+//! A browser for channel lists (`/list` results). Shown in server tabs, on top of the messages.
+
+use std::cmp::Ordering;
+
+use libtiny_common::{ChanName, ChanNameRef, wildcard_match};
+use libtiny_wire::formatting::remove_irc_control_chars;
+use termbox_simple::Termbox;
+
+use crate::config::{Colors, Style};
+use crate::key_map::KeyAction;
+use crate::utils::draw_str;
+use crate::widget::WidgetRet;
+
+/// Width of the user count column, plus a space.
+const USERS_WIDTH: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortBy {
+    /// Most users first.
+    Users,
+    Name,
+}
+
+struct ChanListItem {
+    chan: ChanName,
+    /// Normalized channel name, for sorting.
+    normalized: String,
+    users: usize,
+    topic: String,
+}
+
+pub(crate) struct ChanList {
+    width: i32,
+    height: i32,
+
+    /// Channel name mask of the `/list` command. Also sent to the server when it supports masks,
+    /// but we filter the results here as well for the servers that don't.
+    pattern: Option<String>,
+
+    /// Minimum number of users of the `/list` command. Handled the same way as `pattern`.
+    min_users: Option<usize>,
+
+    items: Vec<ChanListItem>,
+
+    /// Indices of the `items` that match `filter`, sorted by `sort`.
+    shown: Vec<usize>,
+
+    /// Typed filter. Matches channel names and topics, case insensitive.
+    filter: String,
+
+    sort: SortBy,
+
+    /// Index of the selected item in `shown`.
+    selected: usize,
+
+    /// Index of the first item in `shown` that is drawn.
+    scroll: usize,
+
+    /// Did we get the end of the list (323 RPL_LISTEND)?
+    done: bool,
+}
+
+impl ChanList {
+    pub(crate) fn new(
+        width: i32,
+        height: i32,
+        pattern: Option<&str>,
+        min_users: Option<usize>,
+    ) -> ChanList {
+        ChanList {
+            width,
+            height,
+            pattern: pattern.map(str::to_owned),
+            min_users,
+            items: vec![],
+            shown: vec![],
+            filter: String::new(),
+            sort: SortBy::Users,
+            selected: 0,
+            scroll: 0,
+            done: false,
+        }
+    }
+
+    pub(crate) fn resize(&mut self, width: i32, height: i32) {
+        self.width = width;
+        self.height = height;
+        self.fix_scroll();
+    }
+
+    pub(crate) fn add_item(&mut self, chan: &ChanNameRef, users: usize, topic: &str) {
+        if let Some(pattern) = &self.pattern
+            && !wildcard_match(pattern, chan.display())
+        {
+            return;
+        }
+        if let Some(min_users) = self.min_users
+            && users < min_users
+        {
+            return;
+        }
+
+        let item = ChanListItem {
+            chan: chan.to_owned(),
+            normalized: chan.normalized(),
+            users,
+            topic: remove_irc_control_chars(topic),
+        };
+        let matches = self.matches_filter(&item);
+        self.items.push(item);
+
+        if matches {
+            let item_idx = self.items.len() - 1;
+            let item = &self.items[item_idx];
+            let shown_idx = self
+                .shown
+                .partition_point(|idx| self.cmp(&self.items[*idx], item) != Ordering::Greater);
+            self.shown.insert(shown_idx, item_idx);
+            // Keep the same channel selected once the user moves the selection. The first item
+            // is selected until then.
+            if self.selected > 0 && shown_idx <= self.selected {
+                self.selected += 1;
+                self.fix_scroll();
+            }
+        }
+    }
+
+    pub(crate) fn set_done(&mut self) {
+        self.done = true;
+    }
+
+    fn cmp(&self, item1: &ChanListItem, item2: &ChanListItem) -> Ordering {
+        let by_name = item1.normalized.cmp(&item2.normalized);
+        match self.sort {
+            SortBy::Users => item2.users.cmp(&item1.users).then(by_name),
+            SortBy::Name => by_name,
+        }
+    }
+
+    fn matches_filter(&self, item: &ChanListItem) -> bool {
+        if self.filter.is_empty() {
+            return true;
+        }
+        let filter = self.filter.to_lowercase();
+        item.chan.display().to_lowercase().contains(&filter)
+            || item.topic.to_lowercase().contains(&filter)
+    }
+
+    /// Filter and sort the items again, after updating `filter` or `sort`.
+    fn update_shown(&mut self) {
+        let mut shown: Vec<usize> = (0..self.items.len())
+            .filter(|idx| self.matches_filter(&self.items[*idx]))
+            .collect();
+        shown.sort_by(|idx1, idx2| self.cmp(&self.items[*idx1], &self.items[*idx2]));
+        self.shown = shown;
+        self.selected = 0;
+        self.scroll = 0;
+    }
+
+    /// Number of lines for the items. Last line shows the filter and the status.
+    fn list_height(&self) -> usize {
+        (self.height - 1).max(0) as usize
+    }
+
+    /// Update `scroll` to make the selected item visible.
+    fn fix_scroll(&mut self) {
+        let height = self.list_height();
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if height > 0 && self.selected >= self.scroll + height {
+            self.scroll = self.selected + 1 - height;
+        }
+    }
+
+    fn select(&mut self, selected: usize) {
+        self.selected = selected.min(self.shown.len().saturating_sub(1));
+        self.fix_scroll();
+    }
+
+    pub(crate) fn keypressed(&mut self, key_action: &KeyAction) -> WidgetRet {
+        match key_action {
+            KeyAction::Cancel => WidgetRet::Remove,
+            KeyAction::InputPrevEntry => {
+                self.select(self.selected.saturating_sub(1));
+                WidgetRet::KeyHandled
+            }
+            KeyAction::InputNextEntry => {
+                self.select(self.selected + 1);
+                WidgetRet::KeyHandled
+            }
+            KeyAction::MessagesPageUp => {
+                self.select(self.selected.saturating_sub(self.list_height()));
+                WidgetRet::KeyHandled
+            }
+            KeyAction::MessagesPageDown => {
+                self.select(self.selected + self.list_height());
+                WidgetRet::KeyHandled
+            }
+            KeyAction::MessagesScrollTop => {
+                self.select(0);
+                WidgetRet::KeyHandled
+            }
+            KeyAction::MessagesScrollBottom => {
+                self.select(self.shown.len());
+                WidgetRet::KeyHandled
+            }
+            KeyAction::InputAutoComplete => {
+                self.sort = match self.sort {
+                    SortBy::Users => SortBy::Name,
+                    SortBy::Name => SortBy::Users,
+                };
+                self.update_shown();
+                WidgetRet::KeyHandled
+            }
+            KeyAction::Input(c) => {
+                self.filter.push(*c);
+                self.update_shown();
+                WidgetRet::KeyHandled
+            }
+            KeyAction::InputDeletePrevChar => {
+                self.filter.pop();
+                self.update_shown();
+                WidgetRet::KeyHandled
+            }
+            KeyAction::InputDeleteToStart => {
+                self.filter.clear();
+                self.update_shown();
+                WidgetRet::KeyHandled
+            }
+            KeyAction::InputSend => match self.shown.get(self.selected) {
+                Some(item_idx) => {
+                    WidgetRet::Command(format!("join {}", self.items[*item_idx].chan.display()))
+                }
+                None => WidgetRet::KeyHandled,
+            },
+            _ => WidgetRet::KeyIgnored,
+        }
+    }
+
+    pub(crate) fn draw(&self, tb: &mut Termbox, colors: &Colors, pos_x: i32, pos_y: i32) {
+        let max_x = pos_x + self.width;
+
+        for (row, item_idx) in self
+            .shown
+            .iter()
+            .skip(self.scroll)
+            .take(self.list_height())
+            .enumerate()
+        {
+            let item = &self.items[*item_idx];
+            let y = pos_y + row as i32;
+            let selected = self.scroll + row == self.selected;
+            let style = |style: Style| if selected { colors.completion } else { style };
+
+            let users = format!("{:>width$} ", item.users, width = USERS_WIDTH - 1);
+            let mut x = draw_str(tb, pos_x, y, max_x, &users, style(colors.faded));
+            x = draw_str(tb, x, y, max_x, item.chan.display(), style(colors.user_msg));
+            if !item.topic.is_empty() {
+                x = draw_str(tb, x, y, max_x, " ", style(colors.user_msg));
+                draw_str(tb, x, y, max_x, &item.topic, style(colors.faded));
+            }
+        }
+
+        let status_y = pos_y + self.height - 1;
+        let status = format!(
+            "{}/{} channels{}, by {} ",
+            if self.shown.is_empty() {
+                0
+            } else {
+                self.selected + 1
+            },
+            self.shown.len(),
+            if self.done { "" } else { " (loading)" },
+            match self.sort {
+                SortBy::Users => "users",
+                SortBy::Name => "name",
+            },
+        );
+        let filter_x = draw_str(tb, pos_x, status_y, max_x, &status, colors.exit_dialogue);
+        let cursor_x = draw_str(
+            tb,
+            filter_x,
+            status_y,
+            max_x,
+            &format!("filter: {}", self.filter),
+            colors.exit_dialogue,
+        );
+        draw_str(
+            tb,
+            cursor_x,
+            status_y,
+            max_x,
+            &" ".repeat(self.width.max(0) as usize),
+            colors.exit_dialogue,
+        );
+        if cursor_x < max_x {
+            tb.set_cursor(Some((cursor_x as u16, status_y as u16)));
+        } else {
+            tb.hide_cursor();
+        }
+    }
+}
@@ -1,7 +1,8 @@
 // To see how color numbers map to actual colors in your terminal run
 // `cargo run --example colors`. Use tab to swap fg/bg colors.
 
-use libtiny_common::{ChanName, ChanNameRef};
+use libtiny_common::{ChanName, ChanNameRef, wildcard_match};
+use regex::Regex;
 use serde::Deserialize;
 use serde::de::{self, Deserializer, MapAccess, Visitor};
 use std::collections::HashMap;
@@ -10,6 +11,7 @@ use std::path::Path;
 use termbox_simple::*;
 
 use crate::key_map::KeyMap;
+use crate::messaging::TimestampFormat;
 use crate::notifier::Notifier;
 
 #[derive(Debug, Default, Deserialize)]
@@ -31,14 +33,38 @@ pub(crate) struct Config {
 
     #[serde(default)]
     pub(crate) key_map: Option<KeyMap>,
+
+    #[serde(default = "default_nick_list_width")]
+    pub(crate) nick_list_width: i32,
+
+    #[serde(default)]
+    pub(crate) tab_sidebar: Option<TabSidebar>,
+
+    #[serde(default)]
+    pub(crate) urls: Urls,
+
+    #[serde(default = "default_timestamp_format")]
+    pub(crate) timestamp_format: String,
 }
 
 #[derive(Debug, Deserialize, PartialEq, Eq)]
 pub(crate) struct Server {
+    #[serde(default)]
+    pub(crate) name: Option<String>,
+    #[serde(default)]
     pub(crate) addr: String,
     pub(crate) join: Vec<Chan>,
     #[serde(flatten)]
     pub(crate) config: TabConfig,
+    #[serde(default)]
+    pub(crate) highlights: Vec<HighlightRule>,
+}
+
+impl Server {
+    /// Name of the network. Tabs of the server are named after this.
+    pub(crate) fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.addr)
+    }
 }
 
 #[derive(Debug, Deserialize, PartialEq, Eq)]
@@ -66,8 +92,14 @@ pub enum Chan {
     WithConfig {
         #[serde(deserialize_with = "deser_chan_name")]
         name: ChanName,
+        /// Seconds to wait before rejoining the channel when kicked. Not rejoined when `None`.
+        #[serde(default)]
+        rejoin_on_kick: Option<u64>,
         #[serde(flatten)]
         config: TabConfig,
+        /// Highlight rules of the channel, checked after the rules of the server.
+        #[serde(default)]
+        highlights: Vec<HighlightRule>,
     },
 }
 
@@ -86,7 +118,9 @@ impl Chan {
                 let config = TabConfig::from_cmd_args(args)?;
                 Ok(Chan::WithConfig {
                     name: ChanName::new(name.to_string()),
+                    rejoin_on_kick: None,
                     config,
+                    highlights: vec![],
                 })
             }
             // chan name only
@@ -101,6 +135,13 @@ impl Chan {
         }
         .as_ref()
     }
+
+    pub fn rejoin_on_kick(&self) -> Option<u64> {
+        match self {
+            Chan::Name(_) => None,
+            Chan::WithConfig { rejoin_on_kick, .. } => *rejoin_on_kick,
+        }
+    }
 }
 
 fn deser_chan_name<'de, D>(d: D) -> Result<ChanName, D::Error>
@@ -172,13 +213,13 @@ impl From<&Config> for TabConfigs {
         let mut tab_configs = HashMap::new();
         for server in &config.servers {
             let serv_tc = server.config.or_use(&config.defaults.tab_config);
-            tab_configs.insert(server.addr.clone(), serv_tc);
+            tab_configs.insert(server.name().to_owned(), serv_tc);
             for chan in &server.join {
                 let (name, tc) = match chan {
                     Chan::Name(name) => (name, serv_tc),
-                    Chan::WithConfig { name, config } => (name, config.or_use(&serv_tc)),
+                    Chan::WithConfig { name, config, .. } => (name, config.or_use(&serv_tc)),
                 };
-                tab_configs.insert(format!("{}_{}", server.addr, name.display()), tc);
+                tab_configs.insert(format!("{}_{}", server.name(), name.display()), tc);
             }
         }
         tab_configs.insert("_defaults".to_string(), config.defaults.tab_config);
@@ -187,6 +228,147 @@ impl From<&Config> for TabConfigs {
     }
 }
 
+/// A highlight rule. Messages matching the word or regex, from a sender matching the sender
+/// pattern, are highlighted. Messages matching an `exclude` rule are not highlighted, even when
+/// they mention our nick.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "HighlightRuleConfig")]
+pub struct HighlightRule {
+    /// Regex for the message. For `word` rules this matches the word, case insensitive.
+    msg: Option<Regex>,
+
+    /// Wildcard pattern for the sender's nick.
+    sender: Option<String>,
+
+    exclude: bool,
+}
+
+/// Highlight rule as written in the config file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HighlightRuleConfig {
+    #[serde(default)]
+    word: Option<String>,
+    #[serde(default)]
+    regex: Option<String>,
+    #[serde(default)]
+    sender: Option<String>,
+    #[serde(default)]
+    exclude: bool,
+}
+
+impl TryFrom<HighlightRuleConfig> for HighlightRule {
+    type Error = String;
+
+    fn try_from(config: HighlightRuleConfig) -> Result<Self, Self::Error> {
+        let HighlightRuleConfig {
+            word,
+            regex,
+            sender,
+            exclude,
+        } = config;
+        let msg = match (word, regex) {
+            (Some(_), Some(_)) => {
+                return Err("highlight rule can't have both `word` and `regex`".to_string());
+            }
+            (Some(word), None) => Some(format!(r"(?i)(^|\W){}($|\W)", regex::escape(&word))),
+            (None, Some(regex)) => Some(regex),
+            (None, None) => None,
+        };
+        if msg.is_none() && sender.is_none() {
+            return Err("highlight rule needs a `word`, `regex`, or `sender`".to_string());
+        }
+        let msg = match msg {
+            None => None,
+            Some(msg) => Some(Regex::new(&msg).map_err(|err| err.to_string())?),
+        };
+        Ok(HighlightRule {
+            msg,
+            sender,
+            exclude,
+        })
+    }
+}
+
+impl HighlightRule {
+    fn matches(&self, sender: &str, msg: &str) -> bool {
+        self.msg.as_ref().is_none_or(|regex| regex.is_match(msg))
+            && self
+                .sender
+                .as_ref()
+                .is_none_or(|pattern| wildcard_match(pattern, sender))
+    }
+}
+
+impl PartialEq for HighlightRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.msg.as_ref().map(Regex::as_str) == other.msg.as_ref().map(Regex::as_str)
+            && self.sender == other.sender
+            && self.exclude == other.exclude
+    }
+}
+
+impl Eq for HighlightRule {}
+
+/// Highlight rules of servers and channels, by tab names
+#[derive(Debug, Default)]
+pub(crate) struct Highlights(HashMap<String, Vec<HighlightRule>>);
+
+impl Highlights {
+    /// Whether a message should be highlighted. `mentions_nick` is whether the message mentions
+    /// our nick, which is a highlight unless an `exclude` rule matches the message.
+    pub(crate) fn is_highlight(
+        &self,
+        serv_name: &str,
+        chan_name: Option<&ChanNameRef>,
+        sender: &str,
+        msg: &str,
+        mentions_nick: bool,
+    ) -> bool {
+        let serv_rules = self.0.get(serv_name).into_iter().flatten();
+        let chan_rules = chan_name
+            .and_then(|chan| self.0.get(&format!("{}_{}", serv_name, chan.normalized())))
+            .into_iter()
+            .flatten();
+        let mut highlight = mentions_nick;
+        for rule in serv_rules.chain(chan_rules) {
+            if rule.matches(sender, msg) {
+                if rule.exclude {
+                    return false;
+                }
+                highlight = true;
+            }
+        }
+        highlight
+    }
+}
+
+impl From<&Config> for Highlights {
+    fn from(config: &Config) -> Self {
+        let mut highlights = HashMap::new();
+        for server in &config.servers {
+            if !server.highlights.is_empty() {
+                highlights.insert(server.name().to_owned(), server.highlights.clone());
+            }
+            for chan in &server.join {
+                if let Chan::WithConfig {
+                    name,
+                    highlights: chan_highlights,
+                    ..
+                } = chan
+                    && !chan_highlights.is_empty()
+                {
+                    highlights.insert(
+                        format!("{}_{}", server.name(), name.normalized()),
+                        chan_highlights.clone(),
+                    );
+                }
+            }
+        }
+        Self(highlights)
+    }
+}
+
 #[derive(Debug, Default, Copy, Clone, Deserialize, PartialEq, Eq)]
 pub struct TabConfig {
     /// Whether the join/part messages are ignored.
@@ -237,10 +419,74 @@ impl TabConfig {
     }
 }
 
+fn default_timestamp_format() -> String {
+    TimestampFormat::DEFAULT.to_owned()
+}
+
 fn default_max_nick_length() -> usize {
     12
 }
 
+pub(crate) const DEFAULT_NICK_LIST_WIDTH: i32 = 20;
+
+fn default_nick_list_width() -> i32 {
+    DEFAULT_NICK_LIST_WIDTH
+}
+
+pub(crate) const DEFAULT_SIDEBAR_WIDTH: i32 = 20;
+
+fn default_sidebar_width() -> i32 {
+    DEFAULT_SIDEBAR_WIDTH
+}
+
+/// Vertical tab list, shown instead of the tab bar.
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+pub(crate) struct TabSidebar {
+    #[serde(default)]
+    pub(crate) position: SidebarPosition,
+
+    #[serde(default = "default_sidebar_width")]
+    pub(crate) width: i32,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SidebarPosition {
+    #[default]
+    Left,
+    Right,
+}
+
+/// Opening links in messages with the link picker.
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+pub(crate) struct Urls {
+    /// Command to open links with. The link is passed as the last argument.
+    #[serde(default = "default_url_open_command")]
+    pub(crate) open_command: String,
+
+    /// Whether to send links to the terminal as OSC 8 hyperlinks. Detected from the environment
+    /// when not set.
+    #[serde(default)]
+    pub(crate) hyperlinks: Option<bool>,
+}
+
+impl Default for Urls {
+    fn default() -> Self {
+        Urls {
+            open_command: default_url_open_command(),
+            hyperlinks: None,
+        }
+    }
+}
+
+fn default_url_open_command() -> String {
+    if cfg!(target_os = "macos") {
+        "open".to_owned()
+    } else {
+        "xdg-open".to_owned()
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Style {
     /// Termbox fg
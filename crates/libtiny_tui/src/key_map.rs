@@ -22,6 +22,9 @@ pub(crate) enum KeyAction {
     TabMoveLeft,
     TabMoveRight,
     TabGoto(char),
+    TabCollapse,
+    TabNextHighlight,
+    TabNextActivity,
 
     MessagesPageUp,
     MessagesPageDown,
@@ -29,6 +32,27 @@ pub(crate) enum KeyAction {
     MessagesScrollDown,
     MessagesScrollTop,
     MessagesScrollBottom,
+    MessagesToggleDetails,
+    MessagesScrollUnread,
+
+    NickListToggle,
+    NickListScrollUp,
+    NickListScrollDown,
+
+    Search,
+    SearchNext,
+    SearchPrev,
+
+    SplitHorizontal,
+    SplitVertical,
+    SplitClose,
+    SplitFocusNext,
+    SplitFocusPrev,
+
+    SidebarScrollUp,
+    SidebarScrollDown,
+
+    UrlPicker,
 
     Input(char),
     Command(String),
@@ -69,6 +93,7 @@ impl Default for KeyMap {
             (Key::AltChar('8'), KeyAction::TabGoto('8')),
             (Key::AltChar('9'), KeyAction::TabGoto('9')),
             (Key::AltChar('0'), KeyAction::TabGoto('0')),
+            (Key::FKey(FKey::F4), KeyAction::TabCollapse),
             (Key::Ctrl('u'), KeyAction::MessagesPageUp),
             (Key::Ctrl('d'), KeyAction::MessagesPageDown),
             (Key::PageUp, KeyAction::MessagesPageUp),
@@ -79,6 +104,20 @@ impl Default for KeyMap {
             (Key::MouseWheelDown, KeyAction::MessagesScrollDown),
             (Key::Home, KeyAction::MessagesScrollTop),
             (Key::End, KeyAction::MessagesScrollBottom),
+            (Key::Ctrl('o'), KeyAction::MessagesToggleDetails),
+            (Key::FKey(FKey::F9), KeyAction::MessagesScrollUnread),
+            (Key::FKey(FKey::F2), KeyAction::NickListToggle),
+            (Key::AltArrow(Arrow::Up), KeyAction::NickListScrollUp),
+            (Key::AltArrow(Arrow::Down), KeyAction::NickListScrollDown),
+            (Key::Ctrl('f'), KeyAction::Search),
+            (Key::FKey(FKey::F3), KeyAction::SearchNext),
+            (Key::ShiftF(FKey::F3), KeyAction::SearchPrev),
+            (Key::FKey(FKey::F5), KeyAction::SplitHorizontal),
+            (Key::FKey(FKey::F6), KeyAction::SplitVertical),
+            (Key::FKey(FKey::F7), KeyAction::SplitClose),
+            (Key::CtrlArrow(Arrow::Down), KeyAction::SplitFocusNext),
+            (Key::CtrlArrow(Arrow::Up), KeyAction::SplitFocusPrev),
+            (Key::FKey(FKey::F8), KeyAction::UrlPicker),
             (Key::Tab, KeyAction::InputAutoComplete),
             (Key::Arrow(Arrow::Up), KeyAction::InputPrevEntry),
             (Key::Arrow(Arrow::Down), KeyAction::InputNextEntry),
@@ -303,12 +342,31 @@ impl Display for KeyAction {
             KeyAction::TabMoveLeft => "tab_move_left",
             KeyAction::TabMoveRight => "tab_move_right",
             KeyAction::TabGoto(c) => return writeln!(f, "tab_goto: {c}"),
+            KeyAction::TabCollapse => "tab_collapse",
+            KeyAction::TabNextHighlight => "tab_next_highlight",
+            KeyAction::TabNextActivity => "tab_next_activity",
             KeyAction::MessagesPageUp => "messages_page_up",
             KeyAction::MessagesPageDown => "messages_page_down",
             KeyAction::MessagesScrollUp => "messages_scroll_up",
             KeyAction::MessagesScrollDown => "messages_scroll_down",
             KeyAction::MessagesScrollTop => "messages_scroll_top",
             KeyAction::MessagesScrollBottom => "messages_scroll_bottom",
+            KeyAction::MessagesToggleDetails => "messages_toggle_details",
+            KeyAction::MessagesScrollUnread => "messages_scroll_unread",
+            KeyAction::NickListToggle => "nick_list_toggle",
+            KeyAction::NickListScrollUp => "nick_list_scroll_up",
+            KeyAction::NickListScrollDown => "nick_list_scroll_down",
+            KeyAction::Search => "search",
+            KeyAction::SearchNext => "search_next",
+            KeyAction::SearchPrev => "search_prev",
+            KeyAction::SplitHorizontal => "split_horizontal",
+            KeyAction::SplitVertical => "split_vertical",
+            KeyAction::SplitClose => "split_close",
+            KeyAction::SplitFocusNext => "split_focus_next",
+            KeyAction::SplitFocusPrev => "split_focus_prev",
+            KeyAction::SidebarScrollUp => "sidebar_scroll_up",
+            KeyAction::SidebarScrollDown => "sidebar_scroll_down",
+            KeyAction::UrlPicker => "url_picker",
             KeyAction::Input(c) => return writeln!(f, "input_{c}"),
             KeyAction::Command(string) => return writeln!(f, "command_{string}"),
             KeyAction::InputAutoComplete => "input_auto_complete",
@@ -1,6 +1,7 @@
 #![allow(clippy::too_many_arguments)]
 #![allow(clippy::cognitive_complexity)]
 
+mod chan_list;
 pub mod config;
 mod editor;
 mod exit_dialogue;
@@ -10,13 +11,18 @@ mod line_split;
 mod messaging;
 #[doc(hidden)]
 pub mod msg_area; // Public to be able to use in an example
+mod nick_list;
 mod notifier;
+mod search;
+mod sidebar;
+mod split;
 mod tab;
 mod termbox;
 pub mod test_utils;
 #[doc(hidden)]
 pub mod trie; // Public for benchmarks
 pub mod tui; // Public for benchmarks
+mod urls;
 mod utils;
 mod widget;
 
@@ -239,6 +245,8 @@ impl TUI {
     delegate!(new_chan_tab(serv_name: &str, chan: &ChanNameRef,));
     delegate!(close_chan_tab(serv_name: &str, chan: &ChanNameRef,));
     delegate!(close_user_tab(serv_name: &str, nick: &str,));
+    delegate!(new_dcc_chat_tab(serv_name: &str, nick: &str,));
+    delegate!(close_dcc_chat_tab(serv_name: &str, nick: &str,));
     delegate!(add_client_msg(msg: &str, target: &MsgTarget,));
     delegate!(add_msg(msg: &str, ts: Tm, target: &MsgTarget,));
     delegate!(add_err_msg(msg: &str, ts: Tm, target: &MsgTarget,));
@@ -255,6 +263,19 @@ impl TUI {
     ));
     delegate!(add_nick(nick: &str, ts: Option<Tm>, target: &MsgTarget,));
     delegate!(remove_nick(nick: &str, ts: Option<Tm>, target: &MsgTarget,));
+    delegate!(update_nick_prefix(
+        nick: &str,
+        prefix: char,
+        add: bool,
+        target: &MsgTarget,
+    ));
+    delegate!(add_netsplit(
+        servers: (&str, &str),
+        nicks: &[String],
+        ts: Tm,
+        target: &MsgTarget,
+    ));
+    delegate!(add_netjoin(nicks: &[String], ts: Tm, target: &MsgTarget,));
     delegate!(rename_nick(
         old_nick: &str,
         new_nick: &str,
@@ -268,6 +289,20 @@ impl TUI {
         chan_name: &ChanNameRef,
     ));
     delegate!(set_tab_style(style: TabStyle, target: &MsgTarget,));
+    delegate!(set_transfer(id: usize, line: &str,));
+    delegate!(remove_transfer(id: usize,));
+    delegate!(open_chan_list(
+        serv_name: &str,
+        pattern: Option<&str>,
+        min_users: Option<usize>,
+    ));
+    delegate!(add_chan_list_item(
+        serv_name: &str,
+        chan: &ChanNameRef,
+        users: usize,
+        topic: &str,
+    ));
+    delegate!(chan_list_done(serv_name: &str,));
 
     pub fn get_tab_config(&self, serv_name: &str, chan_name: Option<&ChanNameRef>) -> TabConfig {
         self.inner
@@ -288,6 +323,26 @@ impl TUI {
         }
     }
 
+    /// Whether a message should be highlighted, according to the highlight rules in the config
+    /// file. `mentions_nick` is whether the message mentions our nick. `chan_name` should be
+    /// `None` for messages not in a channel, those only use the server's rules.
+    pub fn is_highlight(
+        &self,
+        serv_name: &str,
+        chan_name: Option<&ChanNameRef>,
+        sender: &str,
+        msg: &str,
+        mentions_nick: bool,
+    ) -> bool {
+        match self.inner.upgrade() {
+            Some(tui) => {
+                tui.borrow()
+                    .is_highlight(serv_name, chan_name, sender, msg, mentions_nick)
+            }
+            None => mentions_nick,
+        }
+    }
+
     pub fn user_tab_exists(&self, serv_name: &str, nick: &str) -> bool {
         match self.inner.upgrade() {
             Some(tui) => tui.borrow().user_tab_exists(serv_name, nick),
@@ -2,15 +2,22 @@ use termbox_simple::Termbox;
 
 use std::convert::From;
 
+use libtiny_common::ChanNameRef;
+
 use time::{self, Tm};
+use unicode_width::UnicodeWidthStr;
 
+use crate::chan_list::ChanList;
 use crate::config::Colors;
 use crate::exit_dialogue::ExitDialogue;
 use crate::input_area::InputArea;
 use crate::key_map::KeyAction;
 use crate::msg_area::line::SegStyle;
 use crate::msg_area::{Layout, MsgArea};
+use crate::nick_list::NickList;
+use crate::search::{SearchPrompt, search_regex};
 use crate::trie::Trie;
+use crate::urls::UrlPicker;
 use crate::widget::WidgetRet;
 
 /// An input field and an area for showing messages and activities of a tab (channel, server,
@@ -25,6 +32,21 @@ pub(crate) struct MessagingUI {
 
     exit_dialogue: Option<ExitDialogue>,
 
+    /// Incremental search prompt, shown instead of `input_field` when available.
+    search_prompt: Option<SearchPrompt>,
+
+    /// Channel list browser, shown instead of `msg_area` and `input_field` when available.
+    /// `exit_dialogue` is shown over it.
+    chan_list: Option<ChanList>,
+
+    /// Whether the server is sending a channel list: set when the list is opened, cleared when
+    /// the list is complete. Used to ignore the rest of the list when it's closed before that.
+    chan_list_loading: bool,
+
+    /// Link picker, shown instead of `msg_area`, `input_field`, and `chan_list` when available.
+    /// `exit_dialogue` is shown over it.
+    url_picker: Option<UrlPicker>,
+
     /// Width of the UI, in characters.
     width: i32,
 
@@ -34,21 +56,29 @@ pub(crate) struct MessagingUI {
     /// All nicks in the channel. Used in autocompletion.
     nicks: Trie,
 
+    /// Members of the channel, for the nick list panel. Unlike `nicks`, only has the nicks that
+    /// we know are in the channel.
+    nick_list: NickList,
+
+    /// Width of the nick list panel, `None` when the panel is hidden.
+    nick_list_width: Option<i32>,
+
     /// The last line in `msg_area` that shows join, leave, disconnect activities.
     last_activity_line: Option<ActivityLine>,
 
     /// Last timestamp added to the UI.
     last_ts: Option<Timestamp>,
+
+    timestamp_format: TimestampFormat,
 }
 
 /// Length of ": " suffix of nicks in messages
 pub(crate) const MSG_NICK_SUFFIX_LEN: usize = 2;
 
-/// Like `time::Tm`, but we only care about hour and minute parts.
-#[derive(PartialEq, Eq, Clone, Copy)]
+/// Time of a message.
+#[derive(Clone, Copy)]
 pub(crate) struct Timestamp {
-    hour: i32,
-    min: i32,
+    tm: Tm,
 }
 
 // 80 characters. TODO: We need to make sure we don't need more whitespace than that. We should
@@ -57,23 +87,77 @@ static WHITESPACE: &str =
     "                                                                                ";
 
 impl Timestamp {
-    /// The width of a timestamp plus a space.
-    pub(crate) const WIDTH: usize = 6;
-
-    /// Spaces for a timestamp slot in aligned layout.
-    pub(crate) const BLANK: &'static str = "      ";
+    /// Year and day of the year.
+    fn date(&self) -> (i32, i32) {
+        (self.tm.tm_year, self.tm.tm_yday)
+    }
 
-    fn stamp(&self) -> String {
-        format!("{:02}:{:02} ", self.hour, self.min)
+    /// Text of the line shown when the date changes between two messages, e.g.
+    /// "— Tuesday, 2026-10-13 —".
+    fn date_separator(&self) -> String {
+        format!(
+            "— {} —",
+            time::strftime("%A, %Y-%m-%d", &self.tm).unwrap_or_default()
+        )
     }
 }
 
 impl From<Tm> for Timestamp {
     fn from(tm: Tm) -> Timestamp {
-        Timestamp {
-            hour: tm.tm_hour,
-            min: tm.tm_min,
+        Timestamp { tm }
+    }
+}
+
+/// A strftime-style format for the timestamps of the messages, e.g. `%H:%M:%S`.
+#[derive(Debug, Clone)]
+pub(crate) struct TimestampFormat {
+    format: String,
+
+    /// Width of the formatted timestamps, plus a space.
+    width: usize,
+}
+
+impl TimestampFormat {
+    pub(crate) const DEFAULT: &'static str = "%H:%M";
+
+    pub(crate) fn new(format: &str) -> Result<TimestampFormat, time::ParseError> {
+        // Names of the days and months have different lengths, so find the widest timestamp.
+        // Month `i` is paired with weekday `i % 7` to cover all months and weekdays.
+        let mut width = 0;
+        for i in 0..12 {
+            let tm = Tm {
+                tm_mon: i,
+                tm_wday: i % 7,
+                ..time::empty_tm()
+            };
+            width = width.max(time::strftime(format, &tm)?.width());
         }
+        Ok(TimestampFormat {
+            format: format.to_owned(),
+            width: width + 1,
+        })
+    }
+
+    /// Width of the timestamps, plus a space. Timestamp column of aligned layout has this width.
+    pub(crate) fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Format the timestamp, padded to `width`.
+    fn stamp(&self, ts: Timestamp) -> String {
+        let stamp = time::strftime(&self.format, &ts.tm).unwrap_or_default();
+        format!("{stamp:width$}", width = self.width)
+    }
+
+    /// Whether the timestamps are shown the same, and are on the same day.
+    fn same_stamp(&self, ts1: Timestamp, ts2: Timestamp) -> bool {
+        ts1.date() == ts2.date() && self.stamp(ts1) == self.stamp(ts2)
+    }
+}
+
+impl Default for TimestampFormat {
+    fn default() -> Self {
+        TimestampFormat::new(TimestampFormat::DEFAULT).unwrap()
     }
 }
 
@@ -92,16 +176,30 @@ impl MessagingUI {
         height: i32,
         scrollback: usize,
         msg_layout: Layout,
+        timestamp_format: TimestampFormat,
     ) -> MessagingUI {
         MessagingUI {
-            msg_area: MsgArea::new(width, height - 1, scrollback, msg_layout),
+            msg_area: MsgArea::new(
+                width,
+                height - 1,
+                scrollback,
+                msg_layout,
+                timestamp_format.width(),
+            ),
             input_field: InputArea::new(width, get_input_field_max_height(height)),
             exit_dialogue: None,
+            search_prompt: None,
+            chan_list: None,
+            chan_list_loading: false,
+            url_picker: None,
             width,
             height,
             nicks: Trie::new(),
+            nick_list: NickList::new(),
+            nick_list_width: None,
             last_activity_line: None,
             last_ts: None,
+            timestamp_format,
         }
     }
 
@@ -114,30 +212,168 @@ impl MessagingUI {
         self.resize(w, h);
     }
 
+    /// Set the timestamp format of new messages. The next message is shown with a timestamp.
+    pub(crate) fn set_timestamp_format(&mut self, timestamp_format: TimestampFormat) {
+        self.msg_area.set_timestamp_width(timestamp_format.width());
+        self.timestamp_format = timestamp_format;
+        self.last_ts = None;
+        self.last_activity_line = None;
+    }
+
     pub(crate) fn get_nick(&self) -> Option<String> {
         self.input_field.get_nick()
     }
 
+    /// Width and height of the UI.
+    pub(crate) fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
     pub(crate) fn draw(&mut self, tb: &mut Termbox, colors: &Colors, pos_x: i32, pos_y: i32) {
+        if let Some(url_picker) = &self.url_picker {
+            url_picker.draw(tb, colors, pos_x, pos_y);
+            if let Some(exit_dialogue) = &self.exit_dialogue {
+                exit_dialogue.draw(tb, colors, pos_x, pos_y + self.height - 1);
+            }
+            return;
+        }
+
+        if let Some(chan_list) = &self.chan_list {
+            chan_list.draw(tb, colors, pos_x, pos_y);
+            if let Some(exit_dialogue) = &self.exit_dialogue {
+                exit_dialogue.draw(tb, colors, pos_x, pos_y + self.height - 1);
+            }
+            return;
+        }
+
+        // Draw the nick list first as exit dialogue is drawn over it
+        if self.shown_nick_list_width().is_some() {
+            self.nick_list
+                .draw(tb, colors, pos_x + self.msgs_width(), pos_y, |nick| {
+                    self.get_nick_color(nick)
+                });
+        }
+
         match &self.exit_dialogue {
             Some(exit_dialogue) => {
-                exit_dialogue.draw(tb, colors, pos_x, self.height - 1);
-            }
-            None => {
-                // Draw InputArea first because it can trigger a resize of MsgArea
-                self.input_field
-                    .draw(tb, colors, pos_x, pos_y, self.height, &mut self.msg_area);
+                exit_dialogue.draw(tb, colors, pos_x, pos_y + self.height - 1);
             }
+            None => match &self.search_prompt {
+                Some(search_prompt) => {
+                    search_prompt.draw(
+                        tb,
+                        colors,
+                        pos_x,
+                        pos_y + self.height - 1,
+                        self.msg_area.search_status(),
+                    );
+                }
+                None => {
+                    // Draw InputArea first because it can trigger a resize of MsgArea
+                    self.input_field.draw(
+                        tb,
+                        colors,
+                        pos_x,
+                        pos_y,
+                        self.height,
+                        &mut self.msg_area,
+                    );
+                }
+            },
         }
         self.msg_area.draw(tb, colors, pos_x, pos_y);
     }
 
     pub(crate) fn keypressed(&mut self, key_action: &KeyAction) -> WidgetRet {
+        if self.exit_dialogue.is_none()
+            && *key_action != KeyAction::Exit
+            && let Some(url_picker) = &mut self.url_picker
+        {
+            return match url_picker.keypressed(key_action) {
+                WidgetRet::Remove => {
+                    self.url_picker = None;
+                    WidgetRet::KeyHandled
+                }
+                ret @ (WidgetRet::OpenUrl(_) | WidgetRet::CopyUrl(_)) => {
+                    self.url_picker = None;
+                    ret
+                }
+                ret => ret,
+            };
+        }
+
+        if self.exit_dialogue.is_none()
+            && *key_action != KeyAction::Exit
+            && let Some(chan_list) = &mut self.chan_list
+        {
+            return match chan_list.keypressed(key_action) {
+                WidgetRet::Remove => {
+                    self.chan_list = None;
+                    WidgetRet::KeyHandled
+                }
+                ret => ret,
+            };
+        }
+
+        if self.exit_dialogue.is_none()
+            && let Some(search_prompt) = &mut self.search_prompt
+        {
+            match key_action {
+                KeyAction::Cancel => {
+                    self.search_prompt = None;
+                    self.msg_area.clear_search();
+                    return WidgetRet::KeyHandled;
+                }
+                KeyAction::InputSend => {
+                    // Keep the search, to be able to jump between the hits
+                    self.search_prompt = None;
+                    return WidgetRet::KeyHandled;
+                }
+                KeyAction::InputPrevEntry => {
+                    self.msg_area.search_older();
+                    return WidgetRet::KeyHandled;
+                }
+                KeyAction::InputNextEntry => {
+                    self.msg_area.search_newer();
+                    return WidgetRet::KeyHandled;
+                }
+                key_action => {
+                    if let WidgetRet::KeyHandled = search_prompt.keypressed(key_action) {
+                        let query = search_prompt.query();
+                        // Incomplete regexes don't match anything
+                        let regex = if query.is_empty() {
+                            None
+                        } else {
+                            search_regex(query).ok()
+                        };
+                        self.msg_area.set_search(regex);
+                        return WidgetRet::KeyHandled;
+                    }
+                }
+            }
+        }
+
         match key_action {
             KeyAction::Exit => {
                 self.toggle_exit_dialogue();
                 WidgetRet::KeyHandled
             }
+            KeyAction::Search if self.exit_dialogue.is_none() => {
+                self.search_prompt = Some(SearchPrompt::new(self.msgs_width()));
+                WidgetRet::KeyHandled
+            }
+            KeyAction::SearchNext => {
+                self.msg_area.search_older();
+                WidgetRet::KeyHandled
+            }
+            KeyAction::SearchPrev => {
+                self.msg_area.search_newer();
+                WidgetRet::KeyHandled
+            }
+            KeyAction::Cancel if self.exit_dialogue.is_none() && self.msg_area.has_search() => {
+                self.msg_area.clear_search();
+                WidgetRet::KeyHandled
+            }
             KeyAction::MessagesPageUp => {
                 self.msg_area.page_up();
                 WidgetRet::KeyHandled
@@ -162,8 +398,24 @@ impl MessagingUI {
                 self.msg_area.scroll_bottom();
                 WidgetRet::KeyHandled
             }
+            KeyAction::MessagesToggleDetails => {
+                self.msg_area.toggle_details();
+                WidgetRet::KeyHandled
+            }
+            KeyAction::MessagesScrollUnread => {
+                self.msg_area.scroll_to_unread_marker();
+                WidgetRet::KeyHandled
+            }
+            KeyAction::NickListScrollUp => {
+                self.nick_list.scroll_up();
+                WidgetRet::KeyHandled
+            }
+            KeyAction::NickListScrollDown => {
+                self.nick_list.scroll_down();
+                WidgetRet::KeyHandled
+            }
             KeyAction::InputAutoComplete => {
-                if self.exit_dialogue.is_none() {
+                if self.exit_dialogue.is_none() && self.search_prompt.is_none() {
                     self.input_field.autocomplete(&self.nicks);
                 }
                 WidgetRet::KeyHandled
@@ -172,6 +424,8 @@ impl MessagingUI {
                 let ret = {
                     if let Some(exit_dialogue) = self.exit_dialogue.as_ref() {
                         exit_dialogue.keypressed(key_action)
+                    } else if self.search_prompt.is_some() {
+                        WidgetRet::KeyIgnored
                     } else {
                         self.input_field.keypressed(key_action)
                     }
@@ -191,16 +445,33 @@ impl MessagingUI {
         self.width = width;
         self.height = height;
 
+        let msgs_width = self.msgs_width();
         self.input_field
-            .resize(width, get_input_field_max_height(height));
+            .resize(msgs_width, get_input_field_max_height(height));
         // msg_area should resize based on input_field's rendered height
-        let msg_area_height = height - self.input_field.get_height(width);
-        self.msg_area.resize(width, msg_area_height);
+        let msg_area_height = height - self.input_field.get_height(msgs_width);
+        self.msg_area.resize(msgs_width, msg_area_height);
+
+        if let Some(nick_list_width) = self.shown_nick_list_width() {
+            self.nick_list.resize(nick_list_width, height);
+        }
 
         // We don't show the nick in exit dialogue, so it has the full width
         if let Some(exit_dialogue) = &mut self.exit_dialogue {
             exit_dialogue.resize(width);
         }
+
+        if let Some(search_prompt) = &mut self.search_prompt {
+            search_prompt.resize(msgs_width);
+        }
+
+        if let Some(chan_list) = &mut self.chan_list {
+            chan_list.resize(width, height);
+        }
+
+        if let Some(url_picker) = &mut self.url_picker {
+            url_picker.resize(width, height);
+        }
     }
 
     /// Get contents of the input field and cursor location and clear it.
@@ -208,6 +479,16 @@ impl MessagingUI {
         self.input_field.flush()
     }
 
+    /// Move the unread marker after the last visible message. Call when leaving the tab.
+    pub(crate) fn set_unread_marker(&mut self) {
+        self.msg_area.set_unread_marker();
+    }
+
+    /// Clear the unread marker if there are no unread messages. Call when the tab is shown.
+    pub(crate) fn clear_pending_unread_marker(&mut self) {
+        self.msg_area.clear_pending_unread_marker();
+    }
+
     /// Add a line to input field history.
     pub(crate) fn add_input_field_history(&mut self, str: &str) {
         self.input_field.add_history(str)
@@ -223,6 +504,25 @@ impl MessagingUI {
         self.input_field.set_cursor(cursor);
     }
 
+    /// Show the nick list panel with the given width, or hide it when `width` is `None`.
+    pub(crate) fn set_nick_list_width(&mut self, width: Option<i32>) {
+        self.nick_list_width = width;
+        self.resize(self.width, self.height);
+    }
+
+    /// Width of the nick list panel, including the separator. `None` when the panel is hidden,
+    /// or when the UI is too narrow to show it: the panel can take half of the width at most.
+    fn shown_nick_list_width(&self) -> Option<i32> {
+        self.nick_list_width
+            .map(|width| width + 1)
+            .filter(|width| 2 * width <= self.width)
+    }
+
+    /// Width of the message area and the input field.
+    fn msgs_width(&self) -> i32 {
+        self.width - self.shown_nick_list_width().unwrap_or(0)
+    }
+
     fn toggle_exit_dialogue(&mut self) {
         if self.exit_dialogue.take().is_none() {
             // We don't show the nick in exit dialogue, so it has the full width
@@ -244,16 +544,29 @@ impl MessagingUI {
     ///
     /// In compact layout this adds the indentation for the timestamp column if we're already
     /// showing the timestamp.
+    ///
+    /// When the date is different than the date of the last timestamp, this adds a line showing the
+    /// new date first.
     fn add_timestamp(&mut self, ts: Timestamp) {
         if let Some(ts_) = self.last_ts {
-            if ts_ != ts {
-                self.msg_area.add_text(&ts.stamp(), SegStyle::Timestamp);
-            } else if self.msg_area.layout().is_aligned() {
+            if ts_.date() != ts.date() {
                 self.msg_area
-                    .add_text(Timestamp::BLANK, SegStyle::Timestamp);
+                    .add_text(&ts.date_separator(), SegStyle::Faded);
+                self.msg_area.flush_line();
+                self.msg_area
+                    .add_text(&self.timestamp_format.stamp(ts), SegStyle::Timestamp);
+            } else if !self.timestamp_format.same_stamp(ts_, ts) {
+                self.msg_area
+                    .add_text(&self.timestamp_format.stamp(ts), SegStyle::Timestamp);
+            } else if self.msg_area.layout().is_aligned() {
+                self.msg_area.add_text(
+                    &" ".repeat(self.timestamp_format.width()),
+                    SegStyle::Timestamp,
+                );
             }
         } else {
-            self.msg_area.add_text(&ts.stamp(), SegStyle::Timestamp);
+            self.msg_area
+                .add_text(&self.timestamp_format.stamp(ts), SegStyle::Timestamp);
         }
         self.last_ts = Some(ts);
     }
@@ -367,16 +680,83 @@ impl MessagingUI {
     }
 }
 
+////////////////////////////////////////////////////////////////////////////////
+// Searching
+
+impl MessagingUI {
+    /// Start a new search from the last line. Returns whether there's a hit. The search is
+    /// cleared when there isn't.
+    pub(crate) fn search(&mut self, query: &str) -> Result<bool, regex::Error> {
+        let regex = search_regex(query)?;
+        self.msg_area.clear_search();
+        self.msg_area.set_search(Some(regex));
+        let found = matches!(self.msg_area.search_status(), Some((_, total)) if total > 0);
+        if !found {
+            self.msg_area.set_search(None);
+        }
+        Ok(found)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Channel list
+
+impl MessagingUI {
+    /// Show an empty channel list, replacing the current one.
+    pub(crate) fn open_chan_list(&mut self, pattern: Option<&str>, min_users: Option<usize>) {
+        self.chan_list = Some(ChanList::new(self.width, self.height, pattern, min_users));
+        self.chan_list_loading = true;
+    }
+
+    /// Add a channel to the channel list. A list without filters is shown for lists that were not
+    /// opened with `open_chan_list` (e.g. `/raw LIST`). Ignored when the list is closed before
+    /// the server sends the whole list.
+    pub(crate) fn add_chan_list_item(&mut self, chan: &ChanNameRef, users: usize, topic: &str) {
+        match &mut self.chan_list {
+            Some(chan_list) => chan_list.add_item(chan, users, topic),
+            None if self.chan_list_loading => {}
+            None => {
+                self.open_chan_list(None, None);
+                self.add_chan_list_item(chan, users, topic);
+            }
+        }
+    }
+
+    pub(crate) fn chan_list_done(&mut self) {
+        self.chan_list_loading = false;
+        if let Some(chan_list) = &mut self.chan_list {
+            chan_list.set_done();
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Links
+
+impl MessagingUI {
+    /// Show the links in the tab in a picker. Returns whether there are any links to show.
+    pub(crate) fn open_url_picker(&mut self) -> bool {
+        let urls = self.msg_area.urls();
+        if urls.is_empty() {
+            return false;
+        }
+        self.url_picker = Some(UrlPicker::new(self.width, self.height, urls));
+        true
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // Keeping nick list up-to-date
 
 impl MessagingUI {
     pub(crate) fn clear_nicks(&mut self) {
         self.nicks.clear();
+        self.nick_list.clear();
     }
 
     pub(crate) fn join(&mut self, nick: &str, ts: Option<Timestamp>, ignore: bool) {
         self.nicks.insert(nick);
+        self.nick_list.insert(nick);
 
         if !ignore && let Some(ts) = ts {
             let line_idx = self.get_activity_line_idx(ts);
@@ -389,6 +769,7 @@ impl MessagingUI {
 
     pub(crate) fn part(&mut self, nick: &str, ts: Option<Timestamp>, ignore: bool) {
         self.nicks.remove(nick);
+        self.nick_list.remove(nick);
 
         if !ignore && let Some(ts) = ts {
             let line_idx = self.get_activity_line_idx(ts);
@@ -402,6 +783,7 @@ impl MessagingUI {
     pub(crate) fn nick(&mut self, old_nick: &str, new_nick: &str, ts: Timestamp) {
         self.nicks.remove(old_nick);
         self.nicks.insert(new_nick);
+        self.nick_list.rename(old_nick, new_nick);
 
         let line_idx = self.get_activity_line_idx(ts);
         self.msg_area.modify_line(line_idx, |line| {
@@ -411,10 +793,75 @@ impl MessagingUI {
         });
     }
 
+    /// Add or remove a membership prefix (e.g. `@`) of a nick in the nick list.
+    pub(crate) fn update_nick_prefix(&mut self, nick: &str, prefix: char, add: bool) {
+        self.nick_list.update_prefix(nick, prefix, add);
+    }
+
+    /// Add a netsplit summary line. Nicks are shown when details are expanded.
+    pub(crate) fn netsplit(
+        &mut self,
+        servers: (&str, &str),
+        nicks: &[String],
+        ts: Timestamp,
+        ignore: bool,
+    ) {
+        for nick in nicks {
+            self.nicks.remove(nick);
+            self.nick_list.remove(nick);
+        }
+
+        if !ignore {
+            self.add_activity_prefix(ts);
+            self.msg_area.add_text("Netsplit", SegStyle::Part);
+            self.msg_area.add_text(
+                &format!(" {} <-> {}, {} quit", servers.0, servers.1, nicks.len()),
+                SegStyle::Faded,
+            );
+            self.add_summary_details(nicks);
+        }
+    }
+
+    /// Add a netjoin summary line. Nicks are shown when details are expanded.
+    pub(crate) fn netjoin(&mut self, nicks: &[String], ts: Timestamp, ignore: bool) {
+        for nick in nicks {
+            self.nicks.insert(nick);
+            self.nick_list.insert(nick);
+        }
+
+        if !ignore {
+            self.add_activity_prefix(ts);
+            self.msg_area.add_text("Netjoin", SegStyle::Join);
+            self.msg_area
+                .add_text(&format!(" {} joined", nicks.len()), SegStyle::Faded);
+            self.add_summary_details(nicks);
+        }
+    }
+
+    fn add_summary_details(&mut self, nicks: &[String]) {
+        self.msg_area
+            .set_details(format!(": {}", nicks.join(", ")), SegStyle::Faded);
+        self.msg_area.set_current_line_alignment();
+        self.msg_area.flush_line();
+    }
+
+    /// Add timestamp and, in aligned layout, the padding for the nick column to the current
+    /// line, for a line without a sender.
+    fn add_activity_prefix(&mut self, ts: Timestamp) {
+        self.add_timestamp(ts);
+        if let Layout::Aligned { max_nick_len, .. } = self.msg_area.layout() {
+            self.msg_area.add_text(
+                &WHITESPACE[..max_nick_len + MSG_NICK_SUFFIX_LEN],
+                SegStyle::UserMsg,
+            )
+        }
+    }
+
     fn get_activity_line_idx(&mut self, ts: Timestamp) -> usize {
         match &self.last_activity_line {
             Some(l)
-                if l.ts == ts && Some(l.line_idx) == self.msg_area.num_lines().checked_sub(1) =>
+                if self.timestamp_format.same_stamp(l.ts, ts)
+                    && Some(l.line_idx) == self.msg_area.num_lines().checked_sub(1) =>
             {
                 let line_idx = l.line_idx;
                 // FIXME: It's a bit hacky to add a space in this function which from the name
@@ -427,13 +874,7 @@ impl MessagingUI {
                 line_idx
             }
             _ => {
-                self.add_timestamp(ts);
-                if let Layout::Aligned { max_nick_len, .. } = self.msg_area.layout() {
-                    self.msg_area.add_text(
-                        &WHITESPACE[..max_nick_len + MSG_NICK_SUFFIX_LEN],
-                        SegStyle::UserMsg,
-                    )
-                }
+                self.add_activity_prefix(ts);
                 self.msg_area.set_current_line_alignment();
                 let line_idx = self.msg_area.flush_line();
                 self.last_activity_line = Some(ActivityLine { ts, line_idx });
@@ -1,7 +1,11 @@
 use crate::config::{Colors, Style};
 use crate::line_split::{LineDataCache, LineType};
+use crate::urls::find_urls;
+
+use std::ops::Range;
 
 use libtiny_wire::formatting::{Color, IrcFormatEvent, parse_irc_formatting};
+use regex::Regex;
 use termbox_simple::{self, Termbox};
 
 /// A single line added to the widget. May be rendered as multiple lines on the
@@ -14,6 +18,19 @@ pub(crate) struct Line {
     /// The segment we're currently extending.
     current_seg: StyledString,
 
+    /// Details of a summary line (e.g. nicks in a netsplit), shown after the segments when the
+    /// line is expanded.
+    details: Option<StyledString>,
+
+    /// Whether to show `details`.
+    expanded: bool,
+
+    /// Links in the message segments, with their char index ranges. Set by `detect_urls`.
+    urls: Vec<(Range<usize>, String)>,
+
+    /// Char index ranges of the matches of the current search. Set by `set_search`.
+    search_matches: Vec<Range<usize>>,
+
     line_data: LineDataCache,
 }
 
@@ -81,6 +98,10 @@ impl Line {
         Line {
             segments: vec![],
             current_seg: StyledString::default(),
+            details: None,
+            expanded: false,
+            urls: vec![],
+            search_matches: vec![],
             line_data: LineDataCache::msg_line(0, None),
         }
     }
@@ -177,22 +198,116 @@ impl Line {
         self.line_data.set_dirty();
     }
 
+    /// Set details of the line, shown when the line is expanded. The text should not contain IRC
+    /// formatting characters or other control characters.
+    pub(crate) fn set_details(&mut self, details: String, style: SegStyle) {
+        self.details = Some(StyledString {
+            string: details,
+            style,
+        });
+        self.line_data.set_dirty();
+    }
+
+    /// Show or hide details of the line. Returns whether the line's contents changed.
+    pub(crate) fn set_expanded(&mut self, expanded: bool) -> bool {
+        if self.details.is_none() || self.expanded == expanded {
+            return false;
+        }
+        self.expanded = expanded;
+        self.line_data.set_dirty();
+        true
+    }
+
+    /// Segments to render: the message segments, followed by the details when expanded.
+    fn visible_segments<'a>(
+        segments: &'a [StyledString],
+        current_seg: &'a StyledString,
+        details: Option<&'a StyledString>,
+        expanded: bool,
+    ) -> impl Iterator<Item = &'a StyledString> {
+        segments
+            .iter()
+            .chain(std::iter::once(current_seg))
+            .chain(details.filter(|_| expanded))
+    }
+
     /// Calculates the number of lines that this line will be.
     /// The calculation is only done if the line_data is dirty or the window is resized.
     pub(crate) fn rendered_height(&mut self, width: i32) -> i32 {
         let msg_padding = self.line_type().msg_padding();
         if self.line_data.is_dirty() || self.line_data.needs_resize(width, 0, msg_padding) {
             self.line_data = LineDataCache::msg_line(width, msg_padding);
-            let mut full_line = self
-                .segments
-                .iter()
-                .flat_map(|s| s.string.chars())
-                .chain(self.current_seg.string.chars());
+            let mut full_line = Self::visible_segments(
+                &self.segments,
+                &self.current_seg,
+                self.details.as_ref(),
+                self.expanded,
+            )
+            .flat_map(|s| s.string.chars());
             self.line_data.calculate_height(&mut full_line, 0);
         }
         self.line_data.get_line_count().unwrap() as i32
     }
 
+    /// Visible text of the line, without the formatting.
+    fn text(&self) -> String {
+        Self::visible_segments(
+            &self.segments,
+            &self.current_seg,
+            self.details.as_ref(),
+            self.expanded,
+        )
+        .map(|s| s.string.as_str())
+        .collect()
+    }
+
+    /// Char index ranges of the non-empty matches of `regex` in the visible text of the line.
+    fn match_ranges(&self, regex: &Regex) -> Vec<Range<usize>> {
+        let text = self.text();
+        regex
+            .find_iter(&text)
+            .filter(|m| !m.is_empty())
+            .map(|m| {
+                let start = text[..m.start()].chars().count();
+                start..start + m.as_str().chars().count()
+            })
+            .collect()
+    }
+
+    /// Find the links in the message segments, to underline them when drawing. Called when the
+    /// line is added to the message area.
+    pub(crate) fn detect_urls(&mut self) {
+        let text: String = self
+            .segments
+            .iter()
+            .chain(std::iter::once(&self.current_seg))
+            .map(|s| s.string.as_str())
+            .collect();
+        self.urls = find_urls(&text)
+            .into_iter()
+            .map(|range| {
+                let start = text[..range.start].chars().count();
+                let url = &text[range];
+                (start..start + url.chars().count(), url.to_owned())
+            })
+            .collect();
+    }
+
+    /// Links in the line, in the order they appear.
+    pub(crate) fn urls(&self) -> impl DoubleEndedIterator<Item = &str> {
+        self.urls.iter().map(|(_, url)| url.as_str())
+    }
+
+    /// Find the matches of `regex` in the visible text of the line, to highlight them when
+    /// drawing. `None` clears the matches. Returns whether the line matches.
+    pub(crate) fn set_search(&mut self, regex: Option<&Regex>) -> bool {
+        self.search_matches = regex
+            .map(|regex| self.match_ranges(regex))
+            .unwrap_or_default();
+        !self.search_matches.is_empty()
+    }
+
+    /// Draw the line. Matches of the search are highlighted, links are underlined.
     pub(crate) fn draw(
         &self,
         tb: &mut Termbox,
@@ -207,11 +322,12 @@ impl Line {
         let mut char_idx = 0;
         let mut split_indices_iter = self.line_data.get_splits().iter().copied().peekable();
 
-        for seg in self
-            .segments
-            .iter()
-            .chain(std::iter::once(&self.current_seg))
-        {
+        for seg in Self::visible_segments(
+            &self.segments,
+            &self.current_seg,
+            self.details.as_ref(),
+            self.expanded,
+        ) {
             let sty = seg.style(colors);
             for c in seg.string.chars() {
                 // If split_indices_iter yields we already know the indices for the start of each line. If it
@@ -237,7 +353,27 @@ impl Line {
                 }
                 // Write out the character
                 if line_num >= first_line {
+                    let mut sty = if self
+                        .search_matches
+                        .iter()
+                        .any(|range| range.contains(&char_idx))
+                    {
+                        colors.completion
+                    } else {
+                        sty
+                    };
+                    let url = self
+                        .urls
+                        .iter()
+                        .find(|(range, _)| range.contains(&char_idx))
+                        .map(|(_, url)| url);
+                    if url.is_some() {
+                        sty.fg |= termbox_simple::TB_UNDERLINE;
+                    }
                     tb.change_cell(col, pos_y + line_num, c, sty.fg, sty.bg);
+                    if let Some(url) = url {
+                        tb.set_cell_link(col, pos_y + line_num, url);
+                    }
                 }
                 col += 1;
                 char_idx += 1;
@@ -1,5 +1,6 @@
 pub(crate) mod line;
 
+use regex::Regex;
 use std::collections::VecDeque;
 use std::{cmp::max, mem, str};
 use termbox_simple::Termbox;
@@ -7,7 +8,7 @@ use termbox_simple::Termbox;
 pub(crate) use self::line::{Line, SegStyle};
 use crate::config::Colors;
 use crate::line_split::LineType;
-use crate::messaging::{MSG_NICK_SUFFIX_LEN, Timestamp};
+use crate::messaging::MSG_NICK_SUFFIX_LEN;
 
 pub(crate) struct MsgArea {
     lines: VecDeque<Line>,
@@ -29,6 +30,66 @@ pub(crate) struct MsgArea {
     lines_height: Option<i32>,
 
     layout: Layout,
+
+    /// Width of the timestamp column in aligned layout.
+    timestamp_width: usize,
+
+    /// Whether to show details of summary lines (e.g. nicks in a netsplit).
+    show_details: bool,
+
+    search: Option<Search>,
+
+    /// Index of the first line that was not visible when the tab was last left. A "new messages"
+    /// line is drawn above it. The line may not be added yet, the marker is shown when it's
+    /// added.
+    unread_marker: Option<usize>,
+}
+
+/// A search in the lines. Matches are highlighted when drawing.
+struct Search {
+    regex: Regex,
+
+    /// Indices of the matching lines in `lines`, in increasing order. Updated as lines are added,
+    /// removed, and modified.
+    hits: VecDeque<usize>,
+
+    /// Index of the line of the current hit in `lines`.
+    current: Option<usize>,
+}
+
+impl Search {
+    /// Index of the last matching line before `end`.
+    fn find_older(&self, end: usize) -> Option<usize> {
+        let idx = self.hits.partition_point(|hit| *hit < end);
+        idx.checked_sub(1).map(|idx| self.hits[idx])
+    }
+
+    /// Index of the first matching line at or after `start`.
+    fn find_newer(&self, start: usize) -> Option<usize> {
+        let idx = self.hits.partition_point(|hit| *hit < start);
+        self.hits.get(idx).copied()
+    }
+
+    /// Update `hits` after the line at `idx` is modified.
+    fn update_hit(&mut self, idx: usize, matches: bool) {
+        match (self.hits.binary_search(&idx), matches) {
+            (Ok(pos), false) => {
+                self.hits.remove(pos);
+            }
+            (Err(pos), true) => self.hits.insert(pos, idx),
+            _ => {}
+        }
+    }
+}
+
+/// Update search matches of `lines` for `regex`, or clear them when `regex` is `None`. Returns the
+/// indices of the matching lines.
+fn search_lines(lines: &mut VecDeque<Line>, regex: Option<&Regex>) -> VecDeque<usize> {
+    lines
+        .iter_mut()
+        .enumerate()
+        .filter_map(|(idx, line)| line.set_search(regex).then_some(idx))
+        .collect()
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -42,18 +103,24 @@ impl Layout {
         matches!(self, Layout::Aligned { .. })
     }
 
-    fn msg_padding(&self) -> usize {
+    fn msg_padding(&self, timestamp_width: usize) -> usize {
         match self {
             Layout::Compact => 0,
             Layout::Aligned { max_nick_len } => {
-                Timestamp::WIDTH + max_nick_len + MSG_NICK_SUFFIX_LEN
+                timestamp_width + max_nick_len + MSG_NICK_SUFFIX_LEN
             }
         }
     }
 }
 
 impl MsgArea {
-    pub(crate) fn new(width: i32, height: i32, scrollback: usize, layout: Layout) -> MsgArea {
+    pub(crate) fn new(
+        width: i32,
+        height: i32,
+        scrollback: usize,
+        layout: Layout,
+        timestamp_width: usize,
+    ) -> MsgArea {
         MsgArea {
             lines: VecDeque::with_capacity(512.min(scrollback)),
             scrollback,
@@ -63,6 +130,10 @@ impl MsgArea {
             line_buf: Line::new(),
             lines_height: Some(0),
             layout,
+            timestamp_width,
+            show_details: false,
+            search: None,
+            unread_marker: None,
         }
     }
 
@@ -89,9 +160,14 @@ impl MsgArea {
         self.layout
     }
 
+    /// Set width of the timestamp column. Only affects lines added after this call.
+    pub(crate) fn set_timestamp_width(&mut self, timestamp_width: usize) {
+        self.timestamp_width = timestamp_width;
+    }
+
     /// Used to force a line to be aligned.
     pub(crate) fn set_current_line_alignment(&mut self) {
-        let msg_padding = self.layout.msg_padding();
+        let msg_padding = self.layout.msg_padding(self.timestamp_width);
         self.line_buf.set_type(LineType::AlignedMsg { msg_padding });
     }
 
@@ -102,6 +178,8 @@ impl MsgArea {
         // How many visible lines to skip
         let mut skip = self.scroll;
 
+        let unread_marker = self.shown_unread_marker();
+
         // Draw lines in reverse order
         let mut line_idx = (self.lines.len() as i32) - 1;
         while line_idx >= 0 && row >= pos_y {
@@ -111,28 +189,44 @@ impl MsgArea {
 
             if skip >= line_height {
                 // skip the whole line
-                line_idx -= 1;
                 skip -= line_height;
-                continue;
-            }
+            } else {
+                // Rendered line height
+                let height = line_height - skip;
 
-            // Rendered line height
-            let height = line_height - skip;
+                // Where to start rendering this line?
+                let line_row = row - height + 1;
 
-            // Where to start rendering this line?
-            let line_row = row - height + 1;
+                // How many lines to skip in the `Line` before rendering
+                let render_from = max(0, pos_y - line_row);
 
-            // How many lines to skip in the `Line` before rendering
-            let render_from = max(0, pos_y - line_row);
+                line.draw(tb, colors, pos_x, line_row, render_from, height);
+                row = line_row - 1;
+                skip = 0;
+            }
+
+            // The unread marker takes one line above the first unread line
+            if unread_marker == Some(line_idx as usize) {
+                if skip > 0 {
+                    skip -= 1;
+                } else {
+                    if row >= pos_y {
+                        self.draw_unread_marker(tb, colors, pos_x, row);
+                    }
+                    row -= 1;
+                }
+            }
 
-            line.draw(tb, colors, pos_x, line_row, render_from, height);
-            row = line_row - 1;
             line_idx -= 1;
-            skip = 0;
+        }
+    }
 
-            if line_row < pos_y {
-                break;
-            }
+    fn draw_unread_marker(&self, tb: &mut Termbox, colors: &Colors, pos_x: i32, pos_y: i32) {
+        let style = colors.highlight;
+        let text = "── new messages ";
+        let chars = text.chars().chain(std::iter::repeat('─'));
+        for (col, c) in (pos_x..pos_x + self.width).zip(chars) {
+            tb.change_cell(col, pos_y, c, style.fg, style.bg);
         }
     }
 }
@@ -150,6 +244,9 @@ impl MsgArea {
                 for line in &mut self.lines {
                     total_height += line.rendered_height(self.width);
                 }
+                if self.shown_unread_marker().is_some() {
+                    total_height += 1;
+                }
                 self.lines_height = Some(total_height);
                 total_height
             }
@@ -166,6 +263,7 @@ impl MsgArea {
         if self.scroll > 0 {
             self.scroll -= 1;
         }
+        self.clear_passed_unread_marker();
     }
 
     pub(crate) fn scroll_top(&mut self) {
@@ -174,6 +272,7 @@ impl MsgArea {
 
     pub(crate) fn scroll_bottom(&mut self) {
         self.scroll = 0;
+        self.clear_passed_unread_marker();
     }
 
     pub(crate) fn page_up(&mut self) {
@@ -184,6 +283,7 @@ impl MsgArea {
 
     pub(crate) fn page_down(&mut self) {
         self.scroll = max(0, self.scroll - 10);
+        self.clear_passed_unread_marker();
     }
 
     /// Recalculate the scroll offset due to resizing of the window
@@ -196,6 +296,213 @@ impl MsgArea {
     }
 }
 
+////////////////////////////////////////////////////////////////////////////////
+// Searching
+
+impl MsgArea {
+    /// Start a new search, or update the current one. Jumps to the first hit at or above the
+    /// current hit, or to the last hit when there isn't one. `None` clears the search, without
+    /// scrolling.
+    pub(crate) fn set_search(&mut self, regex: Option<Regex>) {
+        let regex = match regex {
+            None => {
+                if self.search.take().is_some() {
+                    search_lines(&mut self.lines, None);
+                }
+                return;
+            }
+            Some(regex) => regex,
+        };
+        let current = self.search.as_ref().and_then(|search| search.current);
+        let mut search = Search {
+            hits: search_lines(&mut self.lines, Some(&regex)),
+            regex,
+            current: None,
+        };
+        search.current = current
+            .and_then(|current| search.find_older(current + 1))
+            .or_else(|| search.find_older(self.lines.len()));
+        let hit = search.current;
+        self.search = Some(search);
+        if let Some(hit) = hit {
+            self.scroll_to_line(hit);
+        }
+    }
+
+    /// Clear the search and scroll to the bottom.
+    pub(crate) fn clear_search(&mut self) {
+        self.set_search(None);
+        self.scroll_bottom();
+    }
+
+    pub(crate) fn has_search(&self) -> bool {
+        self.search.is_some()
+    }
+
+    /// Jump to the previous (older) hit.
+    pub(crate) fn search_older(&mut self) {
+        if let Some(search) = &self.search {
+            let start = search.current.unwrap_or(self.lines.len());
+            if let Some(hit) = search.find_older(start) {
+                self.jump_to_hit(hit);
+            }
+        }
+    }
+
+    /// Jump to the next (newer) hit.
+    pub(crate) fn search_newer(&mut self) {
+        if let Some(search) = &self.search
+            && let Some(current) = search.current
+            && let Some(hit) = search.find_newer(current + 1)
+        {
+            self.jump_to_hit(hit);
+        }
+    }
+
+    /// Number of the current hit, counting from the last line, and the total number of hits.
+    pub(crate) fn search_status(&self) -> Option<(usize, usize)> {
+        let search = self.search.as_ref()?;
+        let hits = &search.hits;
+        let current = match search.current {
+            Some(current) => hits.len() - hits.partition_point(|hit| *hit < current),
+            None => 0,
+        };
+        Some((current, hits.len()))
+    }
+
+    fn jump_to_hit(&mut self, hit: usize) {
+        if let Some(search) = &mut self.search {
+            search.current = Some(hit);
+        }
+        self.scroll_to_line(hit);
+    }
+
+    /// Scroll to show the line at the given index at the bottom of the area, or its first
+    /// rendered line at the top when the line doesn't fit.
+    fn scroll_to_line(&mut self, idx: usize) {
+        let mut below = 0;
+        for line in self.lines.iter_mut().skip(idx + 1) {
+            below += line.rendered_height(self.width);
+        }
+        if self
+            .shown_unread_marker()
+            .is_some_and(|marker| marker > idx)
+        {
+            below += 1;
+        }
+        let line_height = self.lines[idx].rendered_height(self.width);
+        let max_scroll = max(0, self.update_total_visible_lines() - self.height);
+        self.scroll = (below + max(0, line_height - self.height)).min(max_scroll);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Unread marker
+
+impl MsgArea {
+    /// Index of the line the unread marker is drawn above, when the line is added.
+    fn shown_unread_marker(&self) -> Option<usize> {
+        self.unread_marker
+            .filter(|marker| *marker < self.lines.len())
+    }
+
+    /// Number of rendered lines below the unread marker, i.e. distance of the marker from the
+    /// bottom of the area when not scrolled.
+    fn unread_marker_offset(&mut self, marker: usize) -> i32 {
+        let mut offset = 0;
+        for line in self.lines.iter_mut().skip(marker) {
+            offset += line.rendered_height(self.width);
+        }
+        offset
+    }
+
+    /// Move the unread marker after the last visible line. Call when leaving the tab.
+    pub(crate) fn set_unread_marker(&mut self) {
+        self.clear_unread_marker();
+
+        // Find the last visible line
+        let mut skip = self.scroll;
+        let mut marker = self.lines.len();
+        while marker > 0 {
+            let line_height = self.lines[marker - 1].rendered_height(self.width);
+            if skip < line_height {
+                break;
+            }
+            skip -= line_height;
+            marker -= 1;
+        }
+        self.unread_marker = Some(marker);
+
+        if marker < self.lines.len() {
+            // The marker is added below the visible lines, scroll to keep them visible
+            self.scroll += 1;
+            if let Some(total_height) = &mut self.lines_height {
+                *total_height += 1;
+            }
+        }
+    }
+
+    pub(crate) fn clear_unread_marker(&mut self) {
+        if let Some(marker) = self.shown_unread_marker() {
+            if self.unread_marker_offset(marker) < self.scroll {
+                // The marker is below the visible lines, scroll to keep them visible
+                self.scroll -= 1;
+            }
+            if let Some(total_height) = &mut self.lines_height {
+                *total_height -= 1;
+            }
+        }
+        self.unread_marker = None;
+    }
+
+    /// Clear the unread marker if no lines were added after it. Call when the tab is shown, lines
+    /// added to a shown tab are read.
+    pub(crate) fn clear_pending_unread_marker(&mut self) {
+        if self.shown_unread_marker().is_none() {
+            self.unread_marker = None;
+        }
+    }
+
+    /// Clear the unread marker if it's scrolled past, i.e. it's above the visible lines.
+    fn clear_passed_unread_marker(&mut self) {
+        if let Some(marker) = self.shown_unread_marker()
+            && self.unread_marker_offset(marker) >= self.scroll + self.height
+        {
+            self.clear_unread_marker();
+        }
+    }
+
+    /// Scroll to show the unread marker at the top of the area. Returns whether there's a
+    /// marker to show.
+    pub(crate) fn scroll_to_unread_marker(&mut self) -> bool {
+        match self.shown_unread_marker() {
+            None => false,
+            Some(marker) => {
+                let offset = self.unread_marker_offset(marker);
+                let max_scroll = max(0, self.update_total_visible_lines() - self.height);
+                self.scroll = max(0, offset + 1 - self.height).min(max_scroll);
+                true
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Links
+
+impl MsgArea {
+    /// Links in the lines, most recent first. Each link is listed once.
+    pub(crate) fn urls(&self) -> Vec<String> {
+        let mut urls: Vec<String> = vec![];
+        for url in self.lines.iter().rev().flat_map(|line| line.urls().rev()) {
+            if !urls.iter().any(|url_| url_ == url) {
+                urls.push(url.to_owned());
+            }
+        }
+        urls
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // Adding/removing text
 impl MsgArea {
@@ -203,16 +510,68 @@ impl MsgArea {
         self.line_buf.add_text(str, style);
     }
 
+    /// Set details of the current line. See `Line::set_details`.
+    pub(crate) fn set_details(&mut self, details: String, style: SegStyle) {
+        self.line_buf.set_details(details, style);
+        self.line_buf.set_expanded(self.show_details);
+    }
+
+    /// Show or hide details of summary lines.
+    pub(crate) fn toggle_details(&mut self) {
+        self.show_details = !self.show_details;
+        let mut changed = false;
+        for line in &mut self.lines {
+            changed |= line.set_expanded(self.show_details);
+        }
+        if changed && let Some(search) = &mut self.search {
+            // Details are searched when shown
+            search.hits = search_lines(&mut self.lines, Some(&search.regex));
+        }
+        if changed {
+            let old_total_lines = self.update_total_visible_lines();
+            self.lines_height = None;
+            self.recalculate_scroll(self.height, old_total_lines);
+        }
+    }
+
     pub(crate) fn flush_line(&mut self) -> usize {
-        let line_height = self.line_buf.rendered_height(self.width);
+        self.line_buf.detect_urls();
+        let mut line_height = self.line_buf.rendered_height(self.width);
         // Check if we're about to overflow
         let mut removed_line_height = 0;
         if self.lines.len() == self.scrollback {
             // Remove oldest line
             if let Some(mut removed) = self.lines.pop_front() {
                 removed_line_height = removed.rendered_height(self.width);
+                if let Some(search) = &mut self.search {
+                    search.current = search.current.and_then(|current| current.checked_sub(1));
+                    if search.hits.front() == Some(&0) {
+                        search.hits.pop_front();
+                    }
+                    for hit in &mut search.hits {
+                        *hit -= 1;
+                    }
+                }
+                // The unread marker is removed with its line
+                match self.unread_marker {
+                    Some(0) => {
+                        self.unread_marker = None;
+                        removed_line_height += 1;
+                    }
+                    Some(marker) => self.unread_marker = Some(marker - 1),
+                    None => {}
+                }
             }
         }
+        if let Some(search) = &mut self.search
+            && self.line_buf.set_search(Some(&search.regex))
+        {
+            search.hits.push_back(self.lines.len());
+        }
+        if self.unread_marker == Some(self.lines.len()) {
+            // The new line is the first unread line, the marker is shown above it
+            line_height += 1;
+        }
         self.lines
             .push_back(mem::replace(&mut self.line_buf, Line::new()));
         if self.scroll != 0 {
@@ -228,11 +587,21 @@ impl MsgArea {
     where
         F: Fn(&mut Line),
     {
-        f(&mut self.lines[idx]);
+        let line = &mut self.lines[idx];
+        f(line);
+        if let Some(search) = &mut self.search {
+            let matches = line.set_search(Some(&search.regex));
+            search.update_hit(idx, matches);
+        }
     }
 
     pub(crate) fn clear(&mut self) {
         self.lines.clear();
+        self.unread_marker = None;
+        if let Some(search) = &mut self.search {
+            search.hits.clear();
+            search.current = None;
+        }
         self.scroll = 0;
         self.lines_height = Some(0);
     }
@@ -245,7 +614,7 @@ mod tests {
 
     #[test]
     fn newline_scrolling() {
-        let mut msg_area = MsgArea::new(100, 1, usize::MAX, Layout::Compact);
+        let mut msg_area = MsgArea::new(100, 1, usize::MAX, Layout::Compact, 6);
         // Adding a new line when scroll is 0 should not change it
         assert_eq!(msg_area.scroll, 0);
         msg_area.add_text("line1", SegStyle::UserMsg);
@@ -266,7 +635,7 @@ mod tests {
     #[test]
     fn test_max_lines() {
         // Can't show more than 3 lines.
-        let mut msg_area = MsgArea::new(100, 1, 3, Layout::Compact);
+        let mut msg_area = MsgArea::new(100, 1, 3, Layout::Compact, 6);
         msg_area.add_text("first", SegStyle::UserMsg);
         msg_area.flush_line();
         msg_area.add_text("second", SegStyle::UserMsg);
@@ -280,4 +649,52 @@ mod tests {
         assert_eq!(msg_area.lines.len(), 3);
         assert_eq!(msg_area.update_total_visible_lines(), 3);
     }
+
+    #[test]
+    fn toggle_details() {
+        let mut msg_area = MsgArea::new(10, 5, usize::MAX, Layout::Compact, 6);
+        msg_area.add_text("Netsplit", SegStyle::Part);
+        msg_area.set_details(": a, b, c, d, e".to_owned(), SegStyle::Faded);
+        msg_area.flush_line();
+        assert_eq!(msg_area.update_total_visible_lines(), 1);
+
+        msg_area.toggle_details();
+        assert_eq!(msg_area.update_total_visible_lines(), 3);
+
+        // New lines are expanded too
+        msg_area.add_text("Netsplit", SegStyle::Part);
+        msg_area.set_details(": a, b".to_owned(), SegStyle::Faded);
+        msg_area.flush_line();
+        assert_eq!(msg_area.update_total_visible_lines(), 5);
+
+        msg_area.toggle_details();
+        assert_eq!(msg_area.update_total_visible_lines(), 2);
+    }
+
+    #[test]
+    fn search_hits() {
+        let mut msg_area = MsgArea::new(100, 5, 3, Layout::Compact, 6);
+        for msg in ["foo", "bar", "foo"] {
+            msg_area.add_text(msg, SegStyle::UserMsg);
+            msg_area.flush_line();
+        }
+        msg_area.set_search(Some(Regex::new("foo").unwrap()));
+        assert_eq!(msg_area.search_status(), Some((1, 2)));
+
+        // New lines are searched, evicted lines are removed from the hits
+        msg_area.add_text("foo", SegStyle::UserMsg);
+        msg_area.flush_line();
+        assert_eq!(msg_area.search_status(), Some((2, 2)));
+        msg_area.search_newer();
+        assert_eq!(msg_area.search_status(), Some((1, 2)));
+        msg_area.search_older();
+        assert_eq!(msg_area.search_status(), Some((2, 2)));
+
+        // Modified lines are searched again
+        msg_area.modify_line(0, |line| line.add_text("foo", SegStyle::UserMsg));
+        assert_eq!(msg_area.search_status(), Some((2, 3)));
+
+        msg_area.clear();
+        assert_eq!(msg_area.search_status(), Some((0, 0)));
+    }
 }
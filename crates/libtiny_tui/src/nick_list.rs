@@ -0,0 +1,176 @@
+//! Nick list panel of channel tabs.
+
+use libtiny_wire::MEMBERSHIP_MODES;
+use termbox_simple::Termbox;
+use unicode_width::UnicodeWidthChar;
+
+use crate::config::Colors;
+
+/// Members of a channel, sorted by privilege then name.
+pub(crate) struct NickList {
+    members: Vec<Member>,
+
+    width: i32,
+    height: i32,
+
+    /// Index of the first member drawn.
+    scroll: usize,
+}
+
+struct Member {
+    nick: String,
+
+    /// Lowercase `nick`, for sorting.
+    sort_nick: String,
+
+    /// Membership prefixes, highest privilege first, e.g. "@+".
+    prefixes: String,
+}
+
+impl Member {
+    /// Index of the highest privilege of the member in `MEMBERSHIP_MODES`, or
+    /// `MEMBERSHIP_MODES.len()` if the member doesn't have a membership mode.
+    fn rank(&self) -> usize {
+        self.prefixes
+            .chars()
+            .next()
+            .and_then(|c| MEMBERSHIP_MODES.iter().position(|(_, prefix)| *prefix == c))
+            .unwrap_or(MEMBERSHIP_MODES.len())
+    }
+
+    fn sort_key(&self) -> (usize, &str) {
+        (self.rank(), &self.sort_nick)
+    }
+}
+
+impl NickList {
+    pub(crate) fn new() -> NickList {
+        NickList {
+            members: vec![],
+            width: 0,
+            height: 0,
+            scroll: 0,
+        }
+    }
+
+    pub(crate) fn resize(&mut self, width: i32, height: i32) {
+        self.width = width;
+        self.height = height;
+        self.scroll = self.scroll.min(self.max_scroll());
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.members.clear();
+        self.scroll = 0;
+    }
+
+    pub(crate) fn insert(&mut self, nick: &str) {
+        if self.find(nick).is_none() {
+            self.insert_member(Member {
+                nick: nick.to_owned(),
+                sort_nick: nick.to_lowercase(),
+                prefixes: String::new(),
+            });
+        }
+    }
+
+    pub(crate) fn remove(&mut self, nick: &str) {
+        if let Some(idx) = self.find(nick) {
+            self.members.remove(idx);
+            self.scroll = self.scroll.min(self.max_scroll());
+        }
+    }
+
+    pub(crate) fn rename(&mut self, old_nick: &str, new_nick: &str) {
+        if let Some(idx) = self.find(old_nick) {
+            let mut member = self.members.remove(idx);
+            member.nick = new_nick.to_owned();
+            member.sort_nick = new_nick.to_lowercase();
+            self.insert_member(member);
+        }
+    }
+
+    /// Add or remove a membership prefix (e.g. `@`) of a nick.
+    pub(crate) fn update_prefix(&mut self, nick: &str, prefix: char, add: bool) {
+        if let Some(idx) = self.find(nick) {
+            let mut member = self.members.remove(idx);
+            let mut prefixes: Vec<char> =
+                member.prefixes.chars().filter(|c| *c != prefix).collect();
+            if add {
+                prefixes.push(prefix);
+            }
+            member.prefixes = MEMBERSHIP_MODES
+                .iter()
+                .map(|(_, prefix)| *prefix)
+                .filter(|prefix| prefixes.contains(prefix))
+                .collect();
+            self.insert_member(member);
+        }
+    }
+
+    pub(crate) fn scroll_up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    pub(crate) fn scroll_down(&mut self) {
+        self.scroll = (self.scroll + 1).min(self.max_scroll());
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.members
+            .len()
+            .saturating_sub(self.height.max(0) as usize)
+    }
+
+    fn find(&self, nick: &str) -> Option<usize> {
+        self.members.iter().position(|member| member.nick == nick)
+    }
+
+    fn insert_member(&mut self, member: Member) {
+        let idx = self
+            .members
+            .partition_point(|member_| member_.sort_key() < member.sort_key());
+        self.members.insert(idx, member);
+    }
+
+    /// Draw the panel with a separator line on the left. `nick_color` is the index of the color
+    /// of a nick in `Colors::nick`.
+    pub(crate) fn draw<F>(
+        &self,
+        tb: &mut Termbox,
+        colors: &Colors,
+        pos_x: i32,
+        pos_y: i32,
+        nick_color: F,
+    ) where
+        F: Fn(&str) -> usize,
+    {
+        for row in 0..self.height {
+            tb.change_cell(pos_x, pos_y + row, '│', colors.faded.fg, colors.faded.bg);
+        }
+
+        let max_x = pos_x + self.width;
+        for (row, member) in self
+            .members
+            .iter()
+            .skip(self.scroll)
+            .take(self.height.max(0) as usize)
+            .enumerate()
+        {
+            let y = pos_y + row as i32;
+            let prefix = member.prefixes.chars().next().unwrap_or(' ');
+            tb.change_cell(pos_x + 1, y, prefix, colors.user_msg.fg, colors.user_msg.bg);
+
+            let fg = u16::from(colors.nick[nick_color(&member.nick) % colors.nick.len()]);
+            let mut x = pos_x + 2;
+            for c in member.nick.chars() {
+                let width = c.width().unwrap_or(0) as i32;
+                if x + width > max_x {
+                    break;
+                }
+                tb.change_cell(x, y, c, fg, colors.user_msg.bg);
+                x += width;
+            }
+        }
+    }
+}
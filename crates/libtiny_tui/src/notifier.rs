@@ -49,6 +49,8 @@ impl Notifier {
         }
     }
 
+    /// Notify a message. With `Mentions`, channel and private messages are only notified when
+    /// `mention` is set, i.e. when they're highlights.
     pub(crate) fn notify_privmsg(
         &mut self,
         sender: &str,
@@ -64,18 +66,22 @@ impl Notifier {
         let msg = remove_irc_control_chars(msg);
 
         match *target {
-            MsgTarget::Chan { chan, .. } => {
-                if *self == Notifier::Messages || (*self == Notifier::Mentions && mention) {
-                    notify(&format!("{} in {}", sender, chan.display()), &msg)
-                }
+            MsgTarget::Chan { chan, .. }
+                if *self == Notifier::Messages || (*self == Notifier::Mentions && mention) =>
+            {
+                notify(&format!("{} in {}", sender, chan.display()), &msg)
             }
             MsgTarget::User {
                 nick: ref nick_sender,
                 ..
-            } => {
-                if *self != Notifier::Off {
-                    notify(&format!("{nick_sender} sent a private message"), &msg)
-                }
+            } if *self == Notifier::Messages || (*self == Notifier::Mentions && mention) => {
+                notify(&format!("{nick_sender} sent a private message"), &msg)
+            }
+            MsgTarget::DccChat {
+                nick: ref nick_sender,
+                ..
+            } if *self != Notifier::Off => {
+                notify(&format!("{nick_sender} sent a DCC CHAT message"), &msg)
             }
             _ => {}
         }
@@ -0,0 +1,106 @@
+//! Incremental search prompt, shown in place of the input field.
+
+use regex::{Regex, RegexBuilder};
+use termbox_simple::Termbox;
+use unicode_width::UnicodeWidthChar;
+
+use crate::config::Colors;
+use crate::key_map::KeyAction;
+use crate::widget::WidgetRet;
+
+/// Make a regex for a search query. A query in slashes (`/regex/`) is a regex, anything else is
+/// searched as plain text, case insensitive.
+pub(crate) fn search_regex(query: &str) -> Result<Regex, regex::Error> {
+    match query
+        .strip_prefix('/')
+        .and_then(|query| query.strip_suffix('/'))
+    {
+        Some(regex) => Regex::new(regex),
+        None => RegexBuilder::new(&regex::escape(query))
+            .case_insensitive(true)
+            .build(),
+    }
+}
+
+pub(crate) struct SearchPrompt {
+    width: i32,
+    query: String,
+}
+
+impl SearchPrompt {
+    pub(crate) fn new(width: i32) -> SearchPrompt {
+        SearchPrompt {
+            width,
+            query: String::new(),
+        }
+    }
+
+    pub(crate) fn resize(&mut self, width: i32) {
+        self.width = width;
+    }
+
+    pub(crate) fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Handles the keys that edit the query. Returns `KeyHandled` when the query is updated.
+    pub(crate) fn keypressed(&mut self, key_action: &KeyAction) -> WidgetRet {
+        match key_action {
+            KeyAction::Input(c) => self.query.push(*c),
+            KeyAction::InputDeletePrevChar => {
+                self.query.pop();
+            }
+            KeyAction::InputDeleteToStart => self.query.clear(),
+            KeyAction::InputDeletePrevWord => {
+                let trimmed = self.query.trim_end();
+                let word_start = trimmed.rfind(' ').map(|idx| idx + 1).unwrap_or(0);
+                self.query.truncate(word_start);
+            }
+            _ => return WidgetRet::KeyIgnored,
+        }
+        WidgetRet::KeyHandled
+    }
+
+    /// Draw the prompt. `status` is the number of the current hit and the total number of hits.
+    pub(crate) fn draw(
+        &self,
+        tb: &mut Termbox,
+        colors: &Colors,
+        pos_x: i32,
+        pos_y: i32,
+        status: Option<(usize, usize)>,
+    ) {
+        let style = colors.exit_dialogue;
+        let prompt = match status {
+            Some((current, total)) if !self.query.is_empty() => {
+                format!("search [{current}/{total}]: {}", self.query)
+            }
+            _ => format!("search: {}", self.query),
+        };
+
+        let max_x = pos_x + self.width;
+        let mut x = pos_x;
+        for c in prompt.chars() {
+            let width = c.width().unwrap_or(0) as i32;
+            if width == 0 {
+                continue;
+            }
+            if x + width > max_x {
+                break;
+            }
+            tb.change_cell(x, pos_y, c, style.fg, style.bg);
+            x += width;
+        }
+        let cursor_x = x;
+        while x < max_x {
+            tb.change_cell(x, pos_y, ' ', style.fg, style.bg);
+            x += 1;
+        }
+
+        if cursor_x < max_x {
+            tb.set_cursor(Some((cursor_x as u16, pos_y as u16)));
+        } else {
+            tb.hide_cursor();
+        }
+    }
+}
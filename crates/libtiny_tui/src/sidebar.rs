@@ -0,0 +1,194 @@
+//! Vertical tab list, shown instead of the tab bar when enabled in the config.
+
+use std::collections::HashSet;
+
+use libtiny_common::{MsgSource, TabStyle};
+use termbox_simple::Termbox;
+use unicode_width::UnicodeWidthStr;
+
+use crate::config::{Colors, SidebarPosition};
+use crate::split::Rect;
+use crate::tab::{Tab, tab_style};
+
+const EXPANDED: char = '▾';
+const COLLAPSED: char = '▸';
+
+pub(crate) struct Sidebar {
+    position: SidebarPosition,
+
+    /// Width of the tab list, not including the separator line.
+    width: i32,
+
+    /// Index of the first row drawn.
+    scroll: usize,
+
+    /// Active tab when the sidebar was last drawn. We scroll to the active tab when it changes.
+    last_active_idx: Option<usize>,
+
+    /// Names of the servers with collapsed channel and user tabs.
+    collapsed: HashSet<String>,
+}
+
+/// A row in the sidebar. Rows of collapsed servers have the unread messages and highlights of
+/// the hidden tabs.
+struct Row {
+    tab_idx: usize,
+    style: TabStyle,
+    unread: usize,
+    highlights: usize,
+}
+
+impl Sidebar {
+    pub(crate) fn new(position: SidebarPosition, width: i32) -> Sidebar {
+        Sidebar {
+            position,
+            width,
+            scroll: 0,
+            last_active_idx: None,
+            collapsed: HashSet::new(),
+        }
+    }
+
+    /// Update position and width after reloading the config.
+    pub(crate) fn set_config(&mut self, position: SidebarPosition, width: i32) {
+        self.position = position;
+        self.width = width;
+    }
+
+    /// Width of the sidebar, including the separator line.
+    pub(crate) fn width(&self) -> i32 {
+        self.width + 1
+    }
+
+    pub(crate) fn position(&self) -> SidebarPosition {
+        self.position
+    }
+
+    /// Collapse or expand tabs of a server.
+    pub(crate) fn toggle_collapse(&mut self, serv: &str) {
+        if !self.collapsed.remove(serv) {
+            self.collapsed.insert(serv.to_owned());
+        }
+    }
+
+    pub(crate) fn scroll_up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    /// Scroll down. The scroll is capped when drawing, as the number of rows can change.
+    pub(crate) fn scroll_down(&mut self) {
+        self.scroll += 1;
+    }
+
+    fn is_collapsed(&self, src: &MsgSource) -> bool {
+        self.collapsed.contains(src.serv_name())
+    }
+
+    /// Rows to draw. Tabs of collapsed servers are not shown, unless active.
+    fn rows(&self, tabs: &[Tab], active_idx: usize) -> Vec<Row> {
+        let mut rows: Vec<Row> = Vec::with_capacity(tabs.len());
+        // Index of the row of the current server in `rows`
+        let mut serv_row: Option<usize> = None;
+        for (tab_idx, tab) in tabs.iter().enumerate() {
+            let row = Row {
+                tab_idx,
+                style: tab.style,
+                unread: tab.unread,
+                highlights: tab.highlights,
+            };
+            if let MsgSource::Serv { .. } = tab.src {
+                serv_row = Some(rows.len());
+                rows.push(row);
+            } else if tab_idx != active_idx
+                && self.is_collapsed(&tab.src)
+                && let Some(serv_row) = serv_row
+            {
+                let serv_row = &mut rows[serv_row];
+                serv_row.style = serv_row.style.max(row.style);
+                serv_row.unread += row.unread;
+                serv_row.highlights += row.highlights;
+            } else {
+                rows.push(row);
+            }
+        }
+        rows
+    }
+
+    /// Draw the sidebar in `rect`, which includes the separator line.
+    pub(crate) fn draw(
+        &mut self,
+        tb: &mut Termbox,
+        colors: &Colors,
+        tabs: &[Tab],
+        active_idx: usize,
+        rect: Rect,
+    ) {
+        let (list_x, separator_x) = match self.position {
+            SidebarPosition::Left => (rect.x, rect.x + self.width),
+            SidebarPosition::Right => (rect.x + 1, rect.x),
+        };
+        for y in rect.y..rect.y + rect.height {
+            tb.change_cell(separator_x, y, '│', colors.faded.fg, colors.faded.bg);
+        }
+
+        let rows = self.rows(tabs, active_idx);
+        let height = rect.height.max(0) as usize;
+
+        // Scroll to the active tab when it changes, cap the scroll otherwise
+        if self.last_active_idx != Some(active_idx) {
+            self.last_active_idx = Some(active_idx);
+            if let Some(active_row) = rows.iter().position(|row| row.tab_idx == active_idx) {
+                if active_row < self.scroll {
+                    self.scroll = active_row;
+                } else if height > 0 && active_row >= self.scroll + height {
+                    self.scroll = active_row + 1 - height;
+                }
+            }
+        }
+        self.scroll = self.scroll.min(rows.len().saturating_sub(height));
+
+        let max_x = list_x + self.width;
+        for (row_idx, row) in rows.iter().skip(self.scroll).take(height).enumerate() {
+            let y = rect.y + row_idx as i32;
+            let tab = &tabs[row.tab_idx];
+            let style = if row.tab_idx == active_idx {
+                colors.tab_active
+            } else {
+                tab_style(row.style, colors)
+            };
+
+            let is_serv = matches!(tab.src, MsgSource::Serv { .. });
+            let has_tabs = is_serv
+                && tabs
+                    .get(row.tab_idx + 1)
+                    .is_some_and(|next| !matches!(next.src, MsgSource::Serv { .. }));
+            let marker = if !has_tabs {
+                ' '
+            } else if self.is_collapsed(&tab.src) {
+                COLLAPSED
+            } else {
+                EXPANDED
+            };
+            tb.change_cell(list_x, y, marker, colors.faded.fg, colors.faded.bg);
+            let name_x = if is_serv { list_x + 1 } else { list_x + 2 };
+
+            let counts = match (row.unread, row.highlights) {
+                (0, _) => String::new(),
+                (unread, 0) => format!(" {unread}"),
+                (unread, highlights) => format!(" {unread} !{highlights}"),
+            };
+            let counts_x = max_x - counts.width() as i32;
+            tab.draw_name(tb, name_x, y, counts_x, style);
+            let counts_style = if row.highlights > 0 {
+                colors.tab_highlight
+            } else {
+                colors.faded
+            };
+            for (x, c) in (counts_x..).zip(counts.chars()) {
+                if x >= name_x {
+                    tb.change_cell(x, y, c, counts_style.fg, counts_style.bg);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,433 @@
+//! Split windows: the message area can be split to show multiple tabs at once.
+
+/// Splits narrower than this are not shown.
+const MIN_SPLIT_WIDTH: i32 = 10;
+
+/// Splits shorter than this are not shown.
+const MIN_SPLIT_HEIGHT: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SplitDir {
+    /// Splits are stacked, with a horizontal separator line.
+    Horizontal,
+
+    /// Splits are side by side, with a vertical separator line.
+    Vertical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Rect {
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) width: i32,
+    pub(crate) height: i32,
+}
+
+/// A separator line between two splits.
+pub(crate) struct Separator {
+    pub(crate) dir: SplitDir,
+    pub(crate) rect: Rect,
+}
+
+/// A split, with the index of the tab it shows, and the area it's drawn in. `rect` is `None` when
+/// the split is not shown because the screen is too small.
+pub(crate) struct SplitRect {
+    pub(crate) tab_idx: usize,
+    pub(crate) rect: Option<Rect>,
+}
+
+#[derive(Debug)]
+enum Node {
+    /// A split showing a tab. `None` when the tab is closed.
+    Leaf(Option<usize>),
+
+    /// Two nodes, splitting the area in half.
+    Split {
+        dir: SplitDir,
+        first: Box<Node>,
+        second: Box<Node>,
+    },
+}
+
+impl Node {
+    fn num_leaves(&self) -> usize {
+        match self {
+            Node::Leaf(_) => 1,
+            Node::Split { first, second, .. } => first.num_leaves() + second.num_leaves(),
+        }
+    }
+
+    fn leaves(&self, leaves: &mut Vec<Option<usize>>) {
+        match self {
+            Node::Leaf(tab_idx) => leaves.push(*tab_idx),
+            Node::Split { first, second, .. } => {
+                first.leaves(leaves);
+                second.leaves(leaves);
+            }
+        }
+    }
+
+    fn leaves_mut<'a>(&'a mut self, leaves: &mut Vec<&'a mut Option<usize>>) {
+        match self {
+            Node::Leaf(tab_idx) => leaves.push(tab_idx),
+            Node::Split { first, second, .. } => {
+                first.leaves_mut(leaves);
+                second.leaves_mut(leaves);
+            }
+        }
+    }
+
+    /// Replace the leaf with the given index with `f(leaf)`.
+    fn update_leaf<F>(self, idx: usize, f: F) -> Node
+    where
+        F: FnOnce(Node) -> Node,
+    {
+        match self {
+            Node::Leaf(_) => f(self),
+            Node::Split { dir, first, second } => {
+                let first_leaves = first.num_leaves();
+                if idx < first_leaves {
+                    Node::Split {
+                        dir,
+                        first: Box::new(first.update_leaf(idx, f)),
+                        second,
+                    }
+                } else {
+                    Node::Split {
+                        dir,
+                        first,
+                        second: Box::new(second.update_leaf(idx - first_leaves, f)),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Remove the leaf with the given index. Its sibling takes the place of its parent. Returns
+    /// `None` when the node is the leaf.
+    fn remove_leaf(self, idx: usize) -> Option<Node> {
+        match self {
+            Node::Leaf(_) => None,
+            Node::Split { dir, first, second } => {
+                let first_leaves = first.num_leaves();
+                if idx < first_leaves {
+                    match first.remove_leaf(idx) {
+                        None => Some(*second),
+                        Some(first) => Some(Node::Split {
+                            dir,
+                            first: Box::new(first),
+                            second,
+                        }),
+                    }
+                } else {
+                    match second.remove_leaf(idx - first_leaves) {
+                        None => Some(*first),
+                        Some(second) => Some(Node::Split {
+                            dir,
+                            first,
+                            second: Box::new(second),
+                        }),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Calculate areas of the leaves and separators in `rect`. When `rect` is too small to show
+    /// both nodes of a split only the one with the leaf `focus` (or the first one) is shown.
+    fn layout(
+        &self,
+        rect: Option<Rect>,
+        focus: Option<usize>,
+        leaves: &mut Vec<Option<Rect>>,
+        separators: &mut Vec<Separator>,
+    ) {
+        match self {
+            Node::Leaf(_) => leaves.push(rect),
+            Node::Split { dir, first, second } => {
+                let first_leaves = first.num_leaves();
+                let first_focus = focus.filter(|focus| *focus < first_leaves);
+                let second_focus = focus.and_then(|focus| focus.checked_sub(first_leaves));
+
+                let (first_rect, second_rect) = match rect {
+                    None => (None, None),
+                    Some(rect) => match split_rect(rect, *dir) {
+                        Some((first_rect, separator, second_rect)) => {
+                            separators.push(Separator {
+                                dir: *dir,
+                                rect: separator,
+                            });
+                            (Some(first_rect), Some(second_rect))
+                        }
+                        None if second_focus.is_some() => (None, Some(rect)),
+                        None => (Some(rect), None),
+                    },
+                };
+
+                first.layout(first_rect, first_focus, leaves, separators);
+                second.layout(second_rect, second_focus, leaves, separators);
+            }
+        }
+    }
+}
+
+/// Split `rect` in half, with a separator line in between. Returns `None` when the halves would
+/// be too small.
+fn split_rect(rect: Rect, dir: SplitDir) -> Option<(Rect, Rect, Rect)> {
+    match dir {
+        SplitDir::Horizontal => {
+            if rect.height < 2 * MIN_SPLIT_HEIGHT + 1 {
+                return None;
+            }
+            let first_height = (rect.height - 1) / 2;
+            let second_height = rect.height - first_height - 1;
+            Some((
+                Rect {
+                    height: first_height,
+                    ..rect
+                },
+                Rect {
+                    y: rect.y + first_height,
+                    height: 1,
+                    ..rect
+                },
+                Rect {
+                    y: rect.y + first_height + 1,
+                    height: second_height,
+                    ..rect
+                },
+            ))
+        }
+        SplitDir::Vertical => {
+            if rect.width < 2 * MIN_SPLIT_WIDTH + 1 {
+                return None;
+            }
+            let first_width = (rect.width - 1) / 2;
+            let second_width = rect.width - first_width - 1;
+            Some((
+                Rect {
+                    width: first_width,
+                    ..rect
+                },
+                Rect {
+                    x: rect.x + first_width,
+                    width: 1,
+                    ..rect
+                },
+                Rect {
+                    x: rect.x + first_width + 1,
+                    width: second_width,
+                    ..rect
+                },
+            ))
+        }
+    }
+}
+
+/// Layout of the splits. One of the splits is focused: it shows the active tab and gets the
+/// input.
+pub(crate) struct Splits {
+    root: Node,
+
+    /// Index of the focused split, in the order the splits are drawn (left to right, top to
+    /// bottom).
+    focus: usize,
+}
+
+impl Splits {
+    pub(crate) fn new() -> Splits {
+        Splits {
+            root: Node::Leaf(None),
+            focus: 0,
+        }
+    }
+
+    pub(crate) fn is_split(&self) -> bool {
+        matches!(self.root, Node::Split { .. })
+    }
+
+    /// Tabs shown in the splits, in the order the splits are drawn.
+    fn tabs(&self) -> Vec<Option<usize>> {
+        let mut tabs = Vec::with_capacity(self.root.num_leaves());
+        self.root.leaves(&mut tabs);
+        tabs
+    }
+
+    /// Whether the tab is shown in a split.
+    pub(crate) fn shows_tab(&self, tab_idx: usize) -> bool {
+        self.tabs().contains(&Some(tab_idx))
+    }
+
+    /// Update the focused split to show the active tab. When the active tab is shown in another
+    /// split, that split gets the tab the focused split showed before. Splits of closed tabs are
+    /// closed.
+    pub(crate) fn set_active_tab(&mut self, active_idx: usize) {
+        let mut leaves = vec![];
+        self.root.leaves_mut(&mut leaves);
+        let old_tab = *leaves[self.focus];
+        for (leaf_idx, leaf) in leaves.into_iter().enumerate() {
+            if leaf_idx == self.focus {
+                *leaf = Some(active_idx);
+            } else if *leaf == Some(active_idx) {
+                *leaf = old_tab;
+            }
+        }
+
+        while let Some(closed_idx) = self.tabs().iter().position(Option::is_none) {
+            self.remove(closed_idx);
+        }
+    }
+
+    /// Update tab indices of the splits after tabs are added, removed, or moved. `f` maps the old
+    /// indices to the new ones, or to `None` if the tab is closed.
+    pub(crate) fn update_tabs<F>(&mut self, f: F)
+    where
+        F: Fn(usize) -> Option<usize>,
+    {
+        let mut leaves = vec![];
+        self.root.leaves_mut(&mut leaves);
+        for leaf in leaves {
+            *leaf = leaf.and_then(&f);
+        }
+    }
+
+    /// Split the focused split, showing the tab `tab_idx` in the new split. The new split is
+    /// focused.
+    pub(crate) fn split(&mut self, dir: SplitDir, tab_idx: usize) {
+        let root = std::mem::replace(&mut self.root, Node::Leaf(None));
+        self.root = root.update_leaf(self.focus, |leaf| Node::Split {
+            dir,
+            first: Box::new(leaf),
+            second: Box::new(Node::Leaf(Some(tab_idx))),
+        });
+        self.focus += 1;
+    }
+
+    /// Close the focused split. Returns the tab of the newly focused split, or `None` if the
+    /// focused split is the only one.
+    pub(crate) fn close(&mut self) -> Option<usize> {
+        if !self.is_split() {
+            return None;
+        }
+        self.remove(self.focus);
+        self.focused_tab()
+    }
+
+    /// Focus the next split. Returns the tab of the newly focused split.
+    pub(crate) fn focus_next(&mut self) -> Option<usize> {
+        self.focus = (self.focus + 1) % self.root.num_leaves();
+        self.focused_tab()
+    }
+
+    /// Focus the previous split. Returns the tab of the newly focused split.
+    pub(crate) fn focus_prev(&mut self) -> Option<usize> {
+        let num_leaves = self.root.num_leaves();
+        self.focus = (self.focus + num_leaves - 1) % num_leaves;
+        self.focused_tab()
+    }
+
+    fn focused_tab(&self) -> Option<usize> {
+        self.tabs()[self.focus]
+    }
+
+    fn remove(&mut self, leaf_idx: usize) {
+        let root = std::mem::replace(&mut self.root, Node::Leaf(None));
+        match root.remove_leaf(leaf_idx) {
+            Some(root) => {
+                self.root = root;
+                if self.focus >= leaf_idx {
+                    self.focus = self.focus.saturating_sub(1);
+                }
+            }
+            None => {
+                // Not reachable as we don't close the last split, but keep the leaf
+                self.root = Node::Leaf(None);
+                self.focus = 0;
+            }
+        }
+    }
+
+    /// Calculate areas of the splits and separators in `rect`.
+    pub(crate) fn layout(&self, rect: Rect) -> (Vec<SplitRect>, Vec<Separator>) {
+        let mut rects = vec![];
+        let mut separators = vec![];
+        self.root
+            .layout(Some(rect), Some(self.focus), &mut rects, &mut separators);
+        let splits = self
+            .tabs()
+            .into_iter()
+            .zip(rects)
+            .filter_map(|(tab_idx, rect)| tab_idx.map(|tab_idx| SplitRect { tab_idx, rect }))
+            .collect();
+        (splits, separators)
+    }
+}
+
+#[test]
+fn test_splits() {
+    let mut splits = Splits::new();
+    splits.set_active_tab(0);
+    splits.split(SplitDir::Vertical, 1);
+    splits.set_active_tab(1);
+    splits.split(SplitDir::Horizontal, 2);
+    splits.set_active_tab(2);
+    assert_eq!(splits.tabs(), vec![Some(0), Some(1), Some(2)]);
+
+    let (rects, separators) = splits.layout(Rect {
+        x: 0,
+        y: 0,
+        width: 41,
+        height: 11,
+    });
+    let rects: Vec<(usize, Option<Rect>)> = rects
+        .into_iter()
+        .map(|split| (split.tab_idx, split.rect))
+        .collect();
+    assert_eq!(
+        rects,
+        vec![
+            (
+                0,
+                Some(Rect {
+                    x: 0,
+                    y: 0,
+                    width: 20,
+                    height: 11
+                })
+            ),
+            (
+                1,
+                Some(Rect {
+                    x: 21,
+                    y: 0,
+                    width: 20,
+                    height: 5
+                })
+            ),
+            (
+                2,
+                Some(Rect {
+                    x: 21,
+                    y: 6,
+                    width: 20,
+                    height: 5
+                })
+            ),
+        ]
+    );
+    assert_eq!(separators.len(), 2);
+
+    // Switching to a tab shown in another split swaps the tabs
+    splits.set_active_tab(0);
+    assert_eq!(splits.tabs(), vec![Some(2), Some(1), Some(0)]);
+
+    // Splits of closed tabs are closed
+    splits.update_tabs(|tab_idx| tab_idx.checked_sub(1));
+    splits.set_active_tab(0);
+    assert_eq!(splits.tabs(), vec![Some(1), Some(0)]);
+
+    assert_eq!(splits.focus_next(), Some(1));
+    assert_eq!(splits.close(), Some(0));
+    assert!(!splits.is_split());
+    assert_eq!(splits.close(), None);
+}
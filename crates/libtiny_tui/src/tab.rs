@@ -15,9 +15,16 @@ pub(crate) struct Tab {
     pub(crate) style: TabStyle,
     /// Alt-character to use to switch to this tab.
     pub(crate) switch: Option<char>,
+    /// Number of messages since the tab was last shown.
+    pub(crate) unread: usize,
+    /// Number of highlights since the tab was last shown.
+    pub(crate) highlights: usize,
+    /// When the tab got its current style, as a counter incremented on every style change. Used
+    /// to find the tab that waited the longest among the tabs with the same style.
+    pub(crate) style_seq: u64,
 }
 
-fn tab_style(style: TabStyle, colors: &Colors) -> Style {
+pub(crate) fn tab_style(style: TabStyle, colors: &Colors) -> Style {
     match style {
         TabStyle::Normal => colors.tab_normal,
         TabStyle::JoinOrPart => colors.tab_joinpart,
@@ -33,6 +40,22 @@ impl Tab {
 
     pub(crate) fn set_style(&mut self, style: TabStyle) {
         self.style = style;
+        if style == TabStyle::Normal {
+            self.unread = 0;
+            self.highlights = 0;
+        }
+    }
+
+    /// Count a new message in the tab, with the tab style it causes.
+    pub(crate) fn count_msg(&mut self, style: TabStyle) {
+        match style {
+            TabStyle::Normal | TabStyle::JoinOrPart => {}
+            TabStyle::NewMsg => self.unread += 1,
+            TabStyle::Highlight => {
+                self.unread += 1;
+                self.highlights += 1;
+            }
+        }
     }
 
     pub(crate) fn update_source<F>(&mut self, f: &F)
@@ -50,7 +73,7 @@ impl Tab {
         &self,
         tb: &mut Termbox,
         colors: &Colors,
-        mut pos_x: i32,
+        pos_x: i32,
         pos_y: i32,
         active: bool,
     ) {
@@ -59,9 +82,23 @@ impl Tab {
         } else {
             tab_style(self.style, colors)
         };
+        self.draw_name(tb, pos_x, pos_y, i32::MAX, style);
+    }
 
+    /// Draw the tab name, truncated at `max_x` (exclusive).
+    pub(crate) fn draw_name(
+        &self,
+        tb: &mut Termbox,
+        mut pos_x: i32,
+        pos_y: i32,
+        max_x: i32,
+        style: Style,
+    ) {
         let mut switch_drawn = false;
         for ch in self.visible_name().chars() {
+            if pos_x >= max_x {
+                break;
+            }
             if Some(ch) == self.switch && !switch_drawn {
                 tb.change_cell(pos_x, pos_y, ch, style.fg | TB_UNDERLINE, style.bg);
                 switch_drawn = true;
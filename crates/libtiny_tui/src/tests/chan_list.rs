@@ -0,0 +1,78 @@
+use std::panic::Location;
+
+use libtiny_common::ChanNameRef;
+use term_input::{Event, Key};
+
+use crate::test_utils::expect_screen;
+use crate::tui::{TUI, TUIRet};
+
+use super::enter_string;
+
+#[test]
+fn test_chan_list() {
+    let mut tui = TUI::new_test(40, 5);
+    let serv = "irc.server_1.org";
+    tui.new_server_tab(serv, None);
+    tui.open_chan_list(serv, Some("#*"), Some(3));
+    tui.add_chan_list_item(serv, ChanNameRef::new("#tiny"), 12, "IRC client");
+    tui.add_chan_list_item(serv, ChanNameRef::new("#empty"), 2, "");
+    tui.add_chan_list_item(serv, ChanNameRef::new("&local"), 50, "");
+    tui.add_chan_list_item(
+        serv,
+        ChanNameRef::new("#rust"),
+        300,
+        "\x02Rust\x02 language",
+    );
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|  300 #rust Rust language               |
+         |   12 #tiny IRC client                  |
+         |                                        |
+         |1/2 channels (loading), by users filter:|
+         |mentions irc.server_1.org               |";
+    expect_screen(screen, &tui.get_front_buffer(), 40, 5, Location::caller());
+
+    // Sort by name, filter, select
+    tui.chan_list_done(serv);
+    tui.handle_input_event(Event::Key(Key::Tab), &mut None);
+    enter_string(&mut tui, "r");
+    tui.handle_input_event(Event::Key(Key::Arrow(term_input::Arrow::Down)), &mut None);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|  300 #rust Rust language               |
+         |   12 #tiny IRC client                  |
+         |                                        |
+         |2/2 channels, by name filter: r         |
+         |mentions irc.server_1.org               |";
+    expect_screen(screen, &tui.get_front_buffer(), 40, 5, Location::caller());
+
+    match tui.handle_input_event(Event::Key(Key::Char('\r')), &mut None) {
+        Some(TUIRet::KeyCommand { cmd, .. }) => assert_eq!(cmd, "join #tiny"),
+        ret => panic!("Unexpected return value: {ret:?}"),
+    }
+
+    // Esc closes the list
+    tui.handle_input_event(Event::Key(Key::Esc), &mut None);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|                                        |
+         |                                        |
+         |                                        |
+         |                                        |
+         |mentions irc.server_1.org               |";
+    expect_screen(screen, &tui.get_front_buffer(), 40, 5, Location::caller());
+
+    // Replies that arrive after the list is closed don't open it again
+    tui.open_chan_list(serv, Some("#*"), Some(3));
+    tui.handle_input_event(Event::Key(Key::Esc), &mut None);
+    tui.add_chan_list_item(serv, ChanNameRef::new("#late"), 10, "");
+    tui.chan_list_done(serv);
+    tui.draw();
+    expect_screen(screen, &tui.get_front_buffer(), 40, 5, Location::caller());
+}
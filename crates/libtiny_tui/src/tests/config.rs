@@ -12,6 +12,7 @@ fn parsing_tab_configs() {
               - name: "#tiny"
                 ignore: true
                 notify: "messages"
+                rejoin_on_kick: 10
             notify: "mentions"
           - addr: "server2"
             join:
@@ -26,26 +27,32 @@ fn parsing_tab_configs() {
     let expected = Config {
         servers: vec![
             Server {
+                name: None,
                 addr: "server".to_string(),
                 join: vec![Chan::WithConfig {
                     name: ChanName::new("#tiny".to_string()),
+                    rejoin_on_kick: Some(10),
                     config: TabConfig {
                         ignore: Some(true),
                         notify: Some(Notifier::Messages),
                     },
+                    highlights: vec![],
                 }],
                 config: TabConfig {
                     notify: Some(Notifier::Mentions),
                     ..Default::default()
                 },
+                highlights: vec![],
             },
             Server {
+                name: None,
                 addr: "server2".to_string(),
                 join: vec![Chan::Name(ChanName::new("#tiny2".to_string()))],
                 config: TabConfig {
                     ignore: Some(true),
                     ..Default::default()
                 },
+                highlights: vec![],
             },
         ],
         defaults: Defaults {
@@ -99,6 +106,101 @@ fn parsing_tab_configs() {
     );
 }
 
+#[test]
+fn parsing_network_tab_configs() {
+    let config_str = r##"
+        servers:
+          - name: "network"
+            endpoints: ["server2:6667", "server3:6697:tls"]
+            join:
+              - "#tiny2"
+            ignore: true
+        defaults:
+            notify: off
+        "##;
+    let config: Config = serde_yaml::from_str(config_str).expect("parsed config");
+    let tab_configs: TabConfigs = (&config).into();
+    assert_eq!(
+        config.servers,
+        vec![Server {
+            name: Some("network".to_string()),
+            addr: "".to_string(),
+            join: vec![Chan::Name(ChanName::new("#tiny2".to_string()))],
+            config: TabConfig {
+                ignore: Some(true),
+                ..Default::default()
+            },
+            highlights: vec![],
+        }]
+    );
+
+    // Tabs of networks are named after the network, not the endpoints
+    assert_eq!(
+        tab_configs.get("network", Some(ChanNameRef::new("#tiny2"))),
+        Some(TabConfig {
+            ignore: Some(true),
+            notify: Some(Notifier::Off)
+        })
+    );
+    assert_eq!(tab_configs.get("server2", None), None);
+}
+
+#[test]
+fn parsing_highlights() {
+    let config_str = r##"
+        servers:
+          - addr: "server"
+            join:
+              - name: "#tiny"
+                highlights:
+                  - regex: "^deploy(ed)? "
+                  - word: "oncall"
+                    exclude: true
+              - "#tiny2"
+            highlights:
+              - word: "Team-A"
+              - sender: "*bot"
+                exclude: true
+              - word: "release"
+                sender: "alice"
+        defaults: {}
+        "##;
+    let config: Config = serde_yaml::from_str(config_str).expect("parsed config");
+    let highlights = Highlights::from(&config);
+    let tiny = Some(ChanNameRef::new("#Tiny"));
+    let tiny2 = Some(ChanNameRef::new("#tiny2"));
+
+    // Words are matched case insensitive, on word boundaries
+    assert!(highlights.is_highlight("server", tiny2, "bob", "ping team-a!", false));
+    assert!(!highlights.is_highlight("server", tiny2, "bob", "ping team-abc", false));
+    assert!(!highlights.is_highlight("other", tiny2, "bob", "ping team-a", false));
+
+    // Sender filters
+    assert!(highlights.is_highlight("server", tiny2, "Alice", "release done", false));
+    assert!(!highlights.is_highlight("server", tiny2, "bob", "release done", false));
+    assert!(!highlights.is_highlight("server", tiny2, "ci-bot", "team-a: hi", false));
+    assert!(!highlights.is_highlight("server", tiny2, "ci-bot", "osa1: hi", true));
+    assert!(highlights.is_highlight("server", tiny2, "bob", "osa1: hi", true));
+
+    // Channel rules are checked after server rules
+    assert!(highlights.is_highlight("server", tiny, "bob", "deployed v1", false));
+    assert!(!highlights.is_highlight("server", tiny2, "bob", "deployed v1", false));
+    assert!(!highlights.is_highlight("server", tiny, "bob", "team-a oncall", false));
+
+    // Invalid rules
+    for rule in [
+        "{}",
+        "{ word: a, regex: b }",
+        "{ regex: '(' }",
+        "{ words: a }",
+    ] {
+        assert!(
+            serde_yaml::from_str::<HighlightRule>(rule).is_err(),
+            "{rule}"
+        );
+    }
+}
+
 #[test]
 fn tab_config_command() {
     assert_eq!(
@@ -69,6 +69,74 @@ fn test_alignment_long_string() {
     expect_screen(screen, &tui.get_front_buffer(), 40, 5, Location::caller());
 }
 
+#[test]
+fn test_timestamp_format() {
+    let mut tui = TUI::new_test(40, 6);
+    tui.set_layout(Layout::Aligned { max_nick_len: 12 });
+    tui.set_timestamp_format("%H:%M:%S");
+    let serv = "irc.server_1.org";
+    let chan = ChanNameRef::new("#chan");
+    tui.new_server_tab(serv, None);
+    tui.set_nick(serv, "osa1");
+    tui.new_chan_tab(serv, chan);
+    tui.next_tab(); // mentions -> server
+    tui.next_tab(); // server -> channel
+
+    let target = MsgTarget::Chan { serv, chan };
+    let ts = time::at_utc(time::Timespec::new(0, 0));
+    tui.add_privmsg("osa1", "hi", ts, &target, false, false);
+    tui.add_privmsg("osa1", "yo", ts, &target, false, false);
+    // Next day
+    let ts = time::at_utc(time::Timespec::new(24 * 60 * 60 + 5, 0));
+    tui.add_privmsg("osa1", "bye", ts, &target, false, false);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|00:00:00         osa1: hi               |
+         |                 osa1: yo               |
+         |— Friday, 1970-01-02 —                  |
+         |00:00:05         osa1: bye              |
+         |osa1:                                   |
+         |mentions irc.server_1.org #chan         |";
+
+    expect_screen(screen, &tui.get_front_buffer(), 40, 6, Location::caller());
+}
+
+#[test]
+fn test_timestamp_format_existing_tabs() {
+    let mut tui = TUI::new_test(40, 6);
+    tui.set_layout(Layout::Aligned { max_nick_len: 12 });
+    let serv = "irc.server_1.org";
+    let chan = ChanNameRef::new("#chan");
+    tui.new_server_tab(serv, None);
+    tui.set_nick(serv, "osa1");
+    tui.new_chan_tab(serv, chan);
+    tui.next_tab(); // mentions -> server
+    tui.next_tab(); // server -> channel
+
+    let target = MsgTarget::Chan { serv, chan };
+    let ts = time::at_utc(time::Timespec::new(0, 0));
+    tui.add_privmsg("osa1", "hi", ts, &target, false, false);
+
+    // New messages in existing tabs use the new format and padding
+    tui.set_timestamp_format("%H:%M:%S");
+    tui.add_privmsg("osa1", "yo", ts, &target, false, false);
+    tui.add_privmsg("osa1", "bye", ts, &target, false, false);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|                                        |
+         |00:00         osa1: hi                  |
+         |00:00:00         osa1: yo               |
+         |                 osa1: bye              |
+         |osa1:                                   |
+         |mentions irc.server_1.org #chan         |";
+
+    expect_screen(screen, &tui.get_front_buffer(), 40, 6, Location::caller());
+}
+
 #[test]
 fn test_mnemonic_generation() {
     let mut tui = TUI::new_test(10, 10);
@@ -9,7 +9,14 @@ use crate::tui::TUI;
 mod layout;
 mod resize;
 
+mod chan_list;
 mod config;
+mod nick_list;
+mod search;
+mod sidebar;
+mod tab_activity;
+mod unread_marker;
+mod urls;
 
 fn enter_string(tui: &mut TUI, s: &str) {
     for c in s.chars() {
@@ -0,0 +1,96 @@
+use std::panic::Location;
+
+use libtiny_common::{ChanNameRef, MsgTarget};
+use term_input::{Arrow, Event, FKey, Key};
+
+use crate::test_utils::expect_screen;
+use crate::tui::TUI;
+
+#[test]
+fn test_nick_list() {
+    let mut tui = TUI::new_test(30, 5);
+    let serv = "irc.server_1.org";
+    let chan = ChanNameRef::new("#chan");
+    tui.new_server_tab(serv, None);
+    tui.new_chan_tab(serv, chan);
+    tui.next_tab();
+    tui.next_tab();
+    tui.set_nick_list_width(8);
+
+    let target = MsgTarget::Chan { serv, chan };
+    for nick in ["bob", "Alice", "carol", "dave", "eve"] {
+        tui.add_nick(nick, None, &target);
+    }
+    tui.update_nick_prefix("carol", '+', true, &target);
+    tui.update_nick_prefix("dave", '@', true, &target);
+    tui.update_nick_prefix("dave", '+', true, &target);
+    tui.add_msg(
+        "hello world",
+        time::at_utc(time::Timespec::new(0, 0)),
+        &target,
+    );
+
+    // Hidden by default
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|                              |
+         |                              |
+         |00:00 hello world             |
+         |                              |
+         |< irc.server_1.org #chan      |";
+    expect_screen(screen, &tui.get_front_buffer(), 30, 5, Location::caller());
+
+    tui.handle_input_event(Event::Key(Key::FKey(FKey::F2)), &mut None);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|                     │@dave   |
+         |                     │+carol  |
+         |00:00 hello world    │ Alice  |
+         |                     │ bob    |
+         |< irc.server_1.org #chan      |";
+    expect_screen(screen, &tui.get_front_buffer(), 30, 5, Location::caller());
+
+    // Scrolls independently of the messages
+    tui.handle_input_event(Event::Key(Key::AltArrow(Arrow::Down)), &mut None);
+    tui.handle_input_event(Event::Key(Key::AltArrow(Arrow::Down)), &mut None);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|                     │+carol  |
+         |                     │ Alice  |
+         |00:00 hello world    │ bob    |
+         |                     │ eve    |
+         |< irc.server_1.org #chan      |";
+    expect_screen(screen, &tui.get_front_buffer(), 30, 5, Location::caller());
+
+    tui.remove_nick("bob", None, &target);
+    tui.update_nick_prefix("dave", '@', false, &target);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|                     │+carol  |
+         |                     │+dave   |
+         |00:00 hello world    │ Alice  |
+         |                     │ eve    |
+         |< irc.server_1.org #chan      |";
+    expect_screen(screen, &tui.get_front_buffer(), 30, 5, Location::caller());
+
+    // Hidden when the screen is too narrow
+    tui.set_size(16, 5);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|                |
+         |00:00 hello     |
+         |world           |
+         |                |
+         |< >             |";
+    expect_screen(screen, &tui.get_front_buffer(), 16, 5, Location::caller());
+}
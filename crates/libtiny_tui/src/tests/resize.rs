@@ -3,7 +3,7 @@ use std::io::{BufRead, BufReader};
 use std::panic::Location;
 
 use libtiny_common::{ChanNameRef, MsgTarget};
-use term_input::{Arrow, Key};
+use term_input::{Arrow, Event, FKey, Key};
 
 use crate::test_utils::expect_screen;
 use crate::tui::TUI;
@@ -357,3 +357,125 @@ fn test_clear_resize_recalc_scroll() {
 
     expect_screen(screen1, &tui.get_front_buffer(), 20, 6, Location::caller());
 }
+
+#[test]
+fn test_resize_splits() {
+    let mut tui = TUI::new_test(41, 8);
+    let serv = "irc.server_1.org";
+    let chan = ChanNameRef::new("#chan");
+    let ops = ChanNameRef::new("#ops");
+    tui.new_server_tab(serv, None);
+    tui.set_nick(serv, "osa1");
+    tui.new_chan_tab(serv, chan);
+    tui.new_chan_tab(serv, ops);
+    tui.next_tab();
+    tui.next_tab();
+
+    let ts = time::at_utc(time::Timespec::new(0, 0));
+    let chan_target = MsgTarget::Chan { serv, chan };
+    let ops_target = MsgTarget::Chan { serv, chan: ops };
+    tui.add_privmsg("bob", "chan msg", ts, &chan_target, false, false);
+    tui.add_privmsg("alice", "ops msg", ts, &ops_target, false, false);
+
+    // The new split shows the next tab and gets the input
+    tui.handle_input_event(Event::Key(Key::FKey(FKey::F6)), &mut None);
+    tui.handle_input_event(Event::Key(Key::Char('x')), &mut None);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+       "|                    │                    |
+        |                    │                    |
+        |                    │                    |
+        |                    │                    |
+        |                    │                    |
+        |00:00 bob: chan msg │00:00 alice: ops msg|
+        |osa1:               │osa1: x             |
+        |mentions irc.server_1.org #chan #ops     |";
+
+    expect_screen(screen, &tui.get_front_buffer(), 41, 8, Location::caller());
+
+    tui.set_size(31, 8);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+       "|               │               |
+        |               │               |
+        |               │               |
+        |               │               |
+        |00:00 bob: chan│00:00 alice:   |
+        |msg            │ops msg        |
+        |               │x              |
+        |< irc.server_1.org #chan #ops  |";
+
+    expect_screen(screen, &tui.get_front_buffer(), 31, 8, Location::caller());
+
+    // Too narrow for two splits: only the focused split is shown
+    tui.set_size(20, 8);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+       "|                    |
+        |                    |
+        |                    |
+        |                    |
+        |                    |
+        |00:00 alice: ops msg|
+        |osa1: x             |
+        |< #chan #ops        |";
+
+    expect_screen(screen, &tui.get_front_buffer(), 20, 8, Location::caller());
+
+    tui.set_size(41, 8);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+       "|                    │                    |
+        |                    │                    |
+        |                    │                    |
+        |                    │                    |
+        |                    │                    |
+        |00:00 bob: chan msg │00:00 alice: ops msg|
+        |osa1:               │osa1: x             |
+        |mentions irc.server_1.org #chan #ops     |";
+
+    expect_screen(screen, &tui.get_front_buffer(), 41, 8, Location::caller());
+
+    // Split horizontally, shows the mentions tab
+    tui.handle_input_event(Event::Key(Key::FKey(FKey::F5)), &mut None);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+       "|                    │                    |
+        |                    │00:00 alice: ops msg|
+        |                    │osa1: x             |
+        |                    │────────────────────|
+        |                    │Any mentions to you |
+        |00:00 bob: chan msg │will be listed here.|
+        |osa1:               │                    |
+        |mentions irc.server_1.org #chan #ops     |";
+
+    expect_screen(screen, &tui.get_front_buffer(), 41, 8, Location::caller());
+
+    // Move focus to the first split, close it
+    tui.handle_input_event(Event::Key(Key::CtrlArrow(Arrow::Down)), &mut None);
+    tui.handle_input_event(Event::Key(Key::FKey(FKey::F7)), &mut None);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+       "|                                         |
+        |00:00 alice: ops msg                     |
+        |osa1: x                                  |
+        |─────────────────────────────────────────|
+        |                                         |
+        |Any mentions to you will be listed here. |
+        |                                         |
+        |mentions irc.server_1.org #chan #ops     |";
+
+    expect_screen(screen, &tui.get_front_buffer(), 41, 8, Location::caller());
+}
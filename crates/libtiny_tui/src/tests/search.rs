@@ -0,0 +1,121 @@
+use std::panic::Location;
+
+use libtiny_common::{MsgSource, MsgTarget};
+use term_input::{Arrow, Event, FKey, Key};
+
+use crate::test_utils::expect_screen;
+use crate::tui::TUI;
+
+#[test]
+fn test_search() {
+    // Searching in the mentions tab
+    let mut tui = TUI::new_test(20, 5);
+    tui.clear(&MsgTarget::CurrentTab);
+    for msg in ["one", "foo two", "three", "four", "Foo five", "six"] {
+        tui.add_client_msg(msg, &MsgTarget::CurrentTab);
+    }
+
+    tui.handle_input_event(Event::Key(Key::Ctrl('f')), &mut None);
+    for c in "foo".chars() {
+        tui.handle_input_event(Event::Key(Key::Char(c)), &mut None);
+    }
+    tui.draw();
+
+    // Jumps to the last hit. Plain text search is case insensitive.
+    #[rustfmt::skip]
+    let screen =
+        "|three               |
+         |four                |
+         |Foo five            |
+         |search [1/2]: foo   |
+         |mentions            |";
+    expect_screen(screen, &tui.get_front_buffer(), 20, 5, Location::caller());
+
+    // Matches are highlighted
+    let colors = crate::config::Colors::default();
+    let cells = tui.get_front_buffer().cells;
+    assert_eq!(cells[2 * 20].fg, colors.completion.fg);
+    assert_eq!(cells[2 * 20 + 3].fg, colors.user_msg.fg);
+
+    tui.handle_input_event(Event::Key(Key::Arrow(Arrow::Up)), &mut None);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|one                 |
+         |foo two             |
+         |three               |
+         |search [2/2]: foo   |
+         |mentions            |";
+    expect_screen(screen, &tui.get_front_buffer(), 20, 5, Location::caller());
+
+    // Enter closes the prompt, but keeps the search
+    tui.handle_input_event(Event::Key(Key::Char('\r')), &mut None);
+    tui.handle_input_event(Event::Key(Key::ShiftF(FKey::F3)), &mut None);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|three               |
+         |four                |
+         |Foo five            |
+         |                    |
+         |mentions            |";
+    expect_screen(screen, &tui.get_front_buffer(), 20, 5, Location::caller());
+
+    tui.handle_input_event(Event::Key(Key::FKey(FKey::F3)), &mut None);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|one                 |
+         |foo two             |
+         |three               |
+         |                    |
+         |mentions            |";
+    expect_screen(screen, &tui.get_front_buffer(), 20, 5, Location::caller());
+
+    // Esc returns to the bottom
+    tui.handle_input_event(Event::Key(Key::Esc), &mut None);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|four                |
+         |Foo five            |
+         |six                 |
+         |                    |
+         |mentions            |";
+    expect_screen(screen, &tui.get_front_buffer(), 20, 5, Location::caller());
+    let cells = tui.get_front_buffer().cells;
+    assert_eq!(cells[20].fg, colors.user_msg.fg);
+
+    // Regex search with the command. Regexes are case sensitive.
+    let src = MsgSource::Serv {
+        serv: "mentions".to_owned(),
+    };
+    tui.try_handle_cmd("search /f.*o$/", &src);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|one                 |
+         |foo two             |
+         |three               |
+         |                    |
+         |mentions            |";
+    expect_screen(screen, &tui.get_front_buffer(), 20, 5, Location::caller());
+
+    tui.handle_input_event(Event::Key(Key::Esc), &mut None);
+    tui.try_handle_cmd("search seven", &src);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|Foo five            |
+         |six                 |
+         |No matches for seven|
+         |                    |
+         |mentions            |";
+    expect_screen(screen, &tui.get_front_buffer(), 20, 5, Location::caller());
+}
@@ -0,0 +1,137 @@
+use std::panic::Location;
+
+use libtiny_common::{ChanNameRef, MsgTarget, TabStyle};
+use term_input::{Event, FKey, Key};
+
+use crate::config::{SidebarPosition, TabSidebar};
+use crate::key_map::KeyMap;
+use crate::test_utils::expect_screen;
+use crate::tui::TUI;
+
+#[test]
+fn test_sidebar() {
+    let mut tui = TUI::new_test(30, 6);
+    tui.set_sidebar(Some(TabSidebar {
+        position: SidebarPosition::Left,
+        width: 12,
+    }));
+    let serv = "libera";
+    let chan_a = ChanNameRef::new("#a");
+    let chan_b = ChanNameRef::new("#b");
+    tui.new_server_tab(serv, None);
+    tui.new_chan_tab(serv, chan_a);
+    tui.new_chan_tab(serv, chan_b);
+    tui.new_server_tab("oftc", None);
+    tui.next_tab();
+    tui.next_tab();
+
+    let target = MsgTarget::Chan { serv, chan: chan_b };
+    let ts = time::at_utc(time::Timespec::new(0, 0));
+    for msg in ["hi", "osa1: hey", "bye"] {
+        tui.add_privmsg("bob", msg, ts, &target, false, false);
+    }
+    tui.set_tab_style(TabStyle::NewMsg, &target);
+    tui.set_tab_style(TabStyle::Highlight, &target);
+    tui.set_tab_style(TabStyle::NewMsg, &target);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "| mentions   │                 |
+         |▾libera     │                 |
+         |  #a        │                 |
+         |  #b    3 !1│                 |
+         | oftc       │                 |
+         |            │                 |";
+    expect_screen(screen, &tui.get_front_buffer(), 30, 6, Location::caller());
+
+    // Collapsing selects the server tab and sums the counts
+    tui.handle_input_event(Event::Key(Key::FKey(FKey::F4)), &mut None);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "| mentions   │                 |
+         |▸libera 3 !1│                 |
+         | oftc       │                 |
+         |            │                 |
+         |            │                 |
+         |            │                 |";
+    expect_screen(screen, &tui.get_front_buffer(), 30, 6, Location::caller());
+
+    // Hidden tabs are shown when active
+    tui.next_tab();
+    tui.next_tab();
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "| mentions   │                 |
+         |▸libera     │                 |
+         |  #b        │00:00 bob: hi    |
+         | oftc       │bob: osa1: hey   |
+         |            │bob: bye         |
+         |            │                 |";
+    expect_screen(screen, &tui.get_front_buffer(), 30, 6, Location::caller());
+
+    // Falls back to the tab bar on small screens
+    tui.set_size(20, 3);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|bob: bye            |
+         |                    |
+         |< libera #a #b oftc |";
+    expect_screen(screen, &tui.get_front_buffer(), 20, 3, Location::caller());
+}
+
+#[test]
+fn test_sidebar_mouse_wheel() {
+    let mut tui = TUI::new_test(30, 3);
+    tui.set_sidebar(Some(TabSidebar {
+        position: SidebarPosition::Left,
+        width: 12,
+    }));
+    let serv = "libera";
+    let chan = ChanNameRef::new("#a");
+    tui.new_server_tab(serv, None);
+    tui.new_chan_tab(serv, chan);
+    tui.new_chan_tab(serv, ChanNameRef::new("#b"));
+    tui.next_tab();
+    tui.next_tab();
+
+    let target = MsgTarget::Chan { serv, chan };
+    let ts = time::at_utc(time::Timespec::new(0, 0));
+    for msg in ["1", "2", "3", "4", "5"] {
+        tui.add_privmsg("bob", msg, ts, &target, false, false);
+    }
+    tui.draw();
+
+    // The sidebar has more tabs than fit, but the wheel still scrolls the messages
+    tui.handle_input_event(Event::Key(Key::MouseWheelUp), &mut None);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "| mentions   │bob: 3           |
+         |▾libera     │bob: 4           |
+         |  #a        │                 |";
+    expect_screen(screen, &tui.get_front_buffer(), 30, 3, Location::caller());
+
+    // The wheel scrolls the sidebar when mapped to the sidebar actions
+    let key_map: KeyMap = serde_yaml::from_str(
+        "mouse_wheel_up: sidebar_scroll_up\nmouse_wheel_down: sidebar_scroll_down",
+    )
+    .unwrap();
+    tui.load_key_map(&key_map);
+    tui.handle_input_event(Event::Key(Key::MouseWheelDown), &mut None);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|▾libera     │bob: 3           |
+         |  #a        │bob: 4           |
+         |  #b        │                 |";
+    expect_screen(screen, &tui.get_front_buffer(), 30, 3, Location::caller());
+}
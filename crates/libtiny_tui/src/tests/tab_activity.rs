@@ -0,0 +1,62 @@
+use libtiny_common::{ChanNameRef, MsgSource, MsgTarget, TabStyle};
+use term_input::{Event, Key};
+
+use crate::key_map::KeyMap;
+use crate::tui::TUI;
+
+fn current_chan(tui: &TUI) -> Option<&str> {
+    match tui.current_tab() {
+        MsgSource::Chan { chan, .. } => Some(chan.display()),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_tab_next_activity() {
+    let mut tui = TUI::new_test(40, 5);
+    let key_map: KeyMap = serde_yaml::from_str(
+        "alt_a: tab_next_activity
+alt_h: tab_next_highlight",
+    )
+    .unwrap();
+    tui.load_key_map(&key_map);
+
+    let serv = "libera";
+    tui.new_server_tab(serv, None);
+    for chan in ["#a", "#b", "#c", "#d"] {
+        tui.new_chan_tab(serv, ChanNameRef::new(chan));
+    }
+
+    let set_style = |tui: &mut TUI, chan: &str, style: TabStyle| {
+        let chan = ChanNameRef::new(chan);
+        tui.set_tab_style(style, &MsgTarget::Chan { serv, chan });
+    };
+    set_style(&mut tui, "#a", TabStyle::JoinOrPart);
+    set_style(&mut tui, "#b", TabStyle::NewMsg);
+    set_style(&mut tui, "#c", TabStyle::Highlight);
+    set_style(&mut tui, "#d", TabStyle::NewMsg);
+    set_style(&mut tui, "#b", TabStyle::Highlight);
+
+    let alt = |tui: &mut TUI, c: char| {
+        tui.handle_input_event(Event::Key(Key::AltChar(c)), &mut None);
+    };
+
+    // Highlights first, the one highlighted first wins
+    alt(&mut tui, 'h');
+    assert_eq!(current_chan(&tui), Some("#c"));
+    alt(&mut tui, 'h');
+    assert_eq!(current_chan(&tui), Some("#b"));
+
+    // No highlights left
+    alt(&mut tui, 'h');
+    assert_eq!(current_chan(&tui), Some("#b"));
+
+    alt(&mut tui, 'a');
+    assert_eq!(current_chan(&tui), Some("#d"));
+    alt(&mut tui, 'a');
+    assert_eq!(current_chan(&tui), Some("#a"));
+
+    // No activity left
+    alt(&mut tui, 'a');
+    assert_eq!(current_chan(&tui), Some("#a"));
+}
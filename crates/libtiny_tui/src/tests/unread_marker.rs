@@ -0,0 +1,150 @@
+use std::panic::Location;
+
+use libtiny_common::{ChanNameRef, MsgTarget};
+use term_input::{Arrow, Event, FKey, Key};
+
+use crate::test_utils::expect_screen;
+use crate::tui::TUI;
+
+#[test]
+fn test_unread_marker() {
+    let mut tui = TUI::new_test(20, 6);
+    let serv = "s";
+    let chan = ChanNameRef::new("#c");
+    tui.new_server_tab(serv, None);
+    tui.new_chan_tab(serv, chan);
+    tui.next_tab(); // mentions -> server
+    tui.next_tab(); // server -> channel
+    let target = MsgTarget::Chan { serv, chan };
+    tui.add_client_msg("a", &target);
+    tui.add_client_msg("b", &target);
+    tui.draw();
+
+    // Leave the tab, new messages are added after the marker
+    tui.prev_tab();
+    tui.draw();
+    for msg in ["c", "d", "e"] {
+        tui.add_client_msg(msg, &target);
+    }
+    tui.next_tab();
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|── new messages ────|
+         |c                   |
+         |d                   |
+         |e                   |
+         |                    |
+         |mentions s #c       |";
+    expect_screen(screen, &tui.get_front_buffer(), 20, 6, Location::caller());
+
+    // Jump to the marker after more messages
+    for msg in ["f", "g"] {
+        tui.add_client_msg(msg, &target);
+    }
+    tui.handle_input_event(Event::Key(Key::FKey(FKey::F9)), &mut None);
+    tui.draw();
+    expect_screen(screen, &tui.get_front_buffer(), 20, 6, Location::caller());
+
+    // Scrolling past the marker clears it
+    tui.handle_input_event(Event::Key(Key::End), &mut None);
+    tui.handle_input_event(Event::Key(Key::ShiftArrow(Arrow::Up)), &mut None);
+    tui.handle_input_event(Event::Key(Key::ShiftArrow(Arrow::Up)), &mut None);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|b                   |
+         |c                   |
+         |d                   |
+         |e                   |
+         |                    |
+         |mentions s #c       |";
+    expect_screen(screen, &tui.get_front_buffer(), 20, 6, Location::caller());
+
+    // When leaving a scrolled tab, the marker is added after the last visible line
+    tui.prev_tab();
+    tui.draw();
+    tui.next_tab();
+    tui.draw();
+    expect_screen(screen, &tui.get_front_buffer(), 20, 6, Location::caller());
+
+    tui.handle_input_event(Event::Key(Key::ShiftArrow(Arrow::Down)), &mut None);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|c                   |
+         |d                   |
+         |e                   |
+         |── new messages ────|
+         |                    |
+         |mentions s #c       |";
+    expect_screen(screen, &tui.get_front_buffer(), 20, 6, Location::caller());
+}
+
+#[test]
+fn test_unread_marker_no_new_lines() {
+    let mut tui = TUI::new_test(20, 6);
+    let serv = "s";
+    let chan = ChanNameRef::new("#c");
+    tui.new_server_tab(serv, None);
+    tui.new_chan_tab(serv, chan);
+    tui.next_tab(); // mentions -> server
+    tui.next_tab(); // server -> channel
+    let target = MsgTarget::Chan { serv, chan };
+    tui.add_client_msg("a", &target);
+    tui.draw();
+
+    // Leave and return without new messages, messages added while reading are not marked
+    tui.prev_tab();
+    tui.draw();
+    tui.next_tab();
+    tui.draw();
+    tui.add_client_msg("b", &target);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|                    |
+         |                    |
+         |a                   |
+         |b                   |
+         |                    |
+         |mentions s #c       |";
+    expect_screen(screen, &tui.get_front_buffer(), 20, 6, Location::caller());
+}
+
+#[test]
+fn test_unread_marker_splits() {
+    let mut tui = TUI::new_test(41, 6);
+    let serv = "s";
+    let chan = ChanNameRef::new("#c");
+    let ops = ChanNameRef::new("#ops");
+    tui.new_server_tab(serv, None);
+    tui.new_chan_tab(serv, chan);
+    tui.new_chan_tab(serv, ops);
+    tui.next_tab(); // mentions -> server
+    tui.next_tab(); // server -> #c
+    let target = MsgTarget::Chan { serv, chan };
+    tui.add_client_msg("a", &target);
+    tui.draw();
+
+    // Split, the new split shows #ops and is focused. #c is still shown, new messages are not
+    // marked.
+    tui.handle_input_event(Event::Key(Key::FKey(FKey::F6)), &mut None);
+    tui.draw();
+    tui.add_client_msg("b", &target);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|                    │                    |
+         |                    │                    |
+         |a                   │                    |
+         |b                   │                    |
+         |                    │                    |
+         |mentions s #c #ops                       |";
+    expect_screen(screen, &tui.get_front_buffer(), 41, 6, Location::caller());
+}
@@ -0,0 +1,75 @@
+use std::panic::Location;
+
+use libtiny_common::{MsgSource, MsgTarget};
+use term_input::{Arrow, Event, FKey, Key};
+use termbox_simple::TB_UNDERLINE;
+
+use crate::test_utils::expect_screen;
+use crate::tui::TUI;
+
+#[test]
+fn test_urls() {
+    let mut tui = TUI::new_test(40, 5);
+    tui.clear(&MsgTarget::CurrentTab);
+    let src = MsgSource::Serv {
+        serv: "mentions".to_owned(),
+    };
+
+    tui.try_handle_cmd("urls", &src);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|                                        |
+         |                                        |
+         |No links in this tab.                   |
+         |                                        |
+         |mentions                                |";
+    expect_screen(screen, &tui.get_front_buffer(), 40, 5, Location::caller());
+
+    tui.clear(&MsgTarget::CurrentTab);
+    tui.add_client_msg("see https://a.org/x.", &MsgTarget::CurrentTab);
+    tui.add_client_msg("https://b.org and https://a.org/x", &MsgTarget::CurrentTab);
+    tui.draw();
+
+    // Links are underlined
+    let cells = tui.get_front_buffer().cells;
+    assert_eq!(cells[40 + 3].fg & TB_UNDERLINE, 0);
+    assert_ne!(cells[40 + 4].fg & TB_UNDERLINE, 0);
+    assert_ne!(cells[40 + 18].fg & TB_UNDERLINE, 0);
+    assert_eq!(cells[40 + 19].fg & TB_UNDERLINE, 0);
+
+    // Most recent links first, duplicates are listed once
+    tui.handle_input_event(Event::Key(Key::FKey(FKey::F8)), &mut None);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|1 https://a.org/x                       |
+         |2 https://b.org                         |
+         |                                        |
+         |1/2 links, enter: open, c: copy         |
+         |mentions                                |";
+    expect_screen(screen, &tui.get_front_buffer(), 40, 5, Location::caller());
+
+    // Copying closes the picker
+    tui.handle_input_event(Event::Key(Key::Arrow(Arrow::Down)), &mut None);
+    tui.handle_input_event(Event::Key(Key::Char('c')), &mut None);
+    tui.draw();
+
+    #[rustfmt::skip]
+    let screen =
+        "|see https://a.org/x.                    |
+         |https://b.org and https://a.org/x       |
+         |Copied https://b.org to the clipboard.  |
+         |                                        |
+         |mentions                                |";
+    expect_screen(screen, &tui.get_front_buffer(), 40, 5, Location::caller());
+
+    // Esc closes the picker without changing the input field
+    tui.try_handle_cmd("urls", &src);
+    tui.handle_input_event(Event::Key(Key::Char('x')), &mut None);
+    tui.handle_input_event(Event::Key(Key::Esc), &mut None);
+    tui.draw();
+    expect_screen(screen, &tui.get_front_buffer(), 40, 5, Location::caller());
+}
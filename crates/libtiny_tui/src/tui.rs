@@ -8,18 +8,25 @@
 
 use std::borrow::Borrow;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
 use std::path::PathBuf;
 use std::str::{self, SplitWhitespace};
 use time::Tm;
 
-use crate::config::{Colors, Config, Style, TabConfig, TabConfigs, parse_config};
+use crate::config::{
+    Colors, Config, DEFAULT_NICK_LIST_WIDTH, Highlights, SidebarPosition, Style, TabConfig,
+    TabConfigs, TabSidebar, Urls, parse_config,
+};
 use crate::editor;
 use crate::key_map::{KeyAction, KeyMap};
-use crate::messaging::{MessagingUI, Timestamp};
+use crate::messaging::{MessagingUI, Timestamp, TimestampFormat};
 use crate::msg_area::Layout;
 use crate::notifier::Notifier;
+use crate::sidebar::Sidebar;
+use crate::split::{Rect, SplitDir, Splits};
 use crate::tab::Tab;
+use crate::urls::open_url;
 use crate::widget::WidgetRet;
 
 use libtiny_common::{ChanNameRef, MsgSource, MsgTarget, TabStyle};
@@ -66,9 +73,16 @@ const NOTIFY_CMD: CmdUsage = CmdUsage::new(
 );
 const SWITCH_CMD: CmdUsage = CmdUsage::new("switch", "Switches to tab", "`/switch <tab name>`");
 const RELOAD_CMD: CmdUsage = CmdUsage::new("reload", "Reloads config file", "`/reload`");
+const SEARCH_CMD: CmdUsage = CmdUsage::new(
+    "search",
+    "Searches messages in tab",
+    "`/search <text>` or `/search /<regex>/`",
+);
+
+const URLS_CMD: CmdUsage = CmdUsage::new("urls", "Lists links in tab", "`/urls`");
 
-const TUI_COMMANDS: [CmdUsage; 6] = [
-    QUIT_CMD, CLEAR_CMD, IGNORE_CMD, NOTIFY_CMD, SWITCH_CMD, RELOAD_CMD,
+const TUI_COMMANDS: [CmdUsage; 8] = [
+    QUIT_CMD, CLEAR_CMD, IGNORE_CMD, NOTIFY_CMD, SWITCH_CMD, RELOAD_CMD, SEARCH_CMD, URLS_CMD,
 ];
 
 // Public for benchmarks
@@ -85,6 +99,9 @@ pub struct TUI {
     /// Messaging area layout: aligned or compact
     msg_layout: Layout,
 
+    /// Format of the timestamps of the messages
+    timestamp_format: TimestampFormat,
+
     tabs: Vec<Tab>,
     active_idx: usize,
     width: i32,
@@ -98,6 +115,34 @@ pub struct TUI {
 
     /// TabConfig settings loaded from config file
     tab_configs: TabConfigs,
+
+    /// Highlight rules loaded from config file
+    highlights: Highlights,
+
+    /// Lines of the "transfers" tab, by transfer id.
+    transfers: BTreeMap<usize, String>,
+
+    /// Width of the nick list panels of channel tabs
+    nick_list_width: i32,
+
+    /// Are nick list panels shown?
+    show_nick_list: bool,
+
+    /// Splits of the message area. The focused split shows the tab `active_idx`.
+    splits: Splits,
+
+    /// Vertical tab list, shown instead of the tab bar when set.
+    sidebar: Option<Sidebar>,
+
+    /// Source of the active tab in the last `draw`. Used to move the unread marker of a tab when
+    /// we leave it.
+    last_active_src: Option<MsgSource>,
+
+    /// Incremented on every `set_tab_style`, see `Tab::style_seq`.
+    style_seq: u64,
+
+    /// Command to open links with, see `Urls::open_command`.
+    url_open_command: String,
 }
 
 pub(crate) enum CmdResult {
@@ -138,6 +183,22 @@ impl TUI {
         self.msg_layout = layout
     }
 
+    #[cfg(test)]
+    pub(crate) fn set_timestamp_format(&mut self, format: &str) {
+        self.update_timestamp_format(TimestampFormat::new(format).unwrap());
+    }
+
+    #[cfg(test)]
+    pub(crate) fn load_key_map(&mut self, key_map: &KeyMap) {
+        self.key_map.load(key_map);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_nick_list_width(&mut self, width: i32) {
+        self.nick_list_width = width;
+        self.update_nick_lists();
+    }
+
     pub(crate) fn current_tab(&self) -> &MsgSource {
         &self.tabs[self.active_idx].src
     }
@@ -159,6 +220,7 @@ impl TUI {
             colors: Colors::default(),
             scrollback: usize::MAX,
             msg_layout: Layout::Compact,
+            timestamp_format: TimestampFormat::default(),
             tabs: Vec::new(),
             active_idx: 0,
             width,
@@ -167,6 +229,15 @@ impl TUI {
             key_map: KeyMap::default(),
             config_path,
             tab_configs: TabConfigs::default(),
+            highlights: Highlights::default(),
+            transfers: BTreeMap::new(),
+            nick_list_width: DEFAULT_NICK_LIST_WIDTH,
+            show_nick_list: false,
+            splits: Splits::new(),
+            sidebar: None,
+            last_active_src: None,
+            style_seq: 0,
+            url_open_command: Urls::default().open_command,
         };
 
         // Init "mentions" tab. This needs to happen right after creating the TUI to be able to
@@ -202,6 +273,13 @@ impl TUI {
                 self.tab_configs.set(serv, Some(nick_chan), config);
                 (MsgTarget::User { serv, nick }, new_ignore)
             }
+            MsgSource::DccChat { .. } => {
+                self.add_client_err_msg(
+                    "/ignore is not supported in DCC CHAT tabs",
+                    &MsgTarget::CurrentTab,
+                );
+                return;
+            }
         };
         let msg = if ignore {
             "Ignore enabled"
@@ -223,6 +301,14 @@ impl TUI {
             return;
         }
 
+        if let MsgSource::DccChat { .. } = src {
+            self.add_client_err_msg(
+                "/notify is not supported in DCC CHAT tabs",
+                &MsgTarget::CurrentTab,
+            );
+            return;
+        }
+
         let words: Vec<&str> = words.collect();
 
         let mut show_usage = || {
@@ -268,6 +354,7 @@ impl TUI {
                     chan: chan.borrow(),
                 },
                 MsgSource::User { serv, nick } => MsgTarget::User { serv, nick },
+                MsgSource::DccChat { serv, nick } => MsgTarget::DccChat { serv, nick },
             };
             self.set_notifier(notifier, &tab_target);
         }
@@ -281,6 +368,10 @@ impl TUI {
                 CmdResult::Handled
             }
             Some("ignore") => {
+                // `/ignore add|del|list` for ignoring users is handled by the caller
+                if words.next().is_some() {
+                    return CmdResult::Pass;
+                }
                 self.ignore(src);
                 CmdResult::Handled
             }
@@ -298,10 +389,27 @@ impl TUI {
                 }
                 CmdResult::Handled
             }
+            Some("search") => {
+                let query = cmd.trim_start()["search".len()..].trim();
+                if query.is_empty() {
+                    self.add_client_err_msg(
+                        &format!("Usage: {}", SEARCH_CMD.usage),
+                        &MsgTarget::CurrentTab,
+                    );
+                } else {
+                    self.search(query);
+                }
+                CmdResult::Handled
+            }
+            Some("urls") => {
+                self.open_url_picker();
+                CmdResult::Handled
+            }
             Some("reload") => {
                 self.reload_config();
                 self.add_client_notify_msg("Reloaded config file.", &MsgTarget::CurrentTab);
-                CmdResult::Handled
+                // Fall through to reload the config of the caller (e.g. ignore rules)
+                CmdResult::Pass
             }
             Some("help") => {
                 self.add_client_msg("TUI Commands: ", &MsgTarget::CurrentTab);
@@ -351,17 +459,37 @@ impl TUI {
     fn apply_config(&mut self, config: Option<Config>) {
         if let Some(config) = config {
             self.tab_configs = TabConfigs::from(&config);
+            self.highlights = Highlights::from(&config);
             let Config {
                 colors,
                 scrollback,
                 max_nick_length,
                 key_map,
                 layout,
+                nick_list_width,
+                tab_sidebar,
+                urls,
+                timestamp_format,
                 ..
             } = config;
             self.set_colors(colors);
             self.scrollback = scrollback.max(1);
             self.key_map.load(&key_map.unwrap_or_default());
+            self.nick_list_width = nick_list_width.max(1);
+            self.update_nick_lists();
+            self.set_sidebar(tab_sidebar);
+            self.tb.set_hyperlinks(
+                urls.hyperlinks
+                    .unwrap_or_else(termbox_simple::hyperlinks_supported),
+            );
+            self.url_open_command = urls.open_command;
+            match TimestampFormat::new(&timestamp_format) {
+                Ok(timestamp_format) => self.update_timestamp_format(timestamp_format),
+                Err(err) => self.add_client_err_msg(
+                    &format!("Invalid timestamp format {timestamp_format:?}: {err}"),
+                    &MsgTarget::CurrentTab,
+                ),
+            }
             if let Some(layout) = layout {
                 match layout {
                     crate::config::Layout::Compact => self.msg_layout = Layout::Compact,
@@ -375,6 +503,14 @@ impl TUI {
         }
     }
 
+    /// Set the timestamp format of new tabs, and of new messages in existing tabs.
+    fn update_timestamp_format(&mut self, timestamp_format: TimestampFormat) {
+        for tab in &mut self.tabs {
+            tab.widget.set_timestamp_format(timestamp_format.clone());
+        }
+        self.timestamp_format = timestamp_format;
+    }
+
     fn reload_config(&mut self) {
         let config = self.load_config();
         self.apply_config(config);
@@ -391,6 +527,7 @@ impl TUI {
             MsgSource::Serv { serv } => serv.to_owned(),
             MsgSource::Chan { chan, .. } => chan.display().to_owned(),
             MsgSource::User { nick, .. } => nick.to_owned(),
+            MsgSource::DccChat { nick, .. } => format!("={nick}"),
         });
 
         let switch = {
@@ -429,21 +566,214 @@ impl TUI {
             new_tab_switch_char.map(|(ch, _)| ch)
         };
 
+        let rect = self.splits_rect();
         self.tabs.insert(
             idx,
             Tab {
                 visible_name,
                 widget: MessagingUI::new(
-                    self.width,
-                    self.height - 1,
+                    rect.width,
+                    rect.height,
                     self.scrollback,
                     self.msg_layout,
+                    self.timestamp_format.clone(),
                 ),
                 src,
                 style: TabStyle::Normal,
                 switch,
+                unread: 0,
+                highlights: 0,
+                style_seq: 0,
             },
         );
+        self.splits
+            .update_tabs(|tab_idx| Some(if tab_idx >= idx { tab_idx + 1 } else { tab_idx }));
+
+        if self.show_nick_list && matches!(self.tabs[idx].src, MsgSource::Chan { .. }) {
+            self.tabs[idx]
+                .widget
+                .set_nick_list_width(Some(self.nick_list_width));
+        }
+    }
+
+    /// Collapse or expand the channel and user tabs of the current server in the sidebar. Selects
+    /// the server tab, as the current tab may be hidden.
+    fn toggle_collapse(&mut self) {
+        let Some(sidebar) = &mut self.sidebar else {
+            return;
+        };
+        let serv = self.tabs[self.active_idx].src.serv_name().to_owned();
+        sidebar.toggle_collapse(&serv);
+        if let Some(serv_tab_idx) = self.find_serv_tab_idx(&serv) {
+            self.select_tab(serv_tab_idx);
+        }
+    }
+
+    /// Switch to the tab with the most significant style that is at least `min_style`. Among the
+    /// tabs with the same style, the one that got the style first is selected.
+    fn next_activity_tab(&mut self, min_style: TabStyle) {
+        let next_idx = self
+            .tabs
+            .iter()
+            .enumerate()
+            .filter(|(idx, tab)| *idx != self.active_idx && tab.style >= min_style)
+            .max_by(|(_, tab1), (_, tab2)| {
+                tab1.style
+                    .cmp(&tab2.style)
+                    .then(tab2.style_seq.cmp(&tab1.style_seq))
+            })
+            .map(|(idx, _)| idx);
+        if let Some(idx) = next_idx {
+            self.select_tab(idx);
+        }
+    }
+
+    fn toggle_nick_list(&mut self) {
+        self.show_nick_list = !self.show_nick_list;
+        self.update_nick_lists();
+    }
+
+    /// Show or hide nick list panels of channel tabs, after updating `show_nick_list` or
+    /// `nick_list_width`.
+    fn update_nick_lists(&mut self) {
+        let width = if self.show_nick_list {
+            Some(self.nick_list_width)
+        } else {
+            None
+        };
+        for tab in &mut self.tabs {
+            if let MsgSource::Chan { .. } = tab.src {
+                tab.widget.set_nick_list_width(width);
+            }
+        }
+    }
+
+    /// Update tab indices of the splits before removing the tabs for which `removed` returns
+    /// `true`.
+    fn remove_split_tabs<F>(&mut self, removed: F)
+    where
+        F: Fn(usize, &Tab) -> bool,
+    {
+        let mut new_idxs = Vec::with_capacity(self.tabs.len());
+        let mut new_idx = 0;
+        for (tab_idx, tab) in self.tabs.iter().enumerate() {
+            if removed(tab_idx, tab) {
+                new_idxs.push(None);
+            } else {
+                new_idxs.push(Some(new_idx));
+                new_idx += 1;
+            }
+        }
+        self.splits.update_tabs(|tab_idx| new_idxs[tab_idx]);
+    }
+
+    /// Update tab indices of the splits before moving the tabs in `range` to `insert_idx`.
+    /// `insert_idx` is the index after removing the tabs in `range`.
+    fn move_split_tabs(&mut self, range: Range<usize>, insert_idx: usize) {
+        let len = range.len();
+        self.splits.update_tabs(|tab_idx| {
+            if range.contains(&tab_idx) {
+                return Some(insert_idx + tab_idx - range.start);
+            }
+            let tab_idx = if tab_idx >= range.end {
+                tab_idx - len
+            } else {
+                tab_idx
+            };
+            Some(if tab_idx >= insert_idx {
+                tab_idx + len
+            } else {
+                tab_idx
+            })
+        });
+    }
+
+    /// Split the focused split. The new split shows the first tab after the active tab that is
+    /// not already shown in a split, and is focused.
+    fn split(&mut self, dir: SplitDir) {
+        self.splits.set_active_tab(self.active_idx);
+        let new_tab = (1..self.tabs.len())
+            .map(|i| (self.active_idx + i) % self.tabs.len())
+            .find(|tab_idx| !self.splits.shows_tab(*tab_idx));
+        if let Some(new_tab) = new_tab {
+            self.splits.split(dir, new_tab);
+            self.select_tab(new_tab);
+        }
+    }
+
+    fn close_split(&mut self) {
+        self.splits.set_active_tab(self.active_idx);
+        if let Some(tab_idx) = self.splits.close() {
+            self.select_tab(tab_idx);
+        }
+    }
+
+    fn focus_next_split(&mut self) {
+        self.splits.set_active_tab(self.active_idx);
+        if let Some(tab_idx) = self.splits.focus_next() {
+            self.select_tab(tab_idx);
+        }
+    }
+
+    fn focus_prev_split(&mut self) {
+        self.splits.set_active_tab(self.active_idx);
+        if let Some(tab_idx) = self.splits.focus_prev() {
+            self.select_tab(tab_idx);
+        }
+    }
+
+    /// Show tabs in a sidebar with the given config, or in the tab bar when `None`.
+    pub(crate) fn set_sidebar(&mut self, config: Option<TabSidebar>) {
+        match (config, &mut self.sidebar) {
+            (None, sidebar) => *sidebar = None,
+            (Some(TabSidebar { position, width }), Some(sidebar)) => {
+                sidebar.set_config(position, width.max(1))
+            }
+            (Some(TabSidebar { position, width }), sidebar @ None) => {
+                *sidebar = Some(Sidebar::new(position, width.max(1)))
+            }
+        }
+    }
+
+    /// The sidebar, if enabled and the screen is wide enough to show it. Otherwise the tab bar is
+    /// shown.
+    fn shown_sidebar(&self) -> Option<&Sidebar> {
+        self.sidebar
+            .as_ref()
+            .filter(|sidebar| sidebar.width() * 2 <= self.width)
+    }
+
+    /// Area of the message area, split into splits.
+    fn splits_rect(&self) -> Rect {
+        match self.shown_sidebar() {
+            None => Rect {
+                x: 0,
+                y: 0,
+                width: self.width,
+                height: self.height - 1,
+            },
+            Some(sidebar) => Rect {
+                x: match sidebar.position() {
+                    SidebarPosition::Left => sidebar.width(),
+                    SidebarPosition::Right => 0,
+                },
+                y: 0,
+                width: self.width - sidebar.width(),
+                height: self.height,
+            },
+        }
+    }
+
+    fn sidebar_rect(&self, sidebar: &Sidebar) -> Rect {
+        Rect {
+            x: match sidebar.position() {
+                SidebarPosition::Left => 0,
+                SidebarPosition::Right => self.width - sidebar.width(),
+            },
+            y: 0,
+            width: sidebar.width(),
+            height: self.height,
+        }
     }
 
     /// Returns index of the new tab if a new tab is created.
@@ -466,7 +796,11 @@ impl TUI {
 
     /// Closes a server tab and all associated channel tabs.
     pub(crate) fn close_server_tab(&mut self, serv: &str) {
+        if serv == "transfers" {
+            self.transfers.clear();
+        }
         if let Some(tab_idx) = self.find_serv_tab_idx(serv) {
+            self.remove_split_tabs(|_, tab| tab.src.serv_name() == serv);
             self.tabs.retain(|tab: &Tab| tab.src.serv_name() != serv);
             if self.active_idx == tab_idx {
                 self.select_tab(if tab_idx == 0 { 0 } else { tab_idx - 1 });
@@ -508,6 +842,7 @@ impl TUI {
 
     pub(crate) fn close_chan_tab(&mut self, serv: &str, chan: &ChanNameRef) {
         if let Some(tab_idx) = self.find_chan_tab_idx(serv, chan) {
+            self.remove_split_tabs(|idx, _| idx == tab_idx);
             self.tabs.remove(tab_idx);
             if self.active_idx == tab_idx {
                 self.select_tab(if tab_idx == 0 { 0 } else { tab_idx - 1 });
@@ -546,6 +881,46 @@ impl TUI {
 
     pub(crate) fn close_user_tab(&mut self, serv: &str, nick: &str) {
         if let Some(tab_idx) = self.find_user_tab_idx(serv, nick) {
+            self.remove_split_tabs(|idx, _| idx == tab_idx);
+            self.tabs.remove(tab_idx);
+            if self.active_idx == tab_idx {
+                self.select_tab(if tab_idx == 0 { 0 } else { tab_idx - 1 });
+            }
+        }
+        self.fix_scroll_after_close();
+    }
+
+    /// Create a tab for a DCC CHAT with `nick`. The tab is added after the tabs of the server the
+    /// chat was initiated from.
+    pub(crate) fn new_dcc_chat_tab(&mut self, serv: &str, nick: &str) -> Option<usize> {
+        match self.find_dcc_chat_tab_idx(serv, nick) {
+            None => match self.find_last_serv_tab_idx(serv) {
+                None => {
+                    self.new_server_tab(serv, None);
+                    self.new_dcc_chat_tab(serv, nick)
+                }
+                Some(tab_idx) => {
+                    self.new_tab(
+                        tab_idx + 1,
+                        MsgSource::DccChat {
+                            serv: serv.to_owned(),
+                            nick: nick.to_owned(),
+                        },
+                        None,
+                    );
+                    if let Some(nick) = self.tabs[tab_idx].widget.get_nick() {
+                        self.tabs[tab_idx + 1].widget.set_nick(nick);
+                    }
+                    Some(tab_idx + 1)
+                }
+            },
+            Some(_) => None,
+        }
+    }
+
+    pub(crate) fn close_dcc_chat_tab(&mut self, serv: &str, nick: &str) {
+        if let Some(tab_idx) = self.find_dcc_chat_tab_idx(serv, nick) {
+            self.remove_split_tabs(|idx, _| idx == tab_idx);
             self.tabs.remove(tab_idx);
             if self.active_idx == tab_idx {
                 self.select_tab(if tab_idx == 0 { 0 } else { tab_idx - 1 });
@@ -686,6 +1061,25 @@ impl TUI {
                 from: self.tabs[self.active_idx].src.clone(),
             }),
 
+            WidgetRet::OpenUrl(url) => {
+                if let Err(err) = open_url(&self.url_open_command, &url) {
+                    self.add_client_err_msg(
+                        &format!("Can't open link with `{}`: {err}", self.url_open_command),
+                        &MsgTarget::CurrentTab,
+                    );
+                }
+                None
+            }
+
+            WidgetRet::CopyUrl(url) => {
+                self.tb.copy_to_clipboard(&url);
+                self.add_client_notify_msg(
+                    &format!("Copied {url} to the clipboard."),
+                    &MsgTarget::CurrentTab,
+                );
+                None
+            }
+
             WidgetRet::Remove => unimplemented!(),
         }
     }
@@ -718,6 +1112,38 @@ impl TUI {
 
             KeyAction::TabGoto(c) => self.go_to_tab(c),
 
+            KeyAction::NickListToggle => self.toggle_nick_list(),
+
+            KeyAction::SplitHorizontal => self.split(SplitDir::Horizontal),
+
+            KeyAction::SplitVertical => self.split(SplitDir::Vertical),
+
+            KeyAction::SplitClose => self.close_split(),
+
+            KeyAction::SplitFocusNext => self.focus_next_split(),
+
+            KeyAction::SplitFocusPrev => self.focus_prev_split(),
+
+            KeyAction::TabCollapse => self.toggle_collapse(),
+
+            KeyAction::TabNextHighlight => self.next_activity_tab(TabStyle::Highlight),
+
+            KeyAction::TabNextActivity => self.next_activity_tab(TabStyle::JoinOrPart),
+
+            KeyAction::SidebarScrollUp => {
+                if let Some(sidebar) = &mut self.sidebar {
+                    sidebar.scroll_up();
+                }
+            }
+
+            KeyAction::SidebarScrollDown => {
+                if let Some(sidebar) = &mut self.sidebar {
+                    sidebar.scroll_down();
+                }
+            }
+
+            KeyAction::UrlPicker => self.open_url_picker(),
+
             _ => {}
         }
     }
@@ -744,8 +1170,17 @@ impl TUI {
     }
 
     fn resize_(&mut self) {
-        for tab in &mut self.tabs {
-            tab.widget.resize(self.width, self.height - 1);
+        // Tabs that are not shown in a split are resized to the full size
+        let full_rect = self.splits_rect();
+        self.splits.set_active_tab(self.active_idx);
+        let (splits, _) = self.splits.layout(full_rect);
+        for (tab_idx, tab) in self.tabs.iter_mut().enumerate() {
+            let rect = splits
+                .iter()
+                .find(|split| split.tab_idx == tab_idx)
+                .and_then(|split| split.rect)
+                .unwrap_or(full_rect);
+            tab.widget.resize(rect.width, rect.height);
         }
         // scroll the tab bar so that currently active tab is still visible
         let (mut tab_left, mut tab_right) = self.rendered_tabs();
@@ -809,6 +1244,18 @@ impl TUI {
             .unwrap_or_default()
     }
 
+    pub(crate) fn is_highlight(
+        &self,
+        serv_name: &str,
+        chan_name: Option<&ChanNameRef>,
+        sender: &str,
+        msg: &str,
+        mentions_nick: bool,
+    ) -> bool {
+        self.highlights
+            .is_highlight(serv_name, chan_name, sender, msg, mentions_nick)
+    }
+
     pub(crate) fn set_tab_config(
         &mut self,
         serv_name: &str,
@@ -913,10 +1360,49 @@ impl TUI {
             return;
         }
 
-        self.tabs[self.active_idx]
-            .widget
-            .draw(&mut self.tb, &self.colors, 0, 0);
+        self.update_unread_markers();
+        self.draw_splits();
 
+        let sidebar_rect = self
+            .shown_sidebar()
+            .map(|sidebar| self.sidebar_rect(sidebar));
+        match (sidebar_rect, &mut self.sidebar) {
+            (Some(rect), Some(sidebar)) => sidebar.draw(
+                &mut self.tb,
+                &self.colors,
+                &self.tabs,
+                self.active_idx,
+                rect,
+            ),
+            _ => self.draw_tab_bar(),
+        }
+
+        self.tb.present();
+    }
+
+    /// Move the unread marker of the tab we left since the last `draw`, unless the tab is still
+    /// shown in a split. Clear markers of shown tabs that no lines were added after.
+    fn update_unread_markers(&mut self) {
+        self.splits.set_active_tab(self.active_idx);
+        for (tab_idx, tab) in self.tabs.iter_mut().enumerate() {
+            if self.splits.shows_tab(tab_idx) {
+                tab.widget.clear_pending_unread_marker();
+            }
+        }
+
+        let active_src = &self.tabs[self.active_idx].src;
+        if self.last_active_src.as_ref() == Some(active_src) {
+            return;
+        }
+        if let Some(last_src) = self.last_active_src.replace(active_src.clone())
+            && let Some(tab_idx) = self.tabs.iter().position(|tab| tab.src == last_src)
+            && !self.splits.shows_tab(tab_idx)
+        {
+            self.tabs[tab_idx].widget.set_unread_marker();
+        }
+    }
+
+    fn draw_tab_bar(&mut self) {
         // decide whether we need to draw left/right arrows in tab bar
         let left_arr = self.draw_left_arrow();
         let right_arr = self.draw_right_arrow();
@@ -952,8 +1438,46 @@ impl TUI {
             self.tb
                 .change_cell(pos_x, self.height - 1, RIGHT_ARROW, style.fg, style.bg);
         }
+    }
 
-        self.tb.present();
+    fn draw_splits(&mut self) {
+        self.splits.set_active_tab(self.active_idx);
+        let (splits, separators) = self.splits.layout(self.splits_rect());
+
+        let style = self.colors.faded;
+        for separator in separators {
+            let Rect {
+                x,
+                y,
+                width,
+                height,
+            } = separator.rect;
+            let ch = match separator.dir {
+                SplitDir::Horizontal => '─',
+                SplitDir::Vertical => '│',
+            };
+            for x in x..x + width {
+                for y in y..y + height {
+                    self.tb.change_cell(x, y, ch, style.fg, style.bg);
+                }
+            }
+        }
+
+        // Draw the focused split last, to show its cursor
+        let mut splits: Vec<(usize, Rect)> = splits
+            .into_iter()
+            .filter_map(|split| split.rect.map(|rect| (split.tab_idx, rect)))
+            .collect();
+        splits.sort_by_key(|(tab_idx, _)| *tab_idx == self.active_idx);
+
+        for (tab_idx, rect) in splits {
+            let tab = &mut self.tabs[tab_idx];
+            if tab.widget.size() != (rect.width, rect.height) {
+                tab.widget.resize(rect.width, rect.height);
+            }
+            tab.set_style(TabStyle::Normal);
+            tab.widget.draw(&mut self.tb, &self.colors, rect.x, rect.y);
+        }
     }
 
     ////////////////////////////////////////////////////////////////////////////
@@ -1025,6 +1549,28 @@ impl TUI {
         self.h_scroll -= scroll_left;
     }
 
+    /// Search messages of the current tab.
+    fn search(&mut self, query: &str) {
+        match self.tabs[self.active_idx].widget.search(query) {
+            Ok(true) => {}
+            Ok(false) => {
+                self.add_client_notify_msg(
+                    &format!("No matches for {query}"),
+                    &MsgTarget::CurrentTab,
+                );
+            }
+            Err(_) => {
+                self.add_client_err_msg(&format!("Invalid regex: {query}"), &MsgTarget::CurrentTab);
+            }
+        }
+    }
+
+    fn open_url_picker(&mut self) {
+        if !self.tabs[self.active_idx].widget.open_url_picker() {
+            self.add_client_notify_msg("No links in this tab.", &MsgTarget::CurrentTab);
+        }
+    }
+
     pub(crate) fn switch(&mut self, string: &str) {
         let mut next_idx = self.active_idx;
         for (tab_idx, tab) in self.tabs.iter().enumerate() {
@@ -1042,7 +1588,7 @@ impl TUI {
                         break;
                     }
                 }
-                MsgSource::User { ref nick, .. } => {
+                MsgSource::User { ref nick, .. } | MsgSource::DccChat { ref nick, .. } => {
                     if nick.contains(string) {
                         next_idx = tab_idx;
                         break;
@@ -1112,11 +1658,13 @@ impl TUI {
                 while insert_idx > 0 && !self.is_server_tab(insert_idx) {
                     insert_idx -= 1;
                 }
+                self.move_split_tabs(left..right, insert_idx);
                 let to_move: Vec<Tab> = self.tabs.drain(left..right).collect();
                 self.tabs.splice(insert_idx..insert_idx, to_move);
                 self.select_tab(insert_idx);
             }
         } else if !self.is_server_tab(self.active_idx - 1) {
+            self.move_split_tabs(self.active_idx..self.active_idx + 1, self.active_idx - 1);
             let tab = self.tabs.remove(self.active_idx);
             self.tabs.insert(self.active_idx - 1, tab);
             let active_idx = self.active_idx - 1;
@@ -1134,11 +1682,13 @@ impl TUI {
             if right < self.tabs.len() {
                 let right_next = self.server_tab_range(right).1;
                 let insert_idx = right_next - (right - left);
+                self.move_split_tabs(left..right, insert_idx);
                 let to_move: Vec<Tab> = self.tabs.drain(left..right).collect();
                 self.tabs.splice(insert_idx..insert_idx, to_move);
                 self.select_tab(insert_idx);
             }
         } else if !self.is_server_tab(self.active_idx + 1) {
+            self.move_split_tabs(self.active_idx..self.active_idx + 1, self.active_idx + 1);
             let tab = self.tabs.remove(self.active_idx);
             self.tabs.insert(self.active_idx + 1, tab);
             let active_idx = self.active_idx + 1;
@@ -1237,6 +1787,12 @@ impl TUI {
                 }
             }
 
+            MsgTarget::DccChat { serv, nick } => {
+                if let Some(tab_idx) = self.find_dcc_chat_tab_idx(serv, nick) {
+                    target_idxs.push(tab_idx);
+                }
+            }
+
             MsgTarget::AllServTabs { serv } => {
                 for (tab_idx, tab) in self.tabs.iter().enumerate() {
                     if tab.src.serv_name() == serv {
@@ -1259,7 +1815,8 @@ impl TUI {
         }
 
         for tab_idx in target_idxs {
-            f(&mut self.tabs[tab_idx], self.active_idx == tab_idx);
+            let shown = self.active_idx == tab_idx || self.splits.shows_tab(tab_idx);
+            f(&mut self.tabs[tab_idx], shown);
         }
     }
 
@@ -1273,6 +1830,8 @@ impl TUI {
 
             MsgTarget::User { serv, nick } => self.new_user_tab(serv, nick),
 
+            MsgTarget::DccChat { serv, nick } => self.new_dcc_chat_tab(serv, nick),
+
             _ => None,
         }
     }
@@ -1285,9 +1844,16 @@ impl TUI {
             )
             .ignore
             .unwrap_or_default();
+        self.style_seq += 1;
+        let style_seq = self.style_seq;
         self.apply_to_target(target, false, &mut |tab: &mut Tab, is_active: bool| {
-            if (!ignore || style != TabStyle::JoinOrPart) && tab.style < style && !is_active {
+            if is_active {
+                return;
+            }
+            tab.count_msg(style);
+            if (!ignore || style != TabStyle::JoinOrPart) && tab.style < style {
                 tab.set_style(style);
+                tab.style_seq = style_seq;
             }
         });
     }
@@ -1317,7 +1883,8 @@ impl TUI {
     }
 
     /// privmsg is a message coming from a server or client. Shown with sender's
-    /// nick/name and receive time and logged.
+    /// nick/name and receive time and logged. Private messages are not drawn as
+    /// highlights, `highlight` only decides whether they're notified.
     pub(crate) fn add_privmsg(
         &mut self,
         sender: &str,
@@ -1334,9 +1901,10 @@ impl TUI {
         } else {
             Notifier::default()
         };
+        let draw_highlight = highlight && !matches!(target, MsgTarget::User { .. });
         self.apply_to_target(target, true, &mut |tab: &mut Tab, _| {
             tab.widget
-                .add_privmsg(sender, msg, Timestamp::from(ts), highlight, is_action);
+                .add_privmsg(sender, msg, Timestamp::from(ts), draw_highlight, is_action);
             let nick = tab.widget.get_nick();
             if let Some(nick_) = nick {
                 notifier.notify_privmsg(sender, msg, target, &nick_, highlight);
@@ -1388,6 +1956,19 @@ impl TUI {
         });
     }
 
+    /// Add or remove a membership prefix (e.g. `@` for ops) of a nick, shown in nick lists.
+    pub(crate) fn update_nick_prefix(
+        &mut self,
+        nick: &str,
+        prefix: char,
+        add: bool,
+        target: &MsgTarget,
+    ) {
+        self.apply_to_target(target, false, &mut |tab: &mut Tab, _| {
+            tab.widget.update_nick_prefix(nick, prefix, add);
+        });
+    }
+
     pub(crate) fn remove_nick(&mut self, nick: &str, ts: Option<Tm>, target: &MsgTarget) {
         let ignore = self
             .get_tab_config(
@@ -1402,6 +1983,41 @@ impl TUI {
         });
     }
 
+    pub(crate) fn add_netsplit(
+        &mut self,
+        servers: (&str, &str),
+        nicks: &[String],
+        ts: Tm,
+        target: &MsgTarget,
+    ) {
+        let ignore = self
+            .get_tab_config(
+                target.serv_name().unwrap_or_default(),
+                target.chan_or_user_name(),
+            )
+            .ignore
+            .unwrap_or_default();
+
+        self.apply_to_target(target, false, &mut |tab: &mut Tab, _| {
+            tab.widget
+                .netsplit(servers, nicks, Timestamp::from(ts), ignore);
+        });
+    }
+
+    pub(crate) fn add_netjoin(&mut self, nicks: &[String], ts: Tm, target: &MsgTarget) {
+        let ignore = self
+            .get_tab_config(
+                target.serv_name().unwrap_or_default(),
+                target.chan_or_user_name(),
+            )
+            .ignore
+            .unwrap_or_default();
+
+        self.apply_to_target(target, false, &mut |tab: &mut Tab, _| {
+            tab.widget.netjoin(nicks, Timestamp::from(ts), ignore);
+        });
+    }
+
     pub(crate) fn rename_nick(
         &mut self,
         old_nick: &str,
@@ -1428,6 +2044,68 @@ impl TUI {
         });
     }
 
+    /// Add or update the line of a file transfer in the "transfers" tab. The tab is created if it
+    /// doesn't exist.
+    pub(crate) fn set_transfer(&mut self, id: usize, line: &str) {
+        self.transfers.insert(id, line.to_owned());
+        self.new_server_tab("transfers", None);
+        self.update_transfers_tab();
+    }
+
+    /// Remove the line of a file transfer from the "transfers" tab.
+    pub(crate) fn remove_transfer(&mut self, id: usize) {
+        if self.transfers.remove(&id).is_some() {
+            self.update_transfers_tab();
+        }
+    }
+
+    fn update_transfers_tab(&mut self) {
+        if let Some(tab_idx) = self.find_serv_tab_idx("transfers") {
+            let widget = &mut self.tabs[tab_idx].widget;
+            widget.clear();
+            for line in self.transfers.values() {
+                widget.add_client_msg(line);
+            }
+        }
+    }
+
+    /// Show an empty channel list in the server tab and switch to the tab. `pattern` and
+    /// `min_users` are the filters of the `/list` command.
+    pub(crate) fn open_chan_list(
+        &mut self,
+        serv: &str,
+        pattern: Option<&str>,
+        min_users: Option<usize>,
+    ) {
+        if let Some(tab_idx) = self.find_serv_tab_idx(serv) {
+            self.tabs[tab_idx].widget.open_chan_list(pattern, min_users);
+            self.select_tab(tab_idx);
+        }
+    }
+
+    /// Add a channel to the channel list of the server tab. The list is shown if it's not already,
+    /// unless it was closed before the server sent the whole list.
+    pub(crate) fn add_chan_list_item(
+        &mut self,
+        serv: &str,
+        chan: &ChanNameRef,
+        users: usize,
+        topic: &str,
+    ) {
+        if let Some(tab_idx) = self.find_serv_tab_idx(serv) {
+            self.tabs[tab_idx]
+                .widget
+                .add_chan_list_item(chan, users, topic);
+        }
+    }
+
+    /// Mark the channel list of the server tab as complete.
+    pub(crate) fn chan_list_done(&mut self, serv: &str) {
+        if let Some(tab_idx) = self.find_serv_tab_idx(serv) {
+            self.tabs[tab_idx].widget.chan_list_done();
+        }
+    }
+
     pub(crate) fn clear(&mut self, target: &MsgTarget) {
         self.apply_to_target(target, false, &mut |tab: &mut Tab, _| tab.widget.clear());
     }
@@ -1506,6 +2184,18 @@ impl TUI {
         None
     }
 
+    fn find_dcc_chat_tab_idx(&self, serv_: &str, nick_: &str) -> Option<usize> {
+        for (tab_idx, tab) in self.tabs.iter().enumerate() {
+            if let MsgSource::DccChat { ref serv, ref nick } = tab.src
+                && serv_ == serv
+                && nick_ == nick
+            {
+                return Some(tab_idx);
+            }
+        }
+        None
+    }
+
     /// Index of the last tab with the given server name.
     fn find_last_serv_tab_idx(&self, serv: &str) -> Option<usize> {
         for (tab_idx, tab) in self.tabs.iter().enumerate().rev() {
@@ -1519,7 +2209,7 @@ impl TUI {
     fn is_server_tab(&self, idx: usize) -> bool {
         match self.tabs[idx].src {
             MsgSource::Serv { .. } => true,
-            MsgSource::Chan { .. } | MsgSource::User { .. } => false,
+            MsgSource::Chan { .. } | MsgSource::User { .. } | MsgSource::DccChat { .. } => false,
         }
     }
 
@@ -0,0 +1,224 @@
+//! Detecting links in messages, and a picker for opening the links of a tab (`/urls`). Shown on
+//! top of the messages.
+
+use std::io;
+use std::ops::Range;
+use std::process::{Command, Stdio};
+use std::sync::LazyLock;
+
+use regex::Regex;
+use termbox_simple::Termbox;
+
+use crate::config::Colors;
+use crate::key_map::KeyAction;
+use crate::utils::draw_str;
+use crate::widget::WidgetRet;
+
+static URL_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)\b(https?|ftp)://[^\s<>"]+"#).unwrap());
+
+/// Byte ranges of the links in `text`.
+pub(crate) fn find_urls(text: &str) -> Vec<Range<usize>> {
+    URL_REGEX
+        .find_iter(text)
+        .map(|m| m.start()..m.start() + trim_url(m.as_str()).len())
+        .collect()
+}
+
+/// Remove punctuation at the end of a link, e.g. the period in "See https://example.com.", or the
+/// parenthesis in "(https://example.com)". Parentheses that are part of the link are kept, as in
+/// "https://en.wikipedia.org/wiki/Tiny_(disambiguation)".
+fn trim_url(mut url: &str) -> &str {
+    while let Some(last) = url.chars().last() {
+        let trim = match last {
+            '.' | ',' | ':' | ';' | '!' | '?' | '\'' => true,
+            ')' => url.matches('(').count() < url.matches(')').count(),
+            ']' => url.matches('[').count() < url.matches(']').count(),
+            _ => false,
+        };
+        if !trim {
+            break;
+        }
+        url = &url[..url.len() - last.len_utf8()];
+    }
+    url
+}
+
+/// Open `url` with `command`. `command` is split into words, the first word is the program to
+/// run. `url` is passed as the last argument.
+pub(crate) fn open_url(command: &str, url: &str) -> io::Result<()> {
+    let mut words = command.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+    let mut child = Command::new(program)
+        .args(words)
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // Reap the process when it exits. Commands like `xdg-open` return quickly, but a browser may
+    // keep running.
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+pub(crate) struct UrlPicker {
+    width: i32,
+    height: i32,
+
+    /// Links of the tab, most recent first.
+    urls: Vec<String>,
+
+    /// Index of the selected link in `urls`.
+    selected: usize,
+
+    /// Index of the first link in `urls` that is drawn.
+    scroll: usize,
+}
+
+impl UrlPicker {
+    pub(crate) fn new(width: i32, height: i32, urls: Vec<String>) -> UrlPicker {
+        UrlPicker {
+            width,
+            height,
+            urls,
+            selected: 0,
+            scroll: 0,
+        }
+    }
+
+    pub(crate) fn resize(&mut self, width: i32, height: i32) {
+        self.width = width;
+        self.height = height;
+        self.fix_scroll();
+    }
+
+    /// Number of lines for the links. Last line shows the status.
+    fn list_height(&self) -> usize {
+        (self.height - 1).max(0) as usize
+    }
+
+    /// Update `scroll` to make the selected link visible.
+    fn fix_scroll(&mut self) {
+        let height = self.list_height();
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if height > 0 && self.selected >= self.scroll + height {
+            self.scroll = self.selected + 1 - height;
+        }
+    }
+
+    fn select(&mut self, selected: usize) {
+        self.selected = selected.min(self.urls.len().saturating_sub(1));
+        self.fix_scroll();
+    }
+
+    pub(crate) fn keypressed(&mut self, key_action: &KeyAction) -> WidgetRet {
+        match key_action {
+            KeyAction::Cancel => WidgetRet::Remove,
+            KeyAction::InputPrevEntry => {
+                self.select(self.selected.saturating_sub(1));
+                WidgetRet::KeyHandled
+            }
+            KeyAction::InputNextEntry => {
+                self.select(self.selected + 1);
+                WidgetRet::KeyHandled
+            }
+            KeyAction::MessagesPageUp => {
+                self.select(self.selected.saturating_sub(self.list_height()));
+                WidgetRet::KeyHandled
+            }
+            KeyAction::MessagesPageDown => {
+                self.select(self.selected + self.list_height());
+                WidgetRet::KeyHandled
+            }
+            KeyAction::MessagesScrollTop => {
+                self.select(0);
+                WidgetRet::KeyHandled
+            }
+            KeyAction::MessagesScrollBottom => {
+                self.select(self.urls.len());
+                WidgetRet::KeyHandled
+            }
+            KeyAction::InputSend => match self.urls.get(self.selected) {
+                Some(url) => WidgetRet::OpenUrl(url.clone()),
+                None => WidgetRet::KeyHandled,
+            },
+            KeyAction::Input('c') => match self.urls.get(self.selected) {
+                Some(url) => WidgetRet::CopyUrl(url.clone()),
+                None => WidgetRet::KeyHandled,
+            },
+            // Don't let the keys edit the input field under the picker
+            KeyAction::Input(_) => WidgetRet::KeyHandled,
+            _ => WidgetRet::KeyIgnored,
+        }
+    }
+
+    pub(crate) fn draw(&self, tb: &mut Termbox, colors: &Colors, pos_x: i32, pos_y: i32) {
+        let max_x = pos_x + self.width;
+
+        for (row, url) in self
+            .urls
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(self.list_height())
+        {
+            let y = pos_y + (row - self.scroll) as i32;
+            let style = if row == self.selected {
+                colors.completion
+            } else {
+                colors.user_msg
+            };
+            let x = draw_str(tb, pos_x, y, max_x, &format!("{} ", row + 1), colors.faded);
+            draw_str(tb, x, y, max_x, url, style);
+        }
+
+        let status_y = pos_y + self.height - 1;
+        let status = format!(
+            "{}/{} links, enter: open, c: copy ",
+            self.selected + 1,
+            self.urls.len()
+        );
+        let x = draw_str(tb, pos_x, status_y, max_x, &status, colors.exit_dialogue);
+        draw_str(
+            tb,
+            x,
+            status_y,
+            max_x,
+            &" ".repeat(self.width.max(0) as usize),
+            colors.exit_dialogue,
+        );
+        tb.hide_cursor();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_urls() {
+        fn urls(text: &str) -> Vec<&str> {
+            find_urls(text)
+                .into_iter()
+                .map(|range| &text[range])
+                .collect()
+        }
+        assert_eq!(
+            urls("see https://example.com/a?b=c#d, and HTTP://example.org."),
+            vec!["https://example.com/a?b=c#d", "HTTP://example.org"]
+        );
+        assert_eq!(
+            urls("(https://en.wikipedia.org/wiki/Tiny_(disambiguation))"),
+            vec!["https://en.wikipedia.org/wiki/Tiny_(disambiguation)"]
+        );
+        assert_eq!(
+            urls("<ftp://example.com/file>"),
+            vec!["ftp://example.com/file"]
+        );
+        assert_eq!(urls("no links: example.com, https:/x"), Vec::<&str>::new());
+    }
+}
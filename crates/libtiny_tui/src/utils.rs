@@ -1,3 +1,8 @@
+use termbox_simple::Termbox;
+use unicode_width::UnicodeWidthChar;
+
+use crate::config::Style;
+
 pub(crate) struct InsertIterator<'iter, A: 'iter> {
     insert_point: usize,
     current_idx: usize,
@@ -52,3 +57,28 @@ pub(crate) fn is_nick_char(c: char) -> bool {
         || c == '-' // not valid according to RFC 2812 but servers accept it and I've seen nicks with
     // this char in the wild
 }
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Draw `str` starting at column `x`, up to column `max_x`. Returns the column after the string.
+pub(crate) fn draw_str(
+    tb: &mut Termbox,
+    mut x: i32,
+    y: i32,
+    max_x: i32,
+    str: &str,
+    style: Style,
+) -> i32 {
+    for c in str.chars() {
+        let width = c.width().unwrap_or(0) as i32;
+        if width == 0 {
+            continue;
+        }
+        if x + width > max_x {
+            break;
+        }
+        tb.change_cell(x, y, c, style.fg, style.bg);
+        x += width;
+    }
+    x
+}
@@ -11,6 +11,12 @@ pub(crate) enum WidgetRet {
     /// A command is ran.
     Command(String),
 
+    /// Open the link with the configured command.
+    OpenUrl(String),
+
+    /// Copy the link to the clipboard.
+    CopyUrl(String),
+
     /// Remove the widget. E.g. close the tab, hide the dialogue etc.
     Remove,
 }
@@ -47,6 +47,15 @@ where
     format!("JOIN {}\r\n", chans.join(","))
 }
 
+/// `params` are the filters for the channel list, see ELIST in ISUPPORT.
+pub fn list(params: &[String]) -> String {
+    if params.is_empty() {
+        "LIST\r\n".to_string()
+    } else {
+        format!("LIST {}\r\n", params.join(","))
+    }
+}
+
 pub fn part(chan: &ChanNameRef, reason: Option<String>) -> String {
     match reason {
         None => format!("PART {}\r\n", chan.display()),
@@ -67,6 +76,12 @@ pub fn action(msgtarget: &str, msg: &str) -> String {
     format!("PRIVMSG {msgtarget} :\x01ACTION {msg}\x01\r\n")
 }
 
+/// A CTCP request, e.g. `ctcp("nick", "DCC SEND ...")`.
+pub fn ctcp(msgtarget: &str, msg: &str) -> String {
+    assert!(msgtarget.len() + msg.len() + 14 <= 512); // See comments in `privmsg`
+    format!("PRIVMSG {msgtarget} :\x01{msg}\x01\r\n")
+}
+
 pub fn away(msg: Option<&str>) -> String {
     match msg {
         None => "AWAY\r\n".to_string(),
@@ -75,7 +90,7 @@ pub fn away(msg: Option<&str>) -> String {
 }
 
 pub fn cap_ls() -> String {
-    "CAP LS\r\n".to_string()
+    "CAP LS 302\r\n".to_string()
 }
 
 pub fn cap_req(cap_identifiers: &[&str]) -> String {
@@ -204,6 +219,7 @@ pub struct Msg {
 pub enum CTCP {
     Version,
     Action,
+    Dcc,
     Other(String),
 }
 
@@ -212,6 +228,7 @@ impl CTCP {
         match s {
             "VERSION" => CTCP::Version,
             "ACTION" => CTCP::Action,
+            "DCC" => CTCP::Dcc,
             _ => CTCP::Other(s.to_owned()),
         }
     }
@@ -250,6 +267,20 @@ pub enum Cmd {
         chans: Vec<ChanName>,
     },
 
+    KICK {
+        chan: ChanName,
+        /// The nick kicked from the channel.
+        nick: String,
+        msg: Option<String>,
+    },
+
+    INVITE {
+        /// The invited nick. Usually our nick, but with the `invite-notify` capability we also
+        /// get invites of other users to channels we're in.
+        nick: String,
+        chan: ChanName,
+    },
+
     NICK {
         nick: String,
         /// Channels of the user. Channels of the user that just quit. This is not a part of the
@@ -279,6 +310,9 @@ pub enum Cmd {
         client: String,
         subcommand: String,
         params: Vec<String>,
+        /// Whether the reply continues in the next message. Multi-line replies look like
+        /// `CAP <client> LS * :<caps>`, with `*` in all lines but the last.
+        more: bool,
     },
 
     AUTHENTICATE {
@@ -411,13 +445,22 @@ fn parse_one_message(mut msg: &str) -> Result<Msg, String> {
             }
         }
         MsgType::Cmd("QUIT") if params.is_empty() || params.len() == 1 => {
-            let mb_msg = params.get(1).map(|s| (*s).to_owned());
+            let mb_msg = params.first().map(|s| (*s).to_owned());
 
             Cmd::QUIT {
                 msg: mb_msg,
                 chans: Vec::new(),
             }
         }
+        MsgType::Cmd("KICK") if params.len() == 2 || params.len() == 3 => Cmd::KICK {
+            chan: ChanName::new(params[0].to_owned()),
+            nick: params[1].to_owned(),
+            msg: params.get(2).map(|s| (*s).to_owned()),
+        },
+        MsgType::Cmd("INVITE") if params.len() == 2 => Cmd::INVITE {
+            nick: params[0].to_owned(),
+            chan: ChanName::new(params[1].to_owned()),
+        },
         MsgType::Cmd("NICK") if params.len() == 1 => {
             let nick = params[0];
             Cmd::NICK {
@@ -438,11 +481,17 @@ fn parse_one_message(mut msg: &str) -> Result<Msg, String> {
             chan: ChanName::new(params[0].to_owned()),
             topic: params[1].to_owned(),
         },
-        MsgType::Cmd("CAP") if params.len() == 3 => Cmd::CAP {
-            client: params[0].to_owned(),
-            subcommand: params[1].to_owned(),
-            params: params[2].split(' ').map(|s| s.to_owned()).collect(),
-        },
+        MsgType::Cmd("CAP") if params.len() == 3 || (params.len() == 4 && params[2] == "*") => {
+            Cmd::CAP {
+                client: params[0].to_owned(),
+                subcommand: params[1].to_owned(),
+                params: params[params.len() - 1]
+                    .split(' ')
+                    .map(|s| s.to_owned())
+                    .collect(),
+                more: params.len() == 4,
+            }
+        }
         MsgType::Cmd("AUTHENTICATE") if params.len() == 1 => Cmd::AUTHENTICATE {
             param: params[0].to_owned(),
         },
@@ -525,11 +574,129 @@ pub fn drop_nick_prefix(nick: &str) -> &str {
     }
 }
 
+/// Channel membership modes and their nick prefixes, highest privilege first.
+///
+/// Channel membership prefixes: <http://modern.ircdocs.horse/#channel-membership-prefixes>
+pub const MEMBERSHIP_MODES: [(char, char); 5] =
+    [('q', '~'), ('a', '&'), ('o', '@'), ('h', '%'), ('v', '+')];
+
+/// Split membership prefixes of a nick in a NAMES reply, e.g. `@+nick` to `("@+", "nick")`. Nicks
+/// can have more than one prefix with the `multi-prefix` capability.
+pub fn split_nick_prefixes(nick: &str) -> (&str, &str) {
+    let nick_start = nick
+        .find(|c| !MEMBERSHIP_MODES.iter().any(|(_, prefix)| *prefix == c))
+        .unwrap_or(nick.len());
+    nick.split_at(nick_start)
+}
+
+/// A channel membership mode change in a MODE message.
+#[derive(Debug, PartialEq, Eq)]
+pub struct MembershipModeChange<'a> {
+    /// Whether the mode is set or unset.
+    pub add: bool,
+    pub mode: char,
+    /// Nick prefix of the mode, see `MEMBERSHIP_MODES`.
+    pub prefix: char,
+    pub nick: &'a str,
+}
+
+/// Parse membership mode changes in a channel MODE message, e.g. `MODE #chan +o-v nick1 nick2`.
+/// `params` are the parameters after the mode string. Other modes are skipped.
+pub fn parse_membership_modes<'a>(
+    modes: &str,
+    mut params: &'a [String],
+) -> Vec<MembershipModeChange<'a>> {
+    let mut changes = vec![];
+    let mut add = true;
+    for mode in modes.chars() {
+        match mode {
+            '+' => add = true,
+            '-' => add = false,
+            _ => {
+                if let Some((_, prefix)) = MEMBERSHIP_MODES.iter().find(|(mode_, _)| *mode_ == mode)
+                {
+                    let Some((nick, rest)) = params.split_first() else {
+                        break;
+                    };
+                    params = rest;
+                    changes.push(MembershipModeChange {
+                        add,
+                        mode,
+                        prefix: *prefix,
+                        nick,
+                    });
+                } else if matches!(mode, 'b' | 'e' | 'I' | 'k') || (mode == 'l' && add) {
+                    // Other modes with parameters: bans, ban exceptions, invite exceptions, key,
+                    // and limit (only has a parameter when set)
+                    params = params.get(1..).unwrap_or(&[]);
+                }
+            }
+        }
+    }
+    changes
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::io::Write;
 
+    #[test]
+    fn test_membership_modes() {
+        assert_eq!(split_nick_prefixes("@+nick"), ("@+", "nick"));
+        assert_eq!(split_nick_prefixes("nick"), ("", "nick"));
+
+        let params: Vec<String> = ["*!*@host", "a", "b"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            parse_membership_modes("+bo-v", &params),
+            vec![
+                MembershipModeChange {
+                    add: true,
+                    mode: 'o',
+                    prefix: '@',
+                    nick: "a",
+                },
+                MembershipModeChange {
+                    add: false,
+                    mode: 'v',
+                    prefix: '+',
+                    nick: "b",
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cap_ls_parsing() {
+        let mut buf = vec![];
+        write!(
+            &mut buf,
+            ":x.y.z CAP * LS * :multi-prefix sasl\r\n:x.y.z CAP * LS :sts=port=6697\r\n"
+        )
+        .unwrap();
+        assert_eq!(
+            parse_irc_msg(&mut buf).unwrap().unwrap().cmd,
+            Cmd::CAP {
+                client: "*".to_owned(),
+                subcommand: "LS".to_owned(),
+                params: vec!["multi-prefix".to_owned(), "sasl".to_owned()],
+                more: true,
+            }
+        );
+        assert_eq!(
+            parse_irc_msg(&mut buf).unwrap().unwrap().cmd,
+            Cmd::CAP {
+                client: "*".to_owned(),
+                subcommand: "LS".to_owned(),
+                params: vec!["sts=port=6697".to_owned()],
+                more: false,
+            }
+        );
+    }
+
     #[test]
     fn test_parse_params() {
         assert_eq!(parse_params("p1 p2 p3"), vec!["p1", "p2", "p3"]);
@@ -664,6 +831,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_quit_parsing() {
+        let mut buf = vec![];
+        write!(
+            &mut buf,
+            ":tiny!~tiny@123.123.123.123 QUIT :irc.server1.net irc.server2.net\r\n"
+        )
+        .unwrap();
+        assert_eq!(
+            parse_irc_msg(&mut buf).unwrap().unwrap().cmd,
+            Cmd::QUIT {
+                msg: Some("irc.server1.net irc.server2.net".to_owned()),
+                chans: vec![],
+            }
+        );
+    }
+
     #[test]
     fn test_join_parsing() {
         let mut buf = vec![];
@@ -683,6 +867,64 @@ mod tests {
         assert_eq!(buf.len(), 0);
     }
 
+    #[test]
+    fn test_kick_parsing() {
+        let mut buf = vec![];
+        write!(
+            &mut buf,
+            ":op!~op@192.168.0.1 KICK #haskell tiny :flooding\r\n"
+        )
+        .unwrap();
+        write!(&mut buf, ":op!~op@192.168.0.1 KICK #haskell tiny\r\n").unwrap();
+        let pfx = Some(Pfx::User {
+            nick: "op".to_owned(),
+            user: "~op@192.168.0.1".to_owned(),
+        });
+        assert_eq!(
+            parse_irc_msg(&mut buf).unwrap().unwrap(),
+            Msg {
+                pfx: pfx.clone(),
+                cmd: Cmd::KICK {
+                    chan: ChanName::new("#haskell".to_owned()),
+                    nick: "tiny".to_owned(),
+                    msg: Some("flooding".to_owned()),
+                },
+            }
+        );
+        assert_eq!(
+            parse_irc_msg(&mut buf).unwrap().unwrap(),
+            Msg {
+                pfx,
+                cmd: Cmd::KICK {
+                    chan: ChanName::new("#haskell".to_owned()),
+                    nick: "tiny".to_owned(),
+                    msg: None,
+                },
+            }
+        );
+        assert_eq!(buf.len(), 0);
+    }
+
+    #[test]
+    fn test_invite_parsing() {
+        let mut buf = vec![];
+        write!(&mut buf, ":op!~op@192.168.0.1 INVITE tiny :#haskell\r\n").unwrap();
+        assert_eq!(
+            parse_irc_msg(&mut buf).unwrap().unwrap(),
+            Msg {
+                pfx: Some(Pfx::User {
+                    nick: "op".to_owned(),
+                    user: "~op@192.168.0.1".to_owned(),
+                }),
+                cmd: Cmd::INVITE {
+                    nick: "tiny".to_owned(),
+                    chan: ChanName::new("#haskell".to_owned()),
+                },
+            }
+        );
+        assert_eq!(buf.len(), 0);
+    }
+
     // Example from https://tools.ietf.org/id/draft-oakley-irc-ctcp-01.html
     #[test]
     fn test_ctcp_action_parsing_1() {
@@ -790,6 +1032,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn dcc_ctcp_parsing() {
+        let mut buf = vec![];
+        write!(
+            &mut buf,
+            ":a!b@c PRIVMSG target :\x01DCC SEND \"a b.txt\" 2130706433 1234 100\x01\r\n"
+        )
+        .unwrap();
+        assert_eq!(
+            parse_irc_msg(&mut buf).unwrap().unwrap().cmd,
+            Cmd::PRIVMSG {
+                target: MsgTarget::User("target".to_owned()),
+                msg: "SEND \"a b.txt\" 2130706433 1234 100".to_owned(),
+                is_notice: false,
+                ctcp: Some(CTCP::Dcc),
+            }
+        );
+    }
+
     #[test]
     fn other_ctcp_parsing() {
         let mut buf = vec![];
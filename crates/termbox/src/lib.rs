@@ -47,6 +47,12 @@ pub struct Termbox {
     terminal_cursor: (u16, u16),
     output_buffer: Vec<u8>,
     // total_flushed: u64,
+    /// Whether to send the links of the cells as OSC 8 hyperlinks.
+    hyperlinks: bool,
+    /// Links of the cells in the back buffer. `Cell::link` is an index to this plus one.
+    links: Vec<String>,
+    /// Links of the cells in the front buffer.
+    front_links: Vec<String>,
 }
 
 #[derive(Clone)]
@@ -59,12 +65,15 @@ pub struct Cell {
     pub ch: char,
     pub fg: u16,
     pub bg: u16,
+    /// Link of the cell, see `Termbox::set_cell_link`. 0 when the cell is not a link.
+    pub link: u16,
 }
 
 const EMPTY_CELL: Cell = Cell {
     ch: ' ',
     fg: 0,
     bg: 0,
+    link: 0,
 };
 
 impl CellBuf {
@@ -79,6 +88,7 @@ impl CellBuf {
             cell.ch = ' ';
             cell.fg = u16::from(fg);
             cell.bg = u16::from(bg);
+            cell.link = 0;
         }
     }
 
@@ -167,6 +177,9 @@ impl Termbox {
             terminal_cursor: (0, 0),
             output_buffer: Vec::with_capacity(32 * 1024),
             // total_flushed: 0,
+            hyperlinks: false,
+            links: vec![],
+            front_links: vec![],
         };
 
         termbox.hide_cursor();
@@ -192,6 +205,9 @@ impl Termbox {
             cursor: Some((0, 0)),
             terminal_cursor: (0, 0),
             output_buffer: Vec::with_capacity(32 * 1024),
+            hyperlinks: false,
+            links: vec![],
+            front_links: vec![],
         }
     }
 
@@ -254,6 +270,7 @@ impl Termbox {
             self.buffer_size_change_request = false;
         }
         self.back_buffer.clear(self.clear_fg, self.clear_bg);
+        self.links.clear();
     }
 
     pub fn set_clear_attributes(&mut self, fg: u8, bg: u8) {
@@ -270,6 +287,9 @@ impl Termbox {
             self.buffer_size_change_request = false;
         }
 
+        // Link of the last character sent, 0 when we're not in a hyperlink.
+        let mut open_link = 0;
+
         for y in 0..usize::from(self.term_height) {
             let mut x = 0;
             while x < usize::from(self.term_width) {
@@ -280,7 +300,15 @@ impl Termbox {
                 let cw0 = UnicodeWidthChar::width(back_cell.ch).unwrap_or(1);
                 let cw = std::cmp::max(cw0, 1);
                 // eprintln!("UnicodeWidthChar({:?}) = {}", back_cell.ch, cw);
-                if *front_cell == back_cell {
+                // Link indices are per frame, so compare the links themselves
+                let same_link = link_of(&self.front_links, front_cell.link)
+                    == link_of(&self.links, back_cell.link);
+                if front_cell.ch == back_cell.ch
+                    && front_cell.fg == back_cell.fg
+                    && front_cell.bg == back_cell.bg
+                    && same_link
+                {
+                    front_cell.link = back_cell.link;
                     x += cw;
                     continue;
                 }
@@ -288,6 +316,14 @@ impl Termbox {
 
                 self.send_attr(back_cell.fg, back_cell.bg);
 
+                if self.hyperlinks && back_cell.link != open_link {
+                    open_link = back_cell.link;
+                    send_hyperlink(
+                        &mut self.output_buffer,
+                        link_of(&self.links, open_link).unwrap_or(""),
+                    );
+                }
+
                 if cw > 1 && (x + (cw - 1)) >= usize::from(self.term_width) {
                     // Not enough room for wide ch, send spaces
                     for i in x..usize::from(self.term_width) {
@@ -313,6 +349,11 @@ impl Termbox {
             }
         }
 
+        if open_link != 0 {
+            send_hyperlink(&mut self.output_buffer, "");
+        }
+        self.front_links.clone_from(&self.links);
+
         if let Some((x, y)) = self.cursor {
             goto(&mut self.output_buffer, x + 1, y + 1);
         }
@@ -364,6 +405,41 @@ impl Termbox {
         cell.ch = ch;
         cell.fg = fg;
         cell.bg = bg;
+        cell.link = 0;
+    }
+
+    /// Make the cell a link to `url`. Should be called after `change_cell`, which resets the link
+    /// of the cell. Links are sent to the terminal as OSC 8 hyperlinks when enabled with
+    /// `set_hyperlinks`.
+    pub fn set_cell_link(&mut self, x: i32, y: i32, url: &str) {
+        if !self.hyperlinks {
+            return;
+        }
+        let link = match self.links.iter().rposition(|link| link == url) {
+            Some(idx) => idx + 1,
+            None => {
+                self.links.push(url.to_owned());
+                self.links.len()
+            }
+        };
+        let cell =
+            &mut self.back_buffer.cells[(y as usize) * (self.term_width as usize) + (x as usize)];
+        cell.link = link as u16;
+    }
+
+    /// Enable or disable sending links of the cells as OSC 8 hyperlinks. See also
+    /// `hyperlinks_supported`.
+    pub fn set_hyperlinks(&mut self, hyperlinks: bool) {
+        self.hyperlinks = hyperlinks;
+    }
+
+    /// Copy `text` to the system clipboard with an OSC 52 escape sequence. Terminals that don't
+    /// support it ignore the sequence.
+    pub fn copy_to_clipboard(&mut self, text: &str) {
+        self.output_buffer.extend_from_slice(b"\x1b]52;c;");
+        base64_encode(&mut self.output_buffer, text.as_bytes());
+        self.output_buffer.extend_from_slice(b"\x1b\\");
+        self.flush_output_buffer();
     }
 
     fn flush_output_buffer(&mut self) {
@@ -489,6 +565,65 @@ fn num_to_buf(buf: &mut Vec<u8>, mut num: u16) {
     }
 }
 
+/// Start an OSC 8 hyperlink to `url`, or end the current one when `url` is empty.
+fn send_hyperlink(buf: &mut Vec<u8>, url: &str) {
+    buf.extend_from_slice(b"\x1b]8;;");
+    buf.extend_from_slice(url.as_bytes());
+    buf.extend_from_slice(b"\x1b\\");
+}
+
+/// Link with the given index, see `Cell::link`.
+fn link_of(links: &[String], link: u16) -> Option<&str> {
+    usize::from(link)
+        .checked_sub(1)
+        .and_then(|idx| links.get(idx))
+        .map(String::as_str)
+}
+
+fn base64_encode(buf: &mut Vec<u8>, bytes: &[u8]) {
+    const CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                buf.push(CHARS[((n >> (18 - 6 * i)) & 0x3F) as usize]);
+            } else {
+                buf.push(b'=');
+            }
+        }
+    }
+}
+
+/// Whether the terminal is known to support OSC 8 hyperlinks, based on the environment variables
+/// terminals set.
+pub fn hyperlinks_supported() -> bool {
+    let env = |var: &str| std::env::var(var).unwrap_or_default();
+    if matches!(
+        env("TERM_PROGRAM").as_str(),
+        "iTerm.app" | "WezTerm" | "vscode" | "ghostty"
+    ) {
+        return true;
+    }
+    if env("VTE_VERSION")
+        .parse::<u32>()
+        .is_ok_and(|version| version >= 5000)
+    {
+        return true;
+    }
+    if std::env::var_os("KITTY_WINDOW_ID").is_some() || std::env::var_os("WT_SESSION").is_some() {
+        return true;
+    }
+    let term = env("TERM");
+    ["kitty", "foot", "alacritty", "wezterm"]
+        .iter()
+        .any(|name| term.contains(name))
+}
+
 // Inputs are 1-based
 fn goto(buf: &mut Vec<u8>, x: u16, y: u16) {
     debug_assert!(x > 0 && y > 0);
@@ -541,4 +676,55 @@ impl Termbox {
         self.front_buffer.resize(old_w, old_h, w, h);
         self.front_buffer.clear(self.clear_fg, self.clear_bg);
     }
+
+    /// Returns the link of a cell in the front buffer. Useful when testing.
+    pub fn get_front_link(&self, x: u16, y: u16) -> Option<&str> {
+        let cell =
+            self.front_buffer.cells[usize::from(y) * usize::from(self.term_width) + usize::from(x)];
+        link_of(&self.front_links, cell.link)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64() {
+        let encode = |bytes: &[u8]| {
+            let mut buf = vec![];
+            base64_encode(&mut buf, bytes);
+            String::from_utf8(buf).unwrap()
+        };
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(b"f"), "Zg==");
+        assert_eq!(encode(b"fo"), "Zm8=");
+        assert_eq!(encode(b"foo"), "Zm9v");
+        assert_eq!(
+            encode(b"https://example.com/"),
+            "aHR0cHM6Ly9leGFtcGxlLmNvbS8="
+        );
+    }
+
+    #[test]
+    fn links() {
+        let mut tb = Termbox::init_test(4, 1);
+        tb.set_hyperlinks(true);
+        for (x, ch) in "ab".chars().enumerate() {
+            tb.change_cell(x as i32, 0, ch, 0, 0);
+            tb.set_cell_link(x as i32, 0, "https://a.org");
+        }
+        tb.present();
+        assert_eq!(tb.get_front_link(0, 0), Some("https://a.org"));
+        assert_eq!(tb.get_front_link(1, 0), Some("https://a.org"));
+        assert_eq!(tb.get_front_link(2, 0), None);
+
+        // Same characters with a different link are updated
+        tb.clear();
+        tb.change_cell(0, 0, 'a', 0, 0);
+        tb.set_cell_link(0, 0, "https://b.org");
+        tb.present();
+        assert_eq!(tb.get_front_link(0, 0), Some("https://b.org"));
+        assert_eq!(tb.get_front_link(1, 0), None);
+    }
 }
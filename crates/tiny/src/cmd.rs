@@ -1,16 +1,21 @@
 use crate::config::Defaults;
+use crate::ignore::IgnoreRule;
 use crate::ui::UI;
 use crate::utils;
-use libtiny_client::{Client, ServerInfo};
+use libtiny_client::{Client, ConnectionState, DccId, Endpoint, ServerInfo};
 use libtiny_common::{ChanNameRef, MsgSource, MsgTarget};
 use libtiny_tui::config::Chan;
 
 use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 pub(crate) fn run_cmd(
     cmd: &str,
     src: MsgSource,
     defaults: &Defaults,
+    download_dir: &Path,
+    sts_file: &Path,
     ui: &UI,
     clients: &mut Vec<Client>,
 ) {
@@ -19,6 +24,8 @@ pub(crate) fn run_cmd(
             let cmd_args = CmdArgs {
                 args,
                 defaults,
+                download_dir,
+                sts_file,
                 ui,
                 clients,
                 src,
@@ -61,6 +68,9 @@ fn parse_cmd(cmd: &str) -> Option<ParsedCmd<'_>> {
 struct CmdArgs<'a> {
     args: &'a str,
     defaults: &'a Defaults,
+    download_dir: &'a Path,
+    /// File to read and save STS policies of servers added with `/connect`.
+    sts_file: &'a Path,
     ui: &'a UI,
     clients: &'a mut Vec<Client>,
     src: MsgSource,
@@ -98,20 +108,73 @@ fn find_client<'a>(clients: &'a mut [Client], serv_name: &str) -> Option<&'a mut
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
-static CMDS: [&Cmd; 9] = [
+static CMDS: [&Cmd; 16] = [
+    &ACCEPT_CMD,
     &AWAY_CMD,
     &CLOSE_CMD,
     &CONNECT_CMD,
+    &DCC_CMD,
+    &DISCONNECT_CMD,
+    &IGNORE_CMD,
     &JOIN_CMD,
+    &LIST_CMD,
     &ME_CMD,
     &MSG_CMD,
     &NAMES_CMD,
     &NICK_CMD,
+    &RECONNECT_CMD,
+    &RELOAD_CMD,
     &HELP_CMD,
 ];
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
+static ACCEPT_CMD: Cmd = Cmd {
+    name: "accept",
+    cmd_fn: accept,
+    description: "Joins a channel you're invited to",
+    usage: "`/accept` to accept the last invite, or `/accept <chan>`",
+};
+
+fn accept(args: CmdArgs) {
+    let CmdArgs {
+        args,
+        ui,
+        clients,
+        src,
+        ..
+    } = args;
+
+    let serv = src.serv_name();
+    let chan = args.split_whitespace().next().map(ChanNameRef::new);
+    let client = match find_client(clients, serv) {
+        Some(client) => client,
+        None => {
+            return ui.add_client_err_msg(
+                "Switch to a server tab to accept an invite",
+                &MsgTarget::CurrentTab,
+            );
+        }
+    };
+
+    match client.accept_invite(chan) {
+        Some(chan) => {
+            // Create the tab with the server's default config, same as `/join`
+            let config = ui.get_tab_config(serv, Some(&chan));
+            ui.set_tab_config(serv, Some(&chan), config);
+        }
+        None => {
+            let err = match chan {
+                Some(chan) => format!("You're not invited to {}", chan.display()),
+                None => "No pending invites".to_owned(),
+            };
+            ui.add_client_err_msg(&err, &MsgTarget::CurrentTab);
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
 static AWAY_CMD: Cmd = Cmd {
     name: "away",
     cmd_fn: away,
@@ -151,6 +214,9 @@ fn close(args: CmdArgs) {
         MsgSource::Serv { ref serv } if serv == "mentions" => {
             // ignore
         }
+        MsgSource::Serv { ref serv } if serv == "transfers" => {
+            ui.close_server_tab(serv);
+        }
         MsgSource::Serv { serv } => {
             ui.close_server_tab(&serv);
             let client_idx = find_client_idx(clients, &serv).unwrap();
@@ -174,6 +240,12 @@ fn close(args: CmdArgs) {
         MsgSource::User { serv, nick } => {
             ui.close_user_tab(&serv, &nick);
         }
+        MsgSource::DccChat { serv, nick } => {
+            ui.close_dcc_chat_tab(&serv, &nick);
+            if let Some(client) = find_client(clients, &serv) {
+                client.dcc_chat_close(&nick);
+            }
+        }
     }
 }
 
@@ -190,6 +262,7 @@ fn connect(args: CmdArgs) {
     let CmdArgs {
         args,
         defaults,
+        sts_file,
         ui,
         clients,
         src,
@@ -199,8 +272,8 @@ fn connect(args: CmdArgs) {
 
     match words.len() {
         0 => reconnect(ui, clients, src),
-        1 => connect_(words[0], None, defaults, ui, clients),
-        2 => connect_(words[0], Some(words[1]), defaults, ui, clients),
+        1 => connect_(words[0], None, defaults, sts_file, ui, clients),
+        2 => connect_(words[0], Some(words[1]), defaults, sts_file, ui, clients),
         _ => ui.add_client_err_msg(
             &format!("Usage: {}", CONNECT_CMD.usage),
             &MsgTarget::CurrentTab,
@@ -216,7 +289,11 @@ fn reconnect(ui: &UI, clients: &mut [Client], src: MsgSource) {
                 serv: src.serv_name(),
             },
         );
-        client.reconnect(None);
+        if client.state() == ConnectionState::Disconnected {
+            client.connect();
+        } else {
+            client.reconnect(None);
+        }
     }
 }
 
@@ -224,6 +301,7 @@ fn connect_(
     serv_addr: &str,
     pass: Option<&str>,
     defaults: &Defaults,
+    sts_file: &Path,
     ui: &UI,
     clients: &mut Vec<Client>,
 ) {
@@ -266,9 +344,17 @@ fn connect_(
     ui.add_client_msg("Connecting...", &msg_target);
 
     let (client, rcv_ev) = Client::new(ServerInfo {
-        addr: serv_name.to_owned(),
-        port: serv_port,
-        tls: defaults.tls,
+        name: serv_name.to_owned(),
+        endpoints: vec![Endpoint {
+            addr: serv_name.to_owned(),
+            port: serv_port,
+            tls: defaults.tls,
+        }],
+        connect_timeout: libtiny_client::DEFAULT_CONNECT_TIMEOUT_SECS,
+        prefer_addr_family: None,
+        bind_addr: None,
+        sts_file: Some(sts_file.to_owned()),
+        record_dir: None,
         user: None,
         realname: defaults.realname.clone(),
         pass: pass.map(str::to_owned),
@@ -278,6 +364,8 @@ fn connect_(
             .iter()
             .map(|c| ChanNameRef::new(c).to_owned())
             .collect(),
+        auto_join_on_invite: vec![],
+        rejoin_on_kick: HashMap::new(),
         nickserv_ident: None,
         sasl_auth: None,
     });
@@ -292,6 +380,285 @@ fn connect_(
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
+static DCC_CMD: Cmd = Cmd {
+    name: "dcc",
+    cmd_fn: dcc,
+    description: "Sends and receives files, opens direct chats",
+    usage: "`/dcc send [-passive] <nick> <file>`, `/dcc get <id> [-resume]`, `/dcc list`, `/dcc close <id>`, or `/dcc chat <nick>`",
+};
+
+fn dcc(args: CmdArgs) {
+    let CmdArgs {
+        args,
+        download_dir,
+        ui,
+        clients,
+        src,
+        ..
+    } = args;
+    let usage = || {
+        ui.add_client_err_msg(&format!("Usage: {}", DCC_CMD.usage), &MsgTarget::CurrentTab);
+    };
+
+    let (sub_cmd, args) = match args.split_once(char::is_whitespace) {
+        None => (args, ""),
+        Some((sub_cmd, args)) => (sub_cmd, args.trim()),
+    };
+
+    match sub_cmd {
+        "send" => {
+            let (passive, args) = match args.split_once(char::is_whitespace) {
+                Some(("-passive", args)) => (true, args.trim_start()),
+                _ => (false, args),
+            };
+            let (nick, file) = match args.split_once(char::is_whitespace) {
+                Some((nick, file)) if !file.trim().is_empty() => (nick, file.trim()),
+                _ => return usage(),
+            };
+            let client = match find_client(clients, src.serv_name()) {
+                None => {
+                    return ui.add_client_err_msg(
+                        "Switch to a server tab to send a file",
+                        &MsgTarget::CurrentTab,
+                    );
+                }
+                Some(client) => client,
+            };
+            let path = match file.strip_prefix("~/").zip(dirs::home_dir()) {
+                Some((file, home_dir)) => home_dir.join(file),
+                None => PathBuf::from(file),
+            };
+            match client.dcc_send(nick, &path, passive) {
+                Ok(id) => ui.add_client_msg(
+                    &format!("Offered {} to {} (transfer {})", path.display(), nick, id),
+                    &MsgTarget::CurrentTab,
+                ),
+                Err(err) => ui.add_client_err_msg(&err, &MsgTarget::CurrentTab),
+            }
+        }
+
+        "get" | "close" => {
+            let (id, resume) = match args.split_once(char::is_whitespace) {
+                Some((id, "-resume")) if sub_cmd == "get" => (id, true),
+                _ => (args, false),
+            };
+            let id: DccId = match id.parse() {
+                Ok(id) => id,
+                Err(_) => return usage(),
+            };
+            let client = match clients
+                .iter_mut()
+                .find(|client| client.dcc_transfers().iter().any(|t| t.id == id))
+            {
+                None => {
+                    return ui.add_client_err_msg(
+                        &format!("No DCC transfer with id {id}"),
+                        &MsgTarget::CurrentTab,
+                    );
+                }
+                Some(client) => client,
+            };
+            if sub_cmd == "close" {
+                client.dcc_close(id);
+            } else if let Err(err) = client.dcc_get(id, download_dir, resume) {
+                ui.add_client_err_msg(&err, &MsgTarget::CurrentTab);
+            }
+        }
+
+        "chat" => {
+            if args.is_empty() || args.contains(char::is_whitespace) {
+                return usage();
+            }
+            let nick = args;
+            let client = match find_client(clients, src.serv_name()) {
+                None => {
+                    return ui.add_client_err_msg(
+                        "Switch to a server tab to start a chat",
+                        &MsgTarget::CurrentTab,
+                    );
+                }
+                Some(client) => client,
+            };
+            match client.dcc_chat(nick) {
+                Ok(()) => {
+                    let serv = client.get_serv_name();
+                    ui.new_dcc_chat_tab(serv, nick);
+                    ui.add_client_msg(
+                        "Waiting for connection...",
+                        &MsgTarget::DccChat { serv, nick },
+                    );
+                }
+                Err(err) => ui.add_client_err_msg(&err, &MsgTarget::CurrentTab),
+            }
+        }
+
+        "list" => {
+            let transfers: Vec<_> = clients
+                .iter()
+                .flat_map(|client| client.dcc_transfers())
+                .collect();
+            if transfers.is_empty() {
+                ui.add_client_msg("No DCC transfers", &MsgTarget::CurrentTab);
+            }
+            for transfer in transfers {
+                ui.add_client_msg(
+                    &utils::format_dcc_transfer(&transfer),
+                    &MsgTarget::CurrentTab,
+                );
+            }
+        }
+
+        _ => usage(),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+static DISCONNECT_CMD: Cmd = Cmd {
+    name: "disconnect",
+    cmd_fn: disconnect,
+    description: "Disconnects from the server",
+    usage: "`/disconnect` or `/disconnect <reason>`",
+};
+
+fn disconnect(args: CmdArgs) {
+    let CmdArgs {
+        args, clients, src, ..
+    } = args;
+    if let Some(client) = find_client(clients, src.serv_name()) {
+        if args.is_empty() {
+            client.disconnect(None);
+        } else {
+            client.disconnect(Some(args.to_owned()));
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+static RECONNECT_CMD: Cmd = Cmd {
+    name: "reconnect",
+    cmd_fn: reconnect_cmd,
+    description: "Reconnects to the server",
+    usage: "`/reconnect`",
+};
+
+fn reconnect_cmd(args: CmdArgs) {
+    let CmdArgs {
+        ui, clients, src, ..
+    } = args;
+    reconnect(ui, clients, src)
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+// `/reload` reloads the TUI config in the TUI, and is then passed here.
+static RELOAD_CMD: Cmd = Cmd {
+    name: "reload",
+    cmd_fn: reload,
+    description: "Reloads ignore rules",
+    usage: "`/reload`",
+};
+
+fn reload(args: CmdArgs) {
+    let CmdArgs { ui, .. } = args;
+    let result = ui.ignores().reload();
+    if let Err(err) = result {
+        ui.add_client_err_msg(
+            &format!("Couldn't reload ignore rules: {err}"),
+            &MsgTarget::CurrentTab,
+        );
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+// `/ignore` without arguments is handled by the TUI, it toggles join/part messages of the tab.
+static IGNORE_CMD: Cmd = Cmd {
+    name: "ignore",
+    cmd_fn: ignore,
+    description: "Ignores users",
+    usage: "`/ignore add <nick!user@host> [-types messages,notices,ctcps,joins,invites] [-chan|-global]`, `/ignore del <number>`, or `/ignore list`",
+};
+
+fn ignore(args: CmdArgs) {
+    let CmdArgs { args, ui, src, .. } = args;
+    let (subcmd, args) = args.trim().split_once(' ').unwrap_or((args.trim(), ""));
+    match subcmd {
+        "add" => {
+            // Rules added in the mentions and transfers tabs are for all servers
+            let serv = match src.serv_name() {
+                "mentions" | "transfers" => None,
+                serv => Some(serv),
+            };
+            let rule = match IgnoreRule::from_cmd_args(args, serv, src.chan_name()) {
+                Ok(rule) => rule,
+                Err(err) => {
+                    return ui.add_client_err_msg(
+                        &format!("{}. Usage: {}", err, IGNORE_CMD.usage),
+                        &MsgTarget::CurrentTab,
+                    );
+                }
+            };
+            let msg = format!("Ignoring {rule}");
+            let result = ui.ignores().add(rule);
+            match result {
+                Ok(()) => ui.add_client_msg(&msg, &MsgTarget::CurrentTab),
+                Err(err) => ui.add_client_err_msg(
+                    &format!("{msg}, but couldn't update the config file: {err}"),
+                    &MsgTarget::CurrentTab,
+                ),
+            }
+        }
+        "del" => {
+            let idx = match args.trim().parse::<usize>() {
+                Ok(idx) if idx > 0 => idx - 1,
+                _ => {
+                    return ui.add_client_err_msg(
+                        &format!("Usage: {}", IGNORE_CMD.usage),
+                        &MsgTarget::CurrentTab,
+                    );
+                }
+            };
+            let result = ui.ignores().remove(idx);
+            match result {
+                None => ui.add_client_err_msg(
+                    &format!("No ignore rule {}, see `/ignore list`", idx + 1),
+                    &MsgTarget::CurrentTab,
+                ),
+                Some((rule, Ok(()))) => {
+                    ui.add_client_msg(&format!("Not ignoring {rule}"), &MsgTarget::CurrentTab)
+                }
+                Some((rule, Err(err))) => ui.add_client_err_msg(
+                    &format!("Not ignoring {rule}, but couldn't update the config file: {err}"),
+                    &MsgTarget::CurrentTab,
+                ),
+            }
+        }
+        "list" => {
+            let rules: Vec<String> = ui
+                .ignores()
+                .rules()
+                .iter()
+                .enumerate()
+                .map(|(idx, rule)| format!("{}. {}", idx + 1, rule))
+                .collect();
+            if rules.is_empty() {
+                ui.add_client_msg("No ignore rules", &MsgTarget::CurrentTab);
+            }
+            for rule in rules {
+                ui.add_client_msg(&rule, &MsgTarget::CurrentTab);
+            }
+        }
+        _ => ui.add_client_err_msg(
+            &format!("Usage: {}", IGNORE_CMD.usage),
+            &MsgTarget::CurrentTab,
+        ),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
 static JOIN_CMD: Cmd = Cmd {
     name: "join",
     cmd_fn: join,
@@ -309,7 +676,7 @@ fn join(args: CmdArgs) {
     } = args;
 
     if let MsgSource::Serv { serv } = &src
-        && serv == "mentions"
+        && (serv == "mentions" || serv == "transfers")
     {
         return ui.add_client_err_msg(
             "Switch to a server tab to join a channel",
@@ -335,9 +702,14 @@ fn join(args: CmdArgs) {
             Some(MsgSource::Chan { serv, chan }) => {
                 // Rejoin current tab's channel.
                 let config = ui.get_tab_config(&serv, Some(chan.as_ref()));
-                vec![Chan::WithConfig { name: chan, config }]
+                vec![Chan::WithConfig {
+                    name: chan,
+                    rejoin_on_kick: None,
+                    config,
+                    highlights: vec![],
+                }]
             }
-            Some(MsgSource::Serv { .. } | MsgSource::User { .. }) => {
+            Some(MsgSource::Serv { .. } | MsgSource::User { .. } | MsgSource::DccChat { .. }) => {
                 return ui.add_client_err_msg(
                     &format!("Usage: {}", JOIN_CMD.usage),
                     &MsgTarget::CurrentTab,
@@ -359,7 +731,7 @@ fn join(args: CmdArgs) {
                         let config = ui.get_tab_config(serv, Some(name.as_ref()));
                         ui.set_tab_config(serv, Some(name), config)
                     }
-                    Chan::WithConfig { name, config } => {
+                    Chan::WithConfig { name, config, .. } => {
                         ui.set_tab_config(serv, Some(name), config.to_owned())
                     }
                 }
@@ -375,6 +747,64 @@ fn join(args: CmdArgs) {
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
+static LIST_CMD: Cmd = Cmd {
+    name: "list",
+    cmd_fn: list,
+    description: "Browses channels of the server",
+    usage: "`/list [pattern] [>min_users]`. Type to filter, Tab to sort, Enter to join, Esc to close",
+};
+
+fn list(args: CmdArgs) {
+    let CmdArgs {
+        args,
+        ui,
+        clients,
+        src,
+        ..
+    } = args;
+
+    let (pattern, min_users) = match parse_list_args(args) {
+        Ok(args) => args,
+        Err(err) => return ui.add_client_err_msg(&err, &MsgTarget::CurrentTab),
+    };
+
+    let serv = src.serv_name();
+    let client = match find_client(clients, serv) {
+        Some(client) => client,
+        None => {
+            return ui.add_client_err_msg(
+                "Switch to a server tab to list channels",
+                &MsgTarget::CurrentTab,
+            );
+        }
+    };
+
+    ui.open_chan_list(serv, pattern, min_users);
+    client.list(pattern, min_users);
+}
+
+/// Parse `/list` arguments: an optional channel name mask and an optional `>N` for channels with
+/// more than N users. Returns the mask and the minimum number of users.
+fn parse_list_args(args: &str) -> Result<(Option<&str>, Option<usize>), String> {
+    let mut pattern = None;
+    let mut min_users = None;
+    for arg in args.split_whitespace() {
+        if let Some(n) = arg.strip_prefix('>') {
+            match n.parse::<usize>() {
+                Ok(n) if min_users.is_none() => min_users = Some(n + 1),
+                _ => return Err(format!("Invalid user count: {arg}")),
+            }
+        } else if pattern.is_none() {
+            pattern = Some(arg);
+        } else {
+            return Err(format!("Unexpected argument: {arg}"));
+        }
+    }
+    Ok((pattern, min_users))
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
 static ME_CMD: Cmd = Cmd {
     name: "me",
     cmd_fn: me,
@@ -592,3 +1022,15 @@ fn test_msg_args() {
     assert_eq!(split_msg_args("foo ,bar"), Some(("foo", ",bar")));
     assert_eq!(split_msg_args("#blah blah"), None);
 }
+
+#[test]
+fn test_list_args() {
+    assert_eq!(parse_list_args(""), Ok((None, None)));
+    assert_eq!(parse_list_args("#rust*"), Ok((Some("#rust*"), None)));
+    assert_eq!(
+        parse_list_args(">10 #rust*"),
+        Ok((Some("#rust*"), Some(11)))
+    );
+    assert!(parse_list_args(">ten").is_err());
+    assert!(parse_list_args("#a #b").is_err());
+}
@@ -1,4 +1,4 @@
-use libtiny_client::SASLAuth as ClientSASLAuth;
+use libtiny_client::{AddrFamily as ClientAddrFamily, Endpoint, SASLAuth as ClientSASLAuth};
 use serde::{Deserialize, Deserializer};
 
 use shellexpand::LookupError;
@@ -6,11 +6,14 @@ use std::env::VarError;
 use std::fs;
 use std::fs::File;
 use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use libtiny_tui::config::Chan;
 
+use crate::ignore::IgnoreRule;
+
 #[derive(Clone, Deserialize, Debug, PartialEq, Eq)]
 #[serde(untagged, rename_all = "snake_case")]
 pub(crate) enum SASLAuth<P> {
@@ -40,23 +43,66 @@ impl TryFrom<SASLAuth<String>> for ClientSASLAuth {
     }
 }
 
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AddrFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl From<AddrFamily> for ClientAddrFamily {
+    fn from(family: AddrFamily) -> Self {
+        match family {
+            AddrFamily::Ipv4 => ClientAddrFamily::Ipv4,
+            AddrFamily::Ipv6 => ClientAddrFamily::Ipv6,
+        }
+    }
+}
+
 #[derive(Clone, Deserialize)]
 #[serde(bound(deserialize = "P: Deserialize<'de>"))]
 pub(crate) struct Server<P> {
-    /// Address of the server
+    /// Name of the network. Tabs and log files of the server are named after this. Defaults to
+    /// `addr`.
+    #[serde(default)]
+    pub(crate) name: Option<String>,
+
+    /// Address of the server. Not needed when `endpoints` is given.
+    #[serde(default)]
     pub(crate) addr: String,
 
     /// Optional server alias to be shown in the tab line.
     #[serde(default)]
     pub(crate) alias: Option<String>,
 
-    /// Port of the server
-    pub(crate) port: u16,
+    /// Port of the server. Not needed when `endpoints` is given.
+    #[serde(default)]
+    pub(crate) port: Option<u16>,
 
     /// Use tls
     #[serde(default)]
     pub(crate) tls: bool,
 
+    /// Servers of the network, in `<addr>:<port>` or `<addr>:<port>:tls` format. tiny connects to
+    /// the first one, and tries the next one when connecting fails.
+    #[serde(default, deserialize_with = "deser_endpoints")]
+    pub(crate) endpoints: Vec<Endpoint>,
+
+    /// Timeout in seconds for resolving the address and connecting to the server
+    #[serde(
+        default = "default_connect_timeout",
+        deserialize_with = "deser_connect_timeout"
+    )]
+    pub(crate) connect_timeout: u64,
+
+    /// Address family to try first when connecting
+    #[serde(default)]
+    pub(crate) prefer_addr_family: Option<AddrFamily>,
+
+    /// Local address to connect from, as an IP address or `<ip>:<port>`
+    #[serde(default, deserialize_with = "deser_bind_addr")]
+    pub(crate) bind_addr: Option<SocketAddr>,
+
     /// Server password (optional)
     #[serde(default)]
     pub(crate) pass: Option<P>,
@@ -82,6 +128,11 @@ pub(crate) struct Server<P> {
     /// Channels to automatically join.
     pub(crate) join: Vec<Chan>,
 
+    /// Invites to automatically accept: channel patterns like `#rust-*`, or nicks of inviting
+    /// users.
+    #[serde(default)]
+    pub(crate) auto_join_on_invite: Vec<String>,
+
     /// NickServ identification password. Used on connecting to the server and nick change.
     pub(crate) nickserv_ident: Option<P>,
 
@@ -90,10 +141,53 @@ pub(crate) struct Server<P> {
     pub(crate) sasl_auth: Option<SASLAuth<P>>,
 }
 
+impl<P> Server<P> {
+    /// Name of the network, used as the server name in tabs and log files.
+    pub(crate) fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.addr)
+    }
+
+    /// Servers of the network: `endpoints`, or `addr`, `port`, and `tls` when `endpoints` is not
+    /// given.
+    pub(crate) fn endpoints(&self) -> Vec<Endpoint> {
+        if !self.endpoints.is_empty() {
+            return self.endpoints.clone();
+        }
+        // Port is not used with Unix domain sockets
+        let port = if self.addr.starts_with("unix:") {
+            self.port.or(Some(0))
+        } else {
+            self.port
+        };
+        match port {
+            Some(port) if !self.addr.is_empty() => vec![Endpoint {
+                addr: self.addr.clone(),
+                port,
+                tls: self.tls,
+            }],
+            _ => vec![],
+        }
+    }
+
+    /// Whether the network name or the address of an endpoint contains `pattern`. Used to select
+    /// servers to connect to with command line arguments.
+    pub(crate) fn matches_pattern(&self, pattern: &str) -> bool {
+        self.name().contains(pattern)
+            || self
+                .endpoints()
+                .iter()
+                .any(|endpoint| endpoint.addr.contains(pattern))
+    }
+}
+
 fn default_true() -> bool {
     true
 }
 
+fn default_connect_timeout() -> u64 {
+    libtiny_client::DEFAULT_CONNECT_TIMEOUT_SECS
+}
+
 /// Similar to `Server`, but used when connecting via the `/connect` command.
 #[derive(Clone, Deserialize)]
 pub(crate) struct Defaults {
@@ -112,6 +206,15 @@ pub(crate) struct Config<P> {
     pub(crate) servers: Vec<Server<P>>,
     pub(crate) defaults: Defaults,
     pub(crate) log_dir: Option<PathBuf>,
+    /// Directory for files received with DCC.
+    #[serde(default)]
+    pub(crate) download_dir: Option<PathBuf>,
+    /// Directory to record raw traffic of connections, for debugging.
+    #[serde(default)]
+    pub(crate) record_dir: Option<PathBuf>,
+    /// Users to ignore. Updated by the `/ignore add` and `/ignore del` commands.
+    #[serde(default)]
+    pub(crate) ignores: Vec<IgnoreRule>,
 }
 
 fn deser_trimmed_str<'de, D>(d: D) -> Result<String, D::Error>
@@ -122,6 +225,89 @@ where
     Ok(str.trim().to_owned())
 }
 
+fn deser_bind_addr<'de, D>(d: D) -> Result<Option<SocketAddr>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<String>::deserialize(d)? {
+        None => Ok(None),
+        Some(addr) => parse_bind_addr(&addr)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+fn deser_connect_timeout<'de, D>(d: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match u64::deserialize(d)? {
+        0 => Err(serde::de::Error::custom(
+            "connect_timeout can't be 0, please use a timeout of at least one second",
+        )),
+        timeout => Ok(timeout),
+    }
+}
+
+/// Parse an IP address, or an IP address and port. Port 0 is used when the port is not given.
+fn parse_bind_addr(addr: &str) -> Result<SocketAddr, String> {
+    let addr = addr.trim();
+    match addr.parse::<SocketAddr>() {
+        Ok(addr) => Ok(addr),
+        Err(_) => match addr.parse::<IpAddr>() {
+            Ok(ip) => Ok(SocketAddr::new(ip, 0)),
+            Err(_) => Err(format!(
+                "Can't parse bind address {addr:?}, expected an IP address or <ip>:<port>"
+            )),
+        },
+    }
+}
+
+fn deser_endpoints<'de, D>(d: D) -> Result<Vec<Endpoint>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let endpoints: Vec<String> = Vec::deserialize(d)?;
+    endpoints
+        .iter()
+        .map(|endpoint| parse_endpoint(endpoint).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+/// Parse an endpoint in `<addr>:<port>` or `<addr>:<port>:tls` format. IPv6 addresses need to be
+/// in brackets: `[::1]:6667`.
+fn parse_endpoint(endpoint: &str) -> Result<Endpoint, String> {
+    if endpoint.trim().starts_with("unix:") {
+        return Ok(Endpoint {
+            addr: endpoint.trim().to_owned(),
+            port: 0,
+            tls: false,
+        });
+    }
+    let (rest, tls) = match endpoint.trim().strip_suffix(":tls") {
+        Some(rest) => (rest, true),
+        None => (endpoint.trim(), false),
+    };
+    let (addr, port) = rest
+        .rsplit_once(':')
+        .ok_or_else(|| format!("Endpoint {endpoint:?} doesn't have a port"))?;
+    let port = port
+        .parse::<u16>()
+        .map_err(|err| format!("Can't parse port of endpoint {endpoint:?}: {err}"))?;
+    let addr = addr
+        .strip_prefix('[')
+        .and_then(|addr| addr.strip_suffix(']'))
+        .unwrap_or(addr);
+    if addr.is_empty() {
+        return Err(format!("Endpoint {endpoint:?} doesn't have an address"));
+    }
+    Ok(Endpoint {
+        addr: addr.to_owned(),
+        port,
+        tls,
+    })
+}
+
 fn deser_trimmed_str_vec<'de, D>(d: D) -> Result<Vec<String>, D::Error>
 where
     D: Deserializer<'de>,
@@ -258,10 +444,34 @@ impl Config<PassOrCmd> {
         }
 
         for server in &self.servers {
+            if server.endpoints().is_empty() {
+                errors.push(format!(
+                    "Server '{}' doesn't have an address, please add 'addr' and 'port', or \
+                     'endpoints'",
+                    server.name()
+                ));
+            }
+
+            let unix_endpoints = server.endpoints().iter().any(|ep| ep.unix_path().is_some());
+            if unix_endpoints && server.name.is_none() {
+                errors.push(format!(
+                    "Server '{}' connects to a Unix domain socket, please add a 'name' to use in \
+                     tabs and log files",
+                    server.name()
+                ));
+            }
+            if unix_endpoints && server.tls {
+                errors.push(format!(
+                    "Server '{}' connects to a Unix domain socket, TLS is not supported with Unix \
+                     domain sockets",
+                    server.name()
+                ));
+            }
+
             if server.nicks.is_empty() {
                 errors.push(format!(
                     "Nick list for server '{}' is empty, please add at least one nick",
-                    server.addr
+                    server.name()
                 ));
             }
 
@@ -269,7 +479,8 @@ impl Config<PassOrCmd> {
                 if nick.is_empty() {
                     errors.push(format!(
                         "Nicks can't be empty, please update nick {} for '{}'",
-                        nick_idx, server.addr
+                        nick_idx,
+                        server.name()
                     ));
                 }
             }
@@ -277,14 +488,14 @@ impl Config<PassOrCmd> {
             if server.realname.is_empty() {
                 errors.push(format!(
                     "'realname' can't be empty, please update 'realname' field of '{}'",
-                    server.addr
+                    server.name()
                 ));
             }
 
             if let Some(ref pass) = server.pass
                 && pass.is_empty_cmd()
             {
-                errors.push(format!("Empty PASS command for '{}'", server.addr));
+                errors.push(format!("Empty PASS command for '{}'", server.name()));
             }
 
             if let Some(ref nickserv_ident) = server.nickserv_ident
@@ -292,23 +503,26 @@ impl Config<PassOrCmd> {
             {
                 errors.push(format!(
                     "Empty NickServ password command for '{}'",
-                    server.addr
+                    server.name()
                 ));
             }
 
             if let Some(SASLAuth::Plain { password, .. }) = &server.sasl_auth
                 && password.is_empty_cmd()
             {
-                errors.push(format!("Empty SASL password command for '{}'", server.addr));
+                errors.push(format!(
+                    "Empty SASL password command for '{}'",
+                    server.name()
+                ));
             }
 
             if let Some(SASLAuth::External { .. }) = &server.sasl_auth
-                && !server.tls
+                && server.endpoints().iter().any(|endpoint| !endpoint.tls)
             {
                 errors.push(format!(
                     "TLS is not enabled for '{}', but SASL EXTERNAL authentication requires TLS. \
                      Please enable TLS for this server in the config file.",
-                    server.addr
+                    server.name()
                 ));
             }
         }
@@ -336,6 +550,16 @@ impl Config<PassOrCmd> {
             Some(dir) => Some(expand_path(dir.to_path_buf(), &home_dir, &env_var)?),
         };
 
+        self.download_dir = match &self.download_dir {
+            None => None,
+            Some(dir) => Some(expand_path(dir.to_path_buf(), &home_dir, &env_var)?),
+        };
+
+        self.record_dir = match &self.record_dir {
+            None => None,
+            Some(dir) => Some(expand_path(dir.to_path_buf(), &home_dir, &env_var)?),
+        };
+
         Ok(())
     }
 
@@ -346,36 +570,49 @@ impl Config<PassOrCmd> {
             servers,
             defaults,
             log_dir,
+            download_dir,
+            record_dir,
+            ignores,
         } = self;
 
         let mut servers_: Vec<Server<String>> = Vec::with_capacity(servers.len());
 
         for server in servers {
             let Server {
+                name,
                 addr,
                 alias,
                 port,
                 tls,
+                endpoints,
+                connect_timeout,
+                prefer_addr_family,
+                bind_addr,
                 pass,
                 autoconnect,
                 user,
                 realname,
                 nicks,
                 join,
+                auto_join_on_invite,
                 nickserv_ident,
                 sasl_auth,
             } = server;
 
+            let serv_name = name.as_deref().unwrap_or(&addr);
+
             let pass = match pass {
                 None => None,
                 Some(PassOrCmd::Pass(pass)) => Some(pass),
-                Some(PassOrCmd::Cmd(cmd)) => Some(run_command("server password", &addr, &cmd)?),
+                Some(PassOrCmd::Cmd(cmd)) => Some(run_command("server password", serv_name, &cmd)?),
             };
 
             let nickserv_ident = match nickserv_ident {
                 None => None,
                 Some(PassOrCmd::Pass(pass)) => Some(pass),
-                Some(PassOrCmd::Cmd(cmd)) => Some(run_command("NickServ password", &addr, &cmd)?),
+                Some(PassOrCmd::Cmd(cmd)) => {
+                    Some(run_command("NickServ password", serv_name, &cmd)?)
+                }
             };
 
             let sasl_auth = match sasl_auth {
@@ -391,23 +628,29 @@ impl Config<PassOrCmd> {
                     username,
                     password: PassOrCmd::Cmd(cmd),
                 }) => {
-                    let password = run_command("SASL password", &addr, &cmd)?;
+                    let password = run_command("SASL password", serv_name, &cmd)?;
                     Some(SASLAuth::Plain { username, password })
                 }
                 Some(SASLAuth::External { pem }) => Some(SASLAuth::External { pem }),
             };
 
             servers_.push(Server {
+                name,
                 addr,
                 alias,
                 port,
                 tls,
+                endpoints,
+                connect_timeout,
+                prefer_addr_family,
+                bind_addr,
                 pass,
                 autoconnect,
                 user,
                 realname,
                 nicks,
                 join,
+                auto_join_on_invite,
                 nickserv_ident,
                 sasl_auth,
             });
@@ -417,6 +660,9 @@ impl Config<PassOrCmd> {
             servers: servers_,
             defaults,
             log_dir,
+            download_dir,
+            record_dir,
+            ignores,
         })
     }
 }
@@ -520,26 +766,158 @@ mod tests {
                     vec![Chan::Name(ChanName::new("#tiny".to_string()))]
                 );
                 assert!(servers[0].tls);
+                assert_eq!(servers[0].connect_timeout, 30);
+                assert_eq!(servers[0].prefer_addr_family, None);
             }
         }
     }
 
+    #[test]
+    fn parse_endpoints() {
+        assert_eq!(
+            parse_endpoint("irc.libera.chat:6697:tls"),
+            Ok(Endpoint {
+                addr: "irc.libera.chat".to_owned(),
+                port: 6697,
+                tls: true,
+            })
+        );
+        assert_eq!(
+            parse_endpoint("[::1]:6667"),
+            Ok(Endpoint {
+                addr: "::1".to_owned(),
+                port: 6667,
+                tls: false,
+            })
+        );
+        assert_eq!(
+            parse_endpoint("unix:/run/user/1000/soju.sock"),
+            Ok(Endpoint {
+                addr: "unix:/run/user/1000/soju.sock".to_owned(),
+                port: 0,
+                tls: false,
+            })
+        );
+        assert!(parse_endpoint("irc.libera.chat").is_err());
+        assert!(parse_endpoint("irc.libera.chat:tls").is_err());
+        assert!(parse_endpoint(":6667").is_err());
+
+        let server = "
+            name: libera
+            endpoints:
+              - irc.libera.chat:6697:tls
+              - irc.eu.libera.chat:6667
+            realname: tiny
+            nicks: [tiny]
+            join: []
+        ";
+        let server: Server<String> = serde_yaml::from_str(server).unwrap();
+        assert_eq!(server.name(), "libera");
+        assert_eq!(server.endpoints().len(), 2);
+        assert_eq!(server.endpoints()[1].addr, "irc.eu.libera.chat");
+        assert!(server.matches_pattern("libera"));
+        assert!(server.matches_pattern("irc.eu"));
+        assert!(!server.matches_pattern("oftc"));
+
+        let server = "
+            addr: irc.libera.chat
+            port: 6697
+            tls: true
+            realname: tiny
+            nicks: [tiny]
+            join: []
+        ";
+        let server: Server<String> = serde_yaml::from_str(server).unwrap();
+        assert_eq!(server.name(), "irc.libera.chat");
+        assert_eq!(
+            server.endpoints(),
+            vec![Endpoint {
+                addr: "irc.libera.chat".to_owned(),
+                port: 6697,
+                tls: true,
+            }]
+        );
+
+        let server = "
+            name: bouncer
+            addr: unix:/run/user/1000/soju.sock
+            realname: tiny
+            nicks: [tiny]
+            join: []
+        ";
+        let server: Server<String> = serde_yaml::from_str(server).unwrap();
+        assert_eq!(server.name(), "bouncer");
+        assert_eq!(
+            server.endpoints()[0].unix_path(),
+            Some(std::path::Path::new("/run/user/1000/soju.sock"))
+        );
+
+        let server = "
+            addr: irc.libera.chat
+            connect_timeout: 0
+            realname: tiny
+            nicks: [tiny]
+            join: []
+        ";
+        let err = serde_yaml::from_str::<Server<String>>(server)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("connect_timeout can't be 0"));
+    }
+
+    #[test]
+    fn parse_bind_addrs() {
+        assert_eq!(
+            parse_bind_addr("192.168.1.2"),
+            Ok("192.168.1.2:0".parse().unwrap())
+        );
+        assert_eq!(
+            parse_bind_addr("192.168.1.2:5000"),
+            Ok("192.168.1.2:5000".parse().unwrap())
+        );
+        assert_eq!(parse_bind_addr("::1"), Ok("[::1]:0".parse().unwrap()));
+        assert_eq!(
+            parse_bind_addr("[::1]:5000"),
+            Ok("[::1]:5000".parse().unwrap())
+        );
+        assert!(parse_bind_addr("eth0").is_err());
+    }
+
+    #[test]
+    fn parse_prefer_addr_family() {
+        assert_eq!(
+            serde_yaml::from_str::<AddrFamily>("ipv4").unwrap(),
+            AddrFamily::Ipv4
+        );
+        assert_eq!(
+            serde_yaml::from_str::<AddrFamily>("ipv6").unwrap(),
+            AddrFamily::Ipv6
+        );
+        assert!(serde_yaml::from_str::<AddrFamily>("ipv5").is_err());
+    }
+
     #[test]
     fn validation() {
         // We trim the string fields when deserializing, so `validate` doesn't consider non-empty
         // strings as empty even if they have only spaces, it assumes spaces should be trimmed
         let config = Config {
             servers: vec![Server {
+                name: None,
                 addr: "my_server".to_owned(),
                 alias: None,
-                port: 123,
+                port: Some(123),
                 tls: false,
+                endpoints: vec![],
+                connect_timeout: 30,
+                prefer_addr_family: None,
+                bind_addr: None,
                 pass: None,
                 autoconnect: true,
                 user: None,
                 realname: "".to_owned(),
                 nicks: vec!["".to_owned()],
                 join: vec![],
+                auto_join_on_invite: vec![],
                 nickserv_ident: None,
                 sasl_auth: None,
             }],
@@ -550,6 +928,9 @@ mod tests {
                 tls: false,
             },
             log_dir: None,
+            download_dir: None,
+            record_dir: None,
+            ignores: vec![],
         };
 
         let errors = config.validate();
@@ -599,16 +980,22 @@ mod tests {
     fn config_shell_expansion() {
         let mut config: Config<PassOrCmd> = Config {
             servers: vec![Server {
+                name: None,
                 addr: "my_server".to_owned(),
                 alias: None,
-                port: 123,
+                port: Some(123),
                 tls: false,
+                endpoints: vec![],
+                connect_timeout: 30,
+                prefer_addr_family: None,
+                bind_addr: None,
                 pass: None,
                 autoconnect: true,
                 user: None,
                 realname: "".to_owned(),
                 nicks: vec!["".to_owned()],
                 join: vec![],
+                auto_join_on_invite: vec![],
                 nickserv_ident: None,
                 sasl_auth: Some(SASLAuth::External {
                     pem: "~/a/$SASL/b".into(),
@@ -621,6 +1008,9 @@ mod tests {
                 tls: false,
             },
             log_dir: Some("~/b/$LOG/c".into()),
+            download_dir: Some("~/dl".into()),
+            record_dir: Some("~/rec".into()),
+            ignores: vec![],
         };
         config
             .expand_fields(
@@ -643,6 +1033,8 @@ mod tests {
             config.log_dir,
             Some(PathBuf::from("/home/test/b/log_val/c"))
         );
+        assert_eq!(config.download_dir, Some(PathBuf::from("/home/test/dl")));
+        assert_eq!(config.record_dir, Some(PathBuf::from("/home/test/rec")));
     }
 
     #[test]
@@ -656,6 +1048,9 @@ mod tests {
                 tls: false,
             },
             log_dir: Some("~/logs/$MISSING/data".into()),
+            download_dir: None,
+            record_dir: None,
+            ignores: vec![],
         };
         let err = config
             .expand_fields(
@@ -3,10 +3,15 @@
 
 //! IRC event handling
 
+use crate::ignore::IgnoreType;
 use crate::ui::UI;
+use crate::utils;
+use libtiny_client::{ConnectionState, DccChatEvent, DccId, DccStatus, DccTransfer};
 use libtiny_common::{ChanNameRef, MsgTarget, TabStyle};
 use libtiny_wire as wire;
 
+use std::collections::HashSet;
+
 use tokio::sync::mpsc;
 use tokio_stream::StreamExt;
 use tokio_stream::wrappers::ReceiverStream;
@@ -17,6 +22,14 @@ pub(crate) trait Client {
     fn get_nick(&self) -> String;
 
     fn is_nick_accepted(&self) -> bool;
+
+    fn is_joined(&self, chan: &ChanNameRef) -> bool;
+
+    fn auto_join_invite(&self, nick: &str, chan: &ChanNameRef) -> bool;
+
+    fn dcc_close(&self, id: DccId) -> bool;
+
+    fn dcc_chat_close(&self, nick: &str) -> bool;
 }
 
 impl Client for libtiny_client::Client {
@@ -31,6 +44,22 @@ impl Client for libtiny_client::Client {
     fn is_nick_accepted(&self) -> bool {
         self.is_nick_accepted()
     }
+
+    fn is_joined(&self, chan: &ChanNameRef) -> bool {
+        self.is_joined(chan)
+    }
+
+    fn auto_join_invite(&self, nick: &str, chan: &ChanNameRef) -> bool {
+        self.auto_join_invite(nick, chan)
+    }
+
+    fn dcc_close(&self, id: DccId) -> bool {
+        self.dcc_close(id)
+    }
+
+    fn dcc_chat_close(&self, nick: &str) -> bool {
+        self.dcc_chat_close(nick)
+    }
 }
 
 pub(crate) async fn task(
@@ -56,14 +85,32 @@ fn handle_conn_ev(ui: &UI, client: &dyn Client, ev: libtiny_client::Event) {
                 },
             );
         }
-        Connecting(sock_addr) => {
+        Connecting { addr, family } => {
+            ui.add_client_msg(
+                &format!("Connecting to {addr} ({family})"),
+                &MsgTarget::AllServTabs {
+                    serv: client.get_serv_name(),
+                },
+            );
+        }
+        StsUpgrade { port } => {
             ui.add_client_msg(
-                &format!("Connecting to {sock_addr}"),
+                &format!("Server has an STS policy, connecting with TLS on port {port}"),
                 &MsgTarget::AllServTabs {
                     serv: client.get_serv_name(),
                 },
             );
         }
+        StateChanged(ConnectionState::Disconnected) => {
+            let serv = client.get_serv_name();
+            ui.add_err_msg(
+                "Disconnected. Use `/reconnect` to connect again.",
+                time::now(),
+                &MsgTarget::AllServTabs { serv },
+            );
+            ui.clear_nicks(serv);
+        }
+        StateChanged(_) => {}
         Connected => {
             ui.add_client_msg(
                 "Connected.",
@@ -142,6 +189,137 @@ fn handle_conn_ev(ui: &UI, client: &dyn Client, ev: libtiny_client::Event) {
                 chan: &chan,
             },
         ),
+        Netsplit { servers, chans } => {
+            let serv = client.get_serv_name();
+            let ts = time::now();
+            let mut user_tabs: HashSet<&str> = HashSet::new();
+            for (chan, nicks) in &chans {
+                let target = MsgTarget::Chan { serv, chan };
+                ui.add_netsplit((&servers.0, &servers.1), nicks, ts, &target);
+                ui.set_tab_style(TabStyle::JoinOrPart, &target);
+                for nick in nicks {
+                    if user_tabs.insert(nick) && ui.user_tab_exists(serv, nick) {
+                        ui.remove_nick(nick, Some(ts), &MsgTarget::User { serv, nick });
+                    }
+                }
+            }
+        }
+        Netjoin { chans } => {
+            let serv = client.get_serv_name();
+            let ts = time::now();
+            let mut user_tabs: HashSet<&str> = HashSet::new();
+            for (chan, nicks) in &chans {
+                let target = MsgTarget::Chan { serv, chan };
+                ui.add_netjoin(nicks, ts, &target);
+                ui.set_tab_style(TabStyle::JoinOrPart, &target);
+                for nick in nicks {
+                    if user_tabs.insert(nick) && ui.user_tab_exists(serv, nick) {
+                        ui.add_nick(nick, Some(ts), &MsgTarget::User { serv, nick });
+                    }
+                }
+            }
+        }
+        Dcc(transfer) => {
+            handle_dcc(ui, client, transfer);
+        }
+        DccChat { nick, ev } => {
+            handle_dcc_chat(ui, client, &nick, ev);
+        }
+    }
+}
+
+fn handle_dcc(ui: &UI, client: &dyn Client, transfer: DccTransfer) {
+    let serv = client.get_serv_name();
+    let nick = &transfer.nick;
+    let msg_target = if ui.user_tab_exists(serv, nick) {
+        MsgTarget::User { serv, nick }
+    } else {
+        MsgTarget::Server { serv }
+    };
+
+    match &transfer.status {
+        DccStatus::Offered => {
+            // DCC requests are CTCPs, reject offers from users with CTCPs ignored
+            if let Some(pfx) = &transfer.pfx
+                && ui.is_ignored(serv, None, pfx, IgnoreType::Ctcps)
+            {
+                client.dcc_close(transfer.id);
+                return;
+            }
+            let size = match transfer.size {
+                Some(size) => format!(" ({size} bytes)"),
+                None => "".to_owned(),
+            };
+            ui.add_client_msg(
+                &format!(
+                    "{} offers file {}{}. Use `/dcc get {}` to accept, `/dcc close {}` to reject.",
+                    nick, transfer.file_name, size, transfer.id, transfer.id
+                ),
+                &msg_target,
+            );
+            ui.set_tab_style(TabStyle::Highlight, &msg_target);
+        }
+        DccStatus::Done => {
+            let path = transfer
+                .path
+                .as_deref()
+                .unwrap_or(transfer.file_name.as_ref());
+            ui.add_client_msg(
+                &format!("DCC transfer {} done: {}", transfer.id, path.display()),
+                &msg_target,
+            );
+        }
+        DccStatus::Failed(err) => {
+            ui.add_client_err_msg(
+                &format!("DCC transfer {} failed: {}", transfer.id, err),
+                &msg_target,
+            );
+        }
+        DccStatus::Closed => {
+            ui.remove_transfer(transfer.id);
+            return;
+        }
+        DccStatus::Waiting | DccStatus::Active => {}
+    }
+
+    ui.set_transfer(transfer.id, &utils::format_dcc_transfer(&transfer));
+}
+
+fn handle_dcc_chat(ui: &UI, client: &dyn Client, nick: &str, ev: DccChatEvent) {
+    let serv = client.get_serv_name();
+    let chat_target = MsgTarget::DccChat { serv, nick };
+
+    match ev {
+        DccChatEvent::Offered { pfx } => {
+            if ui.is_ignored(serv, None, &pfx, IgnoreType::Ctcps) {
+                client.dcc_chat_close(nick);
+                return;
+            }
+            let msg_target = if ui.user_tab_exists(serv, nick) {
+                MsgTarget::User { serv, nick }
+            } else {
+                MsgTarget::Server { serv }
+            };
+            ui.add_client_msg(
+                &format!("{nick} offers a DCC CHAT. Use `/dcc chat {nick}` to accept."),
+                &msg_target,
+            );
+            ui.set_tab_style(TabStyle::Highlight, &msg_target);
+        }
+        DccChatEvent::Connected => {
+            ui.new_dcc_chat_tab(serv, nick);
+            ui.add_client_msg("DCC CHAT connected", &chat_target);
+        }
+        DccChatEvent::Msg { msg, is_action } => {
+            ui.add_privmsg(nick, &msg, time::now(), &chat_target, false, is_action);
+            ui.set_tab_style(TabStyle::Highlight, &chat_target);
+        }
+        DccChatEvent::Closed(None) => {
+            ui.add_client_msg("DCC CHAT closed", &chat_target);
+        }
+        DccChatEvent::Closed(Some(err)) => {
+            ui.add_client_err_msg(&format!("DCC CHAT closed: {err}"), &chat_target);
+        }
     }
 }
 
@@ -173,6 +351,21 @@ fn handle_irc_msg(ui: &UI, client: &dyn Client, msg: wire::Msg) {
                 User { ref nick, .. } | Ambiguous(ref nick) => nick,
             };
 
+            let ignore_type = if is_notice {
+                IgnoreType::Notices
+            } else if ctcp.is_some() && ctcp != Some(wire::CTCP::Action) {
+                IgnoreType::Ctcps
+            } else {
+                IgnoreType::Messages
+            };
+            let ignore_chan = match target {
+                wire::MsgTarget::Chan(ref chan) => Some(chan.as_ref()),
+                wire::MsgTarget::User(_) => None,
+            };
+            if ui.is_ignored(serv, ignore_chan, &pfx, ignore_type) {
+                return;
+            }
+
             if ctcp == Some(wire::CTCP::Version) {
                 let msg_target = if ui.user_tab_exists(serv, sender) {
                     MsgTarget::User { serv, nick: sender }
@@ -186,13 +379,28 @@ fn handle_irc_msg(ui: &UI, client: &dyn Client, msg: wire::Msg) {
                 return;
             }
 
+            if ctcp == Some(wire::CTCP::Dcc) {
+                // DCC requests that the client handles are not reported as messages
+                let msg_target = if ui.user_tab_exists(serv, sender) {
+                    MsgTarget::User { serv, nick: sender }
+                } else {
+                    MsgTarget::Server { serv }
+                };
+                ui.add_client_msg(
+                    &format!("Unsupported DCC request from {sender}: {msg}"),
+                    &msg_target,
+                );
+                return;
+            }
+
             let is_action = ctcp == Some(wire::CTCP::Action);
 
             match target {
                 wire::MsgTarget::Chan(chan) => {
                     let ui_msg_target = MsgTarget::Chan { serv, chan: &chan };
-                    // Highlight the message if it mentions us.
-                    if mentions_user(&msg, &client.get_nick()) {
+                    // Highlight the message if it mentions us, or matches a highlight rule.
+                    let mentions_nick = mentions_user(&msg, &client.get_nick());
+                    if ui.is_highlight(serv, Some(&chan), sender, &msg, mentions_nick) {
                         ui.add_privmsg(sender, &msg, ts, &ui_msg_target, true, is_action);
                         ui.set_tab_style(TabStyle::Highlight, &ui_msg_target);
                         let mentions_target = MsgTarget::Server { serv: "mentions" };
@@ -232,8 +440,17 @@ fn handle_irc_msg(ui: &UI, client: &dyn Client, msg: wire::Msg) {
                                 } else {
                                     MsgTarget::User { serv, nick }
                                 };
-                                ui.add_privmsg(nick, &msg, ts, &msg_target, false, is_action);
-                                ui.set_tab_style(TabStyle::Highlight, &msg_target);
+                                // Highlight rules can exclude messages, e.g. from bots, which
+                                // are then not notified. Only the server's rules apply to
+                                // private messages.
+                                let highlight = ui.is_highlight(serv, None, nick, &msg, true);
+                                ui.add_privmsg(nick, &msg, ts, &msg_target, highlight, is_action);
+                                let style = if highlight {
+                                    TabStyle::Highlight
+                                } else {
+                                    TabStyle::NewMsg
+                                };
+                                ui.set_tab_style(style, &msg_target);
                             } else {
                                 // PRIVMSG not sent to us. This case can happen in a few cases:
                                 //
@@ -300,7 +517,7 @@ fn handle_irc_msg(ui: &UI, client: &dyn Client, msg: wire::Msg) {
                                                 &msg,
                                                 ts,
                                                 &msg_target,
-                                                false,
+                                                true,
                                                 is_action,
                                             );
                                             ui.set_tab_style(TabStyle::Highlight, &msg_target);
@@ -315,6 +532,9 @@ fn handle_irc_msg(ui: &UI, client: &dyn Client, msg: wire::Msg) {
         }
 
         JOIN { chan } => {
+            let ignored = pfx
+                .as_ref()
+                .is_some_and(|pfx| ui.is_ignored(serv, Some(&chan), pfx, IgnoreType::Joins));
             let nick = match pfx {
                 Some(User { nick, .. }) | Some(Ambiguous(nick)) => nick,
                 Some(Server(_)) | None => {
@@ -331,6 +551,11 @@ fn handle_irc_msg(ui: &UI, client: &dyn Client, msg: wire::Msg) {
                 ui.new_chan_tab(serv, &chan);
             } else {
                 let nick = wire::drop_nick_prefix(&nick);
+                if ignored {
+                    // Update the nick list without showing the join
+                    ui.add_nick(nick, None, &MsgTarget::Chan { serv, chan: &chan });
+                    return;
+                }
                 let ts = Some(time::now());
                 ui.add_nick(nick, ts, &MsgTarget::Chan { serv, chan: &chan });
                 // Also update the private message tab if it exists
@@ -343,6 +568,9 @@ fn handle_irc_msg(ui: &UI, client: &dyn Client, msg: wire::Msg) {
         }
 
         PART { chan, msg } => {
+            let ignored = pfx
+                .as_ref()
+                .is_some_and(|pfx| ui.is_ignored(serv, Some(&chan), pfx, IgnoreType::Joins));
             let nick = match pfx {
                 Some(User { nick, .. }) | Some(Ambiguous(nick)) => nick,
                 Some(Server(_)) | None => {
@@ -355,6 +583,10 @@ fn handle_irc_msg(ui: &UI, client: &dyn Client, msg: wire::Msg) {
                 }
             };
             if nick != client.get_nick() {
+                if ignored {
+                    ui.remove_nick(&nick, None, &MsgTarget::Chan { serv, chan: &chan });
+                    return;
+                }
                 ui.remove_nick(
                     &nick,
                     Some(time::now()),
@@ -364,6 +596,76 @@ fn handle_irc_msg(ui: &UI, client: &dyn Client, msg: wire::Msg) {
             }
         }
 
+        INVITE { nick, chan } => {
+            if let Some(ref pfx) = pfx
+                && ui.is_ignored(serv, Some(&chan), pfx, IgnoreType::Invites)
+            {
+                return;
+            }
+            let inviter = match pfx {
+                Some(User { nick, .. }) | Some(Ambiguous(nick)) => nick,
+                Some(Server(_)) | None => {
+                    debug!(
+                        "INVITE without user prefix: cmd={:?}",
+                        INVITE { nick, chan }
+                    );
+                    return;
+                }
+            };
+            if nick != client.get_nick() {
+                // invite-notify: another user invited to a channel we're in
+                ui.add_client_msg(
+                    &format!("{inviter} invited {nick} to the channel"),
+                    &MsgTarget::Chan { serv, chan: &chan },
+                );
+                return;
+            }
+            // Invites to channels we're in are ignored by the client, see `State`
+            if client.is_joined(&chan) {
+                return;
+            }
+            let msg = if client.auto_join_invite(&inviter, &chan) {
+                let chan = chan.display();
+                format!("{inviter} invited you to {chan}, joining")
+            } else if ui.current_tab().as_ref().map(|tab| tab.serv_name()) == Some(serv) {
+                let chan = chan.display();
+                format!("{inviter} invited you to {chan}. Use `/accept` to join.")
+            } else {
+                let chan = chan.display();
+                format!(
+                    "{inviter} invited you to {chan} on {serv}. Use `/accept` in a {serv} tab to join."
+                )
+            };
+            ui.add_client_msg(&msg, &MsgTarget::CurrentTab);
+        }
+
+        KICK { chan, nick, msg } => {
+            let kicker = match pfx {
+                Some(User { nick, .. }) | Some(Ambiguous(nick)) | Some(Server(nick)) => nick,
+                None => {
+                    debug!("KICK without prefix: cmd={:?}", KICK { chan, nick, msg });
+                    return;
+                }
+            };
+            let reason = match msg {
+                Some(msg) if !msg.is_empty() => format!(" ({msg})"),
+                _ => String::new(),
+            };
+            let target = MsgTarget::Chan { serv, chan: &chan };
+            if nick == client.get_nick() {
+                ui.add_err_msg(&format!("You were kicked by {kicker}{reason}"), ts, &target);
+                ui.set_tab_style(TabStyle::Highlight, &target);
+            } else {
+                ui.remove_nick(&nick, None, &target);
+                ui.add_msg(
+                    &format!("{nick} was kicked by {kicker}{reason}"),
+                    ts,
+                    &target,
+                );
+                ui.set_tab_style(TabStyle::JoinOrPart, &target);
+            }
+        }
+
         QUIT { chans, msg } => {
             let nick = match pfx {
                 Some(User { ref nick, .. }) | Some(Ambiguous(ref nick)) => nick,
@@ -377,11 +679,18 @@ fn handle_irc_msg(ui: &UI, client: &dyn Client, msg: wire::Msg) {
                 }
             };
 
+            // Ignored quits update the nick lists without showing the quit
+            let ts = |chan: Option<&ChanNameRef>| {
+                let ignored = pfx
+                    .as_ref()
+                    .is_some_and(|pfx| ui.is_ignored(serv, chan, pfx, IgnoreType::Joins));
+                if ignored { None } else { Some(time::now()) }
+            };
             for chan in &chans {
-                ui.remove_nick(nick, Some(time::now()), &MsgTarget::Chan { serv, chan });
+                ui.remove_nick(nick, ts(Some(chan)), &MsgTarget::Chan { serv, chan });
             }
             if ui.user_tab_exists(serv, nick) {
-                ui.remove_nick(nick, Some(time::now()), &MsgTarget::User { serv, nick });
+                ui.remove_nick(nick, ts(None), &MsgTarget::User { serv, nick });
             }
         }
 
@@ -445,9 +754,7 @@ fn handle_irc_msg(ui: &UI, client: &dyn Client, msg: wire::Msg) {
         }
 
         CAP {
-            client: _,
-            subcommand,
-            params,
+            subcommand, params, ..
         } => match subcommand.as_ref() {
             "NAK" => {
                 if params.iter().any(|cap| cap.as_str() == "sasl") {
@@ -521,11 +828,26 @@ fn handle_irc_msg(ui: &UI, client: &dyn Client, msg: wire::Msg) {
                 };
 
                 for nick in params[3].split_whitespace() {
-                    ui.add_nick(wire::drop_nick_prefix(nick), None, &chan_target);
+                    let (prefixes, nick) = wire::split_nick_prefixes(nick);
+                    ui.add_nick(nick, None, &chan_target);
+                    for prefix in prefixes.chars() {
+                        ui.update_nick_prefix(nick, prefix, true, &chan_target);
+                    }
                 }
             }
-            // RPL_ENDOFNAMES: End of NAMES list
-            else if n == 366 {
+            // RPL_ENDOFNAMES: End of NAMES list. RPL_LISTSTART: The list view is opened by `/list`
+            else if n == 366 || n == 321 {
+            }
+            // RPL_LIST: A channel in LIST response
+            else if n == 322 && n_params > 2 {
+                let chan = ChanNameRef::new(&params[1]);
+                let users = params[2].parse().unwrap_or(0);
+                let topic = params.get(3).map(String::as_str).unwrap_or("");
+                ui.add_chan_list_item(serv, chan, users, topic);
+            }
+            // RPL_LISTEND
+            else if n == 323 {
+                ui.chan_list_done(serv);
             }
             // RPL_UNAWAY or RPL_NOWAWAY
             else if (n == 305 || n == 306) && n_params > 1 {
@@ -566,23 +888,36 @@ fn handle_irc_msg(ui: &UI, client: &dyn Client, msg: wire::Msg) {
             }
         }
 
-        Other { cmd, params } => match pfx {
-            Some(Server(msg_serv)) => {
-                let msg_target = MsgTarget::Server { serv };
-                ui.add_privmsg(
-                    &msg_serv,
-                    &params.join(" "),
-                    time::now(),
-                    &msg_target,
-                    false,
-                    false,
-                );
-                ui.set_tab_style(TabStyle::NewMsg, &msg_target);
+        Other { cmd, params } => {
+            // Update membership prefixes in nick lists
+            if cmd == "MODE" && params.len() > 2 {
+                let chan_target = MsgTarget::Chan {
+                    serv,
+                    chan: ChanNameRef::new(&params[0]),
+                };
+                for change in wire::parse_membership_modes(&params[1], &params[2..]) {
+                    ui.update_nick_prefix(change.nick, change.prefix, change.add, &chan_target);
+                }
             }
-            Some(User { .. }) | Some(Ambiguous(_)) | None => {
-                debug!("Ignoring command {cmd}: pfx={pfx:?}, params={params:?}");
+
+            match pfx {
+                Some(Server(msg_serv)) => {
+                    let msg_target = MsgTarget::Server { serv };
+                    ui.add_privmsg(
+                        &msg_serv,
+                        &params.join(" "),
+                        time::now(),
+                        &msg_target,
+                        false,
+                        false,
+                    );
+                    ui.set_tab_style(TabStyle::NewMsg, &msg_target);
+                }
+                Some(User { .. }) | Some(Ambiguous(_)) | None => {
+                    debug!("Ignoring command {cmd}: pfx={pfx:?}, params={params:?}");
+                }
             }
-        },
+        }
     }
 }
 
@@ -0,0 +1,461 @@
+//! Ignore rules. Messages from users matching the `nick!user@host` pattern of a rule are dropped
+//! before they're shown in the UI or logged.
+
+use libtiny_common::{ChanNameRef, wildcard_match};
+use libtiny_wire::Pfx;
+use serde::{Deserialize, Serialize};
+
+use std::ffi::OsString;
+use std::fmt::{self, Display};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// Config file key of the ignore rules
+const IGNORES_KEY: &str = "ignores";
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum IgnoreType {
+    /// Channel and private messages, including actions
+    Messages,
+    Notices,
+    /// CTCP requests other than actions
+    Ctcps,
+    /// Join, part, and quit messages
+    Joins,
+    Invites,
+}
+
+const IGNORE_TYPES: [IgnoreType; 5] = [
+    IgnoreType::Messages,
+    IgnoreType::Notices,
+    IgnoreType::Ctcps,
+    IgnoreType::Joins,
+    IgnoreType::Invites,
+];
+
+impl IgnoreType {
+    fn from_str(s: &str) -> Option<IgnoreType> {
+        IGNORE_TYPES.into_iter().find(|ty| ty.as_str() == s)
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            IgnoreType::Messages => "messages",
+            IgnoreType::Notices => "notices",
+            IgnoreType::Ctcps => "ctcps",
+            IgnoreType::Joins => "joins",
+            IgnoreType::Invites => "invites",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub(crate) struct IgnoreRule {
+    /// `nick!user@host` pattern with `*` and `?` wildcards. A pattern without `!` and `@` is a
+    /// nick pattern.
+    pub(crate) mask: String,
+
+    /// Message types to ignore. All types are ignored when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) types: Vec<IgnoreType>,
+
+    /// Server of the rule. The rule applies to all servers when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) server: Option<String>,
+
+    /// Channel of the rule. The rule applies to all channels and private messages when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) chan: Option<String>,
+}
+
+impl IgnoreRule {
+    /// Parse `/ignore add` arguments: `<mask> [-types <type>,...] [-chan|-global]`. The rule is
+    /// for the server `serv` unless `-global` is given, and for the channel `chan` with `-chan`.
+    pub(crate) fn from_cmd_args(
+        args: &str,
+        serv: Option<&str>,
+        chan: Option<&ChanNameRef>,
+    ) -> Result<IgnoreRule, String> {
+        let mut words = args.split_whitespace();
+        let mask = match words.next() {
+            Some(mask) if !mask.starts_with('-') => mask.to_owned(),
+            _ => return Err("Missing mask".to_owned()),
+        };
+        let mut rule = IgnoreRule {
+            mask,
+            types: vec![],
+            server: serv.map(str::to_owned),
+            chan: None,
+        };
+        while let Some(word) = words.next() {
+            match word {
+                "-types" => {
+                    let types = words.next().ok_or("-types parameter missing")?;
+                    for ty in types.split(',') {
+                        let ty = IgnoreType::from_str(ty)
+                            .ok_or_else(|| format!("Unknown message type: {ty:?}"))?;
+                        if !rule.types.contains(&ty) {
+                            rule.types.push(ty);
+                        }
+                    }
+                }
+                "-chan" => match (serv, chan) {
+                    (Some(_), Some(chan)) => rule.chan = Some(chan.display().to_owned()),
+                    _ => return Err("-chan can only be used in channel tabs".to_owned()),
+                },
+                "-global" => rule.server = None,
+                other => return Err(format!("Unexpected parameter: {other:?}")),
+            }
+        }
+        if rule.server.is_none() && rule.chan.is_some() {
+            return Err("-chan and -global can't be used together".to_owned());
+        }
+        Ok(rule)
+    }
+
+    fn matches(&self, serv: &str, chan: Option<&ChanNameRef>, mask: &str, ty: IgnoreType) -> bool {
+        (self.types.is_empty() || self.types.contains(&ty))
+            && self.server.as_ref().is_none_or(|server| server == serv)
+            && self
+                .chan
+                .as_ref()
+                .is_none_or(|chan_| chan == Some(ChanNameRef::new(chan_)))
+            && if self.mask.contains(['!', '@']) {
+                wildcard_match(&self.mask, mask)
+            } else {
+                // Nick pattern
+                wildcard_match(&self.mask, mask.split('!').next().unwrap_or_default())
+            }
+    }
+}
+
+impl Display for IgnoreRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.mask)?;
+        if !self.types.is_empty() {
+            let types: Vec<&str> = self.types.iter().map(|ty| ty.as_str()).collect();
+            write!(f, " ({})", types.join(", "))?;
+        }
+        match (&self.server, &self.chan) {
+            (None, _) => write!(f, " everywhere"),
+            (Some(server), None) => write!(f, " on {server}"),
+            (Some(server), Some(chan)) => write!(f, " in {server}:{chan}"),
+        }
+    }
+}
+
+/// Ignore rules, and the config file to persist them.
+#[derive(Debug)]
+pub(crate) struct Ignores {
+    rules: Vec<IgnoreRule>,
+
+    /// Rules are saved to this config file when updated. Not saved when `None`.
+    config_path: Option<PathBuf>,
+}
+
+impl Ignores {
+    pub(crate) fn new(rules: Vec<IgnoreRule>, config_path: Option<PathBuf>) -> Ignores {
+        Ignores { rules, config_path }
+    }
+
+    pub(crate) fn rules(&self) -> &[IgnoreRule] {
+        &self.rules
+    }
+
+    /// Whether a message of type `ty` from `pfx` in `chan` (`None` for messages not in a
+    /// channel) should be ignored. Messages from servers are never ignored.
+    pub(crate) fn is_ignored(
+        &self,
+        serv: &str,
+        chan: Option<&ChanNameRef>,
+        pfx: &Pfx,
+        ty: IgnoreType,
+    ) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+        let mask = match pfx {
+            Pfx::Server(_) => return false,
+            Pfx::User { nick, user } => format!("{nick}!{user}"),
+            Pfx::Ambiguous(nick) => format!("{nick}!@"),
+        };
+        self.rules
+            .iter()
+            .any(|rule| rule.matches(serv, chan, &mask, ty))
+    }
+
+    /// Add a rule. Rules are read from the config file before adding, to keep the changes made to
+    /// the file. The config file is not updated when it can't be read.
+    pub(crate) fn add(&mut self, rule: IgnoreRule) -> io::Result<()> {
+        let reloaded = self.reload();
+        self.rules.push(rule);
+        reloaded?;
+        self.save()
+    }
+
+    /// Remove the rule at `idx` in `rules`, i.e. the rule numbered `idx + 1` in `/ignore list`.
+    /// Rules are read from the config file before removing as in `add`. The file may have the
+    /// rules in a different order, so the rule is removed by value. Returns `None` when the index
+    /// is out of bounds.
+    pub(crate) fn remove(&mut self, idx: usize) -> Option<(IgnoreRule, io::Result<()>)> {
+        let rule = self.rules.get(idx)?.clone();
+        let reloaded = self.reload();
+        if let Some(idx) = self.rules.iter().position(|rule_| *rule_ == rule) {
+            self.rules.remove(idx);
+        }
+        Some((rule, reloaded.and_then(|()| self.save())))
+    }
+
+    /// Read the rules from the `ignores` section of the config file.
+    pub(crate) fn reload(&mut self) -> io::Result<()> {
+        let config_path = match &self.config_path {
+            None => return Ok(()),
+            Some(config_path) => config_path,
+        };
+        let config = fs::read_to_string(config_path)?;
+        self.rules = parse_ignores(&config)?;
+        Ok(())
+    }
+
+    /// Update the `ignores` section of the config file. The new config is written to a temporary
+    /// file first, which then replaces the config file, so the config file is never left
+    /// half-written.
+    fn save(&self) -> io::Result<()> {
+        let config_path = match &self.config_path {
+            None => return Ok(()),
+            // Update the target when the config file is a symlink
+            Some(config_path) => fs::canonicalize(config_path)?,
+        };
+        let config = fs::read_to_string(&config_path)?;
+
+        let mut tmp_path = OsString::from(&config_path);
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        let write_tmp = || -> io::Result<()> {
+            let mut file = File::create(&tmp_path)?;
+            // The config file may have passwords, keep its permissions
+            file.set_permissions(fs::metadata(&config_path)?.permissions())?;
+            file.write_all(replace_ignores(&config, &self.rules).as_bytes())?;
+            file.sync_all()
+        };
+        match write_tmp().and_then(|()| fs::rename(&tmp_path, &config_path)) {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                let _ = fs::remove_file(&tmp_path);
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Parse the `ignores` section of the config file.
+fn parse_ignores(config: &str) -> io::Result<Vec<IgnoreRule>> {
+    #[derive(Deserialize)]
+    struct Section {
+        #[serde(default)]
+        ignores: Vec<IgnoreRule>,
+    }
+    serde_yaml::from_str::<Section>(config)
+        .map(|section| section.ignores)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Replace the `ignores` section of the config file with `rules`. The section is added to the
+/// end when it doesn't exist. Rest of the file, including comments, is not changed.
+fn replace_ignores(config: &str, rules: &[IgnoreRule]) -> String {
+    let section = if rules.is_empty() {
+        format!("{IGNORES_KEY}: []\n")
+    } else {
+        #[derive(Serialize)]
+        struct Section<'a> {
+            ignores: &'a [IgnoreRule],
+        }
+        let yaml = serde_yaml::to_string(&Section { ignores: rules }).unwrap();
+        let yaml = yaml.strip_prefix("---\n").unwrap_or(&yaml);
+        format!("{}\n", yaml.trim_end())
+    };
+
+    let lines: Vec<&str> = config.split_inclusive('\n').collect();
+    let section_start = lines.iter().position(|line| {
+        line.strip_prefix(IGNORES_KEY)
+            .is_some_and(|rest| rest.trim_start().starts_with(':'))
+    });
+    match section_start {
+        None => {
+            let mut config = config.to_owned();
+            if !config.is_empty() && !config.ends_with('\n') {
+                config.push('\n');
+            }
+            config.push('\n');
+            config.push_str(&section);
+            config
+        }
+        Some(section_start) => {
+            // The section ends at the next line that is not indented, not a list item, and not
+            // empty. Empty lines at the end of the section are kept.
+            let mut section_end = section_start + 1;
+            for (line_idx, line) in lines.iter().enumerate().skip(section_start + 1) {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                if !line.starts_with([' ', '\t', '-']) {
+                    break;
+                }
+                section_end = line_idx + 1;
+            }
+            let mut new_config: String = lines[..section_start].concat();
+            new_config.push_str(&section);
+            new_config.push_str(&lines[section_end..].concat());
+            new_config
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(mask: &str) -> Pfx {
+        Pfx::User {
+            nick: mask.split('!').next().unwrap().to_owned(),
+            user: mask.split('!').nth(1).unwrap().to_owned(),
+        }
+    }
+
+    #[test]
+    fn ignore_rules() {
+        let chan = Some(ChanNameRef::new("#tiny"));
+        let ignores = Ignores::new(
+            vec![
+                IgnoreRule::from_cmd_args("*!*@spam.example.com", None, None).unwrap(),
+                IgnoreRule::from_cmd_args("*bot -types messages,notices", Some("libera"), None)
+                    .unwrap(),
+                IgnoreRule::from_cmd_args("troll -chan", Some("libera"), chan).unwrap(),
+            ],
+            None,
+        );
+
+        let spammer = user("x!y@spam.example.com");
+        assert!(ignores.is_ignored("oftc", None, &spammer, IgnoreType::Invites));
+        assert!(!ignores.is_ignored("oftc", None, &user("x!y@example.com"), IgnoreType::Joins));
+        assert!(!ignores.is_ignored(
+            "oftc",
+            None,
+            &Pfx::Server("x".to_owned()),
+            IgnoreType::Notices
+        ));
+
+        let bot = user("CIBot!bot@example.com");
+        assert!(ignores.is_ignored("libera", chan, &bot, IgnoreType::Messages));
+        assert!(!ignores.is_ignored("libera", chan, &bot, IgnoreType::Joins));
+        assert!(!ignores.is_ignored("oftc", chan, &bot, IgnoreType::Messages));
+
+        let troll = Pfx::Ambiguous("troll".to_owned());
+        assert!(ignores.is_ignored(
+            "libera",
+            Some(ChanNameRef::new("#TINY")),
+            &troll,
+            IgnoreType::Joins
+        ));
+        assert!(!ignores.is_ignored("libera", None, &troll, IgnoreType::Messages));
+
+        assert!(IgnoreRule::from_cmd_args("", None, None).is_err());
+        assert!(IgnoreRule::from_cmd_args("x -types foo", None, None).is_err());
+        assert!(IgnoreRule::from_cmd_args("x -chan", Some("libera"), None).is_err());
+        assert!(IgnoreRule::from_cmd_args("x -chan -global", Some("libera"), chan).is_err());
+    }
+
+    #[test]
+    fn replace_ignores_section() {
+        let rule = IgnoreRule::from_cmd_args("*bot -types ctcps", Some("libera"), None).unwrap();
+        let expected_section = "\
+ignores:
+  - mask: \"*bot\"
+    types:
+      - ctcps
+    server: libera
+";
+
+        // Added to the end
+        let config = "servers: []\n# comment";
+        assert_eq!(
+            replace_ignores(config, std::slice::from_ref(&rule)),
+            format!("servers: []\n# comment\n\n{expected_section}")
+        );
+
+        // Replaced, other sections and comments are kept
+        let config = "\
+servers: []
+ignores:
+- mask: a
+
+  - mask: b
+    types: [joins]
+
+# Logs
+log_dir: logs
+";
+        assert_eq!(
+            replace_ignores(config, std::slice::from_ref(&rule)),
+            format!("servers: []\n{expected_section}\n# Logs\nlog_dir: logs\n")
+        );
+        assert_eq!(
+            replace_ignores(config, &[]),
+            "servers: []\nignores: []\n\n# Logs\nlog_dir: logs\n"
+        );
+
+        // The written section can be parsed back
+        assert_eq!(parse_ignores(expected_section).unwrap(), vec![rule]);
+    }
+
+    #[test]
+    fn save_ignores() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.yml");
+        fs::write(&config_path, "servers: []\nignores:\n  - mask: a\n").unwrap();
+        let mut ignores = Ignores::new(vec![], Some(config_path.clone()));
+        ignores.reload().unwrap();
+        assert_eq!(ignores.rules().len(), 1);
+
+        // Rules added to the file after loading are kept
+        fs::write(
+            &config_path,
+            "servers: []\nignores:\n  - mask: a\n  - mask: b\n",
+        )
+        .unwrap();
+        ignores
+            .add(IgnoreRule::from_cmd_args("c -global", None, None).unwrap())
+            .unwrap();
+        let masks = |ignores: &Ignores| -> Vec<String> {
+            ignores
+                .rules()
+                .iter()
+                .map(|rule| rule.mask.clone())
+                .collect()
+        };
+        assert_eq!(masks(&ignores), vec!["a", "b", "c"]);
+
+        let (rule, result) = ignores.remove(0).unwrap();
+        assert_eq!(rule.mask, "a");
+        result.unwrap();
+
+        // The rule shown in the list is removed, even when the file has another rule at its index
+        fs::write(
+            &config_path,
+            "servers: []\nignores:\n  - mask: d\n  - mask: b\n  - mask: c\n",
+        )
+        .unwrap();
+        let (rule, result) = ignores.remove(1).unwrap();
+        assert_eq!(rule.mask, "c");
+        result.unwrap();
+        assert!(ignores.remove(2).is_none());
+
+        let mut reloaded = Ignores::new(vec![], Some(config_path));
+        reloaded.reload().unwrap();
+        assert_eq!(masks(&reloaded), vec!["d", "b"]);
+        // The temporary file is renamed
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
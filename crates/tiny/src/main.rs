@@ -5,12 +5,14 @@ mod cmd;
 mod config;
 mod conn;
 mod debug_logging;
+mod ignore;
 mod ui;
 mod utils;
 
 #[cfg(test)]
 mod tests;
 
+use ignore::{IgnoreRule, Ignores};
 use libtiny_client::{Client, SASLAuth, ServerInfo};
 use libtiny_common::MsgTarget;
 use libtiny_logger::{Logger, LoggerInitError};
@@ -72,18 +74,34 @@ fn main() {
                     servers,
                     defaults,
                     log_dir,
+                    download_dir,
+                    record_dir,
+                    ignores,
                 } = config;
 
                 let servers = if !server_args.is_empty() {
                     // Connect only to servers that match at least one of the given patterns
                     servers
                         .into_iter()
-                        .filter(|s| server_args.iter().any(|arg| s.addr.contains(arg)))
+                        .filter(|s| server_args.iter().any(|arg| s.matches_pattern(arg)))
                         .collect()
                 } else {
                     servers
                 };
-                run(servers, defaults, config_path, log_dir)
+                let download_dir = download_dir
+                    .or_else(dirs::download_dir)
+                    .or_else(dirs::home_dir)
+                    .unwrap_or_default();
+
+                run(
+                    servers,
+                    defaults,
+                    config_path,
+                    log_dir,
+                    download_dir,
+                    record_dir,
+                    ignores,
+                )
             }
         }
     }
@@ -91,11 +109,16 @@ fn main() {
 
 const DEBUG_LOG_FILE: &str = "tiny_debug_logs.txt";
 
+const STS_FILE: &str = "sts_policies.txt";
+
 fn run(
     servers: Vec<config::Server<String>>,
     defaults: config::Defaults,
     config_path: PathBuf,
     log_dir: Option<PathBuf>,
+    download_dir: PathBuf,
+    record_dir: Option<PathBuf>,
+    ignores: Vec<IgnoreRule>,
 ) {
     let debug_log_file = match log_dir.as_ref() {
         Some(log_dir) => {
@@ -149,37 +172,53 @@ fn run(
                 }
             });
 
-        let tui = UI::new(tui, logger);
+        let ignores = Ignores::new(ignores, Some(config_path.clone()));
+        let tui = UI::new(tui, logger, ignores);
 
         let mut clients: Vec<Client> = Vec::with_capacity(servers.len());
 
+        // STS policies of servers are persisted next to the config file
+        let sts_file = config_path.with_file_name(STS_FILE);
+
         for server in servers.iter().cloned() {
             if !server.autoconnect {
                 continue;
             }
 
-            tui.new_server_tab(&server.addr, server.alias);
+            let serv_name = server.name().to_owned();
+            let endpoints = server.endpoints();
+
+            tui.new_server_tab(&serv_name, server.alias);
 
-            let tls = server.tls;
             let sasl_auth = server.sasl_auth.and_then(|sasl| -> Option<SASLAuth> {
                 match sasl.try_into() {
                     Ok(sasl) => Some(sasl),
                     Err(e) => {
-                        tui.add_client_err_msg(&e, &MsgTarget::Server { serv: &server.addr });
+                        tui.add_client_err_msg(&e, &MsgTarget::Server { serv: &serv_name });
                         None
                     }
                 }
             });
 
             let server_info = ServerInfo {
-                addr: server.addr,
-                port: server.port,
-                tls,
+                name: serv_name,
+                endpoints,
+                connect_timeout: server.connect_timeout,
+                prefer_addr_family: server.prefer_addr_family.map(Into::into),
+                bind_addr: server.bind_addr,
+                sts_file: Some(sts_file.clone()),
+                record_dir: record_dir.clone(),
                 pass: server.pass,
                 user: server.user,
                 realname: server.realname,
                 nicks: server.nicks,
                 auto_join: server.join.iter().map(|c| c.name().to_owned()).collect(),
+                auto_join_on_invite: server.auto_join_on_invite,
+                rejoin_on_kick: server
+                    .join
+                    .iter()
+                    .filter_map(|c| Some((c.name().to_owned(), c.rejoin_on_kick()?)))
+                    .collect(),
                 nickserv_ident: server.nickserv_ident,
                 sasl_auth,
             };
@@ -196,7 +235,7 @@ fn run(
         }
 
         // Block on TUI task
-        ui::task(defaults, tui, clients, rcv_tui_ev).await;
+        ui::task(defaults, download_dir, sts_file, tui, clients, rcv_tui_ev).await;
     });
 
     runtime.block_on(local);
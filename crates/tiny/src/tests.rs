@@ -1,6 +1,7 @@
 use crate::conn;
+use crate::ignore::Ignores;
 use crate::ui::UI;
-use libtiny_common::ChanName;
+use libtiny_common::{ChanName, ChanNameRef};
 use libtiny_tui::TUI;
 use libtiny_tui::test_utils::expect_screen;
 use libtiny_wire::{Cmd, Msg, MsgTarget, Pfx};
@@ -33,9 +34,27 @@ impl conn::Client for TestClient {
     fn is_nick_accepted(&self) -> bool {
         true
     }
+
+    fn is_joined(&self, chan: &ChanNameRef) -> bool {
+        chan == ChanNameRef::new(JOINED_CHAN)
+    }
+
+    fn auto_join_invite(&self, _nick: &str, _chan: &ChanNameRef) -> bool {
+        false
+    }
+
+    fn dcc_close(&self, _id: client::DccId) -> bool {
+        false
+    }
+
+    fn dcc_chat_close(&self, _nick: &str) -> bool {
+        false
+    }
 }
 
 static SERV_NAME: &str = "x.y.z";
+/// `TestClient` is in this channel.
+static JOINED_CHAN: &str = "#joined";
 const DEFAULT_TUI_WIDTH: u16 = 40;
 const DEFAULT_TUI_HEIGHT: u16 = 5;
 
@@ -66,7 +85,7 @@ where
         let (tui, _rcv_tui_ev) =
             TUI::run_test(DEFAULT_TUI_WIDTH, DEFAULT_TUI_HEIGHT, rcv_input_ev.map(Ok));
 
-        let tiny_ui = UI::new(tui.clone(), None);
+        let tiny_ui = UI::new(tui.clone(), None, Ignores::new(vec![], None));
 
         // Create test connection event channel
         let (snd_conn_ev, rcv_conn_ev) = mpsc::channel::<client::Event>(100);
@@ -90,6 +109,66 @@ where
     });
 }
 
+struct ReplayTestSetup {
+    /// TUI test instance
+    tui: TUI,
+    /// Send input events to the TUI using this channel
+    snd_input_ev: mpsc::Sender<input::Event>,
+}
+
+/// Like `run_test`, but connection events are generated by replaying `recording` with
+/// `Client::replay`. Recordings are made with the `record_dir` config field, use this to turn
+/// recordings in bug reports into tests.
+fn run_replay_test<F, Fut>(nick: &str, recording: &str, test: F)
+where
+    F: Fn(ReplayTestSetup) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let local = tokio::task::LocalSet::new();
+
+    local.block_on(&runtime, async move {
+        let (snd_input_ev, rcv_input_ev) = mpsc::channel::<term_input::Event>(100);
+        let rcv_input_ev = ReceiverStream::new(rcv_input_ev);
+        let (tui, _rcv_tui_ev) =
+            TUI::run_test(DEFAULT_TUI_WIDTH, DEFAULT_TUI_HEIGHT, rcv_input_ev.map(Ok));
+
+        let tiny_ui = UI::new(tui.clone(), None, Ignores::new(vec![], None));
+
+        let (client, rcv_conn_ev) = client::Client::replay(
+            client::ServerInfo {
+                name: SERV_NAME.to_owned(),
+                endpoints: vec![],
+                connect_timeout: client::DEFAULT_CONNECT_TIMEOUT_SECS,
+                prefer_addr_family: None,
+                bind_addr: None,
+                sts_file: None,
+                record_dir: None,
+                pass: None,
+                user: None,
+                realname: nick.to_owned(),
+                nicks: vec![nick.to_owned()],
+                auto_join: vec![],
+                auto_join_on_invite: vec![],
+                rejoin_on_kick: Default::default(),
+                nickserv_ident: None,
+                sasl_auth: None,
+            },
+            recording.to_owned(),
+        );
+
+        tui.new_server_tab(SERV_NAME, None);
+        tui.draw();
+
+        tokio::task::spawn_local(conn::task(rcv_conn_ev, tiny_ui, Box::new(client)));
+
+        test(ReplayTestSetup { tui, snd_input_ev }).await;
+    });
+}
+
 #[test]
 fn test_privmsg_from_user_without_user_or_host_part_issue_247() {
     run_test(
@@ -331,6 +410,463 @@ fn test_privmsg_targetmask_issue_278() {
     )
 }
 
+#[test]
+fn test_netsplit_summary() {
+    run_test(
+        "osa1".to_owned(),
+        |TestSetup {
+             tui,
+             snd_input_ev,
+             snd_conn_ev,
+         }| async move {
+            snd_conn_ev.send(client::Event::Connected).await.unwrap();
+            snd_conn_ev
+                .send(client::Event::NickChange {
+                    new_nick: "osa1".to_owned(),
+                })
+                .await
+                .unwrap();
+
+            let join = Msg {
+                pfx: Some(Pfx::User {
+                    nick: "osa1".to_owned(),
+                    user: "a@b".to_owned(),
+                }),
+                cmd: Cmd::JOIN {
+                    chan: ChanName::new("#chan".to_owned()),
+                },
+            };
+            snd_conn_ev.send(client::Event::Msg(join)).await.unwrap();
+
+            snd_conn_ev
+                .send(client::Event::Netsplit {
+                    servers: ("a.net".to_owned(), "b.net".to_owned()),
+                    chans: vec![(
+                        ChanName::new("#chan".to_owned()),
+                        vec!["nick1".to_owned(), "nick2".to_owned(), "nick3".to_owned()],
+                    )],
+                })
+                .await
+                .unwrap();
+            yield_(5).await;
+
+            next_tab(&snd_input_ev).await; // server tab
+            next_tab(&snd_input_ev).await; // channel tab
+            yield_(5).await;
+            tui.draw();
+
+            #[rustfmt::skip]
+            let screen =
+            "|                                        |
+             |                                        |
+             |00:00 Netsplit a.net <-> b.net, 3 quit  |
+             |osa1:                                   |
+             |mentions x.y.z #chan                    |";
+
+            let mut front_buffer = tui.get_front_buffer();
+            normalize_timestamps(&mut front_buffer, DEFAULT_TUI_WIDTH, DEFAULT_TUI_HEIGHT);
+            expect_screen(
+                screen,
+                &front_buffer,
+                DEFAULT_TUI_WIDTH,
+                DEFAULT_TUI_HEIGHT,
+                Location::caller(),
+            );
+
+            // Expand the line to show the nicks
+            snd_input_ev
+                .send(term_input::Event::Key(term_input::Key::Ctrl('o')))
+                .await
+                .unwrap();
+            yield_(5).await;
+            tui.draw();
+
+            #[rustfmt::skip]
+            let screen =
+            "|                                        |
+             |00:00 Netsplit a.net <-> b.net, 3 quit: |
+             |nick1, nick2, nick3                     |
+             |osa1:                                   |
+             |mentions x.y.z #chan                    |";
+
+            let mut front_buffer = tui.get_front_buffer();
+            normalize_timestamps(&mut front_buffer, DEFAULT_TUI_WIDTH, DEFAULT_TUI_HEIGHT);
+            expect_screen(
+                screen,
+                &front_buffer,
+                DEFAULT_TUI_WIDTH,
+                DEFAULT_TUI_HEIGHT,
+                Location::caller(),
+            );
+        },
+    )
+}
+
+#[test]
+fn test_kick_reason() {
+    run_test(
+        "osa1".to_owned(),
+        |TestSetup {
+             tui,
+             snd_input_ev,
+             snd_conn_ev,
+         }| async move {
+            snd_conn_ev.send(client::Event::Connected).await.unwrap();
+            snd_conn_ev
+                .send(client::Event::NickChange {
+                    new_nick: "osa1".to_owned(),
+                })
+                .await
+                .unwrap();
+
+            let join = Msg {
+                pfx: Some(Pfx::User {
+                    nick: "osa1".to_owned(),
+                    user: "a@b".to_owned(),
+                }),
+                cmd: Cmd::JOIN {
+                    chan: ChanName::new("#chan".to_owned()),
+                },
+            };
+            snd_conn_ev.send(client::Event::Msg(join)).await.unwrap();
+
+            let kick = Msg {
+                pfx: Some(Pfx::User {
+                    nick: "op".to_owned(),
+                    user: "a@b".to_owned(),
+                }),
+                cmd: Cmd::KICK {
+                    chan: ChanName::new("#chan".to_owned()),
+                    nick: "osa1".to_owned(),
+                    msg: Some("flooding".to_owned()),
+                },
+            };
+            snd_conn_ev.send(client::Event::Msg(kick)).await.unwrap();
+            yield_(5).await;
+
+            next_tab(&snd_input_ev).await; // server tab
+            next_tab(&snd_input_ev).await; // channel tab
+            yield_(5).await;
+            tui.draw();
+
+            #[rustfmt::skip]
+            let screen =
+            "|                                        |
+             |                                        |
+             |00:00 You were kicked by op (flooding)  |
+             |osa1:                                   |
+             |mentions x.y.z #chan                    |";
+
+            let mut front_buffer = tui.get_front_buffer();
+            normalize_timestamps(&mut front_buffer, DEFAULT_TUI_WIDTH, DEFAULT_TUI_HEIGHT);
+            expect_screen(
+                screen,
+                &front_buffer,
+                DEFAULT_TUI_WIDTH,
+                DEFAULT_TUI_HEIGHT,
+                Location::caller(),
+            );
+        },
+    )
+}
+
+#[test]
+fn test_invite_notice() {
+    run_test(
+        "osa1".to_owned(),
+        |TestSetup {
+             tui,
+             snd_input_ev,
+             snd_conn_ev,
+         }| async move {
+            snd_conn_ev.send(client::Event::Connected).await.unwrap();
+            snd_conn_ev
+                .send(client::Event::NickChange {
+                    new_nick: "osa1".to_owned(),
+                })
+                .await
+                .unwrap();
+
+            next_tab(&snd_input_ev).await; // server tab
+            yield_(5).await;
+
+            let invite = |chan: &str| Msg {
+                pfx: Some(Pfx::User {
+                    nick: "op".to_owned(),
+                    user: "a@b".to_owned(),
+                }),
+                cmd: Cmd::INVITE {
+                    nick: "osa1".to_owned(),
+                    chan: ChanName::new(chan.to_owned()),
+                },
+            };
+            // Invites to channels we're in are ignored
+            snd_conn_ev
+                .send(client::Event::Msg(invite(JOINED_CHAN)))
+                .await
+                .unwrap();
+            snd_conn_ev
+                .send(client::Event::Msg(invite("#chan")))
+                .await
+                .unwrap();
+            yield_(5).await;
+            tui.draw();
+
+            #[rustfmt::skip]
+            let screen =
+            "|Connected.                              |
+             |op invited you to #chan. Use `/accept`  |
+             |to join.                                |
+             |osa1:                                   |
+             |mentions x.y.z                          |";
+
+            let mut front_buffer = tui.get_front_buffer();
+            normalize_timestamps(&mut front_buffer, DEFAULT_TUI_WIDTH, DEFAULT_TUI_HEIGHT);
+            expect_screen(
+                screen,
+                &front_buffer,
+                DEFAULT_TUI_WIDTH,
+                DEFAULT_TUI_HEIGHT,
+                Location::caller(),
+            );
+        },
+    )
+}
+
+#[test]
+fn test_list_replies() {
+    run_test(
+        "osa1".to_owned(),
+        |TestSetup {
+             tui,
+             snd_input_ev,
+             snd_conn_ev,
+         }| async move {
+            snd_conn_ev.send(client::Event::Connected).await.unwrap();
+            next_tab(&snd_input_ev).await; // server tab
+            yield_(5).await;
+
+            // Replies of a LIST sent with `/raw` are shown in the list view as well
+            for (chan, users, topic) in [("#tiny", "12", "IRC client"), ("#rust", "300", "")] {
+                let reply = Msg {
+                    pfx: Some(Pfx::Server("x.y.z".to_owned())),
+                    cmd: Cmd::Reply {
+                        num: 322,
+                        params: vec![
+                            "osa1".to_owned(),
+                            chan.to_owned(),
+                            users.to_owned(),
+                            topic.to_owned(),
+                        ],
+                    },
+                };
+                snd_conn_ev.send(client::Event::Msg(reply)).await.unwrap();
+            }
+            let end = Msg {
+                pfx: Some(Pfx::Server("x.y.z".to_owned())),
+                cmd: Cmd::Reply {
+                    num: 323,
+                    params: vec!["osa1".to_owned(), "End of /LIST".to_owned()],
+                },
+            };
+            snd_conn_ev.send(client::Event::Msg(end)).await.unwrap();
+            yield_(5).await;
+            tui.draw();
+
+            #[rustfmt::skip]
+            let screen =
+            "|  300 #rust                             |
+             |   12 #tiny IRC client                  |
+             |                                        |
+             |1/2 channels, by users filter:          |
+             |mentions x.y.z                          |";
+
+            expect_screen(
+                screen,
+                &tui.get_front_buffer(),
+                DEFAULT_TUI_WIDTH,
+                DEFAULT_TUI_HEIGHT,
+                Location::caller(),
+            );
+        },
+    )
+}
+
+#[test]
+fn test_dcc_transfers_tab() {
+    run_test(
+        "osa1".to_owned(),
+        |TestSetup {
+             tui,
+             snd_input_ev,
+             snd_conn_ev,
+         }| async move {
+            let mut transfer = client::DccTransfer {
+                id: 1,
+                dir: client::DccDir::Recv,
+                nick: "bob".to_owned(),
+                pfx: None,
+                file_name: "a.txt".to_owned(),
+                path: None,
+                size: Some(1024),
+                pos: 0,
+                status: client::DccStatus::Offered,
+            };
+            snd_conn_ev
+                .send(client::Event::Dcc(transfer.clone()))
+                .await
+                .unwrap();
+            transfer.status = client::DccStatus::Active;
+            transfer.pos = 512;
+            snd_conn_ev
+                .send(client::Event::Dcc(transfer.clone()))
+                .await
+                .unwrap();
+            yield_(5).await;
+
+            next_tab(&snd_input_ev).await; // server tab
+            yield_(5).await;
+            tui.draw();
+
+            #[rustfmt::skip]
+            let screen =
+            "|bob offers file a.txt (1024 bytes). Use |
+             |`/dcc get 1` to accept, `/dcc close 1`  |
+             |to reject.                              |
+             |                                        |
+             |mentions x.y.z transfers                |";
+
+            let mut front_buffer = tui.get_front_buffer();
+            normalize_timestamps(&mut front_buffer, DEFAULT_TUI_WIDTH, DEFAULT_TUI_HEIGHT);
+            expect_screen(
+                screen,
+                &front_buffer,
+                DEFAULT_TUI_WIDTH,
+                DEFAULT_TUI_HEIGHT,
+                Location::caller(),
+            );
+
+            next_tab(&snd_input_ev).await; // transfers tab
+            yield_(5).await;
+            tui.draw();
+
+            #[rustfmt::skip]
+            let screen =
+            "|                                        |
+             |[1] a.txt from bob: active, 512 B / 1.0 |
+             |KiB (50%)                               |
+             |                                        |
+             |mentions x.y.z transfers                |";
+
+            let front_buffer = tui.get_front_buffer();
+            expect_screen(
+                screen,
+                &front_buffer,
+                DEFAULT_TUI_WIDTH,
+                DEFAULT_TUI_HEIGHT,
+                Location::caller(),
+            );
+        },
+    )
+}
+
+#[test]
+fn test_dcc_chat_tab() {
+    run_test(
+        "osa1".to_owned(),
+        |TestSetup {
+             tui,
+             snd_input_ev,
+             snd_conn_ev,
+         }| async move {
+            for ev in [
+                client::DccChatEvent::Connected,
+                client::DccChatEvent::Msg {
+                    msg: "hi".to_owned(),
+                    is_action: false,
+                },
+                client::DccChatEvent::Closed(None),
+            ] {
+                snd_conn_ev
+                    .send(client::Event::DccChat {
+                        nick: "bob".to_owned(),
+                        ev,
+                    })
+                    .await
+                    .unwrap();
+            }
+            yield_(5).await;
+
+            next_tab(&snd_input_ev).await; // server tab
+            next_tab(&snd_input_ev).await; // chat tab
+            yield_(5).await;
+            tui.draw();
+
+            #[rustfmt::skip]
+            let screen =
+            "|DCC CHAT connected                      |
+             |00:00 bob: hi                           |
+             |DCC CHAT closed                         |
+             |                                        |
+             |mentions x.y.z =bob                     |";
+
+            let mut front_buffer = tui.get_front_buffer();
+            normalize_timestamps(&mut front_buffer, DEFAULT_TUI_WIDTH, DEFAULT_TUI_HEIGHT);
+            expect_screen(
+                screen,
+                &front_buffer,
+                DEFAULT_TUI_WIDTH,
+                DEFAULT_TUI_HEIGHT,
+                Location::caller(),
+            );
+        },
+    )
+}
+
+#[test]
+fn test_replay_privmsg_from_server() {
+    // Messages from servers (without a user part) to channels are shown in the channel tab
+    let recording = "\
+1700000000000 > CAP LS 302
+1700000000001 > NICK osa1
+1700000000100 < :x.y.z 001 osa1 :Welcome
+1700000000200 > JOIN #chan
+1700000000300 < :osa1!~osa1@127.0.0.1 JOIN #chan
+1700000000400 < :x.y.z 353 osa1 = #chan :osa1 bob
+1700000000500 < :x.y.z PRIVMSG #chan :hi from the server
+";
+    run_replay_test(
+        "osa1",
+        recording,
+        |ReplayTestSetup { tui, snd_input_ev }| async move {
+            yield_(20).await;
+
+            next_tab(&snd_input_ev).await; // server tab
+            next_tab(&snd_input_ev).await; // #chan
+            yield_(5).await;
+            tui.draw();
+
+            #[rustfmt::skip]
+            let screen =
+            "|                                        |
+             |                                        |
+             |00:00 x.y.z: hi from the server         |
+             |osa1:                                   |
+             |mentions x.y.z #chan                    |";
+
+            let mut front_buffer = tui.get_front_buffer();
+            normalize_timestamps(&mut front_buffer, DEFAULT_TUI_WIDTH, DEFAULT_TUI_HEIGHT);
+            expect_screen(
+                screen,
+                &front_buffer,
+                DEFAULT_TUI_WIDTH,
+                DEFAULT_TUI_HEIGHT,
+                Location::caller(),
+            );
+        },
+    )
+}
+
 async fn next_tab(snd_input_ev: &mpsc::Sender<input::Event>) {
     snd_input_ev
         .send(term_input::Event::Key(term_input::Key::Ctrl('n')))
@@ -2,12 +2,17 @@
 
 use crate::cmd::run_cmd;
 use crate::config;
+use crate::ignore::{IgnoreType, Ignores};
 use libtiny_client::Client;
 use libtiny_common::{ChanNameRef, MsgSource, MsgTarget, TabStyle};
 use libtiny_logger::Logger;
 use libtiny_tui::TUI;
 
 use libtiny_tui::config::TabConfig;
+use libtiny_wire::Pfx;
+use std::cell::{RefCell, RefMut};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use time::Tm;
 use tokio::sync::mpsc;
 use tokio_stream::StreamExt;
@@ -36,11 +41,32 @@ macro_rules! delegate_ui {
 pub(crate) struct UI {
     ui: TUI,
     logger: Option<Logger>,
+    ignores: Rc<RefCell<Ignores>>,
 }
 
 impl UI {
-    pub(crate) fn new(ui: TUI, logger: Option<Logger>) -> UI {
-        UI { ui, logger }
+    pub(crate) fn new(ui: TUI, logger: Option<Logger>, ignores: Ignores) -> UI {
+        UI {
+            ui,
+            logger,
+            ignores: Rc::new(RefCell::new(ignores)),
+        }
+    }
+
+    /// Whether a message from `pfx` should be dropped before it's shown and logged. See
+    /// `Ignores::is_ignored`.
+    pub(crate) fn is_ignored(
+        &self,
+        serv: &str,
+        chan: Option<&ChanNameRef>,
+        pfx: &Pfx,
+        ty: IgnoreType,
+    ) -> bool {
+        self.ignores.borrow().is_ignored(serv, chan, pfx, ty)
+    }
+
+    pub(crate) fn ignores(&self) -> RefMut<'_, Ignores> {
+        self.ignores.borrow_mut()
     }
 
     pub(crate) fn new_server_tab(&self, serv_name: &str, alias: Option<String>) {
@@ -54,6 +80,7 @@ impl UI {
     delegate!(new_chan_tab(serv: &str, chan: &ChanNameRef,));
     delegate!(close_chan_tab(serv: &str, chan: &ChanNameRef,));
     delegate!(close_user_tab(serv: &str, nick: &str,));
+    delegate!(close_dcc_chat_tab(serv: &str, nick: &str,));
     delegate!(add_client_msg(msg: &str, target: &MsgTarget,));
     delegate!(add_msg(msg: &str, ts: Tm, target: &MsgTarget,));
     delegate!(add_privmsg(
@@ -66,6 +93,13 @@ impl UI {
     ));
     delegate!(add_nick(nick: &str, ts: Option<Tm>, target: &MsgTarget,));
     delegate!(remove_nick(nick: &str, ts: Option<Tm>, target: &MsgTarget,));
+    delegate!(add_netsplit(
+        servers: (&str, &str),
+        nicks: &[String],
+        ts: Tm,
+        target: &MsgTarget,
+    ));
+    delegate!(add_netjoin(nicks: &[String], ts: Tm, target: &MsgTarget,));
     delegate!(rename_nick(
         old_nick: &str,
         new_nick: &str,
@@ -83,9 +117,37 @@ impl UI {
     delegate_ui!(add_err_msg(msg: &str, ts: Tm, target: &MsgTarget,));
     delegate_ui!(add_client_err_msg(msg: &str, target: &MsgTarget,));
     delegate_ui!(clear_nicks(serv: &str,));
+    delegate_ui!(update_nick_prefix(
+        nick: &str,
+        prefix: char,
+        add: bool,
+        target: &MsgTarget,
+    ));
     delegate_ui!(set_nick(serv: &str, nick: &str,));
     delegate_ui!(set_tab_style(style: TabStyle, target: &MsgTarget,));
+    delegate_ui!(set_transfer(id: usize, line: &str,));
+    delegate_ui!(remove_transfer(id: usize,));
+    delegate_ui!(open_chan_list(
+        serv: &str,
+        pattern: Option<&str>,
+        min_users: Option<usize>,
+    ));
+    delegate_ui!(add_chan_list_item(
+        serv: &str,
+        chan: &ChanNameRef,
+        users: usize,
+        topic: &str,
+    ));
+    delegate_ui!(chan_list_done(serv: &str,));
+    delegate_ui!(new_dcc_chat_tab(serv: &str, nick: &str,));
     delegate_ui!(user_tab_exists(serv_name: &str, nick: &str,) -> bool);
+    delegate_ui!(is_highlight(
+        serv_name: &str,
+        chan_name: Option<&ChanNameRef>,
+        sender: &str,
+        msg: &str,
+        mentions_nick: bool,
+    ) -> bool);
     delegate_ui!(get_tab_config(serv_name: &str, chan_name: Option<&ChanNameRef>,) -> TabConfig);
     delegate_ui!(set_tab_config(
         serv_name: &str,
@@ -100,19 +162,23 @@ impl UI {
 
 pub(crate) async fn task(
     defaults: config::Defaults,
+    download_dir: PathBuf,
+    sts_file: PathBuf,
     ui: UI,
     mut clients: Vec<Client>,
     rcv_ev: mpsc::Receiver<libtiny_common::Event>,
 ) {
     let mut rcv_ev = ReceiverStream::new(rcv_ev);
     while let Some(ev) = rcv_ev.next().await {
-        handle_input_ev(&defaults, &ui, &mut clients, ev);
+        handle_input_ev(&defaults, &download_dir, &sts_file, &ui, &mut clients, ev);
         ui.draw();
     }
 }
 
 fn handle_input_ev(
     defaults: &config::Defaults,
+    download_dir: &Path,
+    sts_file: &Path,
     ui: &UI,
     clients: &mut Vec<Client>,
     ev: libtiny_common::Event,
@@ -136,7 +202,7 @@ fn handle_input_ev(
         }
 
         Cmd { cmd, source } => {
-            run_cmd(&cmd, source, defaults, ui, clients);
+            run_cmd(&cmd, source, defaults, download_dir, sts_file, ui, clients);
         }
     }
 }
@@ -148,7 +214,7 @@ pub(crate) fn send_msg(
     msg: String,
     is_action: bool,
 ) {
-    if src.serv_name() == "mentions" {
+    if src.serv_name() == "mentions" || src.serv_name() == "transfers" {
         if clients.is_empty() {
             ui.add_client_err_msg(
                 "No connected server found, please use `/connect <server>` to connect to a server",
@@ -156,7 +222,10 @@ pub(crate) fn send_msg(
             );
         } else {
             ui.add_client_err_msg(
-                "You are on the mentions tab, please use `/switch <tab name>` to switch to a tab",
+                &format!(
+                    "You are on the {} tab, please use `/switch <tab name>` to switch to a tab",
+                    src.serv_name()
+                ),
                 &MsgTarget::CurrentTab,
             );
         }
@@ -192,6 +261,22 @@ pub(crate) fn send_msg(
                 };
                 (msg_target, nick)
             }
+
+            MsgSource::DccChat { serv, nick } => {
+                // DCC CHAT messages are sent to the peer directly, no need to split
+                match client.dcc_chat_msg(nick, &msg, is_action) {
+                    Ok(()) => ui.add_privmsg(
+                        &client.get_nick(),
+                        &msg,
+                        time::now(),
+                        &MsgTarget::DccChat { serv, nick },
+                        false,
+                        is_action,
+                    ),
+                    Err(err) => ui.add_client_err_msg(&err, &MsgTarget::CurrentTab),
+                }
+                return;
+            }
         }
     };
 
@@ -1,3 +1,5 @@
+use libtiny_client::{DccDir, DccStatus, DccTransfer};
+
 use std::str::SplitWhitespace;
 
 /// Like `std::str::SplitWhitespace`, but returns beginning indices rather than slices.
@@ -56,6 +58,49 @@ pub(crate) fn is_chan_first_char(c: char) -> bool {
 
 ////////////////////////////////////////////////////////////////////////////////
 
+/// Format a DCC transfer for the transfers tab and `/dcc list`.
+pub(crate) fn format_dcc_transfer(transfer: &DccTransfer) -> String {
+    let dir = match transfer.dir {
+        DccDir::Send => "to",
+        DccDir::Recv => "from",
+    };
+    let status = match &transfer.status {
+        DccStatus::Offered => "offered".to_owned(),
+        DccStatus::Waiting => "waiting".to_owned(),
+        DccStatus::Active => "active".to_owned(),
+        DccStatus::Done => "done".to_owned(),
+        DccStatus::Failed(err) => format!("failed: {err}"),
+        DccStatus::Closed => "closed".to_owned(),
+    };
+    let progress = match transfer.size {
+        Some(size) if size != 0 => format!(
+            "{} / {} ({}%)",
+            format_size(transfer.pos),
+            format_size(size),
+            (transfer.pos as f64 * 100.0 / size as f64) as u64
+        ),
+        _ => format_size(transfer.pos),
+    };
+    format!(
+        "[{}] {} {} {}: {}, {}",
+        transfer.id, transfer.file_name, dir, transfer.nick, status, progress
+    )
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -76,4 +121,34 @@ mod tests {
         let idxs: Vec<usize> = split_whitespace_indices(str).collect();
         assert_eq!(idxs, vec![2, 9, 19]);
     }
+
+    #[test]
+    fn dcc_transfer_format() {
+        assert_eq!(format_size(100), "100 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MiB");
+
+        let mut transfer = DccTransfer {
+            id: 3,
+            dir: DccDir::Recv,
+            nick: "osa1".to_owned(),
+            pfx: None,
+            file_name: "logs.tar.gz".to_owned(),
+            path: None,
+            size: Some(2048),
+            pos: 512,
+            status: DccStatus::Active,
+        };
+        assert_eq!(
+            format_dcc_transfer(&transfer),
+            "[3] logs.tar.gz from osa1: active, 512 B / 2.0 KiB (25%)"
+        );
+        transfer.dir = DccDir::Send;
+        transfer.size = None;
+        transfer.status = DccStatus::Failed("Connection refused".to_owned());
+        assert_eq!(
+            format_dcc_transfer(&transfer),
+            "[3] logs.tar.gz to osa1: failed: Connection refused, 512 B"
+        );
+    }
 }